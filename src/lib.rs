@@ -1,7 +1,11 @@
+// The muxer and parser are ports of the C++ libwebm library and keep its
+// naming so the two code bases can be read side by side.
+#![allow(non_snake_case, non_camel_case_types, non_upper_case_globals)]
+
 pub mod mkvmuxer;
 pub mod mkvparser;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum MkvId {
     MkvEBML = 0x1A45DFA3,
     MkvEBMLVersion = 0x4286,
@@ -12,6 +16,7 @@ pub enum MkvId {
     MkvDocTypeVersion = 0x4287,
     MkvDocTypeReadVersion = 0x4285,
     MkvVoid = 0xEC,
+    MkvCrc32 = 0xBF,
     MkvSignatureSlot = 0x1B538667,
     MkvSignatureAlgo = 0x7E8A,
     MkvSignatureHash = 0x7E9A,
@@ -38,6 +43,7 @@ pub enum MkvId {
     // Cluster
     MkvCluster = 0x1F43B675,
     MkvTimecode = 0xE7,
+    MkvPosition = 0xA7,
     MkvPrevSize = 0xAB,
     MkvBlockGroup = 0xA0,
     MkvBlock = 0xA1,
@@ -162,19 +168,274 @@ pub enum MkvId {
     // Chapters
     MkvChapters = 0x1043A770,
     MkvEditionEntry = 0x45B9,
+    MkvEditionUID = 0x45BC,
+    MkvEditionFlagHidden = 0x45BD,
+    MkvEditionFlagDefault = 0x45DB,
+    MkvEditionFlagOrdered = 0x45DD,
     MkvChapterAtom = 0xB6,
     MkvChapterUID = 0x73C4,
     MkvChapterStringUID = 0x5654,
     MkvChapterTimeStart = 0x91,
     MkvChapterTimeEnd = 0x92,
+    MkvChapterFlagHidden = 0x98,
+    MkvChapterFlagEnabled = 0x4598,
+    MkvChapterSegmentUID = 0x6E67,
     MkvChapterDisplay = 0x80,
     MkvChapString = 0x85,
     MkvChapLanguage = 0x437C,
     MkvChapCountry = 0x437E,
+    MkvChapProcess = 0x6944,
+    MkvChapProcessCodecID = 0x6955,
+    MkvChapProcessPrivate = 0x450D,
+    MkvChapProcessCommand = 0x6911,
+    MkvChapProcessTime = 0x6922,
+    MkvChapProcessData = 0x6933,
+    // Attachments
+    MkvAttachments = 0x1941A469,
+    MkvAttachedFile = 0x61A7,
+    MkvFileDescription = 0x467E,
+    MkvFileName = 0x466E,
+    MkvFileMimeType = 0x4660,
+    MkvFileData = 0x465C,
+    MkvFileUID = 0x46AE,
     // Tags
     MkvTags = 0x1254C367,
     MkvTag = 0x7373,
+    MkvTargets = 0x63C0,
+    MkvTargetTypeValue = 0x68CA,
+    MkvTargetType = 0x63CA,
+    MkvTagTrackUID = 0x63C5,
+    MkvTagEditionUID = 0x63C9,
+    MkvTagChapterUID = 0x63C4,
+    MkvTagAttachmentUID = 0x63C6,
     MkvSimpleTag = 0x67C8,
     MkvTagName = 0x45A3,
+    MkvTagLanguage = 0x447A,
+    MkvTagDefault = 0x4484,
     MkvTagString = 0x4487,
+    MkvTagBinary = 0x4485,
+}
+
+impl MkvId {
+    pub fn from_u64(value: u64) -> Option<MkvId> {
+        match value {
+            x if x == MkvId::MkvEBML as u64 => Some(MkvId::MkvEBML),
+            x if x == MkvId::MkvEBMLVersion as u64 => Some(MkvId::MkvEBMLVersion),
+            x if x == MkvId::MkvEBMLReadVersion as u64 => Some(MkvId::MkvEBMLReadVersion),
+            x if x == MkvId::MkvEBMLMaxIDLength as u64 => Some(MkvId::MkvEBMLMaxIDLength),
+            x if x == MkvId::MkvEBMLMaxSizeLength as u64 => Some(MkvId::MkvEBMLMaxSizeLength),
+            x if x == MkvId::MkvDocType as u64 => Some(MkvId::MkvDocType),
+            x if x == MkvId::MkvDocTypeVersion as u64 => Some(MkvId::MkvDocTypeVersion),
+            x if x == MkvId::MkvDocTypeReadVersion as u64 => Some(MkvId::MkvDocTypeReadVersion),
+            x if x == MkvId::MkvVoid as u64 => Some(MkvId::MkvVoid),
+            x if x == MkvId::MkvCrc32 as u64 => Some(MkvId::MkvCrc32),
+            x if x == MkvId::MkvSignatureSlot as u64 => Some(MkvId::MkvSignatureSlot),
+            x if x == MkvId::MkvSignatureAlgo as u64 => Some(MkvId::MkvSignatureAlgo),
+            x if x == MkvId::MkvSignatureHash as u64 => Some(MkvId::MkvSignatureHash),
+            x if x == MkvId::MkvSignaturePublicKey as u64 => Some(MkvId::MkvSignaturePublicKey),
+            x if x == MkvId::MkvSignature as u64 => Some(MkvId::MkvSignature),
+            x if x == MkvId::MkvSignatureElements as u64 => Some(MkvId::MkvSignatureElements),
+            x if x == MkvId::MkvSignatureElementList as u64 => Some(MkvId::MkvSignatureElementList),
+            x if x == MkvId::MkvSignedElement as u64 => Some(MkvId::MkvSignedElement),
+            x if x == MkvId::MkvSegment as u64 => Some(MkvId::MkvSegment),
+            x if x == MkvId::MkvSeekHead as u64 => Some(MkvId::MkvSeekHead),
+            x if x == MkvId::MkvSeek as u64 => Some(MkvId::MkvSeek),
+            x if x == MkvId::MkvSeekID as u64 => Some(MkvId::MkvSeekID),
+            x if x == MkvId::MkvSeekPosition as u64 => Some(MkvId::MkvSeekPosition),
+            x if x == MkvId::MkvInfo as u64 => Some(MkvId::MkvInfo),
+            x if x == MkvId::MkvTimecodeScale as u64 => Some(MkvId::MkvTimecodeScale),
+            x if x == MkvId::MkvDuration as u64 => Some(MkvId::MkvDuration),
+            x if x == MkvId::MkvDateUTC as u64 => Some(MkvId::MkvDateUTC),
+            x if x == MkvId::MkvTitle as u64 => Some(MkvId::MkvTitle),
+            x if x == MkvId::MkvMuxingApp as u64 => Some(MkvId::MkvMuxingApp),
+            x if x == MkvId::MkvWritingApp as u64 => Some(MkvId::MkvWritingApp),
+            x if x == MkvId::MkvCluster as u64 => Some(MkvId::MkvCluster),
+            x if x == MkvId::MkvTimecode as u64 => Some(MkvId::MkvTimecode),
+            x if x == MkvId::MkvPosition as u64 => Some(MkvId::MkvPosition),
+            x if x == MkvId::MkvPrevSize as u64 => Some(MkvId::MkvPrevSize),
+            x if x == MkvId::MkvBlockGroup as u64 => Some(MkvId::MkvBlockGroup),
+            x if x == MkvId::MkvBlock as u64 => Some(MkvId::MkvBlock),
+            x if x == MkvId::MkvBlockDuration as u64 => Some(MkvId::MkvBlockDuration),
+            x if x == MkvId::MkvReferenceBlock as u64 => Some(MkvId::MkvReferenceBlock),
+            x if x == MkvId::MkvLaceNumber as u64 => Some(MkvId::MkvLaceNumber),
+            x if x == MkvId::MkvSimpleBlock as u64 => Some(MkvId::MkvSimpleBlock),
+            x if x == MkvId::MkvBlockAdditions as u64 => Some(MkvId::MkvBlockAdditions),
+            x if x == MkvId::MkvBlockMore as u64 => Some(MkvId::MkvBlockMore),
+            x if x == MkvId::MkvBlockAddID as u64 => Some(MkvId::MkvBlockAddID),
+            x if x == MkvId::MkvBlockAdditional as u64 => Some(MkvId::MkvBlockAdditional),
+            x if x == MkvId::MkvDiscardPadding as u64 => Some(MkvId::MkvDiscardPadding),
+            x if x == MkvId::MkvTracks as u64 => Some(MkvId::MkvTracks),
+            x if x == MkvId::MkvTrackEntry as u64 => Some(MkvId::MkvTrackEntry),
+            x if x == MkvId::MkvTrackNumber as u64 => Some(MkvId::MkvTrackNumber),
+            x if x == MkvId::MkvTrackUID as u64 => Some(MkvId::MkvTrackUID),
+            x if x == MkvId::MkvTrackType as u64 => Some(MkvId::MkvTrackType),
+            x if x == MkvId::MkvFlagEnabled as u64 => Some(MkvId::MkvFlagEnabled),
+            x if x == MkvId::MkvFlagDefault as u64 => Some(MkvId::MkvFlagDefault),
+            x if x == MkvId::MkvFlagForced as u64 => Some(MkvId::MkvFlagForced),
+            x if x == MkvId::MkvFlagLacing as u64 => Some(MkvId::MkvFlagLacing),
+            x if x == MkvId::MkvDefaultDuration as u64 => Some(MkvId::MkvDefaultDuration),
+            x if x == MkvId::MkvMaxBlockAdditionID as u64 => Some(MkvId::MkvMaxBlockAdditionID),
+            x if x == MkvId::MkvName as u64 => Some(MkvId::MkvName),
+            x if x == MkvId::MkvLanguage as u64 => Some(MkvId::MkvLanguage),
+            x if x == MkvId::MkvCodecID as u64 => Some(MkvId::MkvCodecID),
+            x if x == MkvId::MkvCodecPrivate as u64 => Some(MkvId::MkvCodecPrivate),
+            x if x == MkvId::MkvCodecName as u64 => Some(MkvId::MkvCodecName),
+            x if x == MkvId::MkvCodecDelay as u64 => Some(MkvId::MkvCodecDelay),
+            x if x == MkvId::MkvSeekPreRoll as u64 => Some(MkvId::MkvSeekPreRoll),
+            x if x == MkvId::MkvVideo as u64 => Some(MkvId::MkvVideo),
+            x if x == MkvId::MkvFlagInterlaced as u64 => Some(MkvId::MkvFlagInterlaced),
+            x if x == MkvId::MkvStereoMode as u64 => Some(MkvId::MkvStereoMode),
+            x if x == MkvId::MkvAlphaMode as u64 => Some(MkvId::MkvAlphaMode),
+            x if x == MkvId::MkvPixelWidth as u64 => Some(MkvId::MkvPixelWidth),
+            x if x == MkvId::MkvPixelHeight as u64 => Some(MkvId::MkvPixelHeight),
+            x if x == MkvId::MkvPixelCropBottom as u64 => Some(MkvId::MkvPixelCropBottom),
+            x if x == MkvId::MkvPixelCropTop as u64 => Some(MkvId::MkvPixelCropTop),
+            x if x == MkvId::MkvPixelCropLeft as u64 => Some(MkvId::MkvPixelCropLeft),
+            x if x == MkvId::MkvPixelCropRight as u64 => Some(MkvId::MkvPixelCropRight),
+            x if x == MkvId::MkvDisplayWidth as u64 => Some(MkvId::MkvDisplayWidth),
+            x if x == MkvId::MkvDisplayHeight as u64 => Some(MkvId::MkvDisplayHeight),
+            x if x == MkvId::MkvDisplayUnit as u64 => Some(MkvId::MkvDisplayUnit),
+            x if x == MkvId::MkvAspectRatioType as u64 => Some(MkvId::MkvAspectRatioType),
+            x if x == MkvId::MkvColourSpace as u64 => Some(MkvId::MkvColourSpace),
+            x if x == MkvId::MkvFrameRate as u64 => Some(MkvId::MkvFrameRate),
+            x if x == MkvId::MkvColour as u64 => Some(MkvId::MkvColour),
+            x if x == MkvId::MkvMatrixCoefficients as u64 => Some(MkvId::MkvMatrixCoefficients),
+            x if x == MkvId::MkvBitsPerChannel as u64 => Some(MkvId::MkvBitsPerChannel),
+            x if x == MkvId::MkvChromaSubsamplingHorz as u64 => {
+                Some(MkvId::MkvChromaSubsamplingHorz)
+            }
+            x if x == MkvId::MkvChromaSubsamplingVert as u64 => {
+                Some(MkvId::MkvChromaSubsamplingVert)
+            }
+            x if x == MkvId::MkvCbSubsamplingHorz as u64 => Some(MkvId::MkvCbSubsamplingHorz),
+            x if x == MkvId::MkvCbSubsamplingVert as u64 => Some(MkvId::MkvCbSubsamplingVert),
+            x if x == MkvId::MkvChromaSitingHorz as u64 => Some(MkvId::MkvChromaSitingHorz),
+            x if x == MkvId::MkvChromaSitingVert as u64 => Some(MkvId::MkvChromaSitingVert),
+            x if x == MkvId::MkvRange as u64 => Some(MkvId::MkvRange),
+            x if x == MkvId::MkvTransferCharacteristics as u64 => {
+                Some(MkvId::MkvTransferCharacteristics)
+            }
+            x if x == MkvId::MkvPrimaries as u64 => Some(MkvId::MkvPrimaries),
+            x if x == MkvId::MkvMaxCLL as u64 => Some(MkvId::MkvMaxCLL),
+            x if x == MkvId::MkvMaxFALL as u64 => Some(MkvId::MkvMaxFALL),
+            x if x == MkvId::MkvMasteringMetadata as u64 => Some(MkvId::MkvMasteringMetadata),
+            x if x == MkvId::MkvPrimaryRChromaticityX as u64 => {
+                Some(MkvId::MkvPrimaryRChromaticityX)
+            }
+            x if x == MkvId::MkvPrimaryRChromaticityY as u64 => {
+                Some(MkvId::MkvPrimaryRChromaticityY)
+            }
+            x if x == MkvId::MkvPrimaryGChromaticityX as u64 => {
+                Some(MkvId::MkvPrimaryGChromaticityX)
+            }
+            x if x == MkvId::MkvPrimaryGChromaticityY as u64 => {
+                Some(MkvId::MkvPrimaryGChromaticityY)
+            }
+            x if x == MkvId::MkvPrimaryBChromaticityX as u64 => {
+                Some(MkvId::MkvPrimaryBChromaticityX)
+            }
+            x if x == MkvId::MkvPrimaryBChromaticityY as u64 => {
+                Some(MkvId::MkvPrimaryBChromaticityY)
+            }
+            x if x == MkvId::MkvWhitePointChromaticityX as u64 => {
+                Some(MkvId::MkvWhitePointChromaticityX)
+            }
+            x if x == MkvId::MkvWhitePointChromaticityY as u64 => {
+                Some(MkvId::MkvWhitePointChromaticityY)
+            }
+            x if x == MkvId::MkvLuminanceMax as u64 => Some(MkvId::MkvLuminanceMax),
+            x if x == MkvId::MkvLuminanceMin as u64 => Some(MkvId::MkvLuminanceMin),
+            x if x == MkvId::MkvProjection as u64 => Some(MkvId::MkvProjection),
+            x if x == MkvId::MkvProjectionType as u64 => Some(MkvId::MkvProjectionType),
+            x if x == MkvId::MkvProjectionPrivate as u64 => Some(MkvId::MkvProjectionPrivate),
+            x if x == MkvId::MkvProjectionPoseYaw as u64 => Some(MkvId::MkvProjectionPoseYaw),
+            x if x == MkvId::MkvProjectionPosePitch as u64 => Some(MkvId::MkvProjectionPosePitch),
+            x if x == MkvId::MkvProjectionPoseRoll as u64 => Some(MkvId::MkvProjectionPoseRoll),
+            x if x == MkvId::MkvAudio as u64 => Some(MkvId::MkvAudio),
+            x if x == MkvId::MkvSamplingFrequency as u64 => Some(MkvId::MkvSamplingFrequency),
+            x if x == MkvId::MkvOutputSamplingFrequency as u64 => {
+                Some(MkvId::MkvOutputSamplingFrequency)
+            }
+            x if x == MkvId::MkvChannels as u64 => Some(MkvId::MkvChannels),
+            x if x == MkvId::MkvBitDepth as u64 => Some(MkvId::MkvBitDepth),
+            x if x == MkvId::MkvContentEncodings as u64 => Some(MkvId::MkvContentEncodings),
+            x if x == MkvId::MkvContentEncoding as u64 => Some(MkvId::MkvContentEncoding),
+            x if x == MkvId::MkvContentEncodingOrder as u64 => Some(MkvId::MkvContentEncodingOrder),
+            x if x == MkvId::MkvContentEncodingScope as u64 => Some(MkvId::MkvContentEncodingScope),
+            x if x == MkvId::MkvContentEncodingType as u64 => Some(MkvId::MkvContentEncodingType),
+            x if x == MkvId::MkvContentCompression as u64 => Some(MkvId::MkvContentCompression),
+            x if x == MkvId::MkvContentCompAlgo as u64 => Some(MkvId::MkvContentCompAlgo),
+            x if x == MkvId::MkvContentCompSettings as u64 => Some(MkvId::MkvContentCompSettings),
+            x if x == MkvId::MkvContentEncryption as u64 => Some(MkvId::MkvContentEncryption),
+            x if x == MkvId::MkvContentEncAlgo as u64 => Some(MkvId::MkvContentEncAlgo),
+            x if x == MkvId::MkvContentEncKeyID as u64 => Some(MkvId::MkvContentEncKeyID),
+            x if x == MkvId::MkvContentSignature as u64 => Some(MkvId::MkvContentSignature),
+            x if x == MkvId::MkvContentSigKeyID as u64 => Some(MkvId::MkvContentSigKeyID),
+            x if x == MkvId::MkvContentSigAlgo as u64 => Some(MkvId::MkvContentSigAlgo),
+            x if x == MkvId::MkvContentSigHashAlgo as u64 => Some(MkvId::MkvContentSigHashAlgo),
+            x if x == MkvId::MkvContentEncAESSettings as u64 => {
+                Some(MkvId::MkvContentEncAESSettings)
+            }
+            x if x == MkvId::MkvAESSettingsCipherMode as u64 => {
+                Some(MkvId::MkvAESSettingsCipherMode)
+            }
+            x if x == MkvId::MkvAESSettingsCipherInitData as u64 => {
+                Some(MkvId::MkvAESSettingsCipherInitData)
+            }
+            x if x == MkvId::MkvCues as u64 => Some(MkvId::MkvCues),
+            x if x == MkvId::MkvCuePoint as u64 => Some(MkvId::MkvCuePoint),
+            x if x == MkvId::MkvCueTime as u64 => Some(MkvId::MkvCueTime),
+            x if x == MkvId::MkvCueTrackPositions as u64 => Some(MkvId::MkvCueTrackPositions),
+            x if x == MkvId::MkvCueTrack as u64 => Some(MkvId::MkvCueTrack),
+            x if x == MkvId::MkvCueClusterPosition as u64 => Some(MkvId::MkvCueClusterPosition),
+            x if x == MkvId::MkvCueBlockNumber as u64 => Some(MkvId::MkvCueBlockNumber),
+            x if x == MkvId::MkvChapters as u64 => Some(MkvId::MkvChapters),
+            x if x == MkvId::MkvEditionEntry as u64 => Some(MkvId::MkvEditionEntry),
+            x if x == MkvId::MkvEditionUID as u64 => Some(MkvId::MkvEditionUID),
+            x if x == MkvId::MkvEditionFlagHidden as u64 => Some(MkvId::MkvEditionFlagHidden),
+            x if x == MkvId::MkvEditionFlagDefault as u64 => Some(MkvId::MkvEditionFlagDefault),
+            x if x == MkvId::MkvEditionFlagOrdered as u64 => Some(MkvId::MkvEditionFlagOrdered),
+            x if x == MkvId::MkvChapterAtom as u64 => Some(MkvId::MkvChapterAtom),
+            x if x == MkvId::MkvChapterUID as u64 => Some(MkvId::MkvChapterUID),
+            x if x == MkvId::MkvChapterStringUID as u64 => Some(MkvId::MkvChapterStringUID),
+            x if x == MkvId::MkvChapterTimeStart as u64 => Some(MkvId::MkvChapterTimeStart),
+            x if x == MkvId::MkvChapterTimeEnd as u64 => Some(MkvId::MkvChapterTimeEnd),
+            x if x == MkvId::MkvChapterFlagHidden as u64 => Some(MkvId::MkvChapterFlagHidden),
+            x if x == MkvId::MkvChapterFlagEnabled as u64 => Some(MkvId::MkvChapterFlagEnabled),
+            x if x == MkvId::MkvChapterSegmentUID as u64 => Some(MkvId::MkvChapterSegmentUID),
+            x if x == MkvId::MkvChapterDisplay as u64 => Some(MkvId::MkvChapterDisplay),
+            x if x == MkvId::MkvChapString as u64 => Some(MkvId::MkvChapString),
+            x if x == MkvId::MkvChapLanguage as u64 => Some(MkvId::MkvChapLanguage),
+            x if x == MkvId::MkvChapCountry as u64 => Some(MkvId::MkvChapCountry),
+            x if x == MkvId::MkvChapProcess as u64 => Some(MkvId::MkvChapProcess),
+            x if x == MkvId::MkvChapProcessCodecID as u64 => Some(MkvId::MkvChapProcessCodecID),
+            x if x == MkvId::MkvChapProcessPrivate as u64 => Some(MkvId::MkvChapProcessPrivate),
+            x if x == MkvId::MkvChapProcessCommand as u64 => Some(MkvId::MkvChapProcessCommand),
+            x if x == MkvId::MkvChapProcessTime as u64 => Some(MkvId::MkvChapProcessTime),
+            x if x == MkvId::MkvChapProcessData as u64 => Some(MkvId::MkvChapProcessData),
+            x if x == MkvId::MkvAttachments as u64 => Some(MkvId::MkvAttachments),
+            x if x == MkvId::MkvAttachedFile as u64 => Some(MkvId::MkvAttachedFile),
+            x if x == MkvId::MkvFileDescription as u64 => Some(MkvId::MkvFileDescription),
+            x if x == MkvId::MkvFileName as u64 => Some(MkvId::MkvFileName),
+            x if x == MkvId::MkvFileMimeType as u64 => Some(MkvId::MkvFileMimeType),
+            x if x == MkvId::MkvFileData as u64 => Some(MkvId::MkvFileData),
+            x if x == MkvId::MkvFileUID as u64 => Some(MkvId::MkvFileUID),
+            x if x == MkvId::MkvTags as u64 => Some(MkvId::MkvTags),
+            x if x == MkvId::MkvTag as u64 => Some(MkvId::MkvTag),
+            x if x == MkvId::MkvTargets as u64 => Some(MkvId::MkvTargets),
+            x if x == MkvId::MkvTargetTypeValue as u64 => Some(MkvId::MkvTargetTypeValue),
+            x if x == MkvId::MkvTargetType as u64 => Some(MkvId::MkvTargetType),
+            x if x == MkvId::MkvTagTrackUID as u64 => Some(MkvId::MkvTagTrackUID),
+            x if x == MkvId::MkvTagEditionUID as u64 => Some(MkvId::MkvTagEditionUID),
+            x if x == MkvId::MkvTagChapterUID as u64 => Some(MkvId::MkvTagChapterUID),
+            x if x == MkvId::MkvTagAttachmentUID as u64 => Some(MkvId::MkvTagAttachmentUID),
+            x if x == MkvId::MkvSimpleTag as u64 => Some(MkvId::MkvSimpleTag),
+            x if x == MkvId::MkvTagName as u64 => Some(MkvId::MkvTagName),
+            x if x == MkvId::MkvTagLanguage as u64 => Some(MkvId::MkvTagLanguage),
+            x if x == MkvId::MkvTagDefault as u64 => Some(MkvId::MkvTagDefault),
+            x if x == MkvId::MkvTagString as u64 => Some(MkvId::MkvTagString),
+            x if x == MkvId::MkvTagBinary as u64 => Some(MkvId::MkvTagBinary),
+            _ => None,
+        }
+    }
 }
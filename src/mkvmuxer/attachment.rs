@@ -0,0 +1,196 @@
+use super::error::{CheckSize, MuxerError, Result};
+use super::util;
+use super::writer::Writer;
+use crate::MkvId;
+
+// A file attached to the Segment, e.g. a font used by subtitles or cover art.
+#[derive(Debug, Clone)]
+pub struct AttachedFile {
+    file_name_: String,
+    mime_type_: String,
+    data_: Vec<u8>,
+
+    // The binary identifier for this file, referenced by TagAttachmentUID.
+    uid_: u64,
+
+    // Human readable description. Not written when empty.
+    description_: String,
+}
+
+impl Default for AttachedFile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AttachedFile {
+    pub fn new() -> AttachedFile {
+        AttachedFile {
+            file_name_: String::new(),
+            mime_type_: String::new(),
+            data_: Vec::new(),
+            uid_: util::MakeUID(),
+            description_: String::new(),
+        }
+    }
+
+    pub fn set_file_name(&mut self, file_name: &str) {
+        self.file_name_ = String::from(file_name);
+    }
+
+    pub fn file_name(&self) -> &str {
+        &self.file_name_
+    }
+
+    pub fn set_mime_type(&mut self, mime_type: &str) {
+        self.mime_type_ = String::from(mime_type);
+    }
+
+    pub fn mime_type(&self) -> &str {
+        &self.mime_type_
+    }
+
+    pub fn set_data(&mut self, data: &[u8]) {
+        self.data_ = data.to_vec();
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data_
+    }
+
+    pub fn set_uid(&mut self, uid: u64) {
+        self.uid_ = uid;
+    }
+
+    pub fn uid(&self) -> u64 {
+        self.uid_
+    }
+
+    pub fn set_description(&mut self, description: &str) {
+        self.description_ = String::from(description);
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description_
+    }
+
+    pub fn PayloadSize(&self) -> u64 {
+        let mut payload_size = 0;
+
+        if !self.description_.is_empty() {
+            payload_size +=
+                util::EbmlElementSizeArgStr(MkvId::MkvFileDescription, &self.description_);
+        }
+
+        payload_size += util::EbmlElementSizeArgStr(MkvId::MkvFileName, &self.file_name_)
+            + util::EbmlElementSizeArgStr(MkvId::MkvFileMimeType, &self.mime_type_)
+            + util::EbmlElementSizeArgSlice(MkvId::MkvFileData, &self.data_)
+            + util::EbmlElementSizeArgU64(MkvId::MkvFileUID, self.uid_);
+
+        payload_size
+    }
+
+    pub fn Write(&self, writer: &mut dyn Writer) -> Result<()> {
+        if self.file_name_.is_empty() {
+            return Err(MuxerError::kInvalidValue(MkvId::MkvFileName, "empty"));
+        }
+        if self.mime_type_.is_empty() {
+            return Err(MuxerError::kInvalidValue(MkvId::MkvFileMimeType, "empty"));
+        }
+        if self.uid_ == 0 {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvFileUID,
+                "must not be 0",
+            ));
+        }
+
+        let payload_size = self.PayloadSize();
+        let attached_file_size =
+            util::EbmlMasterElementSize(MkvId::MkvAttachedFile, payload_size) + payload_size;
+
+        let start = writer.get_position();
+
+        util::WriteEbmlMasterElement(writer, MkvId::MkvAttachedFile, payload_size)?;
+
+        if !self.description_.is_empty() {
+            util::WriteEbmlElementArgStr(writer, MkvId::MkvFileDescription, &self.description_)?;
+        }
+
+        util::WriteEbmlElementArgStr(writer, MkvId::MkvFileName, &self.file_name_)?;
+
+        util::WriteEbmlElementArgStr(writer, MkvId::MkvFileMimeType, &self.mime_type_)?;
+
+        util::WriteEbmlElementArgSlice(writer, MkvId::MkvFileData, &self.data_)?;
+
+        util::WriteEbmlElementArgU64(writer, MkvId::MkvFileUID, self.uid_)?;
+
+        let stop = writer.get_position();
+        CheckSize(MkvId::MkvAttachedFile, attached_file_size, stop - start)?;
+
+        Ok(())
+    }
+}
+
+pub struct Attachments {
+    files_: Vec<AttachedFile>,
+}
+
+impl Default for Attachments {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Attachments {
+    pub fn new() -> Attachments {
+        Attachments { files_: Vec::new() }
+    }
+
+    pub fn AddFile(&mut self, file: AttachedFile) {
+        self.files_.push(file);
+    }
+
+    // Adds an empty AttachedFile and returns it so the caller can populate
+    // it.
+    pub fn AddEmptyFile(&mut self) -> &mut AttachedFile {
+        self.files_.push(AttachedFile::new());
+        self.files_.last_mut().unwrap()
+    }
+
+    pub fn count(&self) -> usize {
+        self.files_.len()
+    }
+
+    pub fn GetFile(&mut self, index: usize) -> Option<&mut AttachedFile> {
+        self.files_.get_mut(index)
+    }
+
+    pub fn PayloadSize(&self) -> u64 {
+        let mut payload_size = 0;
+
+        for file in &self.files_ {
+            let file_payload_size = file.PayloadSize();
+            payload_size += util::EbmlMasterElementSize(MkvId::MkvAttachedFile, file_payload_size)
+                + file_payload_size;
+        }
+
+        payload_size
+    }
+
+    pub fn Write(&self, writer: &mut dyn Writer) -> Result<()> {
+        let payload_size = self.PayloadSize();
+
+        util::WriteEbmlMasterElement(writer, MkvId::MkvAttachments, payload_size)?;
+
+        let start = writer.get_position();
+
+        for file in &self.files_ {
+            file.Write(writer)?;
+        }
+
+        let stop = writer.get_position();
+        CheckSize(MkvId::MkvAttachments, payload_size, stop - start)?;
+
+        Ok(())
+    }
+}
@@ -1,9 +1,10 @@
+use super::error::{CheckSize, Result};
 use super::track::Track;
 use super::util;
 use super::writer::Writer;
 use crate::MkvId;
 
-use std::ops::Deref;
+use std::ops::{Deref, DerefMut};
 
 pub struct AudioTrack {
     track_: Track,
@@ -22,6 +23,18 @@ impl Deref for AudioTrack {
     }
 }
 
+impl DerefMut for AudioTrack {
+    fn deref_mut(&mut self) -> &mut Track {
+        &mut self.track_
+    }
+}
+
+impl Default for AudioTrack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl AudioTrack {
     pub fn new() -> AudioTrack {
         AudioTrack {
@@ -36,19 +49,19 @@ impl AudioTrack {
         self.bit_depth_ = bit_depth;
     }
     pub fn bit_depth(&self) -> u64 {
-        return self.bit_depth_;
+        self.bit_depth_
     }
     pub fn set_channels(&mut self, channels: u64) {
         self.channels_ = channels;
     }
     pub fn channels(&self) -> u64 {
-        return self.channels_;
+        self.channels_
     }
     pub fn set_sample_rate(&mut self, sample_rate: f64) {
         self.sample_rate_ = sample_rate;
     }
     pub fn sample_rate(&self) -> f64 {
-        return self.sample_rate_;
+        self.sample_rate_
     }
 
     pub fn PayloadSize(&self) -> u64 {
@@ -62,13 +75,17 @@ impl AudioTrack {
         }
         size += util::EbmlMasterElementSize(MkvId::MkvAudio, size);
 
-        return parent_size + size;
+        parent_size + size
     }
 
-    pub fn Write(&self, writer: &mut dyn Writer) -> bool {
-        if !self.track_.Write(writer) {
-            return false;
-        }
+    pub fn Size(&self) -> u64 {
+        let mut size = self.PayloadSize();
+        size += util::EbmlMasterElementSize(MkvId::MkvTrackEntry, size);
+        size
+    }
+
+    pub fn Write(&self, writer: &mut dyn Writer) -> Result<()> {
+        self.track_.WriteEntry(writer, self.PayloadSize())?;
 
         // Calculate AudioSettings size.
         let mut size =
@@ -78,33 +95,23 @@ impl AudioTrack {
             size += util::EbmlElementSizeArgU64(MkvId::MkvBitDepth, self.bit_depth_);
         }
 
-        if !util::WriteEbmlMasterElement(writer, MkvId::MkvAudio, size) {
-            return false;
-        }
+        util::WriteEbmlMasterElement(writer, MkvId::MkvAudio, size)?;
 
         let payload_position = writer.get_position();
 
-        if !util::WriteEbmlElementArgF32(
+        util::WriteEbmlElementArgF32(
             writer,
             MkvId::MkvSamplingFrequency,
             self.sample_rate_ as f32,
-        ) {
-            return false;
-        }
-        if !util::WriteEbmlElementArgU64(writer, MkvId::MkvChannels, self.channels_) {
-            return false;
-        }
+        )?;
+        util::WriteEbmlElementArgU64(writer, MkvId::MkvChannels, self.channels_)?;
         if self.bit_depth_ > 0 {
-            if !util::WriteEbmlElementArgU64(writer, MkvId::MkvBitDepth, self.bit_depth_) {
-                return false;
-            }
+            util::WriteEbmlElementArgU64(writer, MkvId::MkvBitDepth, self.bit_depth_)?;
         }
 
         let stop_position = writer.get_position();
-        if stop_position - payload_position != size {
-            return false;
-        }
+        CheckSize(MkvId::MkvAudio, size, stop_position - payload_position)?;
 
-        return true;
+        Ok(())
     }
 }
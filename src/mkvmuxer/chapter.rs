@@ -1,3 +1,4 @@
+use super::error::{CheckSize, MuxerError, Result};
 use super::util;
 use super::writer::Writer;
 use crate::MkvId;
@@ -22,6 +23,7 @@ impl Display {
         self.title_ = title.to_string();
     }
 
+    #[allow(dead_code)]
     pub fn title(&self) -> &str {
         &self.title_
     }
@@ -30,6 +32,7 @@ impl Display {
         self.language_ = language.to_string();
     }
 
+    #[allow(dead_code)]
     pub fn language(&self) -> &str {
         &self.language_
     }
@@ -38,6 +41,7 @@ impl Display {
         self.country_ = country.to_string();
     }
 
+    #[allow(dead_code)]
     pub fn country(&self) -> &str {
         &self.country_
     }
@@ -55,40 +59,152 @@ impl Display {
         payload_size
     }
 
-    pub fn Write(&self, writer: &mut dyn Writer) -> bool {
+    pub fn Write(&self, writer: &mut dyn Writer) -> Result<()> {
         let payload_size = self.PayloadSize();
         let display_size =
             util::EbmlMasterElementSize(MkvId::MkvChapterDisplay, payload_size) + payload_size;
 
         let start = writer.get_position();
 
-        if !util::WriteEbmlMasterElement(writer, MkvId::MkvChapterDisplay, payload_size) {
-            return false;
-        }
+        util::WriteEbmlMasterElement(writer, MkvId::MkvChapterDisplay, payload_size)?;
 
-        if !util::WriteEbmlElementArgStr(writer, MkvId::MkvChapString, &self.title_) {
-            return false;
-        }
+        util::WriteEbmlElementArgStr(writer, MkvId::MkvChapString, &self.title_)?;
 
         if !self.language_.is_empty() {
-            if !util::WriteEbmlElementArgStr(writer, MkvId::MkvChapLanguage, &self.language_) {
-                return false;
-            }
+            util::WriteEbmlElementArgStr(writer, MkvId::MkvChapLanguage, &self.language_)?;
         }
 
         if !self.country_.is_empty() {
-            if !util::WriteEbmlElementArgStr(writer, MkvId::MkvChapCountry, &self.country_) {
-                return false;
-            }
+            util::WriteEbmlElementArgStr(writer, MkvId::MkvChapCountry, &self.country_)?;
         }
 
         let stop = writer.get_position();
 
-        if stop - start != display_size {
-            return false;
+        CheckSize(MkvId::MkvChapterDisplay, display_size, stop - start)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ProcessCommand {
+    // When the command is run, as a ChapProcessTime value.
+    time_: u64,
+    data_: Vec<u8>,
+}
+
+impl ProcessCommand {
+    pub fn PayloadSize(&self) -> u64 {
+        util::EbmlElementSizeArgU64(MkvId::MkvChapProcessTime, self.time_)
+            + util::EbmlElementSizeArgSlice(MkvId::MkvChapProcessData, &self.data_)
+    }
+
+    pub fn Write(&self, writer: &mut dyn Writer) -> Result<()> {
+        let payload_size = self.PayloadSize();
+        let command_size =
+            util::EbmlMasterElementSize(MkvId::MkvChapProcessCommand, payload_size) + payload_size;
+
+        let start = writer.get_position();
+
+        util::WriteEbmlMasterElement(writer, MkvId::MkvChapProcessCommand, payload_size)?;
+        util::WriteEbmlElementArgU64(writer, MkvId::MkvChapProcessTime, self.time_)?;
+        util::WriteEbmlElementArgSlice(writer, MkvId::MkvChapProcessData, &self.data_)?;
+
+        let stop = writer.get_position();
+        CheckSize(MkvId::MkvChapProcessCommand, command_size, stop - start)?;
+
+        Ok(())
+    }
+}
+
+// A ChapProcess element: commands for a chapter codec (0 for Matroska Script,
+// 1 for DVD menus) that are run when the chapter is played.
+#[derive(Debug, Clone)]
+pub struct ChapterProcess {
+    codec_id_: u64,
+
+    // Codec specific data for the process, written as ChapProcessPrivate.
+    private_: Vec<u8>,
+
+    commands_: Vec<ProcessCommand>,
+}
+
+impl ChapterProcess {
+    pub fn new(codec_id: u64) -> ChapterProcess {
+        ChapterProcess {
+            codec_id_: codec_id,
+            private_: Vec::new(),
+            commands_: Vec::new(),
+        }
+    }
+
+    pub fn codec_id(&self) -> u64 {
+        self.codec_id_
+    }
+
+    pub fn set_private(&mut self, private: &[u8]) {
+        self.private_ = private.to_vec();
+    }
+
+    pub fn private(&self) -> &[u8] {
+        &self.private_
+    }
+
+    // Adds a command to the process. |time| is 0 for commands run during the
+    // whole chapter, 1 before starting playback and 2 after it.
+    pub fn AddCommand(&mut self, time: u64, data: &[u8]) {
+        self.commands_.push(ProcessCommand {
+            time_: time,
+            data_: data.to_vec(),
+        });
+    }
+
+    pub fn command_count(&self) -> usize {
+        self.commands_.len()
+    }
+
+    pub fn PayloadSize(&self) -> u64 {
+        let mut payload_size =
+            util::EbmlElementSizeArgU64(MkvId::MkvChapProcessCodecID, self.codec_id_);
+
+        if !self.private_.is_empty() {
+            payload_size +=
+                util::EbmlElementSizeArgSlice(MkvId::MkvChapProcessPrivate, &self.private_);
+        }
+
+        for c in &self.commands_ {
+            let command_payload_size = c.PayloadSize();
+            payload_size +=
+                util::EbmlMasterElementSize(MkvId::MkvChapProcessCommand, command_payload_size)
+                    + command_payload_size;
         }
 
-        true
+        payload_size
+    }
+
+    pub fn Write(&self, writer: &mut dyn Writer) -> Result<()> {
+        let payload_size = self.PayloadSize();
+        let process_size =
+            util::EbmlMasterElementSize(MkvId::MkvChapProcess, payload_size) + payload_size;
+
+        let start = writer.get_position();
+
+        util::WriteEbmlMasterElement(writer, MkvId::MkvChapProcess, payload_size)?;
+
+        util::WriteEbmlElementArgU64(writer, MkvId::MkvChapProcessCodecID, self.codec_id_)?;
+
+        if !self.private_.is_empty() {
+            util::WriteEbmlElementArgSlice(writer, MkvId::MkvChapProcessPrivate, &self.private_)?;
+        }
+
+        for c in &self.commands_ {
+            c.Write(writer)?;
+        }
+
+        let stop = writer.get_position();
+        CheckSize(MkvId::MkvChapProcess, process_size, stop - start)?;
+
+        Ok(())
     }
 }
 
@@ -110,6 +226,25 @@ pub struct Chapter {
     // The Atom element can contain multiple Display sub-elements, as
     // the same logical title can be rendered in different languages.
     displays_: Vec<Display>,
+
+    // ChapterFlagHidden and ChapterFlagEnabled. Only values that differ from
+    // the defaults (visible and enabled) are written.
+    hidden_: bool,
+    enabled_: bool,
+
+    // UID of the Segment to play for this chapter. Not written when empty.
+    segment_uid_: Vec<u8>,
+
+    processes_: Vec<ChapterProcess>,
+
+    // Nested ChapterAtoms, e.g. the sections of a chapter.
+    chapters_: Vec<Chapter>,
+}
+
+impl Default for Chapter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Chapter {
@@ -120,15 +255,137 @@ impl Chapter {
             end_timecode_: 0,
             uid_: util::MakeUID(),
             displays_: Vec::new(),
+            hidden_: false,
+            enabled_: true,
+            segment_uid_: Vec::new(),
+            processes_: Vec::new(),
+            chapters_: Vec::new(),
         }
     }
 
+    // Sets the string identifier of the chapter (the WebVTT cue identifier).
+    pub fn set_id(&mut self, id: &str) {
+        self.id_ = id.to_string();
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id_
+    }
+
+    pub fn set_uid(&mut self, uid: u64) {
+        self.uid_ = uid;
+    }
+
+    pub fn uid(&self) -> u64 {
+        self.uid_
+    }
+
+    // Sets the start and end time of the chapter in nanoseconds. The values
+    // are converted to timecodes using |timecode_scale|.
+    pub fn set_time(&mut self, timecode_scale: u64, start_ns: u64, end_ns: u64) {
+        self.start_timecode_ = start_ns / timecode_scale;
+        self.end_timecode_ = end_ns / timecode_scale;
+    }
+
+    pub fn start_timecode(&self) -> u64 {
+        self.start_timecode_
+    }
+
+    pub fn end_timecode(&self) -> u64 {
+        self.end_timecode_
+    }
+
+    // Adds a title for the chapter. |language| and |country| may be empty.
+    pub fn add_string(&mut self, title: &str, language: &str, country: &str) {
+        let mut d = Display::new();
+        d.set_title(title);
+        d.set_language(language);
+        d.set_country(country);
+        self.displays_.push(d);
+    }
+
+    pub fn set_hidden(&mut self, hidden: bool) {
+        self.hidden_ = hidden;
+    }
+
+    pub fn hidden(&self) -> bool {
+        self.hidden_
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled_ = enabled;
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled_
+    }
+
+    // Sets the UID of the linked Segment played for this chapter. Matroska
+    // Segment UIDs are 16 bytes.
+    pub fn set_segment_uid(&mut self, segment_uid: &[u8]) {
+        self.segment_uid_ = segment_uid.to_vec();
+    }
+
+    pub fn segment_uid(&self) -> &[u8] {
+        &self.segment_uid_
+    }
+
+    // Adds an empty ChapProcess for |codec_id| and returns it so the caller
+    // can add commands.
+    pub fn AddProcess(&mut self, codec_id: u64) -> &mut ChapterProcess {
+        self.processes_.push(ChapterProcess::new(codec_id));
+        self.processes_.last_mut().unwrap()
+    }
+
+    pub fn process_count(&self) -> usize {
+        self.processes_.len()
+    }
+
+    // Adds a nested chapter.
+    pub fn AddChapter(&mut self, chapter: Chapter) {
+        self.chapters_.push(chapter);
+    }
+
+    // Adds an empty nested chapter and returns it so the caller can populate
+    // it.
+    pub fn AddEmptyChapter(&mut self) -> &mut Chapter {
+        self.chapters_.push(Chapter::new());
+        self.chapters_.last_mut().unwrap()
+    }
+
+    pub fn chapter_count(&self) -> usize {
+        self.chapters_.len()
+    }
+
+    pub fn GetChapter(&mut self, index: usize) -> Option<&mut Chapter> {
+        self.chapters_.get_mut(index)
+    }
+
+    // Returns false if the chapter or a nested chapter uses elements that
+    // are not allowed in WebM: ChapProcess and ChapterSegmentUID.
+    pub fn WebmCompatible(&self) -> bool {
+        self.processes_.is_empty()
+            && self.segment_uid_.is_empty()
+            && self.chapters_.iter().all(|c| c.WebmCompatible())
+    }
+
     pub fn PayloadSize(&self) -> u64 {
         let mut payload_size = util::EbmlElementSizeArgStr(MkvId::MkvChapterStringUID, &self.id_)
             + util::EbmlElementSizeArgU64(MkvId::MkvChapterUID, self.uid_)
             + util::EbmlElementSizeArgU64(MkvId::MkvChapterTimeStart, self.start_timecode_)
             + util::EbmlElementSizeArgU64(MkvId::MkvChapterTimeEnd, self.end_timecode_);
 
+        if self.hidden_ {
+            payload_size += util::EbmlElementSizeArgU64(MkvId::MkvChapterFlagHidden, 1);
+        }
+        if !self.enabled_ {
+            payload_size += util::EbmlElementSizeArgU64(MkvId::MkvChapterFlagEnabled, 0);
+        }
+        if !self.segment_uid_.is_empty() {
+            payload_size +=
+                util::EbmlElementSizeArgSlice(MkvId::MkvChapterSegmentUID, &self.segment_uid_);
+        }
+
         for d in &self.displays_ {
             let display_payload_size = d.PayloadSize();
             payload_size +=
@@ -136,10 +393,31 @@ impl Chapter {
                     + display_payload_size;
         }
 
+        for p in &self.processes_ {
+            let process_payload_size = p.PayloadSize();
+            payload_size +=
+                util::EbmlMasterElementSize(MkvId::MkvChapProcess, process_payload_size)
+                    + process_payload_size;
+        }
+
+        for c in &self.chapters_ {
+            let chapter_payload_size = c.PayloadSize();
+            payload_size +=
+                util::EbmlMasterElementSize(MkvId::MkvChapterAtom, chapter_payload_size)
+                    + chapter_payload_size;
+        }
+
         payload_size
     }
 
-    pub fn Write(&self, writer: &mut dyn Writer) -> bool {
+    pub fn Write(&self, writer: &mut dyn Writer) -> Result<()> {
+        if !self.segment_uid_.is_empty() && self.segment_uid_.len() != 16 {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvChapterSegmentUID,
+                "must be 16 bytes",
+            ));
+        }
+
         let payload_size = self.PayloadSize();
 
         let atom_size =
@@ -147,37 +425,47 @@ impl Chapter {
 
         let start = writer.get_position();
 
-        if !util::WriteEbmlMasterElement(writer, MkvId::MkvChapterAtom, payload_size) {
-            return false;
+        util::WriteEbmlMasterElement(writer, MkvId::MkvChapterAtom, payload_size)?;
+
+        util::WriteEbmlElementArgStr(writer, MkvId::MkvChapterStringUID, &self.id_)?;
+
+        util::WriteEbmlElementArgU64(writer, MkvId::MkvChapterUID, self.uid_)?;
+
+        util::WriteEbmlElementArgU64(writer, MkvId::MkvChapterTimeStart, self.start_timecode_)?;
+
+        util::WriteEbmlElementArgU64(writer, MkvId::MkvChapterTimeEnd, self.end_timecode_)?;
+
+        if self.hidden_ {
+            util::WriteEbmlElementArgU64(writer, MkvId::MkvChapterFlagHidden, 1)?;
         }
 
-        if !util::WriteEbmlElementArgStr(writer, MkvId::MkvChapterStringUID, &self.id_) {
-            return false;
+        if !self.enabled_ {
+            util::WriteEbmlElementArgU64(writer, MkvId::MkvChapterFlagEnabled, 0)?;
         }
 
-        if !util::WriteEbmlElementArgU64(writer, MkvId::MkvChapterUID, self.uid_) {
-            return false;
+        if !self.segment_uid_.is_empty() {
+            util::WriteEbmlElementArgSlice(
+                writer,
+                MkvId::MkvChapterSegmentUID,
+                &self.segment_uid_,
+            )?;
         }
 
-        if !util::WriteEbmlElementArgU64(writer, MkvId::MkvChapterTimeStart, self.start_timecode_) {
-            return false;
+        for d in &self.displays_ {
+            d.Write(writer)?;
         }
 
-        if !util::WriteEbmlElementArgU64(writer, MkvId::MkvChapterTimeEnd, self.end_timecode_) {
-            return false;
+        for p in &self.processes_ {
+            p.Write(writer)?;
         }
 
-        for d in &self.displays_ {
-            if !d.Write(writer) {
-                return false;
-            }
+        for c in &self.chapters_ {
+            c.Write(writer)?;
         }
 
         let stop = writer.get_position();
-        if stop - start != atom_size {
-            return false;
-        }
+        CheckSize(MkvId::MkvChapterAtom, atom_size, stop - start)?;
 
-        true
+        Ok(())
     }
 }
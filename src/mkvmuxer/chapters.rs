@@ -1,20 +1,75 @@
 use super::chapter::Chapter;
+use super::error::{CheckSize, MuxerError, Result};
 use super::util;
 use super::writer::Writer;
 use crate::MkvId;
 
-pub struct Chapters {
+// An EditionEntry: one set of chapters for the Segment, e.g. the theatrical
+// and the director's cut.
+#[derive(Debug, Clone)]
+pub struct Edition {
+    // The binary identifier for this edition. Not written when 0.
+    uid_: u64,
+
+    // EditionFlagHidden, EditionFlagDefault and EditionFlagOrdered. Only set
+    // flags are written.
+    hidden_: bool,
+    default_: bool,
+    ordered_: bool,
+
     // Array for storage of chapter objects.
     chapters_: Vec<Chapter>,
 }
 
-impl Chapters {
-    pub fn new() -> Chapters {
-        Chapters {
+impl Default for Edition {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Edition {
+    pub fn new() -> Edition {
+        Edition {
+            uid_: 0,
+            hidden_: false,
+            default_: false,
+            ordered_: false,
             chapters_: Vec::new(),
         }
     }
 
+    pub fn set_uid(&mut self, uid: u64) {
+        self.uid_ = uid;
+    }
+
+    pub fn uid(&self) -> u64 {
+        self.uid_
+    }
+
+    pub fn set_hidden(&mut self, hidden: bool) {
+        self.hidden_ = hidden;
+    }
+
+    pub fn hidden(&self) -> bool {
+        self.hidden_
+    }
+
+    pub fn set_default(&mut self, default: bool) {
+        self.default_ = default;
+    }
+
+    pub fn default(&self) -> bool {
+        self.default_
+    }
+
+    pub fn set_ordered(&mut self, ordered: bool) {
+        self.ordered_ = ordered;
+    }
+
+    pub fn ordered(&self) -> bool {
+        self.ordered_
+    }
+
     pub fn count(&self) -> usize {
         self.chapters_.len()
     }
@@ -23,8 +78,34 @@ impl Chapters {
         self.chapters_.push(chapter);
     }
 
+    // Adds an empty Chapter and returns it so the caller can populate it.
+    pub fn AddEmptyChapter(&mut self) -> &mut Chapter {
+        self.chapters_.push(Chapter::new());
+        self.chapters_.last_mut().unwrap()
+    }
+
+    pub fn GetChapter(&mut self, index: usize) -> Option<&mut Chapter> {
+        self.chapters_.get_mut(index)
+    }
+
+    pub fn WebmCompatible(&self) -> bool {
+        self.chapters_.iter().all(|c| c.WebmCompatible())
+    }
+
     pub fn PayloadSize(&self) -> u64 {
         let mut payload_size = 0;
+        if self.uid_ != 0 {
+            payload_size += util::EbmlElementSizeArgU64(MkvId::MkvEditionUID, self.uid_);
+        }
+        if self.hidden_ {
+            payload_size += util::EbmlElementSizeArgU64(MkvId::MkvEditionFlagHidden, 1);
+        }
+        if self.default_ {
+            payload_size += util::EbmlElementSizeArgU64(MkvId::MkvEditionFlagDefault, 1);
+        }
+        if self.ordered_ {
+            payload_size += util::EbmlElementSizeArgU64(MkvId::MkvEditionFlagOrdered, 1);
+        }
         for chapter in &self.chapters_ {
             let chapter_payload_size = chapter.PayloadSize();
             payload_size +=
@@ -34,32 +115,135 @@ impl Chapters {
         payload_size
     }
 
-    pub fn Write(&self, writer: &mut dyn Writer) -> bool {
+    pub fn Write(&self, writer: &mut dyn Writer) -> Result<()> {
+        // An EditionEntry must hold at least one ChapterAtom.
+        if self.chapters_.is_empty() {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvEditionEntry,
+                "edition has no chapters",
+            ));
+        }
+
         let payload_size = self.PayloadSize();
         let edition_size =
             util::EbmlMasterElementSize(MkvId::MkvEditionEntry, payload_size) + payload_size;
 
-        if !util::WriteEbmlMasterElement(writer, MkvId::MkvChapters, payload_size) {
-            return false;
+        let start = writer.get_position();
+
+        util::WriteEbmlMasterElement(writer, MkvId::MkvEditionEntry, payload_size)?;
+
+        if self.uid_ != 0 {
+            util::WriteEbmlElementArgU64(writer, MkvId::MkvEditionUID, self.uid_)?;
         }
 
-        let start = writer.get_position();
+        if self.hidden_ {
+            util::WriteEbmlElementArgU64(writer, MkvId::MkvEditionFlagHidden, 1)?;
+        }
 
-        if !util::WriteEbmlMasterElement(writer, MkvId::MkvEditionEntry, payload_size) {
-            return false;
+        if self.default_ {
+            util::WriteEbmlElementArgU64(writer, MkvId::MkvEditionFlagDefault, 1)?;
+        }
+
+        if self.ordered_ {
+            util::WriteEbmlElementArgU64(writer, MkvId::MkvEditionFlagOrdered, 1)?;
         }
 
         for chapter in &self.chapters_ {
-            if !chapter.Write(writer) {
-                return false;
-            }
+            chapter.Write(writer)?;
         }
 
         let stop = writer.get_position();
-        if stop - start != edition_size {
-            return false;
+        CheckSize(MkvId::MkvEditionEntry, edition_size, stop - start)?;
+
+        Ok(())
+    }
+}
+
+pub struct Chapters {
+    // Array for storage of edition objects.
+    editions_: Vec<Edition>,
+}
+
+impl Default for Chapters {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Chapters {
+    pub fn new() -> Chapters {
+        Chapters {
+            editions_: Vec::new(),
+        }
+    }
+
+    // Returns the number of chapters in all editions.
+    pub fn count(&self) -> usize {
+        self.editions_.iter().map(|edition| edition.count()).sum()
+    }
+
+    pub fn edition_count(&self) -> usize {
+        self.editions_.len()
+    }
+
+    // Adds an empty Edition and returns it so the caller can populate it.
+    // Chapters added with |AddChapter| go to the last edition.
+    pub fn AddEdition(&mut self) -> &mut Edition {
+        self.editions_.push(Edition::new());
+        self.editions_.last_mut().unwrap()
+    }
+
+    pub fn GetEdition(&mut self, index: usize) -> Option<&mut Edition> {
+        self.editions_.get_mut(index)
+    }
+
+    // Returns the edition new chapters are added to, creating one if needed.
+    fn LastEdition(&mut self) -> &mut Edition {
+        if self.editions_.is_empty() {
+            self.editions_.push(Edition::new());
+        }
+        self.editions_.last_mut().unwrap()
+    }
+
+    pub fn AddChapter(&mut self, chapter: Chapter) {
+        self.LastEdition().AddChapter(chapter);
+    }
+
+    // Adds an empty Chapter and returns it so the caller can populate it.
+    pub fn AddEmptyChapter(&mut self) -> &mut Chapter {
+        self.LastEdition().AddEmptyChapter()
+    }
+
+    // Returns false if a chapter uses elements that are not allowed in WebM.
+    pub fn WebmCompatible(&self) -> bool {
+        self.editions_.iter().all(|e| e.WebmCompatible())
+    }
+
+    pub fn PayloadSize(&self) -> u64 {
+        let mut payload_size = 0;
+        for edition in &self.editions_ {
+            let edition_payload_size = edition.PayloadSize();
+            payload_size +=
+                util::EbmlMasterElementSize(MkvId::MkvEditionEntry, edition_payload_size)
+                    + edition_payload_size;
         }
+        payload_size
+    }
+
+    pub fn Write(&self, writer: &mut dyn Writer) -> Result<()> {
+        let payload_size = self.PayloadSize();
+
+        util::WriteEbmlMasterElement(writer, MkvId::MkvChapters, payload_size)?;
+
+        let start = writer.get_position();
+
+        for edition in &self.editions_ {
+            edition.Write(writer)?;
+        }
+
+        let stop = writer.get_position();
+        CheckSize(MkvId::MkvChapters, payload_size, stop - start)?;
 
-        true
+        Ok(())
     }
 }
@@ -1,11 +1,13 @@
+use super::error::{IoError, MuxerError, Result};
 use super::frame::Frame;
 use super::util;
-use super::writer::Writer;
+use super::writer::{Crc32Writer, MemoryWriter, Writer};
 use crate::MkvId;
 
 use std::cmp::Reverse;
 use std::collections::BinaryHeap;
 use std::collections::HashMap;
+use std::io;
 
 pub struct Cluster {
     // Number of blocks added to the cluster.
@@ -24,12 +26,44 @@ pub struct Cluster {
     // The size of the cluster elements in bytes.
     payload_size_: u64,
 
-    // The file position used for cue points.
+    // The file position used for cue points. Also written as the Position
+    // element when |write_position_| is set.
     position_for_cues_: i64,
 
+    // Flag telling if the cluster's Position element is written.
+    write_position_: bool,
+
+    // The file position of the cluster's Position element, or -1 if it has not
+    // been written.
+    position_element_pos_: i64,
+
+    // The size of the previous cluster, written as the PrevSize element. 0 if
+    // PrevSize is not written.
+    prev_size_: u64,
+
+    // Flag telling if a CRC-32 element is written in the cluster. The CRC-32
+    // is only written when the cluster's size is written as well.
+    write_crc32_: bool,
+
+    // The file position of the cluster's CRC-32 element, or -1 if it has not
+    // been written.
+    crc32_pos_: i64,
+
+    // The CRC-32 of the cluster's payload following the CRC-32 element.
+    crc32_: u32,
+
+    // When set, a copy of the bytes of the cluster, kept to sign the Segment.
+    // Until the cluster is finalized it only holds the payload following the
+    // CRC-32 element.
+    data_: Option<Vec<u8>>,
+
     // The file position of the cluster's size element.
     size_position_: i64,
 
+    // Flag telling if the cluster's size is left unknown when the cluster is
+    // finalized, so that the writer is never seeked.
+    unknown_size_: bool,
+
     // The absolute timecode of the cluster.
     timecode_: u64,
 
@@ -65,7 +99,15 @@ impl Cluster {
             header_written_: false,
             payload_size_: 0,
             position_for_cues_: cues_pos,
+            write_position_: false,
+            position_element_pos_: -1,
+            prev_size_: 0,
+            write_crc32_: false,
+            crc32_pos_: -1,
+            crc32_: 0,
+            data_: None,
             size_position_: -1,
+            unknown_size_: false,
             timecode_: timecode,
             timecode_scale_: timecode_scale,
             write_last_frame_with_duration_: write_last_frame_with_duration,
@@ -74,28 +116,64 @@ impl Cluster {
         }
     }
     pub fn size_position(&self) -> i64 {
-        return self.size_position_;
+        self.size_position_
+    }
+    pub fn set_unknown_size(&mut self, unknown_size: bool) {
+        self.unknown_size_ = unknown_size;
+    }
+    pub fn unknown_size(&self) -> bool {
+        self.unknown_size_
     }
     pub fn blocks_added(&self) -> i32 {
-        return self.blocks_added_;
+        self.blocks_added_
     }
     pub fn payload_size(&self) -> u64 {
-        return self.payload_size_;
+        self.payload_size_
     }
     pub fn position_for_cues(&self) -> i64 {
-        return self.position_for_cues_;
+        self.position_for_cues_
+    }
+    pub fn set_write_position(&mut self, write_position: bool) {
+        self.write_position_ = write_position;
+    }
+    pub fn write_position(&self) -> bool {
+        self.write_position_
+    }
+    pub fn position_element_pos(&self) -> i64 {
+        self.position_element_pos_
+    }
+    pub fn set_prev_size(&mut self, prev_size: u64) {
+        self.prev_size_ = prev_size;
+    }
+    pub fn prev_size(&self) -> u64 {
+        self.prev_size_
+    }
+    pub fn set_write_crc32(&mut self, write_crc32: bool) {
+        self.write_crc32_ = write_crc32;
+    }
+    pub fn write_crc32(&self) -> bool {
+        self.write_crc32_
+    }
+    pub fn crc32_pos(&self) -> i64 {
+        self.crc32_pos_
+    }
+    pub fn set_keep_data(&mut self, keep_data: bool) {
+        self.data_ = if keep_data { Some(Vec::new()) } else { None };
+    }
+    pub fn keep_data(&self) -> bool {
+        self.data_.is_some()
     }
     pub fn timecode(&self) -> u64 {
-        return self.timecode_;
+        self.timecode_
     }
     pub fn timecode_scale(&self) -> u64 {
-        return self.timecode_scale_;
+        self.timecode_scale_
     }
     pub fn set_write_last_frame_with_duration(&mut self, write_last_frame_with_duration: bool) {
         self.write_last_frame_with_duration_ = write_last_frame_with_duration;
     }
     pub fn write_last_frame_with_duration(&self) -> bool {
-        return self.write_last_frame_with_duration_;
+        self.write_last_frame_with_duration_
     }
 
     fn AddPayloadSize(&mut self, size: u64) {
@@ -103,55 +181,122 @@ impl Cluster {
     }
 
     pub fn Size(&self) -> u64 {
-        let element_size =
-            util::EbmlMasterElementSize(MkvId::MkvCluster, 0xFFFFFFFFFFFFFFFF) + self.payload_size_;
-        element_size
+        util::EbmlMasterElementSize(MkvId::MkvCluster, 0xFFFFFFFFFFFFFFFF) + self.payload_size_
     }
 
-    fn PreWriteBlock(&mut self, writer: &mut dyn Writer) -> bool {
+    fn PreWriteBlock(&mut self, writer: &mut dyn Writer) -> Result<()> {
         if self.finalized_ {
-            return false;
+            return Err(MuxerError::kClusterFinalized);
         }
 
         if !self.header_written_ {
-            if !self.WriteClusterHeader(writer) {
-                return false;
-            }
+            self.WriteClusterHeader(writer)?;
         }
 
-        true
+        Ok(())
     }
 
-    fn WriteClusterHeader(&mut self, writer: &mut dyn Writer) -> bool {
+    fn WriteClusterHeader(&mut self, writer: &mut dyn Writer) -> Result<()> {
         if self.finalized_ {
-            return false;
+            return Err(MuxerError::kClusterFinalized);
         }
 
-        if let Err(e) = util::WriteID(writer, MkvId::MkvCluster) {
-            return false;
-        }
+        util::WriteID(writer, MkvId::MkvCluster).map_err(IoError(MkvId::MkvCluster))?;
 
         // Save for later.
         self.size_position_ = writer.get_position() as i64;
 
         // Write "unknown" (EBML coded -1) as cluster size value. We need to write 8
         // bytes because we do not know how big our cluster will be.
-        if let Err(e) = util::SerializeInt(writer, util::EBML_UNKNOWN_VALUE, 8) {
-            return false;
+        util::SerializeInt(writer, util::EBML_UNKNOWN_VALUE, 8)
+            .map_err(IoError(MkvId::MkvCluster))?;
+
+        if self.write_crc32_ && writer.seekable() && !self.unknown_size_ {
+            // The CRC-32 element must be the first child. Its value is written
+            // once the cluster is finalized.
+            self.crc32_pos_ = writer.get_position() as i64;
+            util::WriteCrc32Element(writer, 0)?;
+            self.AddPayloadSize(util::CRC32_ELEMENT_SIZE);
         }
+
         let timecode_size = if self.fixed_size_timecode_ { 8 } else { 0 };
         let timecode = self.timecode();
-        if !util::WriteEbmlElementArgsU64(writer, MkvId::MkvTimecode, timecode, timecode_size) {
-            return false;
-        }
-        self.AddPayloadSize(util::EbmlElementSizeArgsU64(
-            MkvId::MkvTimecode,
-            timecode,
-            timecode_size,
-        ));
+        self.WriteHashed(writer, |cluster, writer| {
+            util::WriteEbmlElementArgsU64(writer, MkvId::MkvTimecode, timecode, timecode_size)?;
+            cluster.AddPayloadSize(util::EbmlElementSizeArgsU64(
+                MkvId::MkvTimecode,
+                timecode,
+                timecode_size,
+            ));
+
+            if cluster.write_position_ {
+                // Always use 8 bytes so the value can be rewritten in place if
+                // the Clusters are moved.
+                cluster.position_element_pos_ = writer.get_position() as i64;
+                let position = cluster.position_for_cues_ as u64;
+                util::WriteEbmlElementArgsU64(writer, MkvId::MkvPosition, position, 8)?;
+                cluster.AddPayloadSize(util::EbmlElementSizeArgsU64(
+                    MkvId::MkvPosition,
+                    position,
+                    8,
+                ));
+            }
+
+            if cluster.prev_size_ > 0 {
+                let prev_size = cluster.prev_size_;
+                util::WriteEbmlElementArgsU64(writer, MkvId::MkvPrevSize, prev_size, 0)?;
+                cluster.AddPayloadSize(util::EbmlElementSizeArgsU64(
+                    MkvId::MkvPrevSize,
+                    prev_size,
+                    0,
+                ));
+            }
+            Ok(())
+        })?;
         self.header_written_ = true;
 
-        true
+        Ok(())
+    }
+
+    // Runs |write| with a writer that adds the bytes written to the cluster's
+    // CRC-32 and to its copy of the data, if the cluster has them.
+    fn WriteHashed<T>(
+        &mut self,
+        writer: &mut dyn Writer,
+        write: impl FnOnce(&mut Self, &mut dyn Writer) -> Result<T>,
+    ) -> Result<T> {
+        let mut data = self.data_.take();
+        let mut copy_writer;
+        let writer: &mut dyn Writer = match data.as_mut() {
+            Some(data) => {
+                copy_writer = CopyWriter {
+                    inner: writer,
+                    data,
+                };
+                &mut copy_writer
+            }
+            None => writer,
+        };
+
+        let result = if self.crc32_pos_ == -1 {
+            write(self, writer)
+        } else {
+            let mut crc_writer = Crc32Writer::new(writer, self.crc32_);
+            let result = write(self, &mut crc_writer);
+            self.crc32_ = crc_writer.crc();
+            result
+        };
+        self.data_ = data;
+        result
+    }
+
+    // Returns the bytes of the finalized cluster, if the cluster keeps a copy
+    // of them.
+    pub fn TakeData(&mut self) -> Option<Vec<u8>> {
+        if !self.finalized_ {
+            return None;
+        }
+        self.data_.take()
     }
 
     fn PostWriteBlock(&mut self, element_size: u64) {
@@ -163,26 +308,34 @@ impl Cluster {
         let cluster_timecode = self.timecode() as i64;
         let rel_timecode = abs_timecode - cluster_timecode;
 
-        if rel_timecode < 0 || rel_timecode > util::MAX_BLOCK_TIMECODE {
+        if !(0..=util::MAX_BLOCK_TIMECODE).contains(&rel_timecode) {
             return -1;
         }
 
-        return rel_timecode;
+        rel_timecode
     }
 
-    pub fn WriteFrame(&mut self, writer: &mut dyn Writer, frame: &Frame) -> u64 {
-        if !frame.IsValid() || self.timecode_scale() == 0 {
-            return 0;
+    // Writes |frame| as a SimpleBlock or a BlockGroup and returns the number of
+    // bytes written.
+    pub fn WriteFrame(&mut self, writer: &mut dyn Writer, frame: &Frame) -> Result<u64> {
+        frame.Validate()?;
+        if self.timecode_scale() == 0 {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvTimecodeScale,
+                "timecode scale is 0",
+            ));
         }
 
         //  Technically the timecode for a block can be less than the
         //  timecode for the cluster itself (remember that block timecode
         //  is a signed, 16-bit integer).  However, as a simplification we
         //  only permit non-negative cluster-relative timecodes for blocks.
-        let relative_timecode =
-            self.GetRelativeTimecode((frame.timestamp() / self.timecode_scale()) as i64);
-        if relative_timecode < 0 || relative_timecode > util::MAX_BLOCK_TIMECODE {
-            return 0;
+        let abs_timecode = (frame.timestamp() / self.timecode_scale()) as i64;
+        let relative_timecode = self.GetRelativeTimecode(abs_timecode);
+        if !(0..=util::MAX_BLOCK_TIMECODE).contains(&relative_timecode) {
+            return Err(MuxerError::kTimecodeOutOfRange(
+                abs_timecode - self.timecode() as i64,
+            ));
         }
 
         if frame.CanBeSimpleBlock() {
@@ -193,29 +346,24 @@ impl Cluster {
         }
     }
 
-    fn DoWriteFrame(&mut self, writer: &mut dyn Writer, frame: &Frame) -> bool {
-        if !frame.IsValid() {
-            return false;
-        }
+    fn DoWriteFrame(&mut self, writer: &mut dyn Writer, frame: &Frame) -> Result<()> {
+        frame.Validate()?;
 
-        if !self.PreWriteBlock(writer) {
-            return false;
-        }
+        self.PreWriteBlock(writer)?;
 
-        let element_size = self.WriteFrame(writer, frame);
-        if element_size == 0 {
-            return false;
-        }
+        let element_size =
+            self.WriteHashed(writer, |cluster, writer| cluster.WriteFrame(writer, frame))?;
 
         self.PostWriteBlock(element_size);
         self.last_block_timestamp_
             .insert(frame.track_number(), frame.timestamp());
-        true
+        Ok(())
     }
 
-    fn QueueOrWriteFrame(&mut self, writer: &mut dyn Writer, frame: &Frame) -> bool {
-        if !frame.IsValid() {
-            return false;
+    fn QueueOrWriteFrame(&mut self, writer: &mut dyn Writer, frame: &Frame) -> Result<()> {
+        frame.Validate()?;
+        if self.finalized_ {
+            return Err(MuxerError::kClusterFinalized);
         }
 
         // If |write_last_frame_with_duration_| is not set, then write the frame right
@@ -232,7 +380,8 @@ impl Cluster {
         // and write it if it is okay to do so (i.e.) no other track has an held back
         // frame with timestamp <= the timestamp of the frame in question.
         if let Some(mut frames) = self.stored_frames_.remove(&track_number) {
-            frames.retain(|frame_to_write| {
+            let mut written = 0;
+            for frame_to_write in &frames {
                 let mut okay_to_write = true;
                 for (key, val) in self.stored_frames_.iter() {
                     if *key == track_number {
@@ -244,12 +393,13 @@ impl Cluster {
                         }
                     }
                 }
-                if okay_to_write {
-                    !self.DoWriteFrame(writer, frame_to_write)
-                } else {
-                    false
+                if !okay_to_write {
+                    break;
                 }
-            });
+                self.DoWriteFrame(writer, frame_to_write)?;
+                written += 1;
+            }
+            frames.drain(..written);
 
             frames.push(frame_to_store);
             self.stored_frames_.insert(track_number, frames);
@@ -258,7 +408,7 @@ impl Cluster {
                 .insert(track_number, vec![frame_to_store]);
         }
 
-        return true;
+        Ok(())
     }
 
     pub fn AddNewFrame(
@@ -268,21 +418,20 @@ impl Cluster {
         track_number: u64,
         abs_timecode: u64,
         is_key: bool,
-    ) -> bool {
+    ) -> Result<()> {
         let mut frame = Frame::new();
-        if !frame.Init(data) {
-            return false;
-        }
+        frame.Init(data);
         frame.set_track_number(track_number);
         frame.set_timestamp(abs_timecode);
         frame.set_is_key(is_key);
-        return self.QueueOrWriteFrame(writer, &frame);
+        self.QueueOrWriteFrame(writer, &frame)
     }
 
-    pub fn AddFrame(&mut self, writer: &mut dyn Writer, frame: &Frame) -> bool {
-        return self.QueueOrWriteFrame(writer, frame);
+    pub fn AddFrame(&mut self, writer: &mut dyn Writer, frame: &Frame) -> Result<()> {
+        self.QueueOrWriteFrame(writer, frame)
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn AddFrameWithAdditional(
         &mut self,
         writer: &mut dyn Writer,
@@ -292,18 +441,20 @@ impl Cluster {
         track_number: u64,
         abs_timecode: u64,
         is_key: bool,
-    ) -> bool {
+    ) -> Result<()> {
         if additional.is_empty() {
-            return false;
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvBlockAdditional,
+                "empty additional data",
+            ));
         }
         let mut frame = Frame::new();
-        if !frame.Init(data) || !frame.AddAdditionalData(additional, add_id) {
-            return false;
-        }
+        frame.Init(data);
+        frame.AddAdditionalData(additional, add_id);
         frame.set_track_number(track_number);
         frame.set_timestamp(abs_timecode);
         frame.set_is_key(is_key);
-        return self.QueueOrWriteFrame(writer, &frame);
+        self.QueueOrWriteFrame(writer, &frame)
     }
 
     pub fn AddFrameWithDiscardPadding(
@@ -314,16 +465,14 @@ impl Cluster {
         track_number: u64,
         abs_timecode: u64,
         is_key: bool,
-    ) -> bool {
+    ) -> Result<()> {
         let mut frame = Frame::new();
-        if !frame.Init(data) {
-            return false;
-        }
+        frame.Init(data);
         frame.set_discard_padding(discard_padding);
         frame.set_track_number(track_number);
         frame.set_timestamp(abs_timecode);
         frame.set_is_key(is_key);
-        return self.QueueOrWriteFrame(writer, &frame);
+        self.QueueOrWriteFrame(writer, &frame)
     }
 
     pub fn AddMetadata(
@@ -333,16 +482,14 @@ impl Cluster {
         track_number: u64,
         abs_timecode: u64,
         duration_timecode: u64,
-    ) -> bool {
+    ) -> Result<()> {
         let mut frame = Frame::new();
-        if !frame.Init(data) {
-            return false;
-        }
+        frame.Init(data);
         frame.set_track_number(track_number);
         frame.set_timestamp(abs_timecode);
         frame.set_duration(duration_timecode);
         frame.set_is_key(true); // All metadata blocks are keyframes.
-        return self.QueueOrWriteFrame(writer, &frame);
+        self.QueueOrWriteFrame(writer, &frame)
     }
 
     pub fn finalize(
@@ -350,9 +497,9 @@ impl Cluster {
         writer: &mut dyn Writer,
         set_last_frame_duration: bool,
         duration: u64,
-    ) -> bool {
+    ) -> Result<()> {
         if self.finalized_ {
-            return false;
+            return Err(MuxerError::kClusterFinalized);
         }
 
         if self.write_last_frame_with_duration_ {
@@ -372,11 +519,21 @@ impl Cluster {
                     && self.stored_frames_[&frame.track_number()].is_empty()
                     && !frame.duration_set()
                 {
-                    frame.set_duration(duration - frame.timestamp());
-                    if !frame.is_key() && !frame.reference_block_timestamp_set() {
-                        frame.set_reference_block_timestamp(
-                            self.last_block_timestamp_[&frame.track_number()] as i64,
-                        );
+                    let last_block_timestamp = self
+                        .last_block_timestamp_
+                        .get(&frame.track_number())
+                        .copied();
+                    let needs_reference = !frame.is_key() && !frame.reference_block_timestamp_set();
+                    match last_block_timestamp {
+                        Some(timestamp) if needs_reference => {
+                            frame.set_duration(duration - frame.timestamp());
+                            frame.set_reference_block_timestamp(timestamp as i64);
+                        }
+                        // A non-key frame with no earlier block of its track in
+                        // the Cluster has nothing to reference, so it is left a
+                        // SimpleBlock without a duration.
+                        None if needs_reference => {}
+                        _ => frame.set_duration(duration - frame.timestamp()),
                     }
                 }
 
@@ -388,39 +545,94 @@ impl Cluster {
                     }
                 }
 
-                if !wrote_frame {
-                    return false;
-                }
+                wrote_frame?;
             }
         }
 
         if self.size_position_ == -1 {
-            return false;
+            return Err(MuxerError::kInvalidState("Cluster header was not written"));
         }
 
-        if writer.seekable() {
+        if writer.seekable() && !self.unknown_size_ {
             let pos = writer.get_position();
 
-            if let Err(e) = writer.set_position(self.size_position_ as u64) {
-                return false;
-            }
+            writer
+                .set_position(self.size_position_ as u64)
+                .map_err(IoError(MkvId::MkvCluster))?;
 
             let payload_size = self.payload_size();
-            if let Err(e) = util::WriteUIntSize(writer, payload_size, 8) {
-                return false;
+            util::WriteUIntSize(writer, payload_size, 8).map_err(IoError(MkvId::MkvCluster))?;
+
+            if self.crc32_pos_ != -1 {
+                writer
+                    .set_position(self.crc32_pos_ as u64)
+                    .map_err(IoError(MkvId::MkvCrc32))?;
+                util::WriteCrc32Element(writer, self.crc32_)?;
             }
 
-            if let Err(e) = writer.set_position(pos) {
-                return false;
+            writer
+                .set_position(pos)
+                .map_err(IoError(MkvId::MkvCluster))?;
+        }
+
+        if let Some(data) = self.data_.as_mut() {
+            // Put the final header in front of the copy of the payload.
+            let mut header = MemoryWriter::new();
+            util::WriteID(&mut header, MkvId::MkvCluster).map_err(IoError(MkvId::MkvCluster))?;
+            if writer.seekable() && !self.unknown_size_ {
+                util::WriteUIntSize(&mut header, self.payload_size_, 8)
+            } else {
+                util::SerializeInt(&mut header, util::EBML_UNKNOWN_VALUE, 8)
+            }
+            .map_err(IoError(MkvId::MkvCluster))?;
+            if self.crc32_pos_ != -1 {
+                util::WriteCrc32Element(&mut header, self.crc32_)?;
             }
+            data.splice(0..0, header.into_inner());
         }
 
         self.finalized_ = true;
 
-        return true;
+        Ok(())
+    }
+
+    pub fn Finalize(&mut self, writer: &mut dyn Writer) -> Result<()> {
+        if self.write_last_frame_with_duration_ {
+            return Err(MuxerError::kInvalidState(
+                "Cluster holds back frames; call finalize() with a duration",
+            ));
+        }
+        self.finalize(writer, false, 0)
+    }
+}
+
+// Writer that forwards to another writer and keeps a copy of the bytes
+// written through it.
+struct CopyWriter<'a> {
+    inner: &'a mut dyn Writer,
+    data: &'a mut Vec<u8>,
+}
+
+impl<'a> Writer for CopyWriter<'a> {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<()> {
+        self.inner.write(buffer)?;
+        self.data.extend_from_slice(buffer);
+        Ok(())
+    }
+
+    fn get_position(&self) -> u64 {
+        self.inner.get_position()
+    }
+
+    fn set_position(&mut self, position: u64) -> io::Result<()> {
+        self.inner.set_position(position)
+    }
+
+    fn seekable(&self) -> bool {
+        self.inner.seekable()
     }
 
-    pub fn Finalize(&mut self, writer: &mut dyn Writer) -> bool {
-        !self.write_last_frame_with_duration_ && self.finalize(writer, false, 0)
+    fn element_start_notify(&self, element_id: MkvId, position: u64) {
+        self.inner.element_start_notify(element_id, position)
     }
 }
@@ -1,3 +1,4 @@
+use super::error::{MuxerError, Result};
 use super::util;
 use super::writer::Writer;
 use crate::MkvId;
@@ -6,11 +7,17 @@ use crate::MkvId;
 // Colour element.
 
 #[derive(Debug, Copy, Clone)]
-struct PrimaryChromaticity {
+pub struct PrimaryChromaticity {
     x_: f32,
     y_: f32,
 }
 
+impl Default for PrimaryChromaticity {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl PrimaryChromaticity {
     const kChromaticityMin: f32 = 0.0;
     const kChromaticityMax: f32 = 1.0;
@@ -36,12 +43,12 @@ impl PrimaryChromaticity {
         util::EbmlElementSizeArgF32(x_id, self.x_) + util::EbmlElementSizeArgF32(y_id, self.y_)
     }
 
-    pub fn Write(&self, writer: &mut dyn Writer, x_id: MkvId, y_id: MkvId) -> bool {
+    pub fn Write(&self, writer: &mut dyn Writer, x_id: MkvId, y_id: MkvId) -> Result<()> {
         if !self.Valid() {
-            return false;
+            return Err(MuxerError::kInvalidValue(x_id, "chromaticity out of range"));
         }
-        util::WriteEbmlElementArgF32(writer, x_id, self.x_)
-            && util::WriteEbmlElementArgF32(writer, y_id, self.y_)
+        util::WriteEbmlElementArgF32(writer, x_id, self.x_)?;
+        util::WriteEbmlElementArgF32(writer, y_id, self.y_)
     }
 
     pub fn Valid(&self) -> bool {
@@ -62,11 +69,17 @@ pub struct MasteringMetadata {
     white_point_: Option<PrimaryChromaticity>,
 }
 
+impl Default for MasteringMetadata {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MasteringMetadata {
     const kMinLuminance: f32 = 0.0;
     const kMinLuminanceMax: f32 = 999.99;
     const kMaxLuminanceMax: f32 = 9999.99;
-    const kValueNotPresent: f32 = std::f32::MAX;
+    const kValueNotPresent: f32 = f32::MAX;
 
     pub fn r(&self) -> Option<&PrimaryChromaticity> {
         self.r_.as_ref()
@@ -114,21 +127,19 @@ impl MasteringMetadata {
     }
 
     pub fn Valid(&self) -> bool {
-        if self.luminance_min_ != Self::kValueNotPresent {
-            if self.luminance_min_ < Self::kMinLuminance
+        if self.luminance_min_ != Self::kValueNotPresent
+            && (self.luminance_min_ < Self::kMinLuminance
                 || self.luminance_min_ > Self::kMinLuminanceMax
-                || self.luminance_min_ > self.luminance_max_
-            {
-                return false;
-            }
+                || self.luminance_min_ > self.luminance_max_)
+        {
+            return false;
         }
-        if self.luminance_max_ != Self::kValueNotPresent {
-            if self.luminance_max_ < Self::kMinLuminance
+        if self.luminance_max_ != Self::kValueNotPresent
+            && (self.luminance_max_ < Self::kMinLuminance
                 || self.luminance_max_ > Self::kMaxLuminanceMax
-                || self.luminance_max_ < self.luminance_min_
-            {
-                return false;
-            }
+                || self.luminance_max_ < self.luminance_min_)
+        {
+            return false;
         }
         if self.r_.is_some() && !self.r_.as_ref().unwrap().Valid() {
             return false;
@@ -146,65 +157,51 @@ impl MasteringMetadata {
         true
     }
 
-    pub fn Write(&self, writer: &mut dyn Writer) -> bool {
+    pub fn Write(&self, writer: &mut dyn Writer) -> Result<()> {
         let size = self.PayloadSize();
 
         // Don't write an empty element.
         if size == 0 {
-            return true;
+            return Ok(());
         }
 
-        if !util::WriteEbmlMasterElement(writer, MkvId::MkvMasteringMetadata, size) {
-            return false;
-        }
-        if self.luminance_max_ != Self::kValueNotPresent
-            && !util::WriteEbmlElementArgF32(writer, MkvId::MkvLuminanceMax, self.luminance_max_)
-        {
-            return false;
+        util::WriteEbmlMasterElement(writer, MkvId::MkvMasteringMetadata, size)?;
+        if self.luminance_max_ != Self::kValueNotPresent {
+            util::WriteEbmlElementArgF32(writer, MkvId::MkvLuminanceMax, self.luminance_max_)?;
         }
-        if self.luminance_min_ != Self::kValueNotPresent
-            && !util::WriteEbmlElementArgF32(writer, MkvId::MkvLuminanceMin, self.luminance_min_)
-        {
-            return false;
+        if self.luminance_min_ != Self::kValueNotPresent {
+            util::WriteEbmlElementArgF32(writer, MkvId::MkvLuminanceMin, self.luminance_min_)?;
         }
-        if self.r_.is_some()
-            && !self.r_.as_ref().unwrap().Write(
+        if let Some(r) = self.r_.as_ref() {
+            r.Write(
                 writer,
                 MkvId::MkvPrimaryRChromaticityX,
                 MkvId::MkvPrimaryRChromaticityY,
-            )
-        {
-            return false;
+            )?;
         }
-        if self.g_.is_some()
-            && !self.g_.as_ref().unwrap().Write(
+        if let Some(g) = self.g_.as_ref() {
+            g.Write(
                 writer,
                 MkvId::MkvPrimaryGChromaticityX,
                 MkvId::MkvPrimaryGChromaticityY,
-            )
-        {
-            return false;
+            )?;
         }
-        if self.b_.is_some()
-            && !self.b_.as_ref().unwrap().Write(
+        if let Some(b) = self.b_.as_ref() {
+            b.Write(
                 writer,
                 MkvId::MkvPrimaryBChromaticityX,
                 MkvId::MkvPrimaryBChromaticityY,
-            )
-        {
-            return false;
+            )?;
         }
-        if self.white_point_.is_some()
-            && !self.white_point_.as_ref().unwrap().Write(
+        if let Some(w) = self.white_point_.as_ref() {
+            w.Write(
                 writer,
                 MkvId::MkvWhitePointChromaticityX,
                 MkvId::MkvWhitePointChromaticityY,
-            )
-        {
-            return false;
+            )?;
         }
 
-        true
+        Ok(())
     }
 
     pub fn SetChromaticity(
@@ -214,11 +211,11 @@ impl MasteringMetadata {
         b: &PrimaryChromaticity,
         white_point: &PrimaryChromaticity,
     ) -> bool {
-        self.r_ = Some(r.clone());
-        self.g_ = Some(g.clone());
-        self.b_ = Some(b.clone());
-        self.white_point_ = Some(white_point.clone());
-        return true;
+        self.r_ = Some(*r);
+        self.g_ = Some(*g);
+        self.b_ = Some(*b);
+        self.white_point_ = Some(*white_point);
+        true
     }
 
     pub fn PayloadSize(&self) -> u64 {
@@ -445,8 +442,14 @@ pub struct Colour {
     mastering_metadata_: Option<MasteringMetadata>,
 }
 
+impl Default for Colour {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Colour {
-    const kValueNotPresent: u64 = std::u64::MAX;
+    const kValueNotPresent: u64 = u64::MAX;
 
     pub fn new() -> Colour {
         Colour {
@@ -471,7 +474,7 @@ impl Colour {
         self.mastering_metadata_.as_ref()
     }
     pub fn set_mastering_metadata(&mut self, mastering_metadata: &MasteringMetadata) {
-        self.mastering_metadata_ = Some(mastering_metadata.clone());
+        self.mastering_metadata_ = Some(*mastering_metadata);
     }
     pub fn matrix_coefficients(&self) -> u64 {
         self.matrix_coefficients_
@@ -566,30 +569,30 @@ impl Colour {
             return false;
         }
         if self.matrix_coefficients_ != Self::kValueNotPresent
-            && !MatrixCoefficients::from_u64(self.matrix_coefficients_).is_some()
+            && MatrixCoefficients::from_u64(self.matrix_coefficients_).is_none()
         {
             return false;
         }
         if self.chroma_siting_horz_ != Self::kValueNotPresent
-            && !ChromaSitingHorz::from_u64(self.chroma_siting_horz_).is_some()
+            && ChromaSitingHorz::from_u64(self.chroma_siting_horz_).is_none()
         {
             return false;
         }
         if self.chroma_siting_vert_ != Self::kValueNotPresent
-            && !ChromaSitingVert::from_u64(self.chroma_siting_vert_).is_some()
+            && ChromaSitingVert::from_u64(self.chroma_siting_vert_).is_none()
         {
             return false;
         }
-        if self.range_ != Self::kValueNotPresent && !Range::from_u64(self.range_).is_some() {
+        if self.range_ != Self::kValueNotPresent && Range::from_u64(self.range_).is_none() {
             return false;
         }
         if self.transfer_characteristics_ != Self::kValueNotPresent
-            && !TransferCharacteristics::from_u64(self.transfer_characteristics_).is_some()
+            && TransferCharacteristics::from_u64(self.transfer_characteristics_).is_none()
         {
             return false;
         }
         if self.primaries_ != Self::kValueNotPresent
-            && !Primaries::from_u64(self.primaries_).is_some()
+            && Primaries::from_u64(self.primaries_).is_none()
         {
             return false;
         }
@@ -663,132 +666,101 @@ impl Colour {
         size
     }
 
-    pub fn Write(&self, writer: &mut dyn Writer) -> bool {
+    pub fn Write(&self, writer: &mut dyn Writer) -> Result<()> {
         let size = self.PayloadSize();
 
         // Don't write an empty element.
         if size == 0 {
-            return true;
+            return Ok(());
         }
 
         // Don't write an invalid element.
         if !self.Valid() {
-            return false;
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvColour,
+                "invalid colour values",
+            ));
         }
 
-        if !util::WriteEbmlMasterElement(writer, MkvId::MkvColour, size) {
-            return false;
-        }
+        util::WriteEbmlMasterElement(writer, MkvId::MkvColour, size)?;
 
-        if self.matrix_coefficients_ != Self::kValueNotPresent
-            && !util::WriteEbmlElementArgU64(
+        if self.matrix_coefficients_ != Self::kValueNotPresent {
+            util::WriteEbmlElementArgU64(
                 writer,
                 MkvId::MkvMatrixCoefficients,
                 self.matrix_coefficients_,
-            )
-        {
-            return false;
+            )?;
         }
-        if self.bits_per_channel_ != Self::kValueNotPresent
-            && !util::WriteEbmlElementArgU64(
-                writer,
-                MkvId::MkvBitsPerChannel,
-                self.bits_per_channel_,
-            )
-        {
-            return false;
+        if self.bits_per_channel_ != Self::kValueNotPresent {
+            util::WriteEbmlElementArgU64(writer, MkvId::MkvBitsPerChannel, self.bits_per_channel_)?;
         }
-        if self.chroma_subsampling_horz_ != Self::kValueNotPresent
-            && !util::WriteEbmlElementArgU64(
+        if self.chroma_subsampling_horz_ != Self::kValueNotPresent {
+            util::WriteEbmlElementArgU64(
                 writer,
                 MkvId::MkvChromaSubsamplingHorz,
                 self.chroma_subsampling_horz_,
-            )
-        {
-            return false;
+            )?;
         }
-        if self.chroma_subsampling_vert_ != Self::kValueNotPresent
-            && !util::WriteEbmlElementArgU64(
+        if self.chroma_subsampling_vert_ != Self::kValueNotPresent {
+            util::WriteEbmlElementArgU64(
                 writer,
                 MkvId::MkvChromaSubsamplingVert,
                 self.chroma_subsampling_vert_,
-            )
-        {
-            return false;
+            )?;
         }
 
-        if self.cb_subsampling_horz_ != Self::kValueNotPresent
-            && !util::WriteEbmlElementArgU64(
+        if self.cb_subsampling_horz_ != Self::kValueNotPresent {
+            util::WriteEbmlElementArgU64(
                 writer,
                 MkvId::MkvCbSubsamplingHorz,
                 self.cb_subsampling_horz_,
-            )
-        {
-            return false;
+            )?;
         }
-        if self.cb_subsampling_vert_ != Self::kValueNotPresent
-            && !util::WriteEbmlElementArgU64(
+        if self.cb_subsampling_vert_ != Self::kValueNotPresent {
+            util::WriteEbmlElementArgU64(
                 writer,
                 MkvId::MkvCbSubsamplingVert,
                 self.cb_subsampling_vert_,
-            )
-        {
-            return false;
+            )?;
         }
-        if self.chroma_siting_horz_ != Self::kValueNotPresent
-            && !util::WriteEbmlElementArgU64(
+        if self.chroma_siting_horz_ != Self::kValueNotPresent {
+            util::WriteEbmlElementArgU64(
                 writer,
                 MkvId::MkvChromaSitingHorz,
                 self.chroma_siting_horz_,
-            )
-        {
-            return false;
+            )?;
         }
-        if self.chroma_siting_vert_ != Self::kValueNotPresent
-            && !util::WriteEbmlElementArgU64(
+        if self.chroma_siting_vert_ != Self::kValueNotPresent {
+            util::WriteEbmlElementArgU64(
                 writer,
                 MkvId::MkvChromaSitingVert,
                 self.chroma_siting_vert_,
-            )
-        {
-            return false;
+            )?;
         }
-        if self.range_ != Self::kValueNotPresent
-            && !util::WriteEbmlElementArgU64(writer, MkvId::MkvRange, self.range_)
-        {
-            return false;
+        if self.range_ != Self::kValueNotPresent {
+            util::WriteEbmlElementArgU64(writer, MkvId::MkvRange, self.range_)?;
         }
-        if self.transfer_characteristics_ != Self::kValueNotPresent
-            && !util::WriteEbmlElementArgU64(
+        if self.transfer_characteristics_ != Self::kValueNotPresent {
+            util::WriteEbmlElementArgU64(
                 writer,
                 MkvId::MkvTransferCharacteristics,
                 self.transfer_characteristics_,
-            )
-        {
-            return false;
+            )?;
         }
-        if self.primaries_ != Self::kValueNotPresent
-            && !util::WriteEbmlElementArgU64(writer, MkvId::MkvPrimaries, self.primaries_)
-        {
-            return false;
+        if self.primaries_ != Self::kValueNotPresent {
+            util::WriteEbmlElementArgU64(writer, MkvId::MkvPrimaries, self.primaries_)?;
         }
-        if self.max_cll_ != Self::kValueNotPresent
-            && !util::WriteEbmlElementArgU64(writer, MkvId::MkvMaxCLL, self.max_cll_)
-        {
-            return false;
+        if self.max_cll_ != Self::kValueNotPresent {
+            util::WriteEbmlElementArgU64(writer, MkvId::MkvMaxCLL, self.max_cll_)?;
         }
-        if self.max_fall_ != Self::kValueNotPresent
-            && !util::WriteEbmlElementArgU64(writer, MkvId::MkvMaxFALL, self.max_fall_)
-        {
-            return false;
+        if self.max_fall_ != Self::kValueNotPresent {
+            util::WriteEbmlElementArgU64(writer, MkvId::MkvMaxFALL, self.max_fall_)?;
         }
 
-        if self.mastering_metadata_.is_some()
-            && !self.mastering_metadata_.as_ref().unwrap().Write(writer)
-        {
-            return false;
+        if let Some(mastering_metadata) = self.mastering_metadata_.as_ref() {
+            mastering_metadata.Write(writer)?;
         }
 
-        true
+        Ok(())
     }
 }
@@ -1,3 +1,4 @@
+use super::error::{CheckSize, Result};
 use super::util;
 use super::writer::Writer;
 use crate::MkvId;
@@ -11,6 +12,12 @@ pub struct ContentEncAESSettings {
     cipher_mode_: u64,
 }
 
+impl Default for ContentEncAESSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ContentEncAESSettings {
     pub fn new() -> ContentEncAESSettings {
         ContentEncAESSettings { cipher_mode_: CTR }
@@ -29,24 +36,21 @@ impl ContentEncAESSettings {
         util::EbmlMasterElementSize(MkvId::MkvContentEncAESSettings, payload) + payload
     }
 
-    pub fn Write(&self, writer: &mut dyn Writer) -> bool {
+    pub fn Write(&self, writer: &mut dyn Writer) -> Result<()> {
         let payload = self.PayloadSize();
 
-        if !util::WriteEbmlMasterElement(writer, MkvId::MkvContentEncAESSettings, payload) {
-            return false;
-        }
+        util::WriteEbmlMasterElement(writer, MkvId::MkvContentEncAESSettings, payload)?;
         let payload_position = writer.get_position();
 
-        if !util::WriteEbmlElementArgU64(writer, MkvId::MkvAESSettingsCipherMode, self.cipher_mode_)
-        {
-            return false;
-        }
+        util::WriteEbmlElementArgU64(writer, MkvId::MkvAESSettingsCipherMode, self.cipher_mode_)?;
 
         let stop_position = writer.get_position();
-        if stop_position - payload_position != payload {
-            return false;
-        }
+        CheckSize(
+            MkvId::MkvContentEncAESSettings,
+            payload,
+            stop_position - payload_position,
+        )?;
 
-        return true;
+        Ok(())
     }
 }
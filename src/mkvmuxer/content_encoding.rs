@@ -1,17 +1,43 @@
 use super::content_enc_aes_settings::ContentEncAESSettings;
+use super::content_encryptor::ContentEncryptor;
+use super::error::{CheckSize, IoError, MuxerError, Result};
 use super::util;
 use super::writer::Writer;
 use crate::MkvId;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::io::Write as IoWrite;
+
+// ContentEncodingType values.
+pub const kCompression: u64 = 0;
+pub const kEncryption: u64 = 1;
+
+// ContentEncodingScope flags: what the encoding is applied to. Encodings of
+// the next encoding are not supported by the muxer.
+pub const kScopeFrames: u64 = 1;
+pub const kScopeCodecPrivate: u64 = 2;
+pub const kScopeNextEncoding: u64 = 4;
+
+// ContentCompAlgo values supported by the muxer.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ContentCompAlgo {
+    kZlib = 0,
+    // The bytes in ContentCompSettings are removed from the start of every
+    // frame.
+    kHeaderStripping = 3,
+}
 
 ///////////////////////////////////////////////////////////////
 // ContentEncoding element
 // Elements used to describe if the track data has been encrypted or
 // compressed with zlib or header stripping.
-// Currently only whole frames can be encrypted with AES. This dictates that
-// ContentEncodingOrder will be 0, ContentEncodingScope will be 1,
-// ContentEncodingType will be 1, and ContentEncAlgo will be 5.
+// A track may use several encodings, applied by ascending
+// ContentEncodingOrder. Only AES encryption is supported, so ContentEncAlgo
+// will be 5.
 pub struct ContentEncoding {
     // Track element names
+    comp_algo_: ContentCompAlgo,
+    comp_settings_: Vec<u8>,
     enc_algo_: u64,
     enc_key_id_: Vec<u8>,
     encoding_order_: u64,
@@ -20,18 +46,60 @@ pub struct ContentEncoding {
 
     // ContentEncAESSettings element.
     enc_aes_settings_: ContentEncAESSettings,
+
+    // Encrypts the frames of the track. When not set, frames are expected to
+    // be encrypted by the caller.
+    encryptor_: Option<ContentEncryptor>,
+}
+
+impl Default for ContentEncoding {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ContentEncoding {
+    pub fn comp_algo(&self) -> ContentCompAlgo {
+        self.comp_algo_
+    }
+    pub fn comp_settings(&self) -> &[u8] {
+        &self.comp_settings_
+    }
     pub fn enc_algo(&self) -> u64 {
         self.enc_algo_
     }
+    pub fn set_encoding_order(&mut self, encoding_order: u64) {
+        self.encoding_order_ = encoding_order;
+    }
     pub fn encoding_order(&self) -> u64 {
         self.encoding_order_
     }
+    // |encoding_scope| is a combination of kScopeFrames and
+    // kScopeCodecPrivate. Only compression can apply to the CodecPrivate.
+    pub fn set_encoding_scope(&mut self, encoding_scope: u64) {
+        self.encoding_scope_ = encoding_scope;
+    }
     pub fn encoding_scope(&self) -> u64 {
         self.encoding_scope_
     }
+    // Sets the ContentEncodingType, kCompression or kEncryption. An encoding
+    // with an encryptor can't be made a compression.
+    pub fn set_encoding_type(&mut self, encoding_type: u64) -> Result<()> {
+        if encoding_type != kCompression && encoding_type != kEncryption {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvContentEncodingType,
+                "not a compression or an encryption",
+            ));
+        }
+        if encoding_type == kCompression && self.encryptor_.is_some() {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvContentEncodingType,
+                "the encoding has an encryptor",
+            ));
+        }
+        self.encoding_type_ = encoding_type;
+        Ok(())
+    }
     pub fn encoding_type(&self) -> u64 {
         self.encoding_type_
     }
@@ -39,14 +107,88 @@ impl ContentEncoding {
         &self.enc_aes_settings_
     }
 
+    // Returns an AES encryption (ContentEncAlgo 5) of the frames without an
+    // encryptor, as in libwebm. Use set_encoding_type() for a compression.
     pub fn new() -> ContentEncoding {
         ContentEncoding {
+            comp_algo_: ContentCompAlgo::kZlib,
+            comp_settings_: Vec::new(),
             enc_algo_: 5,
             enc_key_id_: Vec::new(),
             encoding_order_: 0,
-            encoding_scope_: 1,
-            encoding_type_: 1,
+            encoding_scope_: kScopeFrames,
+            encoding_type_: kEncryption,
             enc_aes_settings_: ContentEncAESSettings::new(),
+            encryptor_: None,
+        }
+    }
+
+    // Returns a ContentEncoding encrypting the frames with |encryptor|.
+    pub fn new_encryption(encryptor: ContentEncryptor) -> ContentEncoding {
+        let mut encoding = ContentEncoding::new();
+        encoding.enc_key_id_ = encryptor.key_id().to_vec();
+        encoding.encryptor_ = Some(encryptor);
+        encoding
+    }
+
+    // Returns a ContentEncoding compressing the frames with |algo|. For
+    // kHeaderStripping, |settings| holds the bytes stripped from every frame.
+    pub fn new_compression(algo: ContentCompAlgo, settings: &[u8]) -> ContentEncoding {
+        let mut encoding = ContentEncoding::new();
+        encoding.comp_algo_ = algo;
+        encoding.comp_settings_ = settings.to_vec();
+        encoding.encoding_type_ = kCompression;
+        encoding
+    }
+
+    pub fn encryptor(&mut self) -> Option<&mut ContentEncryptor> {
+        self.encryptor_.as_mut()
+    }
+
+    pub fn HasEncryptor(&self) -> bool {
+        self.encryptor_.is_some()
+    }
+
+    // Returns |data| with this encoding applied, as stored in the Block.
+    // |partition_offsets| are passed to the encryptor, see
+    // ContentEncryptor::EncryptFrameWithIV(). Without an encryptor,
+    // encryption is left to the caller and the data is returned unchanged.
+    pub fn EncodeFrame(&mut self, data: &[u8], partition_offsets: &[u32]) -> Result<Vec<u8>> {
+        if self.encoding_type_ != kCompression {
+            return match self.encryptor_.as_mut() {
+                Some(encryptor) => encryptor.EncryptFrame(data, partition_offsets),
+                None => Ok(data.to_vec()),
+            };
+        }
+        self.Compress(data)
+    }
+
+    // Returns |data| compressed with this encoding. |data| is returned
+    // unchanged if this is not a compression.
+    pub fn Compress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        if self.encoding_type_ != kCompression {
+            return Ok(data.to_vec());
+        }
+
+        match self.comp_algo_ {
+            ContentCompAlgo::kHeaderStripping => {
+                if !data.starts_with(&self.comp_settings_) {
+                    return Err(MuxerError::kInvalidValue(
+                        MkvId::MkvContentCompSettings,
+                        "frame does not start with the stripped header",
+                    ));
+                }
+                Ok(data[self.comp_settings_.len()..].to_vec())
+            }
+            ContentCompAlgo::kZlib => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder
+                    .write_all(data)
+                    .map_err(IoError(MkvId::MkvContentCompression))?;
+                encoder
+                    .finish()
+                    .map_err(IoError(MkvId::MkvContentCompression))
+            }
         }
     }
 
@@ -56,19 +198,20 @@ impl ContentEncoding {
     }
 
     pub fn Size(&self) -> u64 {
+        let compression_size = self.CompressionSize();
         let encryption_size = self.EncryptionSize();
-        let encoding_size = self.EncodingSize(0, encryption_size);
+        let encoding_size = self.EncodingSize(compression_size, encryption_size);
         util::EbmlMasterElementSize(MkvId::MkvContentEncoding, encoding_size) + encoding_size
     }
 
-    fn EncodingSize(&self, compresion_size: u64, encryption_size: u64) -> u64 {
-        // TODO(fgalligan): Add support for compression settings.
-        if compresion_size != 0 {
-            return 0;
-        }
-
+    fn EncodingSize(&self, compression_size: u64, encryption_size: u64) -> u64 {
         let mut encoding_size = 0;
 
+        if compression_size > 0 {
+            encoding_size +=
+                util::EbmlMasterElementSize(MkvId::MkvContentCompression, compression_size)
+                    + compression_size;
+        }
         if encryption_size > 0 {
             encoding_size +=
                 util::EbmlMasterElementSize(MkvId::MkvContentEncryption, encryption_size)
@@ -84,7 +227,26 @@ impl ContentEncoding {
         encoding_size
     }
 
+    fn CompressionSize(&self) -> u64 {
+        if self.encoding_type_ != kCompression {
+            return 0;
+        }
+
+        let mut compression_size =
+            util::EbmlElementSizeArgU64(MkvId::MkvContentCompAlgo, self.comp_algo_ as u64);
+        if !self.comp_settings_.is_empty() {
+            compression_size +=
+                util::EbmlElementSizeArgSlice(MkvId::MkvContentCompSettings, &self.comp_settings_);
+        }
+
+        compression_size
+    }
+
     fn EncryptionSize(&self) -> u64 {
+        if self.encoding_type_ != kEncryption {
+            return 0;
+        }
+
         let aes_size = self.enc_aes_settings_.Size();
 
         let mut encryption_size =
@@ -94,55 +256,51 @@ impl ContentEncoding {
         encryption_size + aes_size
     }
 
-    pub fn Write(&self, writer: &mut dyn Writer) -> bool {
+    pub fn Write(&self, writer: &mut dyn Writer) -> Result<()> {
+        let compression_size = self.CompressionSize();
         let encryption_size = self.EncryptionSize();
-        let encoding_size = self.EncodingSize(0, encryption_size);
+        let encoding_size = self.EncodingSize(compression_size, encryption_size);
         let size =
             util::EbmlMasterElementSize(MkvId::MkvContentEncoding, encoding_size) + encoding_size;
 
         let payload_position = writer.get_position();
 
-        if !util::WriteEbmlMasterElement(writer, MkvId::MkvContentEncoding, encoding_size) {
-            return false;
-        }
-        if !util::WriteEbmlElementArgU64(
-            writer,
-            MkvId::MkvContentEncodingOrder,
-            self.encoding_order_,
-        ) {
-            return false;
-        }
-        if !util::WriteEbmlElementArgU64(
-            writer,
-            MkvId::MkvContentEncodingScope,
-            self.encoding_scope_,
-        ) {
-            return false;
-        }
-        if !util::WriteEbmlElementArgU64(writer, MkvId::MkvContentEncodingType, self.encoding_type_)
-        {
-            return false;
-        }
+        util::WriteEbmlMasterElement(writer, MkvId::MkvContentEncoding, encoding_size)?;
+        util::WriteEbmlElementArgU64(writer, MkvId::MkvContentEncodingOrder, self.encoding_order_)?;
+        util::WriteEbmlElementArgU64(writer, MkvId::MkvContentEncodingScope, self.encoding_scope_)?;
+        util::WriteEbmlElementArgU64(writer, MkvId::MkvContentEncodingType, self.encoding_type_)?;
 
-        if !util::WriteEbmlMasterElement(writer, MkvId::MkvContentEncryption, encryption_size) {
-            return false;
-        }
-        if !util::WriteEbmlElementArgU64(writer, MkvId::MkvContentEncAlgo, self.enc_algo_) {
-            return false;
-        }
-        if !util::WriteEbmlElementArgSlice(writer, MkvId::MkvContentEncKeyID, &self.enc_key_id_) {
-            return false;
+        if compression_size > 0 {
+            util::WriteEbmlMasterElement(writer, MkvId::MkvContentCompression, compression_size)?;
+            util::WriteEbmlElementArgU64(
+                writer,
+                MkvId::MkvContentCompAlgo,
+                self.comp_algo_ as u64,
+            )?;
+            if !self.comp_settings_.is_empty() {
+                util::WriteEbmlElementArgSlice(
+                    writer,
+                    MkvId::MkvContentCompSettings,
+                    &self.comp_settings_,
+                )?;
+            }
         }
 
-        if !self.enc_aes_settings_.Write(writer) {
-            return false;
+        if encryption_size > 0 {
+            util::WriteEbmlMasterElement(writer, MkvId::MkvContentEncryption, encryption_size)?;
+            util::WriteEbmlElementArgU64(writer, MkvId::MkvContentEncAlgo, self.enc_algo_)?;
+            util::WriteEbmlElementArgSlice(writer, MkvId::MkvContentEncKeyID, &self.enc_key_id_)?;
+
+            self.enc_aes_settings_.Write(writer)?;
         }
 
         let stop_position = writer.get_position();
-        if stop_position - payload_position != size {
-            return false;
-        }
+        CheckSize(
+            MkvId::MkvContentEncoding,
+            size,
+            stop_position - payload_position,
+        )?;
 
-        return true;
+        Ok(())
     }
 }
@@ -0,0 +1,155 @@
+use super::error::{MuxerError, Result};
+use crate::MkvId;
+use aes::Aes128;
+use ctr::cipher::{KeyIvInit, StreamCipher};
+use rand::Rng;
+use std::iter;
+
+// The counter block is the 8-byte IV followed by a 64-bit big-endian block
+// counter starting at 0.
+type Aes128Ctr = ctr::Ctr64BE<Aes128>;
+
+// Flags of the signal byte that starts every frame of an encrypted track.
+pub const kEncryptedFrame: u8 = 0x01;
+pub const kPartitionedFrame: u8 = 0x02;
+
+pub const kKeySize: usize = 16;
+pub const kIVSize: usize = 8;
+
+// The number of partitions is stored in a single byte.
+pub const kMaxPartitions: usize = 255;
+
+// Encrypts frames with AES-128 in CTR mode, in the WebM encryption format
+// described in https://www.webmproject.org/docs/webm-encryption/:
+//   signal byte | IV (8 bytes) | [partition count | offsets] | data
+// Each frame uses the next IV, so no counter block is used twice with the
+// same key.
+#[derive(Clone)]
+pub struct ContentEncryptor {
+    key_: [u8; kKeySize],
+    key_id_: Vec<u8>,
+
+    // IV of the next encrypted frame. Starts at a random value.
+    next_iv_: u64,
+}
+
+impl ContentEncryptor {
+    // Returns an encryptor for the 16-byte AES |key|. |key_id| is written to
+    // ContentEncKeyID so players can request the key.
+    pub fn new(key: &[u8], key_id: &[u8]) -> Result<ContentEncryptor> {
+        if key.len() != kKeySize {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvContentEncryption,
+                "AES-128 keys are 16 bytes",
+            ));
+        }
+        if key_id.is_empty() {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvContentEncKeyID,
+                "empty",
+            ));
+        }
+
+        let mut key_bytes = [0; kKeySize];
+        key_bytes.copy_from_slice(key);
+        Ok(ContentEncryptor {
+            key_: key_bytes,
+            key_id_: key_id.to_vec(),
+            next_iv_: rand::thread_rng().gen(),
+        })
+    }
+
+    pub fn key_id(&self) -> &[u8] {
+        &self.key_id_
+    }
+
+    pub fn set_next_iv(&mut self, iv: u64) {
+        self.next_iv_ = iv;
+    }
+    pub fn next_iv(&self) -> u64 {
+        self.next_iv_
+    }
+
+    // Encrypts |data| with the next IV. See EncryptFrameWithIV().
+    pub fn EncryptFrame(&mut self, data: &[u8], partition_offsets: &[u32]) -> Result<Vec<u8>> {
+        let iv = self.next_iv_;
+        let frame = self.EncryptFrameWithIV(iv, data, partition_offsets)?;
+        self.next_iv_ = iv.wrapping_add(1);
+        Ok(frame)
+    }
+
+    // Returns |data| encrypted with |iv|, preceded by the signal byte and the
+    // IV. If |partition_offsets| is empty the whole frame is encrypted.
+    // Otherwise the offsets split |data| into partitions that alternate
+    // between clear and encrypted, starting with a clear one; the encrypted
+    // partitions are encrypted as a single stream.
+    pub fn EncryptFrameWithIV(
+        &self,
+        iv: u64,
+        data: &[u8],
+        partition_offsets: &[u32],
+    ) -> Result<Vec<u8>> {
+        if !ValidPartitionOffsets(partition_offsets, data.len()) {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvContentEncryption,
+                "invalid partition offsets",
+            ));
+        }
+
+        let partitioned = !partition_offsets.is_empty();
+        let mut frame =
+            Vec::with_capacity(1 + kIVSize + 1 + partition_offsets.len() * 4 + data.len());
+        frame.push(if partitioned {
+            kEncryptedFrame | kPartitionedFrame
+        } else {
+            kEncryptedFrame
+        });
+        frame.extend_from_slice(&iv.to_be_bytes());
+        if partitioned {
+            frame.push(partition_offsets.len() as u8);
+            for offset in partition_offsets {
+                frame.extend_from_slice(&offset.to_be_bytes());
+            }
+        }
+        let data_start = frame.len();
+        frame.extend_from_slice(data);
+
+        CryptPartitions(&self.key_, iv, &mut frame[data_start..], partition_offsets);
+
+        Ok(frame)
+    }
+}
+
+// Returns true if |partition_offsets| are in increasing order, within
+// |data_size| and fit in the partition count byte.
+pub fn ValidPartitionOffsets(partition_offsets: &[u32], data_size: usize) -> bool {
+    partition_offsets.len() <= kMaxPartitions
+        && partition_offsets.windows(2).all(|pair| pair[0] <= pair[1])
+        && partition_offsets
+            .last()
+            .is_none_or(|&offset| offset as usize <= data_size)
+}
+
+// Encrypts or decrypts in place the partitions of |data| that are encrypted:
+// all of it if |partition_offsets| is empty, otherwise every other partition
+// starting with the second one, as a single AES-CTR stream. The offsets must
+// be valid, see ValidPartitionOffsets().
+pub fn CryptPartitions(key: &[u8; kKeySize], iv: u64, data: &mut [u8], partition_offsets: &[u32]) {
+    let mut counter_block = [0u8; 16];
+    counter_block[..kIVSize].copy_from_slice(&iv.to_be_bytes());
+    let mut cipher = Aes128Ctr::new(key.into(), &counter_block.into());
+
+    let mut start = 0;
+    let mut encrypted = partition_offsets.is_empty();
+    let ends = partition_offsets
+        .iter()
+        .map(|&offset| offset as usize)
+        .chain(iter::once(data.len()));
+    for end in ends {
+        if encrypted {
+            cipher.apply_keystream(&mut data[start..end]);
+        }
+        encrypted = !encrypted;
+        start = end;
+    }
+}
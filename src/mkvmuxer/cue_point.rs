@@ -1,3 +1,4 @@
+use super::error::{CheckSize, MuxerError, Result};
 use super::util;
 use super::writer::Writer;
 use crate::MkvId;
@@ -23,6 +24,12 @@ pub struct CuePoint {
     output_block_number_: bool,
 }
 
+impl Default for CuePoint {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CuePoint {
     pub fn set_time(&mut self, time: u64) {
         self.time_ = time;
@@ -65,9 +72,15 @@ impl CuePoint {
         }
     }
 
-    pub fn Write(&self, writer: &mut dyn Writer) -> bool {
-        if self.track_ < 1 || self.cluster_pos_ < 1 {
-            return false;
+    pub fn Write(&self, writer: &mut dyn Writer) -> Result<()> {
+        if self.track_ < 1 {
+            return Err(MuxerError::kInvalidTrackNumber(self.track_));
+        }
+        if self.cluster_pos_ < 1 {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvCueClusterPosition,
+                "cluster position not set",
+            ));
         }
 
         let mut size: u64 =
@@ -81,37 +94,27 @@ impl CuePoint {
         let payload_size: u64 =
             util::EbmlElementSizeArgU64(MkvId::MkvCueTime, self.time_) + track_pos_size;
 
-        if !util::WriteEbmlMasterElement(writer, MkvId::MkvCuePoint, payload_size) {
-            return false;
-        }
+        util::WriteEbmlMasterElement(writer, MkvId::MkvCuePoint, payload_size)?;
 
         let payload_position = writer.get_position();
 
-        if !util::WriteEbmlElementArgU64(writer, MkvId::MkvCueTime, self.time_) {
-            return false;
-        }
+        util::WriteEbmlElementArgU64(writer, MkvId::MkvCueTime, self.time_)?;
 
-        if !util::WriteEbmlMasterElement(writer, MkvId::MkvCueTrackPositions, size) {
-            return false;
-        }
-        if !util::WriteEbmlElementArgU64(writer, MkvId::MkvCueTrack, self.track_) {
-            return false;
-        }
-        if !util::WriteEbmlElementArgU64(writer, MkvId::MkvCueClusterPosition, self.cluster_pos_) {
-            return false;
-        }
+        util::WriteEbmlMasterElement(writer, MkvId::MkvCueTrackPositions, size)?;
+        util::WriteEbmlElementArgU64(writer, MkvId::MkvCueTrack, self.track_)?;
+        util::WriteEbmlElementArgU64(writer, MkvId::MkvCueClusterPosition, self.cluster_pos_)?;
         if self.output_block_number_ && self.block_number_ > 1 {
-            if !util::WriteEbmlElementArgU64(writer, MkvId::MkvCueBlockNumber, self.block_number_) {
-                return false;
-            }
+            util::WriteEbmlElementArgU64(writer, MkvId::MkvCueBlockNumber, self.block_number_)?;
         }
 
         let stop_position = writer.get_position();
-        if stop_position - payload_position != payload_size {
-            return false;
-        }
+        CheckSize(
+            MkvId::MkvCuePoint,
+            payload_size,
+            stop_position - payload_position,
+        )?;
 
-        true
+        Ok(())
     }
 
     pub fn PayloadSize(&self) -> u64 {
@@ -126,11 +129,11 @@ impl CuePoint {
         let payload_size: u64 =
             util::EbmlElementSizeArgU64(MkvId::MkvCueTime, self.time_) + track_pos_size;
 
-        return payload_size;
+        payload_size
     }
 
     pub fn Size(&self) -> u64 {
         let payload_size: u64 = self.PayloadSize();
-        util::EbmlElementSizeArgU64(MkvId::MkvCuePoint, payload_size) + payload_size
+        util::EbmlMasterElementSize(MkvId::MkvCuePoint, payload_size) + payload_size
     }
 }
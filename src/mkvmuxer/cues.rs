@@ -1,4 +1,5 @@
 use super::cue_point::CuePoint;
+use super::error::{CheckSize, Result};
 use super::util;
 use super::writer::Writer;
 use crate::MkvId;
@@ -15,6 +16,12 @@ pub struct Cues {
     output_block_number_: bool,
 }
 
+impl Default for Cues {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Cues {
     pub fn cue_entries_size(&self) -> usize {
         self.cue_entries_.len()
@@ -57,28 +64,22 @@ impl Cues {
         size
     }
 
-    pub fn Write(&self, writer: &mut dyn Writer) -> bool {
+    pub fn Write(&self, writer: &mut dyn Writer) -> Result<()> {
         let mut size: u64 = 0;
         for i in 0..self.cue_entries_.len() {
             size += self.cue_entries_[i].Size();
         }
 
-        if !util::WriteEbmlMasterElement(writer, MkvId::MkvCues, size) {
-            return false;
-        }
+        util::WriteEbmlMasterElement(writer, MkvId::MkvCues, size)?;
 
         let payload_position = writer.get_position();
         for i in 0..self.cue_entries_.len() {
-            if !self.cue_entries_[i].Write(writer) {
-                return false;
-            }
+            self.cue_entries_[i].Write(writer)?;
         }
 
         let stop_position = writer.get_position();
-        if stop_position - payload_position != size {
-            return false;
-        }
+        CheckSize(MkvId::MkvCues, size, stop_position - payload_position)?;
 
-        true
+        Ok(())
     }
 }
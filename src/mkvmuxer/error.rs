@@ -0,0 +1,67 @@
+use crate::MkvId;
+use std::fmt;
+use std::io;
+
+// Reason a write to the muxer failed.
+#[derive(Debug)]
+pub enum MuxerError {
+    // The writer failed while writing the element.
+    kIoError(MkvId, io::Error),
+    // The track number is 0, too large, or no track has that number.
+    kInvalidTrackNumber(u64),
+    // The block timecode relative to its Cluster does not fit in 16 bits.
+    kTimecodeOutOfRange(i64),
+    // Frames cannot be added to a Cluster once it is finalized.
+    kClusterFinalized,
+    // The bytes written for the element differ from its computed size:
+    // (element, expected, written).
+    kSizeMismatch(MkvId, u64, u64),
+    // A value of the element is missing or out of range.
+    kInvalidValue(MkvId, &'static str),
+    // The call is not allowed in the current state of the muxer.
+    kInvalidState(&'static str),
+}
+
+pub type Result<T> = std::result::Result<T, MuxerError>;
+
+// Returns a function mapping an io error to a kIoError for element |id|, for
+// use with map_err().
+pub fn IoError(id: MkvId) -> impl FnOnce(io::Error) -> MuxerError {
+    move |e| MuxerError::kIoError(id, e)
+}
+
+// Returns kSizeMismatch if |written| bytes differ from the |expected| size of
+// element |id|.
+pub fn CheckSize(id: MkvId, expected: u64, written: u64) -> Result<()> {
+    if expected != written {
+        return Err(MuxerError::kSizeMismatch(id, expected, written));
+    }
+    Ok(())
+}
+
+impl fmt::Display for MuxerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MuxerError::kIoError(id, e) => write!(f, "io error writing {:?}: {}", id, e),
+            MuxerError::kInvalidTrackNumber(n) => write!(f, "invalid track number {}", n),
+            MuxerError::kTimecodeOutOfRange(t) => write!(f, "timecode {} out of range", t),
+            MuxerError::kClusterFinalized => write!(f, "cluster already finalized"),
+            MuxerError::kSizeMismatch(id, expected, written) => write!(
+                f,
+                "size mismatch writing {:?}: expected {} bytes, wrote {}",
+                id, expected, written
+            ),
+            MuxerError::kInvalidValue(id, reason) => write!(f, "invalid {:?}: {}", id, reason),
+            MuxerError::kInvalidState(reason) => write!(f, "invalid state: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for MuxerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MuxerError::kIoError(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
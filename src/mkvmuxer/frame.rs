@@ -1,9 +1,35 @@
+use super::error::{IoError, MuxerError, Result};
 use super::util;
 use super::writer::Writer;
 use crate::MkvId;
 
 const MAX_TRACK_NUMBER: u64 = 126;
 
+// The number of laced frames minus one is stored in a single byte.
+pub const kMaxLacedFrames: usize = 256;
+
+// Lacing used to store several frames in a single block. The value is the
+// lacing field of the block flags.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Lacing {
+    kNoLacing = 0,
+    kXiphLacing = 1,
+    kFixedLacing = 2,
+    kEbmlLacing = 3,
+}
+
+impl Lacing {
+    pub fn from_u8(value: u8) -> Option<Lacing> {
+        match value {
+            0 => Some(Lacing::kNoLacing),
+            1 => Some(Lacing::kXiphLacing),
+            2 => Some(Lacing::kFixedLacing),
+            3 => Some(Lacing::kEbmlLacing),
+            _ => None,
+        }
+    }
+}
+
 // Class to hold data the will be written to a block.
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Frame {
@@ -24,6 +50,11 @@ pub struct Frame {
     // Pointer to the data. Owned by this class.
     frame_: Vec<u8>,
 
+    // Lacing of the block. When set, |frame_| holds the laced frames one after
+    // the other and |lace_sizes_| the size of each of them.
+    lacing_: Lacing,
+    lace_sizes_: Vec<u64>,
+
     // Flag telling if the data should set the key flag of a block.
     is_key_: bool,
 
@@ -41,6 +72,11 @@ pub struct Frame {
 
     // Flag indicating if |reference_block_timestamp_| has been set.
     reference_block_timestamp_set_: bool,
+
+    // Offsets splitting the frame into clear and encrypted partitions when
+    // the track is encrypted, e.g. to keep the uncompressed header of a VP9
+    // frame in the clear. Empty to encrypt the whole frame.
+    partition_offsets_: Vec<u32>,
 }
 
 use std::cmp::Ordering;
@@ -52,7 +88,13 @@ impl Ord for Frame {
 
 impl PartialOrd for Frame {
     fn partial_cmp(&self, other: &Frame) -> Option<Ordering> {
-        Some(self.timestamp_.cmp(&other.timestamp_))
+        Some(self.cmp(other))
+    }
+}
+
+impl Default for Frame {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -79,6 +121,9 @@ impl Frame {
     pub fn frame(&self) -> &[u8] {
         &self.frame_
     }
+    pub fn lacing(&self) -> Lacing {
+        self.lacing_
+    }
     pub fn set_is_key(&mut self, key: bool) {
         self.is_key_ = key;
     }
@@ -116,6 +161,12 @@ impl Frame {
     pub fn reference_block_timestamp_set(&self) -> bool {
         self.reference_block_timestamp_set_
     }
+    pub fn set_partition_offsets(&mut self, partition_offsets: &[u32]) {
+        self.partition_offsets_ = partition_offsets.to_vec();
+    }
+    pub fn partition_offsets(&self) -> &[u32] {
+        &self.partition_offsets_
+    }
 
     pub fn new() -> Frame {
         Frame {
@@ -124,20 +175,86 @@ impl Frame {
             duration_: 0,
             duration_set_: false,
             frame_: Vec::new(),
+            lacing_: Lacing::kNoLacing,
+            lace_sizes_: Vec::new(),
             is_key_: false,
             track_number_: 0,
             timestamp_: 0,
             discard_padding_: 0,
             reference_block_timestamp_: 0,
             reference_block_timestamp_set_: false,
+            partition_offsets_: Vec::new(),
         }
     }
 
     pub fn Init(&mut self, frame: &[u8]) -> bool {
         self.frame_ = frame.to_vec();
+        self.lacing_ = Lacing::kNoLacing;
+        self.lace_sizes_.clear();
+        true
+    }
+
+    // Stores |frames| to be written as a single block using |lacing|. Returns
+    // false if there are no frames or more than kMaxLacedFrames, or if fixed
+    // lacing is used for frames of different sizes.
+    pub fn InitLaced(&mut self, frames: &[&[u8]], lacing: Lacing) -> bool {
+        if frames.is_empty() || frames.len() > kMaxLacedFrames || lacing == Lacing::kNoLacing {
+            return false;
+        }
+        if lacing == Lacing::kFixedLacing && frames.iter().any(|f| f.len() != frames[0].len()) {
+            return false;
+        }
+
+        self.frame_ = frames.concat();
+        self.lacing_ = lacing;
+        self.lace_sizes_ = frames.iter().map(|f| f.len() as u64).collect();
         true
     }
 
+    // Returns the number of frames in the block.
+    pub fn GetFrameCount(&self) -> usize {
+        if self.lacing_ == Lacing::kNoLacing {
+            1
+        } else {
+            self.lace_sizes_.len()
+        }
+    }
+
+    // Returns the frame at |index| of a laced block. Index 0 of an unlaced
+    // block is the whole frame.
+    pub fn GetFrame(&self, index: usize) -> Option<&[u8]> {
+        if self.lacing_ == Lacing::kNoLacing {
+            return if index == 0 { Some(&self.frame_) } else { None };
+        }
+
+        let size = *self.lace_sizes_.get(index)? as usize;
+        let start = self.lace_sizes_[..index].iter().sum::<u64>() as usize;
+        self.frame_.get(start..start + size)
+    }
+
+    // Replaces each frame of the block with the output of |transform|, e.g.
+    // to compress it, keeping the lacing of the block. Returns false if the
+    // new frames can't be laced, i.e. fixed lacing of frames that no longer
+    // have the same size.
+    pub fn TransformFrames<E>(
+        &mut self,
+        mut transform: impl FnMut(&[u8]) -> std::result::Result<Vec<u8>, E>,
+    ) -> std::result::Result<bool, E> {
+        if self.lacing_ == Lacing::kNoLacing {
+            self.frame_ = transform(&self.frame_)?;
+            return Ok(true);
+        }
+
+        let mut frames = Vec::with_capacity(self.lace_sizes_.len());
+        for index in 0..self.lace_sizes_.len() {
+            if let Some(frame) = self.GetFrame(index) {
+                frames.push(transform(frame)?);
+            }
+        }
+        let frames: Vec<&[u8]> = frames.iter().map(|f| f.as_slice()).collect();
+        Ok(self.InitLaced(&frames, self.lacing_))
+    }
+
     pub fn AddAdditionalData(&mut self, additional: &[u8], add_id: u64) -> bool {
         self.additional_ = additional.to_vec();
         self.add_id_ = add_id;
@@ -145,77 +262,140 @@ impl Frame {
     }
 
     pub fn IsValid(&self) -> bool {
+        self.Validate().is_ok()
+    }
+
+    // Returns the reason the frame cannot be written, if any.
+    pub fn Validate(&self) -> Result<()> {
         if self.frame_.is_empty() {
-            return false;
+            return Err(MuxerError::kInvalidValue(MkvId::MkvBlock, "empty frame"));
         }
-        if !self.additional_.is_empty() {
-            return false;
+        if self.lacing_ != Lacing::kNoLacing
+            && self.lace_sizes_.iter().sum::<u64>() != self.length()
+        {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvBlock,
+                "lace sizes do not match the frame data",
+            ));
         }
         if self.track_number_ == 0 || self.track_number_ > MAX_TRACK_NUMBER {
-            return false;
+            return Err(MuxerError::kInvalidTrackNumber(self.track_number_));
         }
         if !self.CanBeSimpleBlock() && !self.is_key_ && !self.reference_block_timestamp_set_ {
-            return false;
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvReferenceBlock,
+                "non-key BlockGroup frame without a reference block timestamp",
+            ));
         }
-        return true;
+        Ok(())
     }
 
-    pub fn CanBeSimpleBlock(&self) -> bool {
-        self.additional_.len() == 0 && self.discard_padding_ == 0 && self.duration_ == 0
+    // Returns the size of the lace header written between the block flags and
+    // the frame data.
+    fn LaceHeaderSize(&self) -> u64 {
+        let sizes = &self.lace_sizes_;
+        let last = sizes.len().saturating_sub(1);
+        match self.lacing_ {
+            Lacing::kNoLacing => 0,
+            Lacing::kFixedLacing => 1,
+            // Each size but the last is coded as a run of 255 values and a
+            // final byte below 255.
+            Lacing::kXiphLacing => 1 + sizes[..last].iter().map(|s| s / 255 + 1).sum::<u64>(),
+            // The first size is coded as an unsigned EBML integer and the
+            // following ones as signed differences to the previous size.
+            Lacing::kEbmlLacing => {
+                if last == 0 {
+                    return 1;
+                }
+                let mut size = 1 + util::GetCodedUIntSize(sizes[0]) as u64;
+                for i in 1..last {
+                    let diff = sizes[i] as i64 - sizes[i - 1] as i64;
+                    size += util::GetCodedIntSize(diff) as u64;
+                }
+                size
+            }
+        }
     }
 
-    pub fn WriteSimpleBlock(&self, writer: &mut dyn Writer, timecode: i64) -> u64 {
-        if let Err(e) = util::WriteID(writer, MkvId::MkvSimpleBlock) {
-            return 0;
+    fn WriteLaceHeader(&self, writer: &mut dyn Writer) -> std::io::Result<()> {
+        if self.lacing_ == Lacing::kNoLacing {
+            return Ok(());
         }
 
-        let size = self.length() + 4;
-        if let Err(e) = util::WriteUInt(writer, size) {
-            return 0;
+        let sizes = &self.lace_sizes_;
+        let last = sizes.len() - 1;
+        util::SerializeInt(writer, last as u64, 1)?;
+        match self.lacing_ {
+            Lacing::kXiphLacing => {
+                for size in &sizes[..last] {
+                    for _ in 0..size / 255 {
+                        util::SerializeInt(writer, 255, 1)?;
+                    }
+                    util::SerializeInt(writer, size % 255, 1)?;
+                }
+            }
+            Lacing::kEbmlLacing if last > 0 => {
+                util::WriteUInt(writer, sizes[0])?;
+                for i in 1..last {
+                    let diff = sizes[i] as i64 - sizes[i - 1] as i64;
+                    util::WriteIntSize(writer, diff, util::GetCodedIntSize(diff))?;
+                }
+            }
+            _ => {}
         }
+        Ok(())
+    }
 
-        if let Err(e) = util::WriteUInt(writer, self.track_number()) {
-            return 0;
-        }
+    pub fn CanBeSimpleBlock(&self) -> bool {
+        self.additional_.is_empty() && self.discard_padding_ == 0 && self.duration_ == 0
+    }
 
-        if let Err(e) = util::SerializeInt(writer, timecode as u64, 2) {
-            return 0;
-        }
+    pub fn WriteSimpleBlock(&self, writer: &mut dyn Writer, timecode: i64) -> Result<u64> {
+        util::WriteID(writer, MkvId::MkvSimpleBlock).map_err(IoError(MkvId::MkvSimpleBlock))?;
+
+        let size = self.length() + 4 + self.LaceHeaderSize();
+        util::WriteUInt(writer, size).map_err(IoError(MkvId::MkvSimpleBlock))?;
+
+        util::WriteUInt(writer, self.track_number()).map_err(IoError(MkvId::MkvSimpleBlock))?;
+
+        util::SerializeInt(writer, timecode as u64, 2).map_err(IoError(MkvId::MkvSimpleBlock))?;
 
         let mut flags = 0;
         if self.is_key() {
             flags |= 0x80;
         }
+        flags |= (self.lacing_ as u64) << 1;
 
-        if let Err(e) = util::SerializeInt(writer, flags, 1) {
-            return 0;
-        }
+        util::SerializeInt(writer, flags, 1).map_err(IoError(MkvId::MkvSimpleBlock))?;
+        self.WriteLaceHeader(writer)
+            .map_err(IoError(MkvId::MkvSimpleBlock))?;
 
-        if let Err(e) = writer.write(self.frame()) {
-            return 0;
-        }
+        writer
+            .write(self.frame())
+            .map_err(IoError(MkvId::MkvSimpleBlock))?;
 
-        return util::GetUIntSize(MkvId::MkvSimpleBlock as u64) as u64
+        Ok(util::GetUIntSize(MkvId::MkvSimpleBlock as u64) as u64
             + util::GetCodedUIntSize(size) as u64
-            + 4
-            + self.length();
+            + size)
     }
 
-    pub fn WriteBlock(&self, writer: &mut dyn Writer, timecode: i64, timecode_scale: u64) -> u64 {
-        let mut block_additional_elem_size = 0;
-        let mut block_addid_elem_size = 0;
+    pub fn WriteBlock(
+        &self,
+        writer: &mut dyn Writer,
+        timecode: i64,
+        timecode_scale: u64,
+    ) -> Result<u64> {
         let mut block_more_payload_size = 0;
-        let mut block_more_elem_size = 0;
         let mut block_additions_payload_size = 0;
         let mut block_additions_elem_size = 0;
         if !self.additional().is_empty() {
-            block_additional_elem_size =
+            let block_additional_elem_size =
                 util::EbmlElementSizeArgSlice(MkvId::MkvBlockAdditional, self.additional());
-            block_addid_elem_size =
+            let block_addid_elem_size =
                 util::EbmlElementSizeArgU64(MkvId::MkvBlockAddID, self.add_id());
 
             block_more_payload_size = block_addid_elem_size + block_additional_elem_size;
-            block_more_elem_size =
+            let block_more_elem_size =
                 util::EbmlMasterElementSize(MkvId::MkvBlockMore, block_more_payload_size)
                     + block_more_payload_size;
             block_additions_payload_size = block_more_elem_size;
@@ -230,7 +410,8 @@ impl Frame {
                 util::EbmlElementSizeArgI64(MkvId::MkvDiscardPadding, self.discard_padding());
         }
 
-        let reference_block_timestamp = self.reference_block_timestamp() as u64 / timecode_scale;
+        let reference_block_timestamp =
+            (self.reference_block_timestamp() / timecode_scale as i64) as u64;
         let mut reference_block_elem_size = 0;
         if !self.is_key() {
             reference_block_elem_size =
@@ -244,7 +425,7 @@ impl Frame {
                 util::EbmlElementSizeArgU64(MkvId::MkvBlockDuration, duration);
         }
 
-        let block_payload_size = 4 + self.length();
+        let block_payload_size = 4 + self.LaceHeaderSize() + self.length();
         let block_elem_size =
             util::EbmlMasterElementSize(MkvId::MkvBlock, block_payload_size) + block_payload_size;
 
@@ -254,80 +435,57 @@ impl Frame {
             + discard_padding_elem_size
             + reference_block_elem_size;
 
-        if !util::WriteEbmlMasterElement(writer, MkvId::MkvBlockGroup, block_group_payload_size) {
-            return 0;
-        }
+        util::WriteEbmlMasterElement(writer, MkvId::MkvBlockGroup, block_group_payload_size)?;
 
-        if !util::WriteEbmlMasterElement(writer, MkvId::MkvBlock, block_payload_size) {
-            return 0;
-        }
+        util::WriteEbmlMasterElement(writer, MkvId::MkvBlock, block_payload_size)?;
 
-        if let Err(e) = util::WriteUInt(writer, self.track_number()) {
-            return 0;
-        }
+        util::WriteUInt(writer, self.track_number()).map_err(IoError(MkvId::MkvBlock))?;
 
-        if let Err(e) = util::SerializeInt(writer, timecode as u64, 2) {
-            return 0;
-        }
+        util::SerializeInt(writer, timecode as u64, 2).map_err(IoError(MkvId::MkvBlock))?;
 
-        // For a Block, flags is always 0.
-        if let Err(e) = util::SerializeInt(writer, 0, 1) {
-            return 0;
-        }
+        // For a Block, only the lacing bits of the flags are used.
+        let flags = (self.lacing_ as u64) << 1;
+        util::SerializeInt(writer, flags, 1).map_err(IoError(MkvId::MkvBlock))?;
+        self.WriteLaceHeader(writer)
+            .map_err(IoError(MkvId::MkvBlock))?;
 
-        if let Err(e) = writer.write(self.frame()) {
-            return 0;
-        }
+        writer
+            .write(self.frame())
+            .map_err(IoError(MkvId::MkvBlock))?;
 
         if !self.additional().is_empty() {
-            if !util::WriteEbmlMasterElement(
+            util::WriteEbmlMasterElement(
                 writer,
                 MkvId::MkvBlockAdditions,
                 block_additions_payload_size,
-            ) {
-                return 0;
-            }
+            )?;
 
-            if !util::WriteEbmlMasterElement(writer, MkvId::MkvBlockMore, block_more_payload_size) {
-                return 0;
-            }
+            util::WriteEbmlMasterElement(writer, MkvId::MkvBlockMore, block_more_payload_size)?;
 
-            if !util::WriteEbmlElementArgU64(writer, MkvId::MkvBlockAddID, self.add_id()) {
-                return 0;
-            }
+            util::WriteEbmlElementArgU64(writer, MkvId::MkvBlockAddID, self.add_id())?;
 
-            if !util::WriteEbmlElementArgSlice(writer, MkvId::MkvBlockAdditional, self.additional())
-            {
-                return 0;
-            }
+            util::WriteEbmlElementArgSlice(writer, MkvId::MkvBlockAdditional, self.additional())?;
         }
 
-        if self.discard_padding() != 0
-            && !util::WriteEbmlElementArgI64(
-                writer,
-                MkvId::MkvDiscardPadding,
-                self.discard_padding(),
-            )
-        {
-            return 0;
+        if self.discard_padding() != 0 {
+            util::WriteEbmlElementArgI64(writer, MkvId::MkvDiscardPadding, self.discard_padding())?;
         }
 
-        if !self.is_key()
-            && !util::WriteEbmlElementArgU64(
+        if !self.is_key() {
+            util::WriteEbmlElementArgU64(
                 writer,
                 MkvId::MkvReferenceBlock,
                 reference_block_timestamp,
-            )
-        {
-            return 0;
+            )?;
         }
 
-        if duration > 0 && !util::WriteEbmlElementArgU64(writer, MkvId::MkvBlockDuration, duration)
-        {
-            return 0;
+        if duration > 0 {
+            util::WriteEbmlElementArgU64(writer, MkvId::MkvBlockDuration, duration)?;
         }
 
-        util::EbmlMasterElementSize(MkvId::MkvBlockGroup, block_group_payload_size)
-            + block_group_payload_size
+        Ok(
+            util::EbmlMasterElementSize(MkvId::MkvBlockGroup, block_group_payload_size)
+                + block_group_payload_size,
+        )
     }
 }
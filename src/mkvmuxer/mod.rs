@@ -1,21 +1,26 @@
-mod audio_track;
-mod chapter;
-mod chapters;
-mod cluster;
-mod colour;
-mod content_enc_aes_settings;
-mod content_encoding;
-mod cue_point;
-mod cues;
-mod frame;
-mod projection;
-mod seek_head;
-mod segment;
-mod segment_info;
-mod tag;
-mod track;
-mod video_track;
-mod tracks;
+pub mod attachment;
+pub mod audio_track;
+pub mod chapter;
+pub mod chapters;
+pub mod cluster;
+pub mod colour;
+pub mod content_enc_aes_settings;
+pub mod content_encoding;
+pub mod content_encryptor;
+pub mod cue_point;
+pub mod cues;
+pub mod error;
+pub mod frame;
+pub mod opus;
+pub mod projection;
+pub mod seek_head;
+pub mod segment;
+pub mod segment_info;
+pub mod signature_slot;
+pub mod tag;
+pub mod track;
+pub mod tracks;
+pub mod video_track;
 
 pub mod util;
 pub mod writer;
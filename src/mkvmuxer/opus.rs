@@ -0,0 +1,259 @@
+use super::audio_track::AudioTrack;
+use super::error::{MuxerError, Result};
+use super::frame::Frame;
+use super::track::Track;
+use super::tracks::kOpusCodecId;
+use crate::MkvId;
+
+// Opus always decodes at 48kHz, whatever the rate of the input.
+pub const kOpusSampleRate: u64 = 48000;
+
+// Pre-roll recommended for Opus: 80ms of audio must be decoded after a seek
+// before the output is valid.
+pub const kOpusSeekPreRoll: u64 = 80000000;
+
+// An Opus packet holds at most 120ms of audio.
+pub const kOpusMaxPacketDuration: u64 = 120000000;
+
+// Size of an OpusHead for channel mapping family 0, which has no channel
+// mapping table.
+pub const kOpusHeadSize: usize = 19;
+
+const kOpusHeadMagic: &[u8; 8] = b"OpusHead";
+const kOpusHeadVersion: u8 = 1;
+
+// Returns the duration of |samples| at 48kHz in nanoseconds.
+fn SamplesToNs(samples: u64) -> u64 {
+    samples * 1000000000 / kOpusSampleRate
+}
+
+// Configuration of an Opus track, used to build its OpusHead CodecPrivate
+// as described in https://tools.ietf.org/html/rfc7845#section-5.1.
+#[derive(Debug, Clone)]
+pub struct OpusConfig {
+    channels_: u8,
+
+    // Samples at 48kHz to discard from the start of the decoded stream.
+    pre_skip_: u16,
+
+    // Sample rate of the original input, for information only. 0 when
+    // unknown.
+    input_sample_rate_: u32,
+
+    // Gain to apply to the decoded output, in Q7.8 dB.
+    output_gain_: i16,
+
+    // Channel mapping family, and for families other than 0 the stream
+    // counts and the channel mapping table.
+    channel_mapping_family_: u8,
+    stream_count_: u8,
+    coupled_count_: u8,
+    channel_mapping_: Vec<u8>,
+}
+
+impl OpusConfig {
+    // Returns a configuration for a mono or stereo stream with channel
+    // mapping family 0.
+    pub fn new(channels: u8) -> OpusConfig {
+        OpusConfig {
+            channels_: channels,
+            pre_skip_: 0,
+            input_sample_rate_: 0,
+            output_gain_: 0,
+            channel_mapping_family_: 0,
+            stream_count_: 1,
+            coupled_count_: if channels > 1 { 1 } else { 0 },
+            channel_mapping_: Vec::new(),
+        }
+    }
+
+    pub fn channels(&self) -> u8 {
+        self.channels_
+    }
+    pub fn set_pre_skip(&mut self, pre_skip: u16) {
+        self.pre_skip_ = pre_skip;
+    }
+    pub fn pre_skip(&self) -> u16 {
+        self.pre_skip_
+    }
+    pub fn set_input_sample_rate(&mut self, input_sample_rate: u32) {
+        self.input_sample_rate_ = input_sample_rate;
+    }
+    pub fn input_sample_rate(&self) -> u32 {
+        self.input_sample_rate_
+    }
+    pub fn set_output_gain(&mut self, output_gain: i16) {
+        self.output_gain_ = output_gain;
+    }
+    pub fn output_gain(&self) -> i16 {
+        self.output_gain_
+    }
+    pub fn channel_mapping_family(&self) -> u8 {
+        self.channel_mapping_family_
+    }
+    pub fn stream_count(&self) -> u8 {
+        self.stream_count_
+    }
+    pub fn coupled_count(&self) -> u8 {
+        self.coupled_count_
+    }
+    pub fn channel_mapping(&self) -> &[u8] {
+        &self.channel_mapping_
+    }
+
+    // Sets a channel mapping family other than 0, e.g. 1 for the Vorbis
+    // channel order. |channel_mapping| maps each output channel to a decoded
+    // channel, or 255 for silence.
+    pub fn set_channel_mapping(
+        &mut self,
+        family: u8,
+        stream_count: u8,
+        coupled_count: u8,
+        channel_mapping: &[u8],
+    ) {
+        self.channel_mapping_family_ = family;
+        self.stream_count_ = stream_count;
+        self.coupled_count_ = coupled_count;
+        self.channel_mapping_ = channel_mapping.to_vec();
+    }
+
+    // Returns the CodecDelay matching the pre-skip, in nanoseconds.
+    pub fn codec_delay(&self) -> u64 {
+        SamplesToNs(self.pre_skip_ as u64)
+    }
+
+    // Returns the reason the configuration can't be written, if any.
+    pub fn Validate(&self) -> Result<()> {
+        if self.channels_ == 0 {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvChannels,
+                "Opus needs at least one channel",
+            ));
+        }
+
+        if self.channel_mapping_family_ == 0 {
+            if self.channels_ > 2 {
+                return Err(MuxerError::kInvalidValue(
+                    MkvId::MkvCodecPrivate,
+                    "channel mapping family 0 is mono or stereo",
+                ));
+            }
+            return Ok(());
+        }
+
+        if self.channel_mapping_family_ == 1 && self.channels_ > 8 {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvCodecPrivate,
+                "channel mapping family 1 has at most 8 channels",
+            ));
+        }
+        let decoded_channels = self.stream_count_ as usize + self.coupled_count_ as usize;
+        if self.stream_count_ == 0
+            || self.coupled_count_ > self.stream_count_
+            || decoded_channels > 255
+        {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvCodecPrivate,
+                "invalid stream counts",
+            ));
+        }
+        if self.channel_mapping_.len() != self.channels_ as usize
+            || self
+                .channel_mapping_
+                .iter()
+                .any(|&channel| channel != 255 && channel as usize >= decoded_channels)
+        {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvCodecPrivate,
+                "invalid channel mapping table",
+            ));
+        }
+        Ok(())
+    }
+
+    // Returns the OpusHead to use as the CodecPrivate of the track.
+    pub fn OpusHead(&self) -> Result<Vec<u8>> {
+        self.Validate()?;
+
+        let mut head = Vec::with_capacity(kOpusHeadSize + 2 + self.channel_mapping_.len());
+        head.extend_from_slice(kOpusHeadMagic);
+        head.push(kOpusHeadVersion);
+        head.push(self.channels_);
+        head.extend_from_slice(&self.pre_skip_.to_le_bytes());
+        head.extend_from_slice(&self.input_sample_rate_.to_le_bytes());
+        head.extend_from_slice(&self.output_gain_.to_le_bytes());
+        head.push(self.channel_mapping_family_);
+        if self.channel_mapping_family_ != 0 {
+            head.push(self.stream_count_);
+            head.push(self.coupled_count_);
+            head.extend_from_slice(&self.channel_mapping_);
+        }
+        Ok(head)
+    }
+
+    // Configures |track| as an Opus track: codec id, OpusHead CodecPrivate,
+    // sample rate, channels, CodecDelay and SeekPreRoll.
+    pub fn Apply(&self, track: &mut AudioTrack) -> Result<()> {
+        let head = self.OpusHead()?;
+        track.set_codec_id(kOpusCodecId);
+        track.set_codec_private(&head);
+        track.set_sample_rate(kOpusSampleRate as f64);
+        track.set_channels(self.channels_ as u64);
+        track.set_codec_delay(self.codec_delay());
+        track.set_seek_pre_roll(kOpusSeekPreRoll);
+        Ok(())
+    }
+}
+
+// Returns the pre-skip of the OpusHead |codec_private|, or None if it is not
+// an OpusHead.
+pub fn OpusHeadPreSkip(codec_private: &[u8]) -> Option<u16> {
+    if codec_private.len() < kOpusHeadSize || !codec_private.starts_with(kOpusHeadMagic) {
+        return None;
+    }
+    Some(u16::from_le_bytes([codec_private[10], codec_private[11]]))
+}
+
+// Checks the DiscardPadding of |frame|, a frame of the Opus |track|, against
+// the frame duration and the pre-skip of the OpusHead. A positive padding
+// trims the end of the frame and a negative one its start, and the pre-skip
+// trims the start of the frames it overlaps. Together they can't trim more
+// than the frame holds, and a frame whose start is trimmed by the pre-skip
+// can't have a negative padding. The duration of the frame is its
+// BlockDuration, the DefaultDuration of the track or else the longest Opus
+// packet. Tracks without an OpusHead are not checked.
+pub fn CheckDiscardPadding(track: &Track, frame: &Frame) -> Result<()> {
+    let pre_skip = match OpusHeadPreSkip(track.codec_private()) {
+        Some(pre_skip) => SamplesToNs(pre_skip as u64),
+        None => return Ok(()),
+    };
+    if track.codec_delay() != pre_skip {
+        return Err(MuxerError::kInvalidValue(
+            MkvId::MkvCodecDelay,
+            "does not match the Opus pre-skip",
+        ));
+    }
+
+    let duration = if frame.duration_set() {
+        frame.duration()
+    } else if track.default_duration() > 0 {
+        track.default_duration()
+    } else {
+        kOpusMaxPacketDuration
+    };
+    let pre_skip_trimmed = pre_skip.saturating_sub(frame.timestamp()).min(duration);
+    let discard_padding = frame.discard_padding();
+    if discard_padding < 0 && pre_skip_trimmed > 0 {
+        return Err(MuxerError::kInvalidValue(
+            MkvId::MkvDiscardPadding,
+            "the pre-skip already trims the start of the frame",
+        ));
+    }
+    if discard_padding.unsigned_abs() > duration - pre_skip_trimmed {
+        return Err(MuxerError::kInvalidValue(
+            MkvId::MkvDiscardPadding,
+            "longer than the frame after the pre-skip",
+        ));
+    }
+    Ok(())
+}
@@ -1,3 +1,4 @@
+use super::error::Result;
 use super::util;
 use super::writer::Writer;
 use crate::MkvId;
@@ -11,6 +12,18 @@ pub enum ProjectionType {
     kMesh = 3,
 }
 
+impl ProjectionType {
+    pub fn from_u64(value: u64) -> Option<ProjectionType> {
+        match value {
+            0 => Some(ProjectionType::kRectangular),
+            1 => Some(ProjectionType::kEquirectangular),
+            2 => Some(ProjectionType::kCubeMap),
+            3 => Some(ProjectionType::kMesh),
+            _ => None,
+        }
+    }
+}
+
 pub struct Projection {
     type_: ProjectionType,
     pose_yaw_: f32,
@@ -19,8 +32,15 @@ pub struct Projection {
     private_data_: Vec<u8>,
 }
 
+impl Default for Projection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Projection {
-    const kValueNotPresent: u64 = std::u64::MAX;
+    #[allow(dead_code)]
+    const kValueNotPresent: u64 = u64::MAX;
 
     pub fn new() -> Projection {
         Projection {
@@ -74,7 +94,7 @@ impl Projection {
     pub fn PayloadSize(&self) -> u64 {
         let mut size = util::EbmlElementSizeArgU64(MkvId::MkvProjection, self.type_ as u64);
 
-        if self.private_data_.len() > 0 {
+        if !self.private_data_.is_empty() {
             size += util::EbmlElementSizeArgSlice(MkvId::MkvProjectionPrivate, &self.private_data_);
         }
 
@@ -85,44 +105,32 @@ impl Projection {
         size
     }
 
-    pub fn Write(&self, writer: &mut dyn Writer) -> bool {
+    pub fn Write(&self, writer: &mut dyn Writer) -> Result<()> {
         let size = self.PayloadSize();
 
         // Don't write an empty element.
         if size == 0 {
-            return true;
+            return Ok(());
         }
 
-        if !util::WriteEbmlMasterElement(writer, MkvId::MkvProjection, size) {
-            return false;
-        }
+        util::WriteEbmlMasterElement(writer, MkvId::MkvProjection, size)?;
 
-        if !util::WriteEbmlElementArgU64(writer, MkvId::MkvProjectionType, self.type_ as u64) {
-            return false;
-        }
+        util::WriteEbmlElementArgU64(writer, MkvId::MkvProjectionType, self.type_ as u64)?;
 
-        if self.private_data_.len() > 0
-            && !util::WriteEbmlElementArgSlice(
+        if !self.private_data_.is_empty() {
+            util::WriteEbmlElementArgSlice(
                 writer,
                 MkvId::MkvProjectionPrivate,
                 &self.private_data_,
-            )
-        {
-            return false;
+            )?;
         }
 
-        if !util::WriteEbmlElementArgF32(writer, MkvId::MkvProjectionPoseYaw, self.pose_yaw_) {
-            return false;
-        }
+        util::WriteEbmlElementArgF32(writer, MkvId::MkvProjectionPoseYaw, self.pose_yaw_)?;
 
-        if !util::WriteEbmlElementArgF32(writer, MkvId::MkvProjectionPosePitch, self.pose_pitch_) {
-            return false;
-        }
+        util::WriteEbmlElementArgF32(writer, MkvId::MkvProjectionPosePitch, self.pose_pitch_)?;
 
-        if !util::WriteEbmlElementArgF32(writer, MkvId::MkvProjectionPoseRoll, self.pose_roll_) {
-            return false;
-        }
+        util::WriteEbmlElementArgF32(writer, MkvId::MkvProjectionPoseRoll, self.pose_roll_)?;
 
-        true
+        Ok(())
     }
 }
@@ -1,8 +1,11 @@
+use super::error::{IoError, MuxerError, Result};
 use super::util;
 use super::writer::Writer;
 use crate::MkvId;
 
-const kSeekEntryCount: usize = 5;
+// Number of entries reserved by default: Info, Tracks, Cues, the first
+// Cluster and one of Chapters, Tags or Attachments.
+pub const kSeekEntryCount: usize = 5;
 
 pub struct SeekHead {
     seek_entry_id_: Vec<u32>,
@@ -14,6 +17,12 @@ pub struct SeekHead {
     start_pos_: u64,
 }
 
+impl Default for SeekHead {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SeekHead {
     pub fn new() -> SeekHead {
         SeekHead {
@@ -23,141 +32,140 @@ impl SeekHead {
         }
     }
 
-    pub fn Write(&self, writer: &mut dyn Writer) -> bool {
-        let entry_size = kSeekEntryCount as u64 * self.MaxEntrySize();
+    // Makes room for at least |count| entries. Must be called before Write(),
+    // which reserves space for every entry.
+    pub fn ReserveEntries(&mut self, count: usize) {
+        if count > self.seek_entry_id_.len() {
+            self.seek_entry_id_.resize(count, 0);
+            self.seek_entry_pos_.resize(count, 0);
+        }
+    }
+
+    pub fn entry_count(&self) -> usize {
+        self.seek_entry_id_.len()
+    }
+
+    pub fn Write(&mut self, writer: &mut dyn Writer) -> Result<()> {
+        let entry_size = self.entry_count() as u64 * self.MaxEntrySize();
         let size = util::EbmlMasterElementSize(MkvId::MkvSeekHead, entry_size);
 
-        let start_pos_ = writer.get_position();
-        let bytes_written = util::WriteVoidElement(writer, size + entry_size);
-        if bytes_written == 0 {
-            return false;
-        }
+        self.start_pos_ = writer.get_position();
+        util::WriteVoidElement(writer, size + entry_size)?;
 
-        true
+        Ok(())
     }
 
-    pub fn AddSeekEntry(&mut self, id: u32, pos: u64) -> bool {
-        for i in 0..kSeekEntryCount {
+    pub fn AddSeekEntry(&mut self, id: u32, pos: u64) -> Result<()> {
+        for i in 0..self.entry_count() {
             if self.seek_entry_id_[i] == 0 {
                 self.seek_entry_id_[i] = id;
                 self.seek_entry_pos_[i] = pos;
-                return true;
+                return Ok(());
             }
         }
-        false
+        Err(MuxerError::kInvalidState("SeekHead has no free entry"))
     }
 
     pub fn GetId(&self, index: usize) -> u32 {
-        if index >= kSeekEntryCount {
-            std::u32::MAX
+        if index >= self.entry_count() {
+            u32::MAX
         } else {
             self.seek_entry_id_[index]
         }
     }
 
     pub fn GetPosition(&self, index: usize) -> u64 {
-        if index >= kSeekEntryCount {
-            std::u64::MAX
+        if index >= self.entry_count() {
+            u64::MAX
         } else {
             self.seek_entry_pos_[index]
         }
     }
 
-    pub fn SetSeekEntry(&mut self, index: usize, id: u32, position: u64) -> bool {
-        if index >= kSeekEntryCount {
-            return false;
+    pub fn SetSeekEntry(&mut self, index: usize, id: u32, position: u64) -> Result<()> {
+        if index >= self.entry_count() {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvSeek,
+                "seek entry index out of range",
+            ));
         }
         self.seek_entry_id_[index] = id;
         self.seek_entry_pos_[index] = position;
-        true
+        Ok(())
     }
 
     pub fn MaxEntrySize(&self) -> u64 {
         let max_entry_payload_size = util::EbmlElementSizeArgU64(MkvId::MkvSeekID, 0xffffffff)
             + util::EbmlElementSizeArgU64(MkvId::MkvSeekPosition, 0xffffffffffffffff);
-        let max_entry_size = util::EbmlMasterElementSize(MkvId::MkvSeek, max_entry_payload_size)
-            + max_entry_payload_size;
-        max_entry_size
+        util::EbmlMasterElementSize(MkvId::MkvSeek, max_entry_payload_size) + max_entry_payload_size
     }
 
-    pub fn Finalize(&self, writer: &mut dyn Writer) -> bool {
+    pub fn Finalize(&self, writer: &mut dyn Writer) -> Result<()> {
         if writer.seekable() {
             //if self.start_pos_ == -1 {
             //    return false;
             //}
 
             let mut payload_size = 0;
-            let mut entry_size = vec![0u64; kSeekEntryCount];
+            let mut entry_size = vec![0u64; self.entry_count()];
 
-            for i in 0..kSeekEntryCount {
+            for (i, size) in entry_size.iter_mut().enumerate() {
                 if self.seek_entry_id_[i] != 0 {
-                    entry_size[i] = util::EbmlElementSizeArgU64(
+                    *size = util::EbmlElementSizeArgU64(
                         MkvId::MkvSeekID,
                         self.seek_entry_id_[i] as u64,
                     );
-                    entry_size[i] += util::EbmlElementSizeArgU64(
+                    *size += util::EbmlElementSizeArgU64(
                         MkvId::MkvSeekPosition,
                         self.seek_entry_pos_[i],
                     );
 
-                    payload_size +=
-                        util::EbmlMasterElementSize(MkvId::MkvSeek, entry_size[i]) + entry_size[i];
+                    payload_size += util::EbmlMasterElementSize(MkvId::MkvSeek, *size) + *size;
                 }
             }
 
             // No SeekHead elements
             if payload_size == 0 {
-                return true;
+                return Ok(());
             }
 
             let pos = writer.get_position();
-            if writer.set_position(self.start_pos_).is_err() {
-                return false;
-            }
+            writer
+                .set_position(self.start_pos_)
+                .map_err(IoError(MkvId::MkvSeekHead))?;
 
-            if !util::WriteEbmlMasterElement(writer, MkvId::MkvSeekHead, payload_size) {
-                return false;
-            }
+            util::WriteEbmlMasterElement(writer, MkvId::MkvSeekHead, payload_size)?;
 
-            for i in 0..kSeekEntryCount {
+            for (i, &size) in entry_size.iter().enumerate() {
                 if self.seek_entry_id_[i] != 0 {
-                    if !util::WriteEbmlMasterElement(writer, MkvId::MkvSeek, entry_size[i]) {
-                        return false;
-                    }
+                    util::WriteEbmlMasterElement(writer, MkvId::MkvSeek, size)?;
 
-                    if !util::WriteEbmlElementArgU64(
+                    util::WriteEbmlElementArgU64(
                         writer,
                         MkvId::MkvSeekID,
                         self.seek_entry_id_[i] as u64,
-                    ) {
-                        return false;
-                    }
+                    )?;
 
-                    if !util::WriteEbmlElementArgU64(
+                    util::WriteEbmlElementArgU64(
                         writer,
                         MkvId::MkvSeekPosition,
                         self.seek_entry_pos_[i],
-                    ) {
-                        return false;
-                    }
+                    )?;
                 }
             }
 
-            let total_entry_size = kSeekEntryCount as u64 * self.MaxEntrySize();
+            let total_entry_size = self.entry_count() as u64 * self.MaxEntrySize();
             let total_size = util::EbmlMasterElementSize(MkvId::MkvSeekHead, total_entry_size)
                 + total_entry_size;
             let size_left = total_size - (writer.get_position() - self.start_pos_);
 
-            let bytes_written = util::WriteVoidElement(writer, size_left);
-            if bytes_written == 0 {
-                return false;
-            }
+            util::WriteVoidElement(writer, size_left)?;
 
-            if writer.set_position(pos).is_err() {
-                return false;
-            }
+            writer
+                .set_position(pos)
+                .map_err(IoError(MkvId::MkvSeekHead))?;
         }
 
-        true
+        Ok(())
     }
 }
@@ -1,24 +1,43 @@
-use super::chapters::Chapters;
+use super::attachment::{AttachedFile, Attachments};
+use super::audio_track::AudioTrack;
+use super::chapter::Chapter;
+use super::chapters::{Chapters, Edition};
 use super::cluster::Cluster;
+use super::cue_point::CuePoint;
 use super::cues::Cues;
-use super::frame::Frame;
-use super::seek_head::SeekHead;
+use super::error::{CheckSize, IoError, MuxerError, Result};
+use super::frame::{Frame, Lacing};
+use super::opus;
+use super::opus::OpusConfig;
+use super::seek_head::{kSeekEntryCount, SeekHead};
 use super::segment_info::SegmentInfo;
-use super::tag::Tags;
-use super::tracks::Tracks;
+use super::signature_slot::{SignatureAlgo, SignatureSlot};
+use super::tag::{Tag, Tags};
+use super::track::Track;
+use super::tracks;
+use super::tracks::{TrackEntry, TrackType, Tracks};
 use super::util;
-use super::writer::MkvWriter;
-use super::writer::Writer;
+use super::video_track::VideoTrack;
+use super::writer::{MemoryWriter, MkvWriter, Writer};
+use crate::mkvparser::reader::Reader;
 use crate::MkvId;
+use std::fs::File;
+use std::io;
 
-#[derive(Debug, Copy, Clone)]
-enum Mode {
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Mode {
     kLive = 0x1,
     kFile = 0x2,
 }
 
-#[derive(Debug, Copy, Clone)]
-enum CuesPosition {
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DocType {
+    kWebm = 0x1,     // Only WebM codecs and elements are allowed
+    kMatroska = 0x2, // Any codec id and element
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CuesPosition {
     kAfterClusters = 0x0,  // Position Cues after Clusters - Default
     kBeforeClusters = 0x1, // Position Cues before Clusters
 }
@@ -27,7 +46,47 @@ const kDefaultDocTypeVersion: u32 = 4;
 const kDefaultMaxClusterDuration: u64 = 30000000000;
 const kMaxTrackNumber: usize = 126;
 
-pub struct Segment {
+// Receives the filename of a finished chunk.
+pub type ChunkCallback<'a> = Box<dyn FnMut(&str) + 'a>;
+
+// Destination of the Clusters: the writer passed to Init(), or the current
+// chunk file when chunking.
+enum ClusterWriter<'w> {
+    Segment(&'w mut dyn Writer),
+    Chunk(&'w mut MkvWriter),
+}
+
+impl Writer for ClusterWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<()> {
+        match self {
+            ClusterWriter::Segment(writer) => writer.write(buf),
+            ClusterWriter::Chunk(writer) => writer.write(buf),
+        }
+    }
+
+    fn get_position(&self) -> u64 {
+        match self {
+            ClusterWriter::Segment(writer) => writer.get_position(),
+            ClusterWriter::Chunk(writer) => writer.get_position(),
+        }
+    }
+
+    fn set_position(&mut self, position: u64) -> io::Result<()> {
+        match self {
+            ClusterWriter::Segment(writer) => writer.set_position(position),
+            ClusterWriter::Chunk(writer) => writer.set_position(position),
+        }
+    }
+
+    fn seekable(&self) -> bool {
+        match self {
+            ClusterWriter::Segment(writer) => writer.seekable(),
+            ClusterWriter::Chunk(writer) => writer.seekable(),
+        }
+    }
+}
+
+pub struct Segment<'a> {
     // Seeds the random number generator used to make UIDs.
     //seed_: usize,
 
@@ -38,6 +97,7 @@ pub struct Segment {
     tracks_: Tracks,
     chapters_: Chapters,
     tags_: Tags,
+    attachments_: Attachments,
 
     // Number of chunks written.
     chunk_count_: isize,
@@ -45,6 +105,9 @@ pub struct Segment {
     // Current chunk filename.
     chunk_name_: String,
 
+    // Called with the filename of each chunk once it is complete.
+    chunk_callback_: Option<ChunkCallback<'a>>,
+
     // Default MkvWriter object created by this class used for writing clusters
     // out in separate files.
     chunk_writer_cluster_: Option<MkvWriter>,
@@ -117,6 +180,10 @@ pub struct Segment {
     // seek backwards.
     mode_: Mode,
 
+    // The DocType set with set_doc_type(). If not set, the Segment is WebM
+    // when every codec and element it uses is allowed in WebM.
+    doc_type_: Option<DocType>,
+
     // Flag telling the muxer that a new cue point should be added.
     new_cuepoint_: bool,
 
@@ -133,6 +200,23 @@ pub struct Segment {
     // Flag whether or not to estimate the file duration.
     estimate_file_duration_: bool,
 
+    // Flag whether or not to write the size of the previous Cluster in each
+    // Cluster.
+    output_cluster_prev_size_: bool,
+
+    // Flag whether or not to write the position of each Cluster in the
+    // Cluster.
+    output_cluster_position_: bool,
+
+    // Ids of the master elements written with a CRC-32 element.
+    crc32_elements_: Vec<MkvId>,
+
+    // The file position of the SegmentInfo element.
+    info_pos_: i64,
+
+    // Hashes and signs the Segment, if it is signed.
+    signature_slot_: Option<SignatureSlot<'a>>,
+
     // The size of the EBML header, used to validate the header if
     // WriteEbmlHeader() is called more than once.
     ebml_header_size_: i32,
@@ -140,7 +224,8 @@ pub struct Segment {
     // The file position of the segment's payload.
     payload_pos_: i64,
 
-    // The file position of the element's size.
+    // The file position of the element's size, or -1 until the header is
+    // written.
     size_position_: i64,
 
     // Current DocTypeVersion (|doc_type_version_|) and that written in
@@ -152,17 +237,23 @@ pub struct Segment {
 
     // If |duration_| is > 0, then explicitly set the duration of the segment.
     duration_: f64,
-    // Pointer to the writer objects. Not owned by this class.
-    //writer_cluster_: &'a mut dyn Writer,
-    //writer_cues_: &'a mut dyn Writer,
-    //writer_header_: &'a mut dyn Writer,
+
+    // The writer passed to Init(). Not owned by this class.
+    writer_: Option<&'a mut dyn Writer>,
+}
+
+impl<'a> Default for Segment<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl Segment {
-    pub fn new() -> Segment {
+impl<'a> Segment<'a> {
+    pub fn new() -> Segment<'a> {
         Segment {
             chunk_count_: 0,
             chunk_name_: String::new(),
+            chunk_callback_: None,
             chunk_writer_cluster_: None,
             chunk_writer_cues_: None,
             chunk_writer_header_: None,
@@ -180,13 +271,19 @@ impl Segment {
             max_cluster_duration_: kDefaultMaxClusterDuration,
             max_cluster_size_: 0,
             mode_: Mode::kFile,
+            doc_type_: None,
             new_cuepoint_: false,
             output_cues_: true,
             accurate_cluster_duration_: false,
             fixed_size_cluster_timecode_: false,
             estimate_file_duration_: false,
+            output_cluster_prev_size_: false,
+            output_cluster_position_: false,
+            crc32_elements_: vec![],
+            info_pos_: -1,
+            signature_slot_: None,
             payload_pos_: 0,
-            size_position_: 0,
+            size_position_: -1,
             doc_type_version_: kDefaultDocTypeVersion,
             doc_type_version_written_: 0,
             duration_: 0.0,
@@ -198,86 +295,1637 @@ impl Segment {
             tracks_: Tracks::new(),
             chapters_: Chapters::new(),
             tags_: Tags::new(),
+            attachments_: Attachments::new(),
             last_track_timestamp_: [0; kMaxTrackNumber],
             track_frames_written_: [0; kMaxTrackNumber],
-            //writer_cluster_(NULL),
-            //writer_cues_(NULL),
-            //writer_header_(NULL)
+            writer_: None,
+        }
+    }
+
+    // Initializes |SegmentInfo| and sets the writer used to output the
+    // segment. The headers are written when the first frame is added. A
+    // seekable |writer| must be at the start of the output, since the EBML
+    // header is rewritten at offset 0 on Finalize().
+    pub fn Init(&mut self, writer: &'a mut dyn Writer) -> Result<()> {
+        if self.header_written_ {
+            return Err(MuxerError::kInvalidState("the headers are already written"));
+        }
+        if writer.seekable() && writer.get_position() != 0 {
+            return Err(MuxerError::kInvalidState(
+                "writer is not at the start of the output",
+            ));
+        }
+        self.segment_info_.Init()?;
+        self.writer_ = Some(writer);
+        self.last_track_timestamp_ = [0; kMaxTrackNumber];
+        self.track_frames_written_ = [0; kMaxTrackNumber];
+        Ok(())
+    }
+
+    // Adds a generic track to the segment. Returns the newly-allocated track
+    // object so the caller can set the codec id and other properties.
+    // |number| is the number to use for the track. |number| must be >= 0. If
+    // |number| == 0 then the muxer will decide on the track number.
+    pub fn AddTrack(&mut self, number: i32) -> Result<&mut Track> {
+        let track = Track::new();
+        self.tracks_.AddTrack(track, number)?;
+        let index = self.tracks_.track_entries_size() - 1;
+        match self.tracks_.GetTrackByIndexMut(index) {
+            Some(t) => Ok(&mut **t),
+            None => Err(MuxerError::kInvalidState("track was not added")),
+        }
+    }
+
+    // Adds a Vorbis audio track to the segment. Returns the number of the
+    // track. |number| is the number to use for the audio track. |number| must
+    // be >= 0. If |number| == 0 then the muxer will decide on the track
+    // number.
+    pub fn AddAudioTrack(&mut self, sample_rate: i32, channels: i32, number: i32) -> Result<u64> {
+        let mut track = AudioTrack::new();
+        track.set_track_type(TrackType::kAudio as u64);
+        track.set_codec_id(tracks::kVorbisCodecId);
+        track.set_sample_rate(sample_rate as f64);
+        track.set_channels(channels as u64);
+
+        self.tracks_.AddTrack(track, number)?;
+        self.LastTrackNumber()
+    }
+
+    // Adds an Opus audio track configured with |config| to the segment: its
+    // OpusHead CodecPrivate, CodecDelay and SeekPreRoll are set from
+    // |config|. Returns the number of the track. |number| is used as in
+    // AddAudioTrack().
+    pub fn AddOpusTrack(&mut self, config: &OpusConfig, number: i32) -> Result<u64> {
+        let mut track = AudioTrack::new();
+        track.set_track_type(TrackType::kAudio as u64);
+        config.Apply(&mut track)?;
+
+        self.tracks_.AddTrack(track, number)?;
+        self.LastTrackNumber()
+    }
+
+    // Adds a VP8 video track to the segment. Returns the number of the track.
+    // |number| is the number to use for the video track. |number| must be
+    // >= 0. If |number| == 0 then the muxer will decide on the track number.
+    pub fn AddVideoTrack(&mut self, width: i32, height: i32, number: i32) -> Result<u64> {
+        let mut track = VideoTrack::new();
+        track.set_track_type(TrackType::kVideo as u64);
+        track.set_codec_id(tracks::kVp8CodecId);
+        track.set_width(width as u64);
+        track.set_height(height as u64);
+
+        self.tracks_.AddTrack(track, number)?;
+        self.has_video_ = true;
+        self.LastTrackNumber()
+    }
+
+    // Adds an empty chapter to the last edition of this segment, creating
+    // the edition if there is none. Returns the chapter so the caller can set
+    // its id, time and titles.
+    pub fn AddChapter(&mut self) -> &mut Chapter {
+        self.chapters_.AddEmptyChapter()
+    }
+
+    // Adds an empty edition to the chapters of this segment. Returns the
+    // edition so the caller can set its flags and add chapters to it.
+    pub fn AddEdition(&mut self) -> &mut Edition {
+        self.chapters_.AddEdition()
+    }
+
+    // Adds an empty tag to the tags of this segment. Returns the tag so the
+    // caller can add simple tags to it.
+    pub fn AddTag(&mut self) -> &mut Tag {
+        self.tags_.AddTag()
+    }
+
+    // Adds an empty attached file to the attachments of this segment. Returns
+    // the file so the caller can set its name, MIME type and data. WebM
+    // Segments can't have attachments.
+    pub fn AddAttachment(&mut self) -> Result<&mut AttachedFile> {
+        if self.doc_type_ == Some(DocType::kWebm) {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvAttachments,
+                "not allowed in WebM",
+            ));
+        }
+        Ok(self.attachments_.AddEmptyFile())
+    }
+
+    // Adds a cue point to the Cues element. |timestamp| is the time in
+    // nanoseconds of the cue's time. |track| is the Track of the Cue. This
+    // function must be called after AddFrame to calculate the correct
+    // BlockNumber for the CuePoint.
+    pub fn AddCuePoint(&mut self, timestamp: u64, track: u64) -> Result<()> {
+        let cluster = match self.cluster_list_.last() {
+            Some(c) => c,
+            None => return Err(MuxerError::kInvalidState("no Cluster for the cue point")),
+        };
+
+        let mut cue = CuePoint::new();
+        cue.set_time(timestamp / self.segment_info_.timecode_scale());
+        cue.set_block_number(cluster.blocks_added() as u64);
+        cue.set_cluster_pos(cluster.position_for_cues() as u64);
+        cue.set_track(track);
+        if !self.cues_.AddCue(cue) {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvCuePoint,
+                "cue point not added",
+            ));
+        }
+
+        self.new_cuepoint_ = false;
+        Ok(())
+    }
+
+    // Writes a frame to the output medium.
+    // Inputs:
+    //   data: Pointer to the data
+    //   track_number: Track to add the data to. Value returned by Add track
+    //                 functions.
+    //   timestamp:    Timestamp of the frame in nanoseconds from 0.
+    //   is_key:       Flag telling whether or not this frame is a key frame.
+    pub fn AddFrame(
+        &mut self,
+        data: &[u8],
+        track_number: u64,
+        timestamp: u64,
+        is_key: bool,
+    ) -> Result<()> {
+        let mut frame = Frame::new();
+        frame.Init(data);
+        frame.set_track_number(track_number);
+        frame.set_timestamp(timestamp);
+        frame.set_is_key(is_key);
+        self.AddGenericFrame(&frame)
+    }
+
+    // Writes a frame with additional data to the output medium.
+    pub fn AddFrameWithAdditional(
+        &mut self,
+        data: &[u8],
+        additional: &[u8],
+        add_id: u64,
+        track_number: u64,
+        timestamp: u64,
+        is_key: bool,
+    ) -> Result<()> {
+        if additional.is_empty() {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvBlockAdditional,
+                "empty additional data",
+            ));
+        }
+        let mut frame = Frame::new();
+        frame.Init(data);
+        frame.AddAdditionalData(additional, add_id);
+        frame.set_track_number(track_number);
+        frame.set_timestamp(timestamp);
+        frame.set_is_key(is_key);
+        self.AddGenericFrame(&frame)
+    }
+
+    // Writes a frame with DiscardPadding to the output medium.
+    pub fn AddFrameWithDiscardPadding(
+        &mut self,
+        data: &[u8],
+        discard_padding: i64,
+        track_number: u64,
+        timestamp: u64,
+        is_key: bool,
+    ) -> Result<()> {
+        let mut frame = Frame::new();
+        frame.Init(data);
+        frame.set_discard_padding(discard_padding);
+        frame.set_track_number(track_number);
+        frame.set_timestamp(timestamp);
+        frame.set_is_key(is_key);
+        self.AddGenericFrame(&frame)
+    }
+
+    // Writes |frames| as a single laced block, using the lacing set on the
+    // track with Track::set_lacing(). All the frames share |timestamp| and
+    // |is_key|.
+    pub fn AddLacedFrames(
+        &mut self,
+        frames: &[&[u8]],
+        track_number: u64,
+        timestamp: u64,
+        is_key: bool,
+    ) -> Result<()> {
+        let lacing = match self.tracks_.GetTrackByNumber(track_number) {
+            Some(t) => t.lacing(),
+            None => return Err(MuxerError::kInvalidTrackNumber(track_number)),
+        };
+        if lacing == Lacing::kNoLacing {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvFlagLacing,
+                "lacing is not set on the track",
+            ));
+        }
+
+        let mut frame = Frame::new();
+        if !frame.InitLaced(frames, lacing) {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvSimpleBlock,
+                "frames cannot be laced",
+            ));
+        }
+        frame.set_track_number(track_number);
+        frame.set_timestamp(timestamp);
+        frame.set_is_key(is_key);
+        self.AddGenericFrame(&frame)
+    }
+
+    // Writes a Frame to the output medium. Chooses the correct way of writing
+    // the frame (Block vs SimpleBlock) based on the parameters passed.
+    pub fn AddGenericFrame(&mut self, frame: &Frame) -> Result<()> {
+        if self.chunking_ {
+            let mut chunk = match self.chunk_writer_cluster_.take() {
+                Some(w) => w,
+                None => return Err(MuxerError::kInvalidState("no open chunk")),
+            };
+            let result = self.DoAddGenericFrame(&mut ClusterWriter::Chunk(&mut chunk), frame);
+            self.chunk_writer_cluster_ = Some(chunk);
+            return result;
+        }
+
+        let writer = match self.writer_.take() {
+            Some(w) => w,
+            None => return Err(MuxerError::kInvalidState("Init() was not called")),
+        };
+        let result = self.DoAddGenericFrame(&mut ClusterWriter::Segment(&mut *writer), frame);
+        self.writer_ = Some(writer);
+        result
+    }
+
+    // Writes a metadata frame to the output medium. Metadata frames are
+    // always written as key frames with a BlockDuration.
+    pub fn AddMetadata(
+        &mut self,
+        data: &[u8],
+        track_number: u64,
+        timestamp_ns: u64,
+        duration_ns: u64,
+    ) -> Result<()> {
+        let mut frame = Frame::new();
+        frame.Init(data);
+        frame.set_track_number(track_number);
+        frame.set_timestamp(timestamp_ns);
+        frame.set_duration(duration_ns);
+        frame.set_is_key(true); // All metadata blocks are keyframes.
+        self.AddGenericFrame(&frame)
+    }
+
+    // Writes out any frames that have not been written out. Finalizes the last
+    // cluster. May update the size and duration of the segment. May output the
+    // Cues element. May finalize the SeekHead element.
+    pub fn Finalize(&mut self) -> Result<()> {
+        if self.chunking_ {
+            // The chunk files are closed once the Segment is finalized.
+            let mut chunk = match self.chunk_writer_cluster_.take() {
+                Some(w) => w,
+                None => return Err(MuxerError::kInvalidState("no open chunk")),
+            };
+            return self.DoFinalize(&mut ClusterWriter::Chunk(&mut chunk));
         }
+
+        let writer = match self.writer_.take() {
+            Some(w) => w,
+            None => return Err(MuxerError::kInvalidState("Init() was not called")),
+        };
+        let result = self.DoFinalize(&mut ClusterWriter::Segment(&mut *writer));
+        self.writer_ = Some(writer);
+        result
+    }
+
+    // Sets the chunking state of the Segment. When |chunking| is true the
+    // Segment header is written to "|filename|.hdr", the Cues to
+    // "|filename|.cues" and every Cluster to its own "|filename|_%06d.chk"
+    // file. The writer passed to Init() is not used. Must be called before
+    // any frame is added.
+    pub fn SetChunking(&mut self, chunking: bool, filename: &str) -> Result<()> {
+        if self.chunk_count_ > 0 || self.header_written_ {
+            return Err(MuxerError::kInvalidState(
+                "chunking must be set before adding frames",
+            ));
+        }
+
+        if chunking {
+            if filename.is_empty() {
+                return Err(MuxerError::kInvalidState("empty chunk base name"));
+            }
+
+            if self.signature_slot_.is_some() {
+                return Err(MuxerError::kInvalidState(
+                    "signed Segments can't be chunked",
+                ));
+            }
+
+            // Check if we are being set to what is already set.
+            if self.chunking_ && self.chunking_base_name_ == filename {
+                return Ok(());
+            }
+
+            let create =
+                |name: &str, id: MkvId| File::create(name).map(MkvWriter::new).map_err(IoError(id));
+            self.chunking_base_name_ = filename.to_string();
+            self.UpdateChunkName("chk");
+            self.chunk_writer_cluster_ = Some(create(&self.chunk_name_, MkvId::MkvCluster)?);
+            self.chunk_writer_cues_ = Some(create(&format!("{}.cues", filename), MkvId::MkvCues)?);
+            self.chunk_writer_header_ =
+                Some(create(&format!("{}.hdr", filename), MkvId::MkvSegment)?);
+        }
+
+        self.chunking_ = chunking;
+        Ok(())
+    }
+
+    // Sets a callback that receives the filename of each chunk once it is
+    // complete: a Cluster chunk when the next Cluster starts, and the last
+    // Cluster, Cues and header files from Finalize().
+    pub fn SetChunkCallback(&mut self, callback: impl FnMut(&str) + 'a) {
+        self.chunk_callback_ = Some(Box::new(callback));
+    }
+
+    // Writes a copy of the finalized Segment to |writer| with the Cues element
+    // placed before the first Cluster. |reader| must read the output of the
+    // finalized Segment. The cue point and seek entry positions are updated to
+    // match the new layout.
+    pub fn CopyAndMoveCuesBeforeClusters(
+        &mut self,
+        reader: &mut dyn Reader,
+        writer: &mut dyn Writer,
+    ) -> Result<()> {
+        if !writer.seekable() {
+            return Err(MuxerError::kInvalidState("writer is not seekable"));
+        }
+        if self.chunking_
+            || self.signature_slot_.is_some()
+            || self.mode_ == Mode::kLive
+            || !self.output_cues_
+            || self.cues_position_ == CuesPosition::kBeforeClusters
+        {
+            return Err(MuxerError::kInvalidState("Cues cannot be moved"));
+        }
+
+        let cluster_offset = match self.cluster_list_.first() {
+            Some(c) => c.size_position() - util::GetUIntSize(MkvId::MkvCluster as u64) as i64,
+            None => return Err(MuxerError::kInvalidState("no Cluster was written")),
+        };
+        if cluster_offset < 0 || self.cluster_end_offset_ < cluster_offset {
+            return Err(MuxerError::kInvalidState("Segment was not finalized"));
+        }
+
+        // Copy the headers.
+        ChunkedCopy(reader, writer, 0, cluster_offset as u64)?;
+
+        // Recompute cue positions and seek entries.
+        self.MoveCuesBeforeClusters()?;
+
+        // Write cues and seek entries.
+        let crc32 = self.output_crc32(MkvId::MkvCues);
+        WriteElement(writer, MkvId::MkvCues, crc32, None, |w| self.cues_.Write(w))?;
+        self.seek_head_.Finalize(writer)?;
+
+        // Copy the Clusters. They move by |shift|, so the Position of each
+        // Cluster, and the CRC-32 covering it, are updated on the way.
+        let shift = writer.get_position() as i64 - cluster_offset;
+        let id_size = util::GetUIntSize(MkvId::MkvCluster as u64) as i64;
+        let mut offset = cluster_offset;
+        for cluster in &self.cluster_list_ {
+            if cluster.position_element_pos() < 0 {
+                continue;
+            }
+            let start = cluster.size_position() - id_size;
+            ChunkedCopy(reader, writer, offset as u64, (start - offset) as u64)?;
+
+            let mut data = vec![0; cluster.Size() as usize];
+            reader
+                .read(start as u64, &mut data)
+                .map_err(IoError(MkvId::MkvCluster))?;
+
+            // The 8 byte Position value follows the element's id and size.
+            let value_pos = (cluster.position_element_pos() - start) as usize
+                + util::GetUIntSize(MkvId::MkvPosition as u64) as usize
+                + 1;
+            let position = (cluster.position_for_cues() + shift) as u64;
+            data[value_pos..value_pos + 8].copy_from_slice(&position.to_be_bytes());
+
+            if cluster.crc32_pos() != -1 {
+                let crc32_end =
+                    (cluster.crc32_pos() - start) as usize + util::CRC32_ELEMENT_SIZE as usize;
+                let crc = util::Crc32Update(0, &data[crc32_end..]);
+                data[crc32_end - 4..crc32_end].copy_from_slice(&crc.to_le_bytes());
+            }
+
+            writer.write(&data).map_err(IoError(MkvId::MkvCluster))?;
+            offset = start + data.len() as i64;
+        }
+        ChunkedCopy(
+            reader,
+            writer,
+            offset as u64,
+            (self.cluster_end_offset_ - offset) as u64,
+        )?;
+        let pos = writer.get_position();
+
+        // Update the Segment size in case the Cues size has changed.
+        let segment_size = pos - self.payload_pos_ as u64;
+        writer
+            .set_position(self.size_position_ as u64)
+            .map_err(IoError(MkvId::MkvSegment))?;
+        util::WriteUIntSize(writer, segment_size, 8).map_err(IoError(MkvId::MkvSegment))?;
+        writer
+            .set_position(pos)
+            .map_err(IoError(MkvId::MkvSegment))?;
+
+        self.cues_position_ = CuesPosition::kBeforeClusters;
+        Ok(())
+    }
+
+    // Toggles whether to output a cues element.
+    pub fn OutputCues(&mut self, output_cues: bool) {
+        self.output_cues_ = output_cues;
+    }
+
+    // Toggles whether to write the last frame in each Cluster with Duration.
+    pub fn AccurateClusterDuration(&mut self, accurate_cluster_duration: bool) {
+        self.accurate_cluster_duration_ = accurate_cluster_duration;
+    }
+
+    // Toggles whether to write the Cluster Timecode using exactly 8 bytes.
+    pub fn UseFixedSizeClusterTimecode(&mut self, fixed_size_cluster_timecode: bool) {
+        self.fixed_size_cluster_timecode_ = fixed_size_cluster_timecode;
+    }
+
+    // Toggles whether to write a PrevSize element in each Cluster after the
+    // first. Together with Position this lets a damaged file be walked
+    // backwards from any Cluster.
+    pub fn OutputClusterPrevSize(&mut self, output_cluster_prev_size: bool) {
+        self.output_cluster_prev_size_ = output_cluster_prev_size;
+    }
+
+    // Toggles whether to write a Position element in each Cluster.
+    pub fn OutputClusterPosition(&mut self, output_cluster_position: bool) {
+        self.output_cluster_position_ = output_cluster_position;
+    }
+
+    // Toggles whether to write a CRC-32 element in the master element |id|,
+    // which must be one of Info, Tracks, Cues, Tags, Chapters, Attachments
+    // or Cluster.
+    // Cluster CRCs are only written when the Cluster sizes are, i.e. not in
+    // live mode or to a writer that can't seek. Must be called before any
+    // frame is added.
+    pub fn OutputCrc32(&mut self, id: MkvId, output_crc32: bool) -> Result<()> {
+        match id {
+            MkvId::MkvInfo
+            | MkvId::MkvTracks
+            | MkvId::MkvCues
+            | MkvId::MkvTags
+            | MkvId::MkvChapters
+            | MkvId::MkvAttachments
+            | MkvId::MkvCluster => {}
+            _ => return Err(MuxerError::kInvalidValue(id, "no CRC-32 for this element")),
+        }
+        if self.header_written_ {
+            return Err(MuxerError::kInvalidState(
+                "CRC-32 must be set before adding frames",
+            ));
+        }
+
+        self.crc32_elements_.retain(|&element| element != id);
+        if output_crc32 {
+            self.crc32_elements_.push(id);
+        }
+        Ok(())
+    }
+
+    pub fn output_crc32(&self, id: MkvId) -> bool {
+        self.crc32_elements_.contains(&id)
+    }
+
+    // Returns true if the element |id| is buffered before it is written, for
+    // its CRC-32 or its signature.
+    fn BuffersElement(&self, id: MkvId) -> bool {
+        self.output_crc32(id)
+            || self
+                .signature_slot_
+                .as_ref()
+                .is_some_and(|slot| slot.Signs(id))
+    }
+
+    // Sets the function used to sign the Segment. The SHA-1 hash of the
+    // elements in |elements| is passed to |signer|, and the returned signature
+    // is written to a SignatureSlot with |algo| and |public_key| on Finalize().
+    // Must be called before any frame is added.
+    pub fn SetSigner(
+        &mut self,
+        elements: &[MkvId],
+        algo: SignatureAlgo,
+        public_key: &[u8],
+        signer: impl FnMut(&[u8]) -> Vec<u8> + 'a,
+    ) -> Result<()> {
+        if self.header_written_ {
+            return Err(MuxerError::kInvalidState(
+                "signer must be set before adding frames",
+            ));
+        }
+        if self.chunking_ {
+            return Err(MuxerError::kInvalidState(
+                "chunked Segments can't be signed",
+            ));
+        }
+        if self.doc_type_ == Some(DocType::kWebm) {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvSignatureSlot,
+                "not allowed in WebM",
+            ));
+        }
+        if elements.is_empty() {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvSignedElement,
+                "no element to sign",
+            ));
+        }
+        for (i, &id) in elements.iter().enumerate() {
+            match id {
+                MkvId::MkvInfo
+                | MkvId::MkvTracks
+                | MkvId::MkvCues
+                | MkvId::MkvTags
+                | MkvId::MkvChapters
+                | MkvId::MkvAttachments
+                | MkvId::MkvCluster => {}
+                _ => return Err(MuxerError::kInvalidValue(id, "element can't be signed")),
+            }
+            if elements[..i].contains(&id) {
+                return Err(MuxerError::kInvalidValue(id, "element listed twice"));
+            }
+        }
+
+        self.signature_slot_ = Some(SignatureSlot::new(elements, algo, public_key, signer));
+        Ok(())
+    }
+
+    // Returns the SignatureSlot, once SetSigner() was called. Its signature is
+    // set when the Segment is finalized.
+    pub fn GetSignatureSlot(&self) -> Option<&SignatureSlot<'a>> {
+        self.signature_slot_.as_ref()
+    }
+
+    // Sets which track to use for the Cues element. Must have added the track
+    // before calling this function. |track_number| is returned by the Add
+    // track functions.
+    pub fn CuesTrack(&mut self, track_number: u64) -> Result<()> {
+        if self.tracks_.GetTrackByNumber(track_number).is_none() {
+            return Err(MuxerError::kInvalidTrackNumber(track_number));
+        }
+
+        self.cues_track_ = track_number;
+        Ok(())
+    }
+
+    // This will force the muxer to create a new Cluster when the next frame is
+    // added.
+    pub fn ForceNewClusterOnNextFrame(&mut self) {
+        self.force_new_cluster_ = true;
+    }
+
+    // Returns the track object associated with |track_number|.
+    pub fn GetTrackByNumber(&mut self, track_number: u64) -> Option<&mut TrackEntry> {
+        self.tracks_.GetTrackByNumberMut(track_number)
     }
 
     // Returns the Cues object.
-    pub fn GetCues(&self) -> &Cues {
-        return &self.cues_;
+    pub fn GetCues(&mut self) -> &mut Cues {
+        &mut self.cues_
     }
 
     // Returns the Segment Information object.
-    pub fn GetSegmentInfo(&self) -> &SegmentInfo {
-        return &self.segment_info_;
+    pub fn GetSegmentInfo(&mut self) -> &mut SegmentInfo {
+        &mut self.segment_info_
     }
 
     pub fn chunking(&self) -> bool {
-        return self.chunking_;
+        self.chunking_
+    }
+    pub fn chunk_count(&self) -> isize {
+        self.chunk_count_
+    }
+    pub fn chunk_name(&self) -> &str {
+        &self.chunk_name_
     }
     pub fn cues_track(&self) -> u64 {
-        return self.cues_track_;
+        self.cues_track_
     }
     pub fn set_max_cluster_duration(&mut self, max_cluster_duration: u64) {
         self.max_cluster_duration_ = max_cluster_duration;
     }
     pub fn max_cluster_duration(&self) -> u64 {
-        return self.max_cluster_duration_;
+        self.max_cluster_duration_
     }
     pub fn set_max_cluster_size(&mut self, max_cluster_size: u64) {
         self.max_cluster_size_ = max_cluster_size;
     }
     pub fn max_cluster_size(&self) -> u64 {
-        return self.max_cluster_size_;
+        self.max_cluster_size_
     }
     pub fn set_mode(&mut self, mode: Mode) {
         self.mode_ = mode;
     }
     pub fn mode(&self) -> Mode {
-        return self.mode_;
+        self.mode_
+    }
+
+    // Sets the DocType of the EBML header to |doc_type|. A WebM Segment may
+    // only use the codecs in |kWebmCodecIds|, and no ContentCompression,
+    // Attachments, SignatureSlot, ChapProcess or ChapterSegmentUID. The
+    // tracks and chapters are checked again when the headers are written.
+    // Must be called before any frame is added.
+    pub fn set_doc_type(&mut self, doc_type: DocType) -> Result<()> {
+        if self.header_written_ {
+            return Err(MuxerError::kInvalidState(
+                "DocType must be set before adding frames",
+            ));
+        }
+        if doc_type == DocType::kWebm {
+            self.CheckWebm()?;
+        }
+        self.doc_type_ = Some(doc_type);
+        Ok(())
+    }
+
+    // Returns the DocType that is written: the one set with set_doc_type(),
+    // or the one the codecs and elements in use allow.
+    pub fn doc_type(&self) -> DocType {
+        match self.doc_type_ {
+            Some(doc_type) => doc_type,
+            None if self.CheckWebm().is_ok() => DocType::kWebm,
+            None => DocType::kMatroska,
+        }
     }
     pub fn cues_position(&self) -> CuesPosition {
-        return self.cues_position_;
+        self.cues_position_
     }
     pub fn output_cues(&self) -> bool {
-        return self.output_cues_;
+        self.output_cues_
     }
     pub fn set_estimate_file_duration(&mut self, estimate_duration: bool) {
         self.estimate_file_duration_ = estimate_duration;
     }
     pub fn estimate_file_duration(&self) -> bool {
-        return self.estimate_file_duration_;
+        self.estimate_file_duration_
     }
     pub fn segment_info(&self) -> &SegmentInfo {
-        return &self.segment_info_;
+        &self.segment_info_
     }
     pub fn set_duration(&mut self, duration: f64) {
         self.duration_ = duration;
     }
     pub fn duration(&self) -> f64 {
-        return self.duration_;
-    }
-
-    pub fn MoveCuesBeforeClustersHelper(&mut self, diff: u64, index: usize, cues_size: &mut [u64]) {
-        /*let Some(cue_point) = self.cues_.GetCueByIndex(index){
-            let old_cue_point_size = cue_point.Size();
-            let cluster_pos = cue_point.cluster_pos() + diff;
-            cue_point.set_cluster_pos(cluster_pos);
-
-            let cue_point_size_diff = cue_point.Size() - old_cue_point_size;
-            let cue_size_diff = util::GetCodedUIntSize(cues_size[cue_point_size_diff]) -
-                util::GetCodedUIntSize(cues_size[0]);
-            cues_size[0] += cue_point_size_diff;
-            let diff = cue_size_diff + cue_point_size_diff;
-            if diff > 0 {
-                for i in 0..cues_.cue_entries_size() {
-                    self.MoveCuesBeforeClustersHelper(diff as u64, i, cues_size);
+        self.duration_
+    }
+
+    fn DoAddGenericFrame(&mut self, writer: &mut ClusterWriter, frame: &Frame) -> Result<()> {
+        self.CheckHeaderInfo(writer)?;
+
+        // Check for non-monotonically increasing timestamps.
+        if frame.timestamp() < self.last_timestamp_ {
+            return Err(MuxerError::kTimecodeOutOfRange(
+                frame.timestamp() as i64 - self.last_timestamp_ as i64,
+            ));
+        }
+
+        // Check if the track number is valid.
+        let track = match self.tracks_.GetTrackByNumberMut(frame.track_number()) {
+            Some(t) => t,
+            None => return Err(MuxerError::kInvalidTrackNumber(frame.track_number())),
+        };
+
+        if frame.discard_padding() != 0 && track.codec_id() == tracks::kOpusCodecId {
+            opus::CheckDiscardPadding(track, frame)?;
+        }
+
+        // Compress or encrypt the frame data if the track has content
+        // encodings.
+        let encoded_frame;
+        let mut frame = frame;
+        if track.content_encoding_count() > 0 || !frame.partition_offsets().is_empty() {
+            let mut new_frame = frame.clone();
+            track.EncodeFrame(&mut new_frame)?;
+            encoded_frame = new_frame;
+            frame = &encoded_frame;
+        }
+
+        if frame.discard_padding() != 0 {
+            self.doc_type_version_ = 4;
+        }
+
+        if let Some(last_cluster) = self.cluster_list_.last() {
+            let timecode_scale = self.segment_info_.timecode_scale();
+            let frame_timecode = frame.timestamp() / timecode_scale;
+
+            let last_cluster_timecode = last_cluster.timecode();
+            let rel_timecode = frame_timecode.wrapping_sub(last_cluster_timecode);
+            if rel_timecode > util::MAX_BLOCK_TIMECODE as u64 {
+                self.force_new_cluster_ = true;
+            }
+        }
+
+        // If the segment has a video track hold onto audio frames to make sure the
+        // audio that is associated with the start time of a video key-frame is
+        // muxed into the same cluster.
+        if self.has_video_
+            && self.tracks_.TrackIsAudio(frame.track_number())
+            && !self.force_new_cluster_
+        {
+            self.frames_.push(frame.clone());
+            self.track_frames_written_[frame.track_number() as usize - 1] += 1;
+            return Ok(());
+        }
+
+        self.DoNewClusterProcessing(
+            writer,
+            frame.track_number(),
+            frame.timestamp(),
+            frame.is_key(),
+        )?;
+
+        let track_index = frame.track_number() as usize - 1;
+
+        // If the Frame is not a SimpleBlock, then set the reference_block_timestamp
+        // if it is not set already.
+        let mut new_frame;
+        if !frame.CanBeSimpleBlock() && !frame.is_key() && !frame.reference_block_timestamp_set() {
+            new_frame = frame.clone();
+            new_frame.set_reference_block_timestamp(self.last_track_timestamp_[track_index] as i64);
+            frame = &new_frame;
+        }
+
+        let cluster = match self.cluster_list_.last_mut() {
+            Some(c) => c,
+            None => return Err(MuxerError::kInvalidState("no Cluster for the frame")),
+        };
+        cluster.AddFrame(writer, frame)?;
+
+        if self.new_cuepoint_ && self.cues_track_ == frame.track_number() {
+            self.AddCuePoint(frame.timestamp(), self.cues_track_)?;
+        }
+
+        self.last_timestamp_ = frame.timestamp();
+        self.last_track_timestamp_[track_index] = frame.timestamp();
+        self.last_block_duration_ = frame.duration();
+        self.track_frames_written_[track_index] += 1;
+
+        Ok(())
+    }
+
+    fn DoFinalize(&mut self, writer: &mut ClusterWriter) -> Result<()> {
+        self.WriteFramesAll(writer)?;
+
+        if let Some(old_cluster) = self.cluster_list_.last_mut() {
+            // For the last frame of the last Cluster, we don't write it as a BlockGroup
+            // with Duration unless the frame itself has duration set explicitly.
+            old_cluster.finalize(writer, false, 0)?;
+            if let (Some(slot), Some(data)) =
+                (self.signature_slot_.as_mut(), old_cluster.TakeData())
+            {
+                slot.AddCluster(&data);
+            }
+        }
+
+        if self.chunking_ {
+            // The last Cluster chunk is complete.
+            let name = self.chunk_name_.clone();
+            self.chunk_count_ += 1;
+            self.NotifyChunk(&name);
+        }
+
+        // In live mode the output is never seeked, so the Segment size, the
+        // duration, the Cues and the SeekHead are left out. The SignatureSlot
+        // follows the last Cluster.
+        if self.mode_ == Mode::kLive {
+            if let Some(slot) = self.signature_slot_.as_mut() {
+                slot.Write(writer)?;
+            }
+            return Ok(());
+        }
+
+        let timecode_scale = self.segment_info_.timecode_scale();
+        let mut duration = (self.last_timestamp_ as f64 + self.last_block_duration_ as f64)
+            / timecode_scale as f64;
+        if self.duration_ > 0.0 {
+            duration = self.duration_;
+        } else if self.last_block_duration_ == 0 && self.estimate_file_duration_ {
+            let num_tracks = self.tracks_.track_entries_size();
+            for i in 0..num_tracks {
+                if self.track_frames_written_[i] < 2 {
+                    continue;
+                }
+
+                // Estimate the duration for the last block of a Track.
+                let nano_per_frame = self.last_track_timestamp_[i] as f64
+                    / (self.track_frames_written_[i] - 1) as f64;
+                let track_duration =
+                    (self.last_track_timestamp_[i] as f64 + nano_per_frame) / timecode_scale as f64;
+                if track_duration > duration {
+                    duration = track_duration;
+                }
+            }
+        }
+        self.segment_info_.set_duration(duration);
+
+        if !self.chunking_ {
+            return self.FinalizeHeader(writer);
+        }
+
+        let mut header = match self.chunk_writer_header_.take() {
+            Some(w) => w,
+            None => return Err(MuxerError::kInvalidState("no open header chunk")),
+        };
+        self.FinalizeHeader(&mut header)?;
+
+        // Close the Cues and header files.
+        self.chunk_writer_cues_ = None;
+        drop(header);
+        let cues_name = format!("{}.cues", self.chunking_base_name_);
+        let header_name = format!("{}.hdr", self.chunking_base_name_);
+        self.NotifyChunk(&cues_name);
+        self.NotifyChunk(&header_name);
+        Ok(())
+    }
+
+    // Updates the Segment header once all Clusters are written: the duration,
+    // the Cues, the SeekHead and the Segment size. |writer| is the writer of
+    // the Segment header.
+    fn FinalizeHeader(&mut self, writer: &mut dyn Writer) -> Result<()> {
+        if !self.BuffersElement(MkvId::MkvInfo) {
+            self.segment_info_.Finalize(writer)?;
+        } else if writer.seekable() && self.segment_info_.duration() > 0.0 {
+            // The duration changes the CRC-32 or the signed data, so the whole
+            // element is rewritten. Its size does not change.
+            if self.info_pos_ == -1 {
+                return Err(MuxerError::kInvalidState("Info position not recorded"));
+            }
+            let pos = writer.get_position();
+            writer
+                .set_position(self.info_pos_ as u64)
+                .map_err(IoError(MkvId::MkvInfo))?;
+            let crc32 = self.output_crc32(MkvId::MkvInfo);
+            let info = &mut self.segment_info_;
+            let slot = self.signature_slot_.as_mut();
+            WriteElement(writer, MkvId::MkvInfo, crc32, slot, |w| info.Write(w))?;
+            writer.set_position(pos).map_err(IoError(MkvId::MkvInfo))?;
+        }
+
+        if self.output_cues_ {
+            let offset = self.MaxOffset(writer);
+            self.seek_head_
+                .AddSeekEntry(MkvId::MkvCues as u32, offset as u64)?;
+        }
+
+        self.cluster_end_offset_ = writer.get_position() as i64;
+
+        // Write the seek headers and cues
+        if self.output_cues_ {
+            let crc32 = self.output_crc32(MkvId::MkvCues);
+            let cues = &mut self.cues_;
+            let slot = self.signature_slot_.as_mut();
+            match self.chunk_writer_cues_.as_mut() {
+                Some(cues_writer) if self.chunking_ => {
+                    WriteElement(cues_writer, MkvId::MkvCues, crc32, slot, |w| cues.Write(w))?
+                }
+                _ => WriteElement(writer, MkvId::MkvCues, crc32, slot, |w| cues.Write(w))?,
+            }
+        }
+
+        if let Some(slot) = self.signature_slot_.as_mut() {
+            slot.Write(writer)?;
+        }
+
+        self.seek_head_.Finalize(writer)?;
+
+        if writer.seekable() {
+            if self.size_position_ == -1 {
+                return Err(MuxerError::kInvalidState("Segment header was not written"));
+            }
+
+            let segment_size = self.MaxOffset(writer);
+            if segment_size < 1 {
+                return Err(MuxerError::kInvalidValue(
+                    MkvId::MkvSegment,
+                    "empty Segment",
+                ));
+            }
+
+            let pos = writer.get_position();
+            self.UpdateDocTypeVersion();
+            if self.doc_type_version_ != self.doc_type_version_written_ {
+                writer.set_position(0).map_err(IoError(MkvId::MkvEBML))?;
+
+                let doc_type = match self.doc_type() {
+                    DocType::kWebm => util::DOC_TYPE_WEBM,
+                    DocType::kMatroska => util::DOC_TYPE_MATROSKA,
+                };
+                util::WriteEbmlHeader(writer, self.doc_type_version_ as u64, doc_type)?;
+                CheckSize(
+                    MkvId::MkvEBML,
+                    self.ebml_header_size_ as u64,
+                    writer.get_position(),
+                )?;
+
+                self.doc_type_version_written_ = self.doc_type_version_;
+            }
+
+            writer
+                .set_position(self.size_position_ as u64)
+                .map_err(IoError(MkvId::MkvSegment))?;
+
+            util::WriteUIntSize(writer, segment_size as u64, 8)
+                .map_err(IoError(MkvId::MkvSegment))?;
+
+            writer
+                .set_position(pos)
+                .map_err(IoError(MkvId::MkvSegment))?;
+        }
+
+        Ok(())
+    }
+
+    // Checks if header information has been output and initialized. If not it
+    // will output the Segment element and initialize the SeekHead elment and
+    // Cues elements.
+    fn CheckHeaderInfo(&mut self, writer: &mut ClusterWriter) -> Result<()> {
+        if !self.header_written_ {
+            if self.chunking_ {
+                let mut header = match self.chunk_writer_header_.take() {
+                    Some(w) => w,
+                    None => return Err(MuxerError::kInvalidState("no open header chunk")),
+                };
+                let written = self.WriteSegmentHeader(&mut header);
+                self.chunk_writer_header_ = Some(header);
+                written?;
+
+                // Nothing is patched in live mode, so the header file is
+                // already complete.
+                if self.mode_ == Mode::kLive {
+                    let name = format!("{}.hdr", self.chunking_base_name_);
+                    self.NotifyChunk(&name);
+                }
+            } else {
+                self.WriteSegmentHeader(writer)?;
+            }
+
+            let offset = self.MaxOffset(writer);
+            self.seek_head_
+                .AddSeekEntry(MkvId::MkvCluster as u32, offset as u64)?;
+
+            if self.output_cues_ && self.cues_track_ == 0 {
+                // Check for a video track
+                for i in 0..self.tracks_.track_entries_size() {
+                    let track = match self.tracks_.GetTrackByIndex(i) {
+                        Some(t) => t,
+                        None => return Err(MuxerError::kInvalidState("missing track")),
+                    };
+
+                    if self.tracks_.TrackIsVideo(track.number()) {
+                        self.cues_track_ = track.number();
+                        break;
+                    }
+                }
+
+                // Set first track found
+                if self.cues_track_ == 0 {
+                    match self.tracks_.GetTrackByIndex(0) {
+                        Some(t) => self.cues_track_ = t.number(),
+                        None => return Err(MuxerError::kInvalidState("Segment has no tracks")),
+                    }
                 }
             }
-        }*/
+        }
+        Ok(())
+    }
+
+    // Returns the number of the most recently added track.
+    fn LastTrackNumber(&self) -> Result<u64> {
+        let index = self.tracks_.track_entries_size().wrapping_sub(1);
+        match self.tracks_.GetTrackByIndex(index) {
+            Some(t) => Ok(t.number()),
+            None => Err(MuxerError::kInvalidState("track was not added")),
+        }
+    }
+
+    // Sets |doc_type_version_| based on the current element requirements.
+    fn UpdateDocTypeVersion(&mut self) {
+        for index in 0..self.tracks_.track_entries_size() {
+            let track = match self.tracks_.GetTrackByIndex(index) {
+                Some(t) => t,
+                None => break,
+            };
+            if (track.codec_delay() > 0 || track.seek_pre_roll() > 0) && self.doc_type_version_ < 4
+            {
+                self.doc_type_version_ = 4;
+                break;
+            }
+        }
+    }
+
+    // Returns the maximum offset within the segment's payload. When chunking
+    // this function is needed to determine offsets of elements within the
+    // chunked files. Returns -1 on error.
+    fn MaxOffset(&self, writer: &dyn Writer) -> i64 {
+        if !self.chunking_ {
+            return writer.get_position() as i64 - self.payload_pos_;
+        }
+
+        // |writer| is only the header writer while that one is taken out to
+        // write or finalize the header.
+        let header = match self.chunk_writer_header_.as_ref() {
+            Some(w) => w as &dyn Writer,
+            None => writer,
+        };
+        let mut offset = header.get_position() as i64 - self.payload_pos_;
+        for cluster in &self.cluster_list_ {
+            offset += cluster.Size() as i64;
+        }
+        if let Some(cues_writer) = self.chunk_writer_cues_.as_ref() {
+            offset += cues_writer.get_position() as i64;
+        }
+        offset
+    }
+
+    // Sets |chunk_name_| to the name of chunk |chunk_count_| with extension
+    // |ext|.
+    fn UpdateChunkName(&mut self, ext: &str) {
+        self.chunk_name_ = format!(
+            "{}_{:06}.{}",
+            self.chunking_base_name_, self.chunk_count_, ext
+        );
+    }
+
+    fn NotifyChunk(&mut self, name: &str) {
+        if let Some(callback) = self.chunk_callback_.as_mut() {
+            callback(name);
+        }
+    }
+
+    // Closes the current Cluster chunk and opens the next one.
+    fn NextChunk(&mut self, writer: &mut ClusterWriter) -> Result<()> {
+        let chunk = match writer {
+            ClusterWriter::Chunk(chunk) => chunk,
+            ClusterWriter::Segment(_) => return Err(MuxerError::kInvalidState("not chunking")),
+        };
+
+        let name = self.chunk_name_.clone();
+        self.chunk_count_ += 1;
+        self.UpdateChunkName("chk");
+        let file = File::create(&self.chunk_name_).map_err(IoError(MkvId::MkvCluster))?;
+        **chunk = MkvWriter::new(file);
+        self.NotifyChunk(&name);
+        Ok(())
+    }
+
+    // Outputs the segment header, Segment Information element, SeekHead
+    // element, and Tracks element to |writer|.
+    fn WriteSegmentHeader(&mut self, writer: &mut dyn Writer) -> Result<()> {
+        self.UpdateDocTypeVersion();
+
+        if self.doc_type_ == Some(DocType::kWebm) {
+            self.CheckWebm()?;
+        }
+        let doc_type = match self.doc_type() {
+            DocType::kWebm => util::DOC_TYPE_WEBM,
+            DocType::kMatroska => util::DOC_TYPE_MATROSKA,
+        };
+        util::WriteEbmlHeader(writer, self.doc_type_version_ as u64, doc_type)?;
+        self.doc_type_version_written_ = self.doc_type_version_;
+        self.ebml_header_size_ = writer.get_position() as i32;
+
+        // Write "unknown" (-1) as segment size value. If mode is kFile, Segment
+        // will write over duration when the file is finalized.
+        util::WriteID(writer, MkvId::MkvSegment).map_err(IoError(MkvId::MkvSegment))?;
+
+        // Save for later.
+        self.size_position_ = writer.get_position() as i64;
+
+        // Write "unknown" (EBML coded -1) as segment size value. We need to write 8
+        // bytes because if we are going to overwrite the segment size later we do
+        // not know how big our segment will be.
+        util::SerializeInt(writer, util::EBML_UNKNOWN_VALUE, 8)
+            .map_err(IoError(MkvId::MkvSegment))?;
+
+        self.payload_pos_ = writer.get_position() as i64;
+
+        if self.mode_ == Mode::kFile && writer.seekable() {
+            // Set the duration > 0.0 so SegmentInfo will write out the duration. When
+            // the muxer is done writing we will set the correct duration and have
+            // SegmentInfo upadte it.
+            self.segment_info_.set_duration(1.0);
+
+            // The default entries only leave room for one of Chapters, Tags
+            // and Attachments.
+            let optional_count = [
+                self.chapters_.count(),
+                self.tags_.Count(),
+                self.attachments_.count(),
+            ]
+            .iter()
+            .filter(|&&count| count > 0)
+            .count();
+            if optional_count > 1 {
+                self.seek_head_
+                    .ReserveEntries(kSeekEntryCount + optional_count - 1);
+            }
+
+            self.seek_head_.Write(writer)?;
+        }
+
+        let offset = self.MaxOffset(writer);
+        self.seek_head_
+            .AddSeekEntry(MkvId::MkvInfo as u32, offset as u64)?;
+        self.info_pos_ = writer.get_position() as i64;
+        let crc32 = self.output_crc32(MkvId::MkvInfo);
+        let info = &mut self.segment_info_;
+        let slot = self.signature_slot_.as_mut();
+        WriteElement(writer, MkvId::MkvInfo, crc32, slot, |w| info.Write(w))?;
+
+        let offset = self.MaxOffset(writer);
+        self.seek_head_
+            .AddSeekEntry(MkvId::MkvTracks as u32, offset as u64)?;
+        let crc32 = self.output_crc32(MkvId::MkvTracks);
+        let tracks = &mut self.tracks_;
+        let slot = self.signature_slot_.as_mut();
+        WriteElement(writer, MkvId::MkvTracks, crc32, slot, |w| tracks.Write(w))?;
+
+        if self.chapters_.count() > 0 {
+            let offset = self.MaxOffset(writer);
+            self.seek_head_
+                .AddSeekEntry(MkvId::MkvChapters as u32, offset as u64)?;
+            let crc32 = self.output_crc32(MkvId::MkvChapters);
+            let chapters = &mut self.chapters_;
+            let slot = self.signature_slot_.as_mut();
+            WriteElement(writer, MkvId::MkvChapters, crc32, slot, |w| {
+                chapters.Write(w)
+            })?;
+        }
+
+        if self.tags_.Count() > 0 {
+            let offset = self.MaxOffset(writer);
+            self.seek_head_
+                .AddSeekEntry(MkvId::MkvTags as u32, offset as u64)?;
+            let crc32 = self.output_crc32(MkvId::MkvTags);
+            let tags = &mut self.tags_;
+            let slot = self.signature_slot_.as_mut();
+            WriteElement(writer, MkvId::MkvTags, crc32, slot, |w| tags.Write(w))?;
+        }
+
+        if self.attachments_.count() > 0 {
+            let offset = self.MaxOffset(writer);
+            self.seek_head_
+                .AddSeekEntry(MkvId::MkvAttachments as u32, offset as u64)?;
+            let crc32 = self.output_crc32(MkvId::MkvAttachments);
+            let attachments = &mut self.attachments_;
+            let slot = self.signature_slot_.as_mut();
+            WriteElement(writer, MkvId::MkvAttachments, crc32, slot, |w| {
+                attachments.Write(w)
+            })?;
+        }
+
+        self.header_written_ = true;
+
+        Ok(())
+    }
+
+    // Here we are testing whether to create a new cluster, given a frame
+    // having time frame_timestamp_ns.
+    //
+    // Returns 1 if a new cluster should be created, 2 if the frame's timecode
+    // is too far from the last cluster's and a new cluster must be created,
+    // 0 if the frame can be written to the current cluster and -1 on error.
+    fn TestFrame(&self, track_number: u64, frame_timestamp_ns: u64, is_key: bool) -> i32 {
+        if self.force_new_cluster_ {
+            return 1;
+        }
+
+        // If no clusters have been created yet, then create a new cluster
+        // and write this frame immediately, in the new cluster.  This path
+        // should only be followed once, the first time we attempt to write
+        // a frame.
+        let last_cluster = match self.cluster_list_.last() {
+            Some(c) => c,
+            None => return 1,
+        };
+
+        // There exists at least one cluster. We must compare the frame to
+        // the last cluster, in order to determine whether the frame is
+        // written to the existing cluster, or that a new cluster should be
+        // created.
+        let timecode_scale = self.segment_info_.timecode_scale();
+        let frame_timecode = frame_timestamp_ns / timecode_scale;
+
+        let last_cluster_timecode = last_cluster.timecode();
+
+        // For completeness we test for the case when the frame's timecode
+        // is less than the cluster's timecode.  Although in principle that
+        // is allowed, this muxer doesn't actually write clusters like that,
+        // so this indicates a bug somewhere in our algorithm.
+        if frame_timecode < last_cluster_timecode {
+            // should never happen
+            return -1;
+        }
+
+        // If the frame has a timestamp significantly larger than the last
+        // cluster (in Matroska, cluster-relative timestamps are serialized
+        // using a 16-bit signed integer), then we cannot write this frame to
+        // that cluster, and so we must create a new cluster.
+        let delta_timecode = (frame_timecode - last_cluster_timecode) as i64;
+
+        if delta_timecode > util::MAX_BLOCK_TIMECODE {
+            return 2;
+        }
+
+        // We decide to create a new cluster when we have a video keyframe.
+        // This will flush queued (audio) frames, and write the keyframe
+        // immediately, in the newly-created cluster.
+        if is_key && self.tracks_.TrackIsVideo(track_number) {
+            return 1;
+        }
+
+        // Create a new cluster if we have accumulated too many frames
+        // already, where "too many" is defined as "the total time of frames
+        // in the cluster exceeds a threshold".
+        let delta_ns = delta_timecode as u64 * timecode_scale;
+
+        if self.max_cluster_duration_ > 0 && delta_ns >= self.max_cluster_duration_ {
+            return 1;
+        }
+
+        // This is similar to the case above, with the difference that a new
+        // cluster is created when the size of the current cluster exceeds a
+        // threshold.
+        let cluster_size = last_cluster.payload_size();
+
+        if self.max_cluster_size_ > 0 && cluster_size >= self.max_cluster_size_ {
+            return 1;
+        }
+
+        // There's no need to create a new cluster, so emit this frame now.
+        0
+    }
+
+    // Creates a new cluster whose timecode is based on |frame_timestamp_ns|
+    // or the earliest queued frame.
+    fn MakeNewCluster(
+        &mut self,
+        writer: &mut ClusterWriter,
+        frame_timestamp_ns: u64,
+    ) -> Result<()> {
+        self.WriteFramesLessThan(writer, frame_timestamp_ns)?;
+
+        let mut prev_size = 0;
+        if let Some(old_cluster) = self.cluster_list_.last_mut() {
+            // Update old cluster's size
+            old_cluster.finalize(writer, true, frame_timestamp_ns)?;
+            prev_size = old_cluster.Size();
+            if let (Some(slot), Some(data)) =
+                (self.signature_slot_.as_mut(), old_cluster.TakeData())
+            {
+                slot.AddCluster(&data);
+            }
+
+            if self.chunking_ {
+                self.NextChunk(writer)?;
+            }
+        }
+
+        if self.output_cues_ {
+            self.new_cuepoint_ = true;
+        }
+
+        let timecode_scale = self.segment_info_.timecode_scale();
+        let frame_timecode = frame_timestamp_ns / timecode_scale;
+
+        let mut cluster_timecode = frame_timecode;
+
+        if let Some(f) = self.frames_.first() {
+            // earliest queued frame
+            let ns = f.timestamp();
+            let tc = ns / timecode_scale;
+
+            if tc < cluster_timecode {
+                cluster_timecode = tc;
+            }
+        }
+
+        let offset = self.MaxOffset(writer);
+        let mut cluster = Cluster::new(
+            cluster_timecode,
+            offset,
+            timecode_scale,
+            self.accurate_cluster_duration_,
+            self.fixed_size_cluster_timecode_,
+        );
+        cluster.set_unknown_size(self.mode_ == Mode::kLive);
+        cluster.set_write_position(self.output_cluster_position_);
+        cluster.set_write_crc32(self.output_crc32(MkvId::MkvCluster));
+        cluster.set_keep_data(
+            self.signature_slot_
+                .as_ref()
+                .is_some_and(|slot| slot.Signs(MkvId::MkvCluster)),
+        );
+        if self.output_cluster_prev_size_ {
+            cluster.set_prev_size(prev_size);
+        }
+        self.cluster_list_.push(cluster);
+        Ok(())
+    }
+
+    // Checks whether a new cluster needs to be created, and if so it creates
+    // a new one.
+    fn DoNewClusterProcessing(
+        &mut self,
+        writer: &mut ClusterWriter,
+        track_number: u64,
+        frame_timestamp_ns: u64,
+        is_key: bool,
+    ) -> Result<()> {
+        loop {
+            // Based on the characteristics of the current frame and current
+            // cluster, decide whether to create a new cluster.
+            let result = self.TestFrame(track_number, frame_timestamp_ns, is_key);
+            if result < 0 {
+                return Err(MuxerError::kInvalidState(
+                    "frame is older than the last Cluster",
+                ));
+            }
+
+            // Always set force_new_cluster_ to false after TestFrame.
+            self.force_new_cluster_ = false;
+
+            // A non-zero result means create a new cluster.
+            if result > 0 {
+                self.MakeNewCluster(writer, frame_timestamp_ns)?;
+            }
+
+            // Write queued (audio) frames.
+            self.WriteFramesAll(writer)?;
+
+            // Write the current frame to the current cluster (if TestFrame
+            // returns 0) or to a newly created cluster (TestFrame returns 1).
+            if result <= 1 {
+                return Ok(());
+            }
+
+            // TestFrame returned 2, which means there was a large time
+            // difference between the cluster and the frame itself.  Do the
+            // test again, comparing the frame to the new cluster.
+        }
+    }
+
+    // Writes a queued frame to the last cluster and updates the cue point and
+    // timestamp bookkeeping.
+    fn WriteQueuedFrame(&mut self, writer: &mut ClusterWriter, frame: &Frame) -> Result<()> {
+        // TODO(jzern/vigneshv): using Segment::AddGenericFrame here would limit the
+        // places where |doc_type_version_| needs to be updated.
+        if frame.discard_padding() != 0 {
+            self.doc_type_version_ = 4;
+        }
+
+        let cluster = match self.cluster_list_.last_mut() {
+            Some(c) => c,
+            None => return Err(MuxerError::kInvalidState("no Cluster for the frame")),
+        };
+        cluster.AddFrame(writer, frame)?;
+
+        if self.new_cuepoint_ && self.cues_track_ == frame.track_number() {
+            self.AddCuePoint(frame.timestamp(), self.cues_track_)?;
+        }
+
+        if frame.timestamp() > self.last_timestamp_ {
+            self.last_timestamp_ = frame.timestamp();
+            self.last_track_timestamp_[frame.track_number() as usize - 1] = frame.timestamp();
+        }
+
+        Ok(())
+    }
+
+    // Output all frames that are queued. Returns the number of frames
+    // written.
+    fn WriteFramesAll(&mut self, writer: &mut ClusterWriter) -> Result<usize> {
+        if self.frames_.is_empty() {
+            return Ok(0);
+        }
+
+        if self.cluster_list_.is_empty() {
+            return Err(MuxerError::kInvalidState(
+                "no Cluster for the queued frames",
+            ));
+        }
+
+        let frames = std::mem::take(&mut self.frames_);
+        for frame in &frames {
+            self.WriteQueuedFrame(writer, frame)?;
+        }
+
+        Ok(frames.len())
+    }
+
+    // Output all frames that are queued that have an end time that is less
+    // then |timestamp|. Succeeds if there are no frames queued.
+    fn WriteFramesLessThan(&mut self, writer: &mut ClusterWriter, timestamp: u64) -> Result<()> {
+        // Check |cluster_list_| to see if this is the first cluster. If it is
+        // the first cluster the audio frames that are less than the first video
+        // timesatmp will be written in a later step.
+        if !self.frames_.is_empty() && !self.cluster_list_.is_empty() {
+            let mut shift_left = 0;
+
+            // TODO(fgalligan): Change this to use the durations of frames instead of
+            // the next frame's start time if the duration is accurate.
+            for i in 1..self.frames_.len() {
+                if self.frames_[i].timestamp() > timestamp {
+                    break;
+                }
+
+                let frame_prev = self.frames_[i - 1].clone();
+                self.WriteQueuedFrame(writer, &frame_prev)?;
+
+                shift_left += 1;
+            }
+
+            if shift_left > 0 {
+                if shift_left >= self.frames_.len() {
+                    return Err(MuxerError::kInvalidState("all queued frames were written"));
+                }
+
+                self.frames_.drain(..shift_left);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Returns an error if the Segment uses a codec or an element that is not
+    // allowed in WebM.
+    fn CheckWebm(&self) -> Result<()> {
+        for track_index in 0..self.tracks_.track_entries_size() {
+            let track = match self.tracks_.GetTrackByIndex(track_index) {
+                Some(t) => t,
+                None => break,
+            };
+            if !tracks::IsWebmCodec(track.codec_id()) {
+                return Err(MuxerError::kInvalidValue(
+                    MkvId::MkvCodecID,
+                    "not a WebM codec",
+                ));
+            }
+            if !track.WebmCompatible() {
+                return Err(MuxerError::kInvalidValue(
+                    MkvId::MkvContentCompression,
+                    "not allowed in WebM",
+                ));
+            }
+        }
+        if self.attachments_.count() > 0 {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvAttachments,
+                "not allowed in WebM",
+            ));
+        }
+        if self.signature_slot_.is_some() {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvSignatureSlot,
+                "not allowed in WebM",
+            ));
+        }
+        if !self.chapters_.WebmCompatible() {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvChapters,
+                "ChapProcess and ChapterSegmentUID are not allowed in WebM",
+            ));
+        }
+        Ok(())
+    }
+
+    // Recomputes the cue point positions and the Cues and Cluster seek
+    // entries as if the Cues were written before the Clusters.
+    fn MoveCuesBeforeClusters(&mut self) -> Result<()> {
+        let crc32_size = if self.output_crc32(MkvId::MkvCues) {
+            util::CRC32_ELEMENT_SIZE
+        } else {
+            0
+        };
+        let current_cue_size = self.cues_.Size() + crc32_size;
+        let mut cue_size = crc32_size;
+        for i in 0..self.cues_.cue_entries_size() {
+            if let Some(cue_point) = self.cues_.GetCueByIndex(i) {
+                cue_size += cue_point.Size();
+            }
+        }
+        for i in 0..self.cues_.cue_entries_size() {
+            self.MoveCuesBeforeClustersHelper(current_cue_size, i, &mut cue_size);
+        }
+
+        // Adjust the Seek Entry to reflect the change in position
+        // of Cluster and Cues
+        let mut cluster_index = 0;
+        let mut cues_index = 0;
+        for i in 0..self.seek_head_.entry_count() {
+            if self.seek_head_.GetId(i) == MkvId::MkvCluster as u32 {
+                cluster_index = i;
+            }
+            if self.seek_head_.GetId(i) == MkvId::MkvCues as u32 {
+                cues_index = i;
+            }
+        }
+        let cluster_pos = self.seek_head_.GetPosition(cluster_index);
+        self.seek_head_
+            .SetSeekEntry(cues_index, MkvId::MkvCues as u32, cluster_pos)?;
+        let cues_pos = self.seek_head_.GetPosition(cues_index);
+        self.seek_head_.SetSeekEntry(
+            cluster_index,
+            MkvId::MkvCluster as u32,
+            self.cues_.Size() + crc32_size + cues_pos,
+        )
+    }
+
+    // Shifts the cluster position of the cue point at |index| by |diff|. If
+    // that grows the cue point, every cue point is shifted again by the
+    // growth of the Cues element. |cues_size| is the sum of the sizes of all
+    // cue points.
+    fn MoveCuesBeforeClustersHelper(&mut self, diff: u64, index: usize, cues_size: &mut u64) {
+        let cue_point = match self.cues_.GetCueByIndex(index) {
+            Some(c) => c,
+            None => return,
+        };
+        let old_cue_point_size = cue_point.Size();
+        let cluster_pos = cue_point.cluster_pos() + diff;
+        cue_point.set_cluster_pos(cluster_pos); // update the new cluster position
+
+        // New size of the cue is computed as follows
+        //    Let a = current sum of size of all CuePoints
+        //    Let b = Increase in Cue Point's size due to this iteration
+        //    Let c = Increase in size of Cues Element's length due to this iteration
+        //            (This is computed as CodedSize(a + b) - CodedSize(a))
+        //    Let d = b + c. Now d is the |diff| passed to the next recursive call.
+        //    Let e = a + b. Now e is the |cues_size| passed to the next recursive
+        //                   call.
+        let cue_point_size_diff = cue_point.Size() - old_cue_point_size;
+        let cue_size_diff = (util::GetCodedUIntSize(*cues_size + cue_point_size_diff)
+            - util::GetCodedUIntSize(*cues_size)) as u64;
+        *cues_size += cue_point_size_diff;
+        let diff = cue_size_diff + cue_point_size_diff;
+        if diff > 0 {
+            for i in 0..self.cues_.cue_entries_size() {
+                self.MoveCuesBeforeClustersHelper(diff, i, cues_size);
+            }
+        }
+    }
+}
+
+// Writes the master element |t| with |write|, adding a CRC-32 element if
+// |crc32| is set. If |signature_slot| signs |t|, it gets a copy of the
+// element.
+fn WriteElement(
+    writer: &mut dyn Writer,
+    t: MkvId,
+    crc32: bool,
+    signature_slot: Option<&mut SignatureSlot>,
+    write: impl FnOnce(&mut dyn Writer) -> Result<()>,
+) -> Result<()> {
+    let signature_slot = match signature_slot {
+        Some(slot) if slot.Signs(t) => slot,
+        _ if crc32 => return util::WriteWithCrc32(writer, t, write),
+        _ => return write(writer),
+    };
+
+    let mut buffer = MemoryWriter::new();
+    if crc32 {
+        util::WriteWithCrc32(&mut buffer, t, write)?;
+    } else {
+        write(&mut buffer)?;
+    }
+    let data = buffer.into_inner();
+    writer.write(&data).map_err(IoError(t))?;
+    signature_slot.SetElementData(t, data);
+    Ok(())
+}
+
+// Copies |size| bytes from |source| at |start| to the current position of
+// |dst|.
+fn ChunkedCopy(source: &mut dyn Reader, dst: &mut dyn Writer, start: u64, size: u64) -> Result<()> {
+    const kBufSize: u64 = 2048;
+    let mut buf = [0u8; kBufSize as usize];
+    let mut offset = start;
+    let mut size = size;
+    while size > 0 {
+        let read_len = size.min(kBufSize) as usize;
+        source
+            .read(offset, &mut buf[..read_len])
+            .map_err(IoError(MkvId::MkvSegment))?;
+        dst.write(&buf[..read_len])
+            .map_err(IoError(MkvId::MkvSegment))?;
+        offset += read_len as u64;
+        size -= read_len as u64;
     }
+    Ok(())
 }
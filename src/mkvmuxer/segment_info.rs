@@ -1,3 +1,4 @@
+use super::error::{CheckSize, IoError, MuxerError, Result};
 use super::util;
 use super::writer::Writer;
 use crate::MkvId;
@@ -19,6 +20,12 @@ pub struct SegmentInfo {
     duration_pos_: i64,
 }
 
+impl Default for SegmentInfo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SegmentInfo {
     pub fn new() -> SegmentInfo {
         SegmentInfo {
@@ -26,7 +33,7 @@ impl SegmentInfo {
             muxing_app_: String::new(),
             timecode_scale_: 1000000,
             writing_app_: String::new(),
-            date_utc_: std::i64::MIN,
+            date_utc_: i64::MIN,
             duration_pos_: -1,
         }
     }
@@ -61,7 +68,7 @@ impl SegmentInfo {
         self.date_utc_
     }
 
-    pub fn Init(&mut self) -> bool {
+    pub fn Init(&mut self) -> Result<()> {
         let mut major = 0;
         let mut minor = 0;
         let mut build = 0;
@@ -71,86 +78,78 @@ impl SegmentInfo {
         let temp = format!("libwebm-{}.{}.{}.{}", major, minor, build, revision);
         self.set_muxing_app(&temp);
         self.set_writing_app(&temp);
-        return true;
+        Ok(())
     }
 
-    pub fn Finalize(&self, writer: &mut dyn Writer) -> bool {
-        if self.duration_ > 0.0 {
-            if writer.seekable() {
-                if self.duration_pos_ == -1 {
-                    return false;
-                }
+    pub fn Finalize(&self, writer: &mut dyn Writer) -> Result<()> {
+        if self.duration_ > 0.0 && writer.seekable() {
+            if self.duration_pos_ == -1 {
+                return Err(MuxerError::kInvalidState("Duration position not recorded"));
+            }
 
-                let pos = writer.get_position();
+            let pos = writer.get_position();
 
-                if writer.set_position(self.duration_pos_ as u64).is_err() {
-                    return false;
-                }
+            writer
+                .set_position(self.duration_pos_ as u64)
+                .map_err(IoError(MkvId::MkvDuration))?;
 
-                if !util::WriteEbmlElementArgF32(writer, MkvId::MkvDuration, self.duration_ as f32)
-                {
-                    return false;
-                }
+            util::WriteEbmlElementArgF32(writer, MkvId::MkvDuration, self.duration_ as f32)?;
 
-                if writer.set_position(pos).is_err() {
-                    return false;
-                }
-            }
+            writer
+                .set_position(pos)
+                .map_err(IoError(MkvId::MkvDuration))?;
         }
 
-        true
+        Ok(())
     }
 
-    pub fn Write(&mut self, writer: &mut dyn Writer) -> bool {
-        if self.muxing_app_.is_empty() || self.writing_app_.is_empty() {
-            return false;
+    pub fn Write(&mut self, writer: &mut dyn Writer) -> Result<()> {
+        if self.muxing_app_.is_empty() {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvMuxingApp,
+                "empty muxing app",
+            ));
+        }
+        if self.writing_app_.is_empty() {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvWritingApp,
+                "empty writing app",
+            ));
         }
 
         let mut size = util::EbmlElementSizeArgU64(MkvId::MkvTimecodeScale, self.timecode_scale_);
         if self.duration_ > 0.0 {
             size += util::EbmlElementSizeArgF32(MkvId::MkvDuration, self.duration_ as f32);
         }
-        if self.date_utc_ != std::i64::MIN {
+        if self.date_utc_ != i64::MIN {
             size += util::EbmlDateElementSize(MkvId::MkvDateUTC);
         }
         size += util::EbmlElementSizeArgStr(MkvId::MkvMuxingApp, &self.muxing_app_);
         size += util::EbmlElementSizeArgStr(MkvId::MkvWritingApp, &self.writing_app_);
 
-        if !util::WriteEbmlMasterElement(writer, MkvId::MkvInfo, size) {
-            return false;
-        }
+        util::WriteEbmlMasterElement(writer, MkvId::MkvInfo, size)?;
 
         let payload_position = writer.get_position();
 
-        if !util::WriteEbmlElementArgU64(writer, MkvId::MkvTimecodeScale, self.timecode_scale_) {
-            return false;
-        }
+        util::WriteEbmlElementArgU64(writer, MkvId::MkvTimecodeScale, self.timecode_scale_)?;
 
         if self.duration_ > 0.0 {
             // Save for later
             self.duration_pos_ = writer.get_position() as i64;
 
-            if !util::WriteEbmlElementArgF32(writer, MkvId::MkvDuration, self.duration_ as f32) {
-                return false;
-            }
+            util::WriteEbmlElementArgF32(writer, MkvId::MkvDuration, self.duration_ as f32)?;
         }
 
-        if self.date_utc_ != std::i64::MIN {
-            util::WriteEbmlDateElement(writer, MkvId::MkvDateUTC, self.date_utc_);
+        if self.date_utc_ != i64::MIN {
+            util::WriteEbmlDateElement(writer, MkvId::MkvDateUTC, self.date_utc_)?;
         }
 
-        if !util::WriteEbmlElementArgStr(writer, MkvId::MkvMuxingApp, &self.muxing_app_) {
-            return false;
-        }
-        if !util::WriteEbmlElementArgStr(writer, MkvId::MkvWritingApp, &self.writing_app_) {
-            return false;
-        }
+        util::WriteEbmlElementArgStr(writer, MkvId::MkvMuxingApp, &self.muxing_app_)?;
+        util::WriteEbmlElementArgStr(writer, MkvId::MkvWritingApp, &self.writing_app_)?;
 
         let stop_position = writer.get_position();
-        if stop_position - payload_position != size {
-            return false;
-        }
+        CheckSize(MkvId::MkvInfo, size, stop_position - payload_position)?;
 
-        true
+        Ok(())
     }
 }
@@ -0,0 +1,180 @@
+use super::error::{CheckSize, Result};
+use super::util;
+use super::writer::Writer;
+use crate::MkvId;
+use sha1::{Digest, Sha1};
+
+// Values of the SignatureAlgo element.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SignatureAlgo {
+    kRsa = 1,
+    kEllipticCurve = 2,
+}
+
+// SignatureHash value for SHA1-160, the hash passed to the signer.
+pub const kSignatureHashSha1: u64 = 1;
+
+// Returns the signature of the hash passed to it.
+pub type Signer<'a> = Box<dyn FnMut(&[u8]) -> Vec<u8> + 'a>;
+
+///////////////////////////////////////////////////////////////
+// SignatureSlot element
+pub struct SignatureSlot<'a> {
+    algo_: SignatureAlgo,
+    public_key_: Vec<u8>,
+
+    // Ids of the signed level 1 elements, in the order they are hashed.
+    // Clusters always come first, as they are hashed while the Segment is
+    // written.
+    signed_elements_: Vec<MkvId>,
+
+    // Final bytes of the signed elements other than Clusters.
+    element_data_: Vec<(MkvId, Vec<u8>)>,
+
+    // Hash of the finalized Clusters so far.
+    hasher_: Sha1,
+
+    signer_: Signer<'a>,
+
+    // Set by Sign().
+    signature_: Vec<u8>,
+}
+
+impl<'a> SignatureSlot<'a> {
+    pub fn new(
+        signed_elements: &[MkvId],
+        algo: SignatureAlgo,
+        public_key: &[u8],
+        signer: impl FnMut(&[u8]) -> Vec<u8> + 'a,
+    ) -> SignatureSlot<'a> {
+        let mut elements: Vec<MkvId> = signed_elements
+            .iter()
+            .copied()
+            .filter(|&id| id == MkvId::MkvCluster)
+            .take(1)
+            .collect();
+        for &id in signed_elements {
+            if !elements.contains(&id) {
+                elements.push(id);
+            }
+        }
+
+        SignatureSlot {
+            algo_: algo,
+            public_key_: public_key.to_vec(),
+            signed_elements_: elements,
+            element_data_: Vec::new(),
+            hasher_: Sha1::new(),
+            signer_: Box::new(signer),
+            signature_: Vec::new(),
+        }
+    }
+
+    pub fn algo(&self) -> SignatureAlgo {
+        self.algo_
+    }
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key_
+    }
+    pub fn signed_elements(&self) -> &[MkvId] {
+        &self.signed_elements_
+    }
+    pub fn signature(&self) -> &[u8] {
+        &self.signature_
+    }
+
+    // Returns true if the level 1 elements with |id| are signed.
+    pub fn Signs(&self, id: MkvId) -> bool {
+        self.signed_elements_.contains(&id)
+    }
+
+    // Hashes the bytes of a finalized Cluster.
+    pub fn AddCluster(&mut self, data: &[u8]) {
+        self.hasher_.update(data);
+    }
+
+    // Sets the final bytes of the element |id|, replacing earlier ones.
+    pub fn SetElementData(&mut self, id: MkvId, data: Vec<u8>) {
+        self.element_data_.retain(|(element, _)| *element != id);
+        self.element_data_.push((id, data));
+    }
+
+    // Hashes the signed elements and sets the signature returned by the
+    // signer.
+    pub fn Sign(&mut self) {
+        let mut hasher = std::mem::take(&mut self.hasher_);
+        for &id in &self.signed_elements_ {
+            for (element, data) in &self.element_data_ {
+                if *element == id {
+                    hasher.update(data);
+                }
+            }
+        }
+        let hash = hasher.finalize();
+        self.signature_ = (self.signer_)(&hash);
+    }
+
+    fn ElementListSize(&self) -> u64 {
+        let mut size = 0;
+        for &id in &self.signed_elements_ {
+            size += util::EbmlElementSizeArgSlice(MkvId::MkvSignedElement, &IdBytes(id));
+        }
+        size
+    }
+
+    pub fn PayloadSize(&self) -> u64 {
+        let list_size = self.ElementListSize();
+        let list_size =
+            util::EbmlMasterElementSize(MkvId::MkvSignatureElementList, list_size) + list_size;
+
+        util::EbmlElementSizeArgU64(MkvId::MkvSignatureAlgo, self.algo_ as u64)
+            + util::EbmlElementSizeArgU64(MkvId::MkvSignatureHash, kSignatureHashSha1)
+            + util::EbmlElementSizeArgSlice(MkvId::MkvSignaturePublicKey, &self.public_key_)
+            + util::EbmlElementSizeArgSlice(MkvId::MkvSignature, &self.signature_)
+            + util::EbmlMasterElementSize(MkvId::MkvSignatureElements, list_size)
+            + list_size
+    }
+
+    pub fn Size(&self) -> u64 {
+        let payload = self.PayloadSize();
+        util::EbmlMasterElementSize(MkvId::MkvSignatureSlot, payload) + payload
+    }
+
+    // Signs the Segment and writes the SignatureSlot.
+    pub fn Write(&mut self, writer: &mut dyn Writer) -> Result<()> {
+        self.Sign();
+
+        let payload = self.PayloadSize();
+        util::WriteEbmlMasterElement(writer, MkvId::MkvSignatureSlot, payload)?;
+        let payload_position = writer.get_position();
+
+        util::WriteEbmlElementArgU64(writer, MkvId::MkvSignatureAlgo, self.algo_ as u64)?;
+        util::WriteEbmlElementArgU64(writer, MkvId::MkvSignatureHash, kSignatureHashSha1)?;
+        util::WriteEbmlElementArgSlice(writer, MkvId::MkvSignaturePublicKey, &self.public_key_)?;
+        util::WriteEbmlElementArgSlice(writer, MkvId::MkvSignature, &self.signature_)?;
+
+        let list_size = self.ElementListSize();
+        util::WriteEbmlMasterElement(
+            writer,
+            MkvId::MkvSignatureElements,
+            util::EbmlMasterElementSize(MkvId::MkvSignatureElementList, list_size) + list_size,
+        )?;
+        util::WriteEbmlMasterElement(writer, MkvId::MkvSignatureElementList, list_size)?;
+        for &id in &self.signed_elements_ {
+            util::WriteEbmlElementArgSlice(writer, MkvId::MkvSignedElement, &IdBytes(id))?;
+        }
+
+        let stop_position = writer.get_position();
+        CheckSize(
+            MkvId::MkvSignatureSlot,
+            payload,
+            stop_position - payload_position,
+        )
+    }
+}
+
+// Returns the bytes of the element id |id| as written in a file.
+fn IdBytes(id: MkvId) -> Vec<u8> {
+    let size = util::GetUIntSize(id as u64) as usize;
+    (id as u64).to_be_bytes()[8 - size..].to_vec()
+}
@@ -1,11 +1,186 @@
+use super::error::{CheckSize, Result};
 use super::util;
 use super::writer::Writer;
 use crate::MkvId;
 
+// TargetTypeValue used when none is set: an album, movie or episode.
+pub const kDefaultTargetTypeValue: u64 = 50;
+
+// The Targets of a Tag: the logical level and the tracks, editions, chapters
+// and attachments the tag applies to. A Tag without targets applies to the
+// whole Segment.
+#[derive(Debug, Clone)]
+pub struct Targets {
+    type_value_: u64,
+
+    // Informational name of the level, e.g. "MOVIE" or "CHAPTER".
+    type_: String,
+
+    track_uids_: Vec<u64>,
+    edition_uids_: Vec<u64>,
+    chapter_uids_: Vec<u64>,
+    attachment_uids_: Vec<u64>,
+}
+
+impl Default for Targets {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Targets {
+    pub fn new() -> Targets {
+        Targets {
+            type_value_: kDefaultTargetTypeValue,
+            type_: String::new(),
+            track_uids_: Vec::new(),
+            edition_uids_: Vec::new(),
+            chapter_uids_: Vec::new(),
+            attachment_uids_: Vec::new(),
+        }
+    }
+
+    pub fn set_type_value(&mut self, type_value: u64) {
+        self.type_value_ = type_value;
+    }
+
+    pub fn type_value(&self) -> u64 {
+        self.type_value_
+    }
+
+    pub fn set_type(&mut self, target_type: &str) {
+        self.type_ = String::from(target_type);
+    }
+
+    pub fn target_type(&self) -> &str {
+        &self.type_
+    }
+
+    pub fn AddTrackUID(&mut self, uid: u64) {
+        self.track_uids_.push(uid);
+    }
+
+    pub fn AddEditionUID(&mut self, uid: u64) {
+        self.edition_uids_.push(uid);
+    }
+
+    pub fn AddChapterUID(&mut self, uid: u64) {
+        self.chapter_uids_.push(uid);
+    }
+
+    pub fn AddAttachmentUID(&mut self, uid: u64) {
+        self.attachment_uids_.push(uid);
+    }
+
+    pub fn track_uids(&self) -> &[u64] {
+        &self.track_uids_
+    }
+
+    pub fn edition_uids(&self) -> &[u64] {
+        &self.edition_uids_
+    }
+
+    pub fn chapter_uids(&self) -> &[u64] {
+        &self.chapter_uids_
+    }
+
+    pub fn attachment_uids(&self) -> &[u64] {
+        &self.attachment_uids_
+    }
+
+    // Returns true if the targets hold only default values, in which case
+    // the Targets element is not written.
+    pub fn IsDefault(&self) -> bool {
+        self.type_value_ == kDefaultTargetTypeValue
+            && self.type_.is_empty()
+            && self.track_uids_.is_empty()
+            && self.edition_uids_.is_empty()
+            && self.chapter_uids_.is_empty()
+            && self.attachment_uids_.is_empty()
+    }
+
+    fn UIDs(&self) -> [(MkvId, &[u64]); 4] {
+        [
+            (MkvId::MkvTagTrackUID, &self.track_uids_),
+            (MkvId::MkvTagEditionUID, &self.edition_uids_),
+            (MkvId::MkvTagChapterUID, &self.chapter_uids_),
+            (MkvId::MkvTagAttachmentUID, &self.attachment_uids_),
+        ]
+    }
+
+    pub fn PayloadSize(&self) -> u64 {
+        let mut payload_size = 0;
+
+        if self.type_value_ != kDefaultTargetTypeValue {
+            payload_size +=
+                util::EbmlElementSizeArgU64(MkvId::MkvTargetTypeValue, self.type_value_);
+        }
+        if !self.type_.is_empty() {
+            payload_size += util::EbmlElementSizeArgStr(MkvId::MkvTargetType, &self.type_);
+        }
+        for (id, uids) in self.UIDs().iter() {
+            for &uid in uids.iter() {
+                payload_size += util::EbmlElementSizeArgU64(*id, uid);
+            }
+        }
+
+        payload_size
+    }
+
+    pub fn Write(&self, writer: &mut dyn Writer) -> Result<()> {
+        let payload_size = self.PayloadSize();
+        let targets_size =
+            util::EbmlMasterElementSize(MkvId::MkvTargets, payload_size) + payload_size;
+
+        let start = writer.get_position();
+
+        util::WriteEbmlMasterElement(writer, MkvId::MkvTargets, payload_size)?;
+
+        if self.type_value_ != kDefaultTargetTypeValue {
+            util::WriteEbmlElementArgU64(writer, MkvId::MkvTargetTypeValue, self.type_value_)?;
+        }
+
+        if !self.type_.is_empty() {
+            util::WriteEbmlElementArgStr(writer, MkvId::MkvTargetType, &self.type_)?;
+        }
+
+        for (id, uids) in self.UIDs().iter() {
+            for &uid in uids.iter() {
+                util::WriteEbmlElementArgU64(writer, *id, uid)?;
+            }
+        }
+
+        let stop = writer.get_position();
+        CheckSize(MkvId::MkvTargets, targets_size, stop - start)?;
+
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct SimpleTag {
     tag_name_: String,
     tag_string_: String,
+
+    // Language of the tag value. Not written when empty, which readers treat
+    // as "und".
+    tag_language_: String,
+
+    // TagDefault. Only written when false.
+    default_: bool,
+
+    // Binary value of the tag. When set it is written as TagBinary instead
+    // of TagString.
+    tag_binary_: Vec<u8>,
+
+    // Nested SimpleTags, e.g. the URL of a publisher.
+    simple_tags_: Vec<SimpleTag>,
+}
+
+impl Default for SimpleTag {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SimpleTag {
@@ -13,6 +188,10 @@ impl SimpleTag {
         SimpleTag {
             tag_name_: String::new(),
             tag_string_: String::new(),
+            tag_language_: String::new(),
+            default_: true,
+            tag_binary_: Vec::new(),
+            simple_tags_: Vec::new(),
         }
     }
 
@@ -20,101 +199,215 @@ impl SimpleTag {
         self.tag_name_ = String::from(tag_name);
     }
 
+    pub fn tag_name(&self) -> &str {
+        &self.tag_name_
+    }
+
     pub fn set_tag_string(&mut self, tag_string: &str) {
         self.tag_string_ = String::from(tag_string);
     }
 
+    pub fn tag_string(&self) -> &str {
+        &self.tag_string_
+    }
+
+    pub fn set_tag_language(&mut self, tag_language: &str) {
+        self.tag_language_ = String::from(tag_language);
+    }
+
+    pub fn tag_language(&self) -> &str {
+        &self.tag_language_
+    }
+
+    pub fn set_default(&mut self, default: bool) {
+        self.default_ = default;
+    }
+
+    pub fn default(&self) -> bool {
+        self.default_
+    }
+
+    pub fn set_tag_binary(&mut self, tag_binary: &[u8]) {
+        self.tag_binary_ = tag_binary.to_vec();
+    }
+
+    pub fn tag_binary(&self) -> &[u8] {
+        &self.tag_binary_
+    }
+
+    // Adds a nested SimpleTag with the given |tag_name| and |tag_string| and
+    // returns it so the caller can set its other fields.
+    pub fn AddSimpleTag(&mut self, tag_name: &str, tag_string: &str) -> &mut SimpleTag {
+        let mut st = SimpleTag::new();
+        st.set_tag_name(tag_name);
+        st.set_tag_string(tag_string);
+        self.simple_tags_.push(st);
+        self.simple_tags_.last_mut().unwrap()
+    }
+
+    pub fn simple_tag_count(&self) -> usize {
+        self.simple_tags_.len()
+    }
+
     pub fn PayloadSize(&self) -> u64 {
         let mut payload_size = util::EbmlElementSizeArgStr(MkvId::MkvTagName, &self.tag_name_);
 
-        payload_size += util::EbmlElementSizeArgStr(MkvId::MkvTagString, &self.tag_string_);
+        if !self.tag_language_.is_empty() {
+            payload_size += util::EbmlElementSizeArgStr(MkvId::MkvTagLanguage, &self.tag_language_);
+        }
+
+        if !self.default_ {
+            payload_size += util::EbmlElementSizeArgU64(MkvId::MkvTagDefault, 0);
+        }
+
+        if self.tag_binary_.is_empty() {
+            payload_size += util::EbmlElementSizeArgStr(MkvId::MkvTagString, &self.tag_string_);
+        } else {
+            payload_size += util::EbmlElementSizeArgSlice(MkvId::MkvTagBinary, &self.tag_binary_);
+        }
+
+        for st in &self.simple_tags_ {
+            let simple_tag_payload_size = st.PayloadSize();
+            payload_size +=
+                util::EbmlMasterElementSize(MkvId::MkvSimpleTag, simple_tag_payload_size)
+                    + simple_tag_payload_size;
+        }
 
         payload_size
     }
-    pub fn Write(&self, writer: &mut dyn Writer) -> bool {
+    pub fn Write(&self, writer: &mut dyn Writer) -> Result<()> {
         let payload_size = self.PayloadSize();
         let simple_tag_size =
             util::EbmlMasterElementSize(MkvId::MkvSimpleTag, payload_size) + payload_size;
 
         let start = writer.get_position();
 
-        if !util::WriteEbmlMasterElement(writer, MkvId::MkvSimpleTag, payload_size) {
-            return false;
+        util::WriteEbmlMasterElement(writer, MkvId::MkvSimpleTag, payload_size)?;
+
+        util::WriteEbmlElementArgStr(writer, MkvId::MkvTagName, &self.tag_name_)?;
+
+        if !self.tag_language_.is_empty() {
+            util::WriteEbmlElementArgStr(writer, MkvId::MkvTagLanguage, &self.tag_language_)?;
         }
 
-        if !util::WriteEbmlElementArgStr(writer, MkvId::MkvTagName, &self.tag_name_) {
-            return false;
+        if !self.default_ {
+            util::WriteEbmlElementArgU64(writer, MkvId::MkvTagDefault, 0)?;
         }
 
-        if !util::WriteEbmlElementArgStr(writer, MkvId::MkvTagString, &self.tag_string_) {
-            return false;
+        if self.tag_binary_.is_empty() {
+            util::WriteEbmlElementArgStr(writer, MkvId::MkvTagString, &self.tag_string_)?;
+        } else {
+            util::WriteEbmlElementArgSlice(writer, MkvId::MkvTagBinary, &self.tag_binary_)?;
+        }
+
+        for st in &self.simple_tags_ {
+            st.Write(writer)?;
         }
 
         let stop = writer.get_position();
 
-        if stop - start != simple_tag_size {
-            return false;
-        }
+        CheckSize(MkvId::MkvSimpleTag, simple_tag_size, stop - start)?;
 
-        true
+        Ok(())
     }
 }
 
 pub struct Tag {
+    targets_: Targets,
     simple_tags_: Vec<SimpleTag>,
 }
 
+impl Default for Tag {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Tag {
     pub fn new() -> Tag {
         Tag {
+            targets_: Targets::new(),
             simple_tags_: Vec::new(),
         }
     }
 
+    // Returns the targets of the tag so the caller can point it at tracks,
+    // editions, chapters or attachments.
+    pub fn targets(&mut self) -> &mut Targets {
+        &mut self.targets_
+    }
+
+    // Adds a SimpleTag with the given |tag_name| and |tag_string|.
+    pub fn add_simple_tag(&mut self, tag_name: &str, tag_string: &str) {
+        self.AddSimpleTag(tag_name, tag_string);
+    }
+
+    // Adds a SimpleTag with the given |tag_name| and |tag_string| and returns
+    // it so the caller can set its language, binary value or nested tags.
+    pub fn AddSimpleTag(&mut self, tag_name: &str, tag_string: &str) -> &mut SimpleTag {
+        let mut st = SimpleTag::new();
+        st.set_tag_name(tag_name);
+        st.set_tag_string(tag_string);
+        self.simple_tags_.push(st);
+        self.simple_tags_.last_mut().unwrap()
+    }
+
+    pub fn simple_tag_count(&self) -> usize {
+        self.simple_tags_.len()
+    }
+
     pub fn clear(&mut self) {
+        self.targets_ = Targets::new();
         self.simple_tags_.clear();
     }
 
     pub fn PayloadSize(&self) -> u64 {
         let mut payload_size = 0;
 
+        if !self.targets_.IsDefault() {
+            let targets_payload_size = self.targets_.PayloadSize();
+            payload_size += util::EbmlMasterElementSize(MkvId::MkvTargets, targets_payload_size)
+                + targets_payload_size;
+        }
+
         for st in &self.simple_tags_ {
-            payload_size += st.PayloadSize();
-            payload_size += util::EbmlMasterElementSize(MkvId::MkvSimpleTag, payload_size);
+            let simple_tag_payload_size = st.PayloadSize();
+            payload_size +=
+                util::EbmlMasterElementSize(MkvId::MkvSimpleTag, simple_tag_payload_size)
+                    + simple_tag_payload_size;
         }
 
         payload_size
     }
 
-    pub fn Write(&self, writer: &mut dyn Writer) -> bool {
+    pub fn Write(&self, writer: &mut dyn Writer) -> Result<()> {
         let payload_size = self.PayloadSize();
 
         let tag_size = util::EbmlMasterElementSize(MkvId::MkvTag, payload_size) + payload_size;
 
         let start = writer.get_position();
 
-        if !util::WriteEbmlMasterElement(writer, MkvId::MkvTag, payload_size) {
-            return false;
+        util::WriteEbmlMasterElement(writer, MkvId::MkvTag, payload_size)?;
+
+        if !self.targets_.IsDefault() {
+            self.targets_.Write(writer)?;
         }
 
         for st in &self.simple_tags_ {
-            if !st.Write(writer) {
-                return false;
-            }
+            st.Write(writer)?;
         }
 
         let stop = writer.get_position();
-        if stop - start != tag_size {
-            return false;
-        }
+        CheckSize(MkvId::MkvTag, tag_size, stop - start)?;
 
-        true
+        Ok(())
     }
 }
 
 impl Clone for Tag {
     fn clone(&self) -> Tag {
         Tag {
+            targets_: self.targets_.clone(),
             simple_tags_: self.simple_tags_.to_vec(),
         }
     }
@@ -124,6 +417,12 @@ pub struct Tags {
     tags_: Vec<Tag>,
 }
 
+impl Default for Tags {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Tags {
     pub fn new() -> Tags {
         Tags { tags_: Vec::new() }
@@ -133,37 +432,42 @@ impl Tags {
         self.tags_.push(tag);
     }
 
+    // Adds an empty Tag and returns it so the caller can populate it.
+    pub fn AddTag(&mut self) -> &mut Tag {
+        self.tags_.push(Tag::new());
+        self.tags_.last_mut().unwrap()
+    }
+
+    pub fn Count(&self) -> usize {
+        self.tags_.len()
+    }
+
     pub fn PayloadSize(&self) -> u64 {
         let mut payload_size = 0;
 
         for t in &self.tags_ {
-            payload_size += t.PayloadSize();
-            payload_size += util::EbmlMasterElementSize(MkvId::MkvTag, payload_size);
+            let tag_payload_size = t.PayloadSize();
+            payload_size +=
+                util::EbmlMasterElementSize(MkvId::MkvTag, tag_payload_size) + tag_payload_size;
         }
 
         payload_size
     }
 
-    pub fn Write(&self, writer: &mut dyn Writer) -> bool {
+    pub fn Write(&self, writer: &mut dyn Writer) -> Result<()> {
         let payload_size = self.PayloadSize();
 
-        if !util::WriteEbmlMasterElement(writer, MkvId::MkvTags, payload_size) {
-            return false;
-        }
+        util::WriteEbmlMasterElement(writer, MkvId::MkvTags, payload_size)?;
 
         let start = writer.get_position();
 
         for t in &self.tags_ {
-            if !t.Write(writer) {
-                return false;
-            }
+            t.Write(writer)?;
         }
 
         let stop = writer.get_position();
-        if stop - start != payload_size {
-            return false;
-        }
+        CheckSize(MkvId::MkvTags, payload_size, stop - start)?;
 
-        true
+        Ok(())
     }
 }
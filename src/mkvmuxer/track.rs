@@ -1,4 +1,9 @@
-use super::content_encoding::ContentEncoding;
+use super::content_encoding::{
+    kCompression, kScopeCodecPrivate, kScopeFrames, ContentCompAlgo, ContentEncoding,
+};
+use super::content_encryptor::ContentEncryptor;
+use super::error::{CheckSize, MuxerError, Result};
+use super::frame::{Frame, Lacing};
 use super::tracks::kAv1CodecId;
 use super::util;
 use super::writer::Writer;
@@ -8,6 +13,11 @@ pub struct Track {
     // Track element names.
     codec_id_: String,
     codec_private_: Vec<u8>,
+
+    // CodecPrivate as written, with the encodings that apply to it. Set by
+    // EncodeCodecPrivate().
+    encoded_codec_private_: Vec<u8>,
+
     language_: String,
     max_block_additional_id_: u64,
     name_: String,
@@ -18,15 +28,26 @@ pub struct Track {
     seek_pre_roll_: u64,
     default_duration_: u64,
 
+    // Lacing used for the frames added with Segment::AddLacedFrames(). When
+    // set, FlagLacing is written.
+    lacing_: Lacing,
+
     // ContentEncoding element list.
     content_encoding_entries_: Vec<ContentEncoding>,
 }
 
+impl Default for Track {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Track {
     pub fn new() -> Track {
         Track {
             codec_id_: String::new(),
             codec_private_: Vec::new(),
+            encoded_codec_private_: Vec::new(),
             language_: String::new(),
             max_block_additional_id_: 0,
             name_: String::new(),
@@ -36,6 +57,7 @@ impl Track {
             codec_delay_: 0,
             seek_pre_roll_: 0,
             default_duration_: 0,
+            lacing_: Lacing::kNoLacing,
             content_encoding_entries_: Vec::new(),
         }
     }
@@ -48,10 +70,29 @@ impl Track {
     }
     pub fn set_codec_private(&mut self, codec_private: &[u8]) {
         self.codec_private_ = codec_private.to_vec();
+        self.encoded_codec_private_ = codec_private.to_vec();
     }
     pub fn codec_private(&self) -> &[u8] {
         &self.codec_private_
     }
+    pub fn encoded_codec_private(&self) -> &[u8] {
+        &self.encoded_codec_private_
+    }
+
+    // Applies the encodings that apply to the CodecPrivate, once before the
+    // track is written, so the size and the written element agree.
+    pub fn EncodeCodecPrivate(&mut self) -> Result<()> {
+        let mut codec_private = self.codec_private_.clone();
+        if !codec_private.is_empty() {
+            for encoding in &self.content_encoding_entries_ {
+                if encoding.encoding_scope() & kScopeCodecPrivate != 0 {
+                    codec_private = encoding.Compress(&codec_private)?;
+                }
+            }
+        }
+        self.encoded_codec_private_ = codec_private;
+        Ok(())
+    }
     pub fn set_language(&mut self, language: &str) {
         self.language_ = language.to_string();
     }
@@ -106,16 +147,173 @@ impl Track {
     pub fn default_duration(&self) -> u64 {
         self.default_duration_
     }
+    pub fn set_lacing(&mut self, lacing: Lacing) {
+        self.lacing_ = lacing;
+    }
+    pub fn lacing(&self) -> Lacing {
+        self.lacing_
+    }
+
+    // Adds |encoding| to the track. Encodings are applied by ascending
+    // ContentEncodingOrder, so e.g. a compression with order 0 and an
+    // encryption with order 1 compress the frames before encrypting them.
+    pub fn AddContentEncoding(&mut self, encoding: ContentEncoding) -> Result<()> {
+        let scope = encoding.encoding_scope();
+        // Encodings of the next encoding (kScopeNextEncoding) are not
+        // supported.
+        if scope == 0 || scope & !(kScopeFrames | kScopeCodecPrivate) != 0 {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvContentEncodingScope,
+                "unsupported scope",
+            ));
+        }
+        if scope & kScopeCodecPrivate != 0 && encoding.encoding_type() != kCompression {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvContentEncodingScope,
+                "only compression can apply to CodecPrivate",
+            ));
+        }
+        if encoding.encoding_type() == kCompression
+            && encoding.comp_algo() == ContentCompAlgo::kHeaderStripping
+            && encoding.comp_settings().is_empty()
+        {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvContentCompSettings,
+                "header stripping requires the stripped bytes",
+            ));
+        }
+        let order = encoding.encoding_order();
+        if self
+            .content_encoding_entries_
+            .iter()
+            .any(|e| e.encoding_order() == order)
+        {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvContentEncodingOrder,
+                "order already used",
+            ));
+        }
+
+        let index = self
+            .content_encoding_entries_
+            .iter()
+            .position(|e| e.encoding_order() > order)
+            .unwrap_or(self.content_encoding_entries_.len());
+        self.content_encoding_entries_.insert(index, encoding);
+        Ok(())
+    }
+
+    // Returns the ContentEncodingOrder following the encodings of the track.
+    fn NextEncodingOrder(&self) -> u64 {
+        self.content_encoding_entries_
+            .last()
+            .map_or(0, |encoding| encoding.encoding_order() + 1)
+    }
+
+    // Compresses the frames of the track with |algo|, after the encodings
+    // already added. For kHeaderStripping, |settings| are the bytes every
+    // frame starts with; they are removed from the frames and restored by the
+    // parser.
+    pub fn AddContentCompression(&mut self, algo: ContentCompAlgo, settings: &[u8]) -> Result<()> {
+        let mut encoding = ContentEncoding::new_compression(algo, settings);
+        encoding.set_encoding_order(self.NextEncodingOrder());
+        self.AddContentEncoding(encoding)
+    }
+
+    // Encrypts the frames of the track with AES-128 in CTR mode using |key|,
+    // in the WebM encryption format, after the encodings already added.
+    // |key_id| is written to ContentEncKeyID.
+    pub fn AddContentEncryption(&mut self, key: &[u8], key_id: &[u8]) -> Result<()> {
+        let mut encoding = ContentEncoding::new_encryption(ContentEncryptor::new(key, key_id)?);
+        encoding.set_encoding_order(self.NextEncodingOrder());
+        self.AddContentEncoding(encoding)
+    }
+
+    pub fn content_encoding_count(&self) -> usize {
+        self.content_encoding_entries_.len()
+    }
+
+    pub fn GetContentEncoding(&mut self, index: usize) -> Option<&mut ContentEncoding> {
+        self.content_encoding_entries_.get_mut(index)
+    }
+
+    // Returns false if the track is compressed, which WebM does not allow.
+    pub fn WebmCompatible(&self) -> bool {
+        self.content_encoding_entries_
+            .iter()
+            .all(|encoding| encoding.encoding_type() != kCompression)
+    }
+
+    // Applies the content encodings of the track to the frames of |frame|.
+    // The partition offsets of |frame| are used by the encryption and
+    // cleared, since the encrypted frame carries them.
+    pub fn EncodeFrame(&mut self, frame: &mut Frame) -> Result<()> {
+        let partition_offsets = frame.partition_offsets().to_vec();
+        if !partition_offsets.is_empty() {
+            if frame.lacing() != Lacing::kNoLacing {
+                return Err(MuxerError::kInvalidValue(
+                    MkvId::MkvContentEncryption,
+                    "laced frames cannot be partitioned",
+                ));
+            }
+            let frame_encodings = self
+                .content_encoding_entries_
+                .iter()
+                .filter(|encoding| encoding.encoding_scope() & kScopeFrames != 0);
+            let mut compressed = false;
+            let mut encrypted = false;
+            for encoding in frame_encodings {
+                if encoding.HasEncryptor() {
+                    encrypted = true;
+                    break;
+                }
+                compressed |= encoding.encoding_type() == kCompression;
+            }
+            if !encrypted {
+                return Err(MuxerError::kInvalidValue(
+                    MkvId::MkvContentEncryption,
+                    "partitioned frames need an encrypted track",
+                ));
+            }
+            // The offsets point into the frame as added, not into the
+            // compressed data the encryption would see.
+            if compressed {
+                return Err(MuxerError::kInvalidValue(
+                    MkvId::MkvContentEncryption,
+                    "partitioned frames can't be compressed before the encryption",
+                ));
+            }
+        }
+
+        for encoding in &mut self.content_encoding_entries_ {
+            if encoding.encoding_scope() & kScopeFrames == 0 {
+                continue;
+            }
+            if !frame.TransformFrames(|data| encoding.EncodeFrame(data, &partition_offsets))? {
+                return Err(MuxerError::kInvalidValue(
+                    MkvId::MkvContentEncoding,
+                    "encoded frames cannot be laced",
+                ));
+            }
+        }
+        frame.set_partition_offsets(&[]);
+        Ok(())
+    }
 
     pub fn PayloadSize(&self) -> u64 {
+        let codec_private = &self.encoded_codec_private_;
+
         let mut size = util::EbmlElementSizeArgU64(MkvId::MkvTrackNumber, self.number_);
         size += util::EbmlElementSizeArgU64(MkvId::MkvTrackUID, self.uid_);
         size += util::EbmlElementSizeArgU64(MkvId::MkvTrackType, self.track_type_);
+        if self.lacing_ != Lacing::kNoLacing {
+            size += util::EbmlElementSizeArgU64(MkvId::MkvFlagLacing, 1);
+        }
         if !self.codec_id_.is_empty() {
             size += util::EbmlElementSizeArgStr(MkvId::MkvCodecID, &self.codec_id_);
         }
-        if !self.codec_private_.is_empty() {
-            size += util::EbmlElementSizeArgSlice(MkvId::MkvCodecPrivate, &self.codec_private_);
+        if !codec_private.is_empty() {
+            size += util::EbmlElementSizeArgSlice(MkvId::MkvCodecPrivate, codec_private);
         }
         if !self.language_.is_empty() {
             size += util::EbmlElementSizeArgStr(MkvId::MkvLanguage, &self.language_);
@@ -158,10 +356,26 @@ impl Track {
         size
     }
 
-    pub fn Write(&self, writer: &mut dyn Writer) -> bool {
+    pub fn Write(&self, writer: &mut dyn Writer) -> Result<()> {
+        self.WriteEntry(writer, self.PayloadSize())
+    }
+
+    // Writes the TrackEntry header and the elements common to all tracks.
+    // |payload_size| may be bigger than what is written out in this function
+    // because derived tracks may write out more data in the TrackEntry element.
+    pub fn WriteEntry(&self, writer: &mut dyn Writer, payload_size: u64) -> Result<()> {
         // mandatory elements without a default value.
-        if self.track_type_ == 0 || self.codec_id_.is_empty() {
-            return false;
+        if self.track_type_ == 0 {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvTrackType,
+                "track type not set",
+            ));
+        }
+        if self.codec_id_.is_empty() {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvCodecID,
+                "codec id not set",
+            ));
         }
 
         // AV1 tracks require a CodecPrivate. See
@@ -169,26 +383,28 @@ impl Track {
         // TODO: Update the above link to the AV1 Matroska mappings to
         // point to a stable version once it is finalized, or our own WebM mappings
         // page on webmproject.org should we decide to release them.
-        if self.codec_id_ == kAv1CodecId && !self.codec_private_.is_empty() {
-            return false;
+        if self.codec_id_ == kAv1CodecId && self.codec_private_.is_empty() {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvCodecPrivate,
+                "AV1 tracks require CodecPrivate",
+            ));
         }
 
-        // |size| may be bigger than what is written out in this function because
-        // derived classes may write out more data in the Track element.
-        let payload_size = self.PayloadSize();
+        let codec_private = &self.encoded_codec_private_;
 
-        if !util::WriteEbmlMasterElement(writer, MkvId::MkvTrackEntry, payload_size) {
-            return false;
-        }
+        util::WriteEbmlMasterElement(writer, MkvId::MkvTrackEntry, payload_size)?;
 
         let mut size = util::EbmlElementSizeArgU64(MkvId::MkvTrackNumber, self.number_);
         size += util::EbmlElementSizeArgU64(MkvId::MkvTrackUID, self.uid_);
         size += util::EbmlElementSizeArgU64(MkvId::MkvTrackType, self.track_type_);
+        if self.lacing_ != Lacing::kNoLacing {
+            size += util::EbmlElementSizeArgU64(MkvId::MkvFlagLacing, 1);
+        }
         if !self.codec_id_.is_empty() {
             size += util::EbmlElementSizeArgStr(MkvId::MkvCodecID, &self.codec_id_);
         }
-        if !self.codec_private_.is_empty() {
-            size += util::EbmlElementSizeArgSlice(MkvId::MkvCodecPrivate, &self.codec_private_);
+        if !codec_private.is_empty() {
+            size += util::EbmlElementSizeArgSlice(MkvId::MkvCodecPrivate, codec_private);
         }
         if !self.language_.is_empty() {
             size += util::EbmlElementSizeArgStr(MkvId::MkvLanguage, &self.language_);
@@ -214,69 +430,47 @@ impl Track {
 
         let payload_position = writer.get_position();
 
-        if !util::WriteEbmlElementArgU64(writer, MkvId::MkvTrackNumber, self.number_) {
-            return false;
-        }
-        if !util::WriteEbmlElementArgU64(writer, MkvId::MkvTrackUID, self.uid_) {
-            return false;
-        }
-        if !util::WriteEbmlElementArgU64(writer, MkvId::MkvTrackType, self.track_type_) {
-            return false;
+        util::WriteEbmlElementArgU64(writer, MkvId::MkvTrackNumber, self.number_)?;
+        util::WriteEbmlElementArgU64(writer, MkvId::MkvTrackUID, self.uid_)?;
+        util::WriteEbmlElementArgU64(writer, MkvId::MkvTrackType, self.track_type_)?;
+        if self.lacing_ != Lacing::kNoLacing {
+            util::WriteEbmlElementArgU64(writer, MkvId::MkvFlagLacing, 1)?;
         }
         if self.max_block_additional_id_ > 0 {
-            if !util::WriteEbmlElementArgU64(
+            util::WriteEbmlElementArgU64(
                 writer,
                 MkvId::MkvMaxBlockAdditionID,
                 self.max_block_additional_id_,
-            ) {
-                return false;
-            }
+            )?;
         }
         if self.codec_delay_ > 0 {
-            if !util::WriteEbmlElementArgU64(writer, MkvId::MkvCodecDelay, self.codec_delay_) {
-                return false;
-            }
+            util::WriteEbmlElementArgU64(writer, MkvId::MkvCodecDelay, self.codec_delay_)?;
         }
         if self.seek_pre_roll_ > 0 {
-            if !util::WriteEbmlElementArgU64(writer, MkvId::MkvSeekPreRoll, self.seek_pre_roll_) {
-                return false;
-            }
+            util::WriteEbmlElementArgU64(writer, MkvId::MkvSeekPreRoll, self.seek_pre_roll_)?;
         }
         if self.default_duration_ > 0 {
-            if !util::WriteEbmlElementArgU64(
+            util::WriteEbmlElementArgU64(
                 writer,
                 MkvId::MkvDefaultDuration,
                 self.default_duration_,
-            ) {
-                return false;
-            }
+            )?;
         }
         if !self.codec_id_.is_empty() {
-            if !util::WriteEbmlElementArgStr(writer, MkvId::MkvCodecID, &self.codec_id_) {
-                return false;
-            }
+            util::WriteEbmlElementArgStr(writer, MkvId::MkvCodecID, &self.codec_id_)?;
         }
-        if !self.codec_private_.is_empty() {
-            if !util::WriteEbmlElementArgSlice(writer, MkvId::MkvCodecPrivate, &self.codec_private_)
-            {
-                return false;
-            }
+        if !codec_private.is_empty() {
+            util::WriteEbmlElementArgSlice(writer, MkvId::MkvCodecPrivate, codec_private)?;
         }
         if !self.language_.is_empty() {
-            if !util::WriteEbmlElementArgStr(writer, MkvId::MkvLanguage, &self.language_) {
-                return false;
-            }
+            util::WriteEbmlElementArgStr(writer, MkvId::MkvLanguage, &self.language_)?;
         }
         if !self.name_.is_empty() {
-            if !util::WriteEbmlElementArgStr(writer, MkvId::MkvName, &self.name_) {
-                return false;
-            }
+            util::WriteEbmlElementArgStr(writer, MkvId::MkvName, &self.name_)?;
         }
 
         let stop_position = writer.get_position();
-        if stop_position - payload_position != size {
-            return false;
-        }
+        CheckSize(MkvId::MkvTrackEntry, size, stop_position - payload_position)?;
 
         if !self.content_encoding_entries_.is_empty() {
             let mut content_encodings_size = 0;
@@ -284,22 +478,18 @@ impl Track {
                 content_encodings_size += encoding.Size();
             }
 
-            if !util::WriteEbmlMasterElement(
+            util::WriteEbmlMasterElement(
                 writer,
                 MkvId::MkvContentEncodings,
                 content_encodings_size,
-            ) {
-                return false;
-            }
+            )?;
 
             for encoding in &self.content_encoding_entries_ {
-                if !encoding.Write(writer) {
-                    return false;
-                }
+                encoding.Write(writer)?;
             }
         }
 
         //stop_position = writer->Position();
-        true
+        Ok(())
     }
 }
@@ -1,31 +1,152 @@
+use super::audio_track::AudioTrack;
+use super::error::{CheckSize, MuxerError, Result};
 use super::track::Track;
 use super::util;
+use super::video_track::VideoTrack;
 use super::writer::Writer;
 use crate::MkvId;
 
-pub const kOpusCodecId: &'static str = "A_OPUS";
-pub const kVorbisCodecId: &'static str = "A_VORBIS";
-pub const kAv1CodecId: &'static str = "V_AV1";
-pub const kVp8CodecId: &'static str = "V_VP8";
-pub const kVp9CodecId: &'static str = "V_VP9";
-pub const kWebVttCaptionsId: &'static str = "D_WEBVTT/CAPTIONS";
-pub const kWebVttDescriptionsId: &'static str = "D_WEBVTT/DESCRIPTIONS";
-pub const kWebVttMetadataId: &'static str = "D_WEBVTT/METADATA";
-pub const kWebVttSubtitlesId: &'static str = "D_WEBVTT/SUBTITLES";
-
-enum TrackType {
+use std::ops::{Deref, DerefMut};
+
+pub const kOpusCodecId: &str = "A_OPUS";
+pub const kVorbisCodecId: &str = "A_VORBIS";
+pub const kAv1CodecId: &str = "V_AV1";
+pub const kVp8CodecId: &str = "V_VP8";
+pub const kVp9CodecId: &str = "V_VP9";
+pub const kWebVttCaptionsId: &str = "D_WEBVTT/CAPTIONS";
+pub const kWebVttDescriptionsId: &str = "D_WEBVTT/DESCRIPTIONS";
+pub const kWebVttMetadataId: &str = "D_WEBVTT/METADATA";
+pub const kWebVttSubtitlesId: &str = "D_WEBVTT/SUBTITLES";
+
+// Codec ids allowed in WebM files.
+pub const kWebmCodecIds: [&str; 9] = [
+    kOpusCodecId,
+    kVorbisCodecId,
+    kAv1CodecId,
+    kVp8CodecId,
+    kVp9CodecId,
+    kWebVttCaptionsId,
+    kWebVttDescriptionsId,
+    kWebVttMetadataId,
+    kWebVttSubtitlesId,
+];
+
+pub fn IsWebmCodec(codec_id: &str) -> bool {
+    kWebmCodecIds.contains(&codec_id)
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum TrackType {
     kVideo = 0x1,
     kAudio = 0x2,
 }
 
+// A TrackEntry held by Tracks. Video and audio tracks write their own
+// settings element after the elements common to all tracks.
+#[allow(clippy::large_enum_variant)]
+pub enum TrackEntry {
+    Generic(Track),
+    Video(VideoTrack),
+    Audio(AudioTrack),
+}
+
+impl Deref for TrackEntry {
+    type Target = Track;
+
+    fn deref(&self) -> &Track {
+        match self {
+            TrackEntry::Generic(t) => t,
+            TrackEntry::Video(t) => t,
+            TrackEntry::Audio(t) => t,
+        }
+    }
+}
+
+impl DerefMut for TrackEntry {
+    fn deref_mut(&mut self) -> &mut Track {
+        match self {
+            TrackEntry::Generic(t) => t,
+            TrackEntry::Video(t) => t,
+            TrackEntry::Audio(t) => t,
+        }
+    }
+}
+
+impl From<Track> for TrackEntry {
+    fn from(track: Track) -> TrackEntry {
+        TrackEntry::Generic(track)
+    }
+}
+
+impl From<VideoTrack> for TrackEntry {
+    fn from(track: VideoTrack) -> TrackEntry {
+        TrackEntry::Video(track)
+    }
+}
+
+impl From<AudioTrack> for TrackEntry {
+    fn from(track: AudioTrack) -> TrackEntry {
+        TrackEntry::Audio(track)
+    }
+}
+
+impl TrackEntry {
+    pub fn as_video(&self) -> Option<&VideoTrack> {
+        match self {
+            TrackEntry::Video(t) => Some(t),
+            _ => None,
+        }
+    }
+    pub fn as_video_mut(&mut self) -> Option<&mut VideoTrack> {
+        match self {
+            TrackEntry::Video(t) => Some(t),
+            _ => None,
+        }
+    }
+    pub fn as_audio(&self) -> Option<&AudioTrack> {
+        match self {
+            TrackEntry::Audio(t) => Some(t),
+            _ => None,
+        }
+    }
+    pub fn as_audio_mut(&mut self) -> Option<&mut AudioTrack> {
+        match self {
+            TrackEntry::Audio(t) => Some(t),
+            _ => None,
+        }
+    }
+
+    pub fn Size(&self) -> u64 {
+        match self {
+            TrackEntry::Generic(t) => t.Size(),
+            TrackEntry::Video(t) => t.Size(),
+            TrackEntry::Audio(t) => t.Size(),
+        }
+    }
+
+    pub fn Write(&self, writer: &mut dyn Writer) -> Result<()> {
+        match self {
+            TrackEntry::Generic(t) => t.Write(writer),
+            TrackEntry::Video(t) => t.Write(writer),
+            TrackEntry::Audio(t) => t.Write(writer),
+        }
+    }
+}
+
 pub struct Tracks {
     // Track element list.
-    track_entries_: Vec<Track>,
+    track_entries_: Vec<TrackEntry>,
 
     // Whether or not Tracks element has already been written via IMkvWriter.
     wrote_tracks_: bool,
 }
 
+impl Default for Tracks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Tracks {
     pub fn new() -> Tracks {
         Tracks {
@@ -34,9 +155,19 @@ impl Tracks {
         }
     }
 
-    pub fn AddTrack(&mut self, track: Track, number: i32) -> bool {
-        if number < 0 || self.wrote_tracks_ {
-            return false;
+    pub fn track_entries_size(&self) -> usize {
+        self.track_entries_.len()
+    }
+
+    pub fn AddTrack(&mut self, track: impl Into<TrackEntry>, number: i32) -> Result<()> {
+        if self.wrote_tracks_ {
+            return Err(MuxerError::kInvalidState("Tracks already written"));
+        }
+        if number < 0 {
+            return Err(MuxerError::kInvalidValue(
+                MkvId::MkvTrackNumber,
+                "negative track number",
+            ));
         }
 
         // This muxer only supports track numbers in the range [1, 126], in
@@ -45,7 +176,7 @@ impl Tracks {
         // for a frame using exactly 4 bytes.
 
         if number > 0x7E {
-            return false;
+            return Err(MuxerError::kInvalidTrackNumber(number as u64));
         }
 
         let mut track_num = number as u64;
@@ -54,7 +185,7 @@ impl Tracks {
             // Check to make sure a track does not already have |track_num|.
             for t in &self.track_entries_ {
                 if t.number() == track_num {
-                    return false;
+                    return Err(MuxerError::kInvalidTrackNumber(track_num));
                 }
             }
         }
@@ -79,27 +210,30 @@ impl Tracks {
             }
         }
 
-        let mut track = track;
+        let mut track = track.into();
         track.set_number(track_num);
         self.track_entries_.push(track);
-        return true;
+        Ok(())
     }
 
-    pub fn GetTrackByIndex(&self, index: usize) -> Option<&Track> {
-        if index >= self.track_entries_.len() {
-            None
-        } else {
-            Some(&self.track_entries_[index])
-        }
+    pub fn GetTrackByIndex(&self, index: usize) -> Option<&TrackEntry> {
+        self.track_entries_.get(index)
     }
 
-    pub fn GetTrackByNumber(&self, track_number: u64) -> Option<&Track> {
-        for t in &self.track_entries_ {
-            if t.number() == track_number {
-                return Some(t);
-            }
-        }
-        None
+    pub fn GetTrackByIndexMut(&mut self, index: usize) -> Option<&mut TrackEntry> {
+        self.track_entries_.get_mut(index)
+    }
+
+    pub fn GetTrackByNumber(&self, track_number: u64) -> Option<&TrackEntry> {
+        self.track_entries_
+            .iter()
+            .find(|t| t.number() == track_number)
+    }
+
+    pub fn GetTrackByNumberMut(&mut self, track_number: u64) -> Option<&mut TrackEntry> {
+        self.track_entries_
+            .iter_mut()
+            .find(|t| t.number() == track_number)
     }
 
     pub fn TrackIsAudio(&self, track_number: u64) -> bool {
@@ -124,30 +258,28 @@ impl Tracks {
         false
     }
 
-    pub fn Write(&mut self, writer: &mut dyn Writer) -> bool {
+    pub fn Write(&mut self, writer: &mut dyn Writer) -> Result<()> {
+        for track in &mut self.track_entries_ {
+            track.EncodeCodecPrivate()?;
+        }
+
         let mut size = 0;
         for track in &self.track_entries_ {
             size += track.Size();
         }
 
-        if !util::WriteEbmlMasterElement(writer, MkvId::MkvTracks, size) {
-            return false;
-        }
+        util::WriteEbmlMasterElement(writer, MkvId::MkvTracks, size)?;
 
         let payload_position = writer.get_position();
 
         for track in &self.track_entries_ {
-            if !track.Write(writer) {
-                return false;
-            }
+            track.Write(writer)?;
         }
 
         let stop_position = writer.get_position();
-        if stop_position - payload_position != size {
-            return false;
-        }
+        CheckSize(MkvId::MkvTracks, size, stop_position - payload_position)?;
 
         self.wrote_tracks_ = true;
-        true
+        Ok(())
     }
 }
@@ -1,8 +1,9 @@
-use super::writer::Writer;
+use super::error::{CheckSize, IoError, MuxerError, Result};
+use super::writer::{MemoryWriter, Writer};
 use crate::MkvId;
 use rand::Rng;
 use std::io;
-use std::io::{Error, ErrorKind};
+use std::io::Error;
 
 pub const EBML_UNKNOWN_VALUE: u64 = 0x01FFFFFFFFFFFFFF;
 pub const MAX_BLOCK_TIMECODE: i64 = 0x07FFF;
@@ -10,8 +11,33 @@ pub const MAX_BLOCK_TIMECODE: i64 = 0x07FFF;
 // Date elements are always 8 octets in size.
 const DATE_ELEMENT_SIZE: i32 = 8;
 
-const DOC_TYPE_WEBM: &'static str = "webm";
-const DOC_TYPE_MATROSKA: &'static str = "matroska";
+// Size of a CRC-32 element: 1 byte id, 1 byte size and the 4 byte CRC.
+pub const CRC32_ELEMENT_SIZE: u64 = 6;
+
+// Lookup table of the CRC-32 used by EBML (IEEE 802.3, reflected polynomial
+// 0xEDB88320).
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB88320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+pub const DOC_TYPE_WEBM: &str = "webm";
+pub const DOC_TYPE_MATROSKA: &str = "matroska";
 
 pub fn GetCodedUIntSize(value: u64) -> i32 {
     if value < 0x000000000000007F {
@@ -32,6 +58,20 @@ pub fn GetCodedUIntSize(value: u64) -> i32 {
     8
 }
 
+// Returns the size in bytes of |value| coded as a signed EBML integer, as used
+// for the frame size differences of EBML lacing.
+pub fn GetCodedIntSize(value: i64) -> i32 {
+    let mut size = 1;
+    while size < 8 {
+        let bias = (1i64 << (size * 7 - 1)) - 1;
+        if -bias <= value && value <= bias {
+            break;
+        }
+        size += 1;
+    }
+    size
+}
+
 pub fn GetUIntSize(value: u64) -> i32 {
     if value < 0x0000000000000100 {
         return 1;
@@ -72,12 +112,12 @@ pub fn GetVersion(major: &mut i32, minor: &mut i32, build: &mut i32, revision: &
 pub fn MakeUID() -> u64 {
     let mut rng = rand::thread_rng();
     let uid: u64 = rng.gen();
-    return uid;
+    uid
 }
 
 pub fn SerializeInt(writer: &mut dyn Writer, value: u64, size: i32) -> io::Result<()> {
-    if size < 1 || size > 8 {
-        Err(Error::new(ErrorKind::Other, "size should be in [1,8]"))
+    if !(1..=8).contains(&size) {
+        Err(Error::other("size should be in [1,8]"))
     } else {
         let mut buffer = vec![0; size as usize];
         for i in 1..=size {
@@ -102,7 +142,7 @@ pub fn SerializeFloat(writer: &mut dyn Writer, f: f32) -> io::Result<()> {
         f: f32,
     }
 
-    let value: U32 = U32 { f: f };
+    let value: U32 = U32 { f };
 
     let mut buffer = vec![0; 4];
     for i in 1..=4 {
@@ -122,26 +162,26 @@ pub fn WriteUInt(writer: &mut dyn Writer, value: u64) -> io::Result<()> {
 }
 
 pub fn WriteUIntSize(writer: &mut dyn Writer, value: u64, size: i32) -> io::Result<()> {
-    if size < 0 || size > 8 {
-        return Err(Error::new(ErrorKind::Other, "size should be in [0,8]"));
+    if !(0..=8).contains(&size) {
+        return Err(Error::other("size should be in [0,8]"));
     }
 
     let mut value = value;
     let mut size = size;
     if size > 0 {
-        let bit = (1 as u64) << (size * 7);
+        let bit = 1u64 << (size * 7);
 
         if value > (bit - 2) {
-            return Err(Error::new(ErrorKind::Other, "value should > bit-2"));
+            return Err(Error::other("value should > bit-2"));
         }
 
         value |= bit;
     } else {
         size = 1;
-        let mut bit = 0;
+        let mut bit;
 
         loop {
-            bit = (1 as u64) << (size * 7);
+            bit = 1u64 << (size * 7);
             let m = bit - 2;
 
             if value <= m {
@@ -152,7 +192,7 @@ pub fn WriteUIntSize(writer: &mut dyn Writer, value: u64, size: i32) -> io::Resu
         }
 
         if size > 8 {
-            return Err(Error::new(ErrorKind::Other, "size cannot > 8"));
+            return Err(Error::other("size cannot > 8"));
         }
 
         value |= bit;
@@ -161,6 +201,21 @@ pub fn WriteUIntSize(writer: &mut dyn Writer, value: u64, size: i32) -> io::Resu
     SerializeInt(writer, value, size)
 }
 
+// Writes |value| as a signed EBML integer of |size| bytes. The value is
+// offset by half the range of the coded unsigned integer.
+pub fn WriteIntSize(writer: &mut dyn Writer, value: i64, size: i32) -> io::Result<()> {
+    if !(1..=8).contains(&size) {
+        return Err(Error::other("size should be in [1,8]"));
+    }
+
+    let bias = (1i64 << (size * 7 - 1)) - 1;
+    if value < -bias || value > bias {
+        return Err(Error::other("value does not fit in size"));
+    }
+
+    WriteUIntSize(writer, (value + bias) as u64, size)
+}
+
 pub fn WriteID(writer: &mut dyn Writer, t: MkvId) -> io::Result<()> {
     writer.element_start_notify(t, writer.get_position());
 
@@ -169,22 +224,68 @@ pub fn WriteID(writer: &mut dyn Writer, t: MkvId) -> io::Result<()> {
     SerializeInt(writer, t as u64, size)
 }
 
-pub fn EbmlMasterElementSize(t: MkvId, _value: u64) -> u64 {
+pub fn EbmlMasterElementSize(t: MkvId, value: u64) -> u64 {
     // Size of EBML ID
     let mut ebml_size: i32 = GetUIntSize(t as u64);
     // Datasize
-    ebml_size += GetCodedUIntSize(t as u64);
+    ebml_size += GetCodedUIntSize(value);
     ebml_size as u64
 }
 
-pub fn WriteEbmlMasterElement(writer: &mut dyn Writer, t: MkvId, size: u64) -> bool {
-    if WriteID(writer, t).is_err() {
-        return false;
-    }
-    if WriteUInt(writer, size).is_err() {
-        return false;
+pub fn WriteEbmlMasterElement(writer: &mut dyn Writer, t: MkvId, size: u64) -> Result<()> {
+    WriteID(writer, t).map_err(IoError(t))?;
+    WriteUInt(writer, size).map_err(IoError(t))
+}
+
+// Returns the CRC-32 of |data| continuing from the CRC-32 |crc| of the
+// preceding data. Use 0 as |crc| for the first call.
+pub fn Crc32Update(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &byte in data {
+        crc = CRC32_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8);
     }
-    true
+    !crc
+}
+
+// Writes a CRC-32 element holding |crc|. The CRC is stored little-endian.
+pub fn WriteCrc32Element(writer: &mut dyn Writer, crc: u32) -> Result<()> {
+    WriteID(writer, MkvId::MkvCrc32).map_err(IoError(MkvId::MkvCrc32))?;
+    WriteUInt(writer, 4).map_err(IoError(MkvId::MkvCrc32))?;
+    writer
+        .write(&crc.to_le_bytes())
+        .map_err(IoError(MkvId::MkvCrc32))
+}
+
+// Returns the size of the master element |t| with a payload of |size| bytes
+// once a CRC-32 element is added to it.
+pub fn EbmlMasterElementSizeWithCrc32(t: MkvId, size: u64) -> u64 {
+    EbmlMasterElementSize(t, size + CRC32_ELEMENT_SIZE) + size + CRC32_ELEMENT_SIZE
+}
+
+// Writes the master element |t| written by |write| with a CRC-32 element as
+// its first child. |write| must write the whole element, header included.
+// The element is buffered, so positions recorded by |write| are not file
+// positions.
+pub fn WriteWithCrc32(
+    writer: &mut dyn Writer,
+    t: MkvId,
+    write: impl FnOnce(&mut dyn Writer) -> Result<()>,
+) -> Result<()> {
+    let mut buffer = MemoryWriter::new();
+    write(&mut buffer)?;
+    let data = buffer.into_inner();
+
+    // Skip the id and the size of the buffered element.
+    let id_size = GetUIntSize(t as u64) as usize;
+    let size_length = match data.get(id_size) {
+        Some(&byte) if byte != 0 => byte.leading_zeros() as usize + 1,
+        _ => return Err(MuxerError::kInvalidState("master element was not written")),
+    };
+    let payload = &data[(id_size + size_length).min(data.len())..];
+
+    WriteEbmlMasterElement(writer, t, payload.len() as u64 + CRC32_ELEMENT_SIZE)?;
+    WriteCrc32Element(writer, Crc32Update(0, payload))?;
+    writer.write(payload).map_err(IoError(t))
 }
 
 pub fn EbmlDateElementSize(t: MkvId) -> u64 {
@@ -197,20 +298,10 @@ pub fn EbmlDateElementSize(t: MkvId) -> u64 {
     ebml_size
 }
 
-pub fn WriteEbmlDateElement(writer: &mut dyn Writer, t: MkvId, value: i64) -> bool {
-    if WriteID(writer, t).is_err() {
-        return false;
-    }
-
-    if WriteUInt(writer, DATE_ELEMENT_SIZE as u64).is_err() {
-        return false;
-    }
-
-    if SerializeInt(writer, value as u64, DATE_ELEMENT_SIZE).is_err() {
-        return false;
-    }
-
-    true
+pub fn WriteEbmlDateElement(writer: &mut dyn Writer, t: MkvId, value: i64) -> Result<()> {
+    WriteID(writer, t).map_err(IoError(t))?;
+    WriteUInt(writer, DATE_ELEMENT_SIZE as u64).map_err(IoError(t))?;
+    SerializeInt(writer, value as u64, DATE_ELEMENT_SIZE).map_err(IoError(t))
 }
 
 pub fn EbmlElementSizeArgI64(t: MkvId, value: i64) -> u64 {
@@ -223,28 +314,19 @@ pub fn EbmlElementSizeArgI64(t: MkvId, value: i64) -> u64 {
     ebml_size
 }
 
-pub fn WriteEbmlElementArgI64(writer: &mut dyn Writer, t: MkvId, value: i64) -> bool {
-    if WriteID(writer, t).is_err() {
-        return false;
-    }
+pub fn WriteEbmlElementArgI64(writer: &mut dyn Writer, t: MkvId, value: i64) -> Result<()> {
+    WriteID(writer, t).map_err(IoError(t))?;
 
     let size = GetIntSize(value);
-    if WriteUInt(writer, size as u64).is_err() {
-        return false;
-    }
-
-    if SerializeInt(writer, value as u64, size).is_err() {
-        return false;
-    }
-
-    true
+    WriteUInt(writer, size as u64).map_err(IoError(t))?;
+    SerializeInt(writer, value as u64, size).map_err(IoError(t))
 }
 
 pub fn EbmlElementSizeArgU64(t: MkvId, value: u64) -> u64 {
     EbmlElementSizeArgsU64(t, value, 0)
 }
 
-pub fn WriteEbmlElementArgU64(writer: &mut dyn Writer, t: MkvId, value: u64) -> bool {
+pub fn WriteEbmlElementArgU64(writer: &mut dyn Writer, t: MkvId, value: u64) -> Result<()> {
     WriteEbmlElementArgsU64(writer, t, value, 0)
 }
 
@@ -258,20 +340,10 @@ pub fn EbmlElementSizeArgF32(t: MkvId, _value: f32) -> u64 {
     ebml_size
 }
 
-pub fn WriteEbmlElementArgF32(writer: &mut dyn Writer, t: MkvId, value: f32) -> bool {
-    if WriteID(writer, t).is_err() {
-        return false;
-    }
-
-    if WriteUInt(writer, 4).is_err() {
-        return false;
-    }
-
-    if SerializeFloat(writer, value).is_err() {
-        return false;
-    }
-
-    true
+pub fn WriteEbmlElementArgF32(writer: &mut dyn Writer, t: MkvId, value: f32) -> Result<()> {
+    WriteID(writer, t).map_err(IoError(t))?;
+    WriteUInt(writer, 4).map_err(IoError(t))?;
+    SerializeFloat(writer, value).map_err(IoError(t))
 }
 
 pub fn EbmlElementSizeArgsU64(t: MkvId, value: u64, fixed_size: u64) -> u64 {
@@ -293,27 +365,21 @@ pub fn WriteEbmlElementArgsU64(
     t: MkvId,
     value: u64,
     fixed_size: u64,
-) -> bool {
-    if WriteID(writer, t).is_err() {
-        return false;
-    }
-
+) -> Result<()> {
     let mut size: u64 = GetUIntSize(value) as u64;
     if fixed_size > 0 {
         if size > fixed_size {
-            return false;
+            return Err(MuxerError::kInvalidValue(
+                t,
+                "value larger than its fixed size",
+            ));
         }
         size = fixed_size;
     }
-    if WriteUInt(writer, size).is_err() {
-        return false;
-    }
 
-    if SerializeInt(writer, value, size as i32).is_err() {
-        return false;
-    }
-
-    true
+    WriteID(writer, t).map_err(IoError(t))?;
+    WriteUInt(writer, size).map_err(IoError(t))?;
+    SerializeInt(writer, value, size as i32).map_err(IoError(t))
 }
 
 pub fn EbmlElementSizeArgStr(t: MkvId, value: &str) -> u64 {
@@ -326,21 +392,8 @@ pub fn EbmlElementSizeArgStr(t: MkvId, value: &str) -> u64 {
     ebml_size
 }
 
-pub fn WriteEbmlElementArgStr(writer: &mut dyn Writer, t: MkvId, value: &str) -> bool {
-    if WriteID(writer, t).is_err() {
-        return false;
-    }
-
-    let length = value.len() as u64;
-    if WriteUInt(writer, length).is_err() {
-        return false;
-    }
-
-    if writer.write(value.as_bytes()).is_err() {
-        return false;
-    }
-
-    return true;
+pub fn WriteEbmlElementArgStr(writer: &mut dyn Writer, t: MkvId, value: &str) -> Result<()> {
+    WriteEbmlElementArgSlice(writer, t, value.as_bytes())
 }
 
 pub fn EbmlElementSizeArgSlice(t: MkvId, value: &[u8]) -> u64 {
@@ -356,55 +409,47 @@ pub fn EbmlElementSizeArgSlice(t: MkvId, value: &[u8]) -> u64 {
     ebml_size
 }
 
-pub fn WriteEbmlElementArgSlice(writer: &mut dyn Writer, t: MkvId, value: &[u8]) -> bool {
-    if WriteID(writer, t).is_err() {
-        return false;
-    }
-
-    if WriteUInt(writer, value.len() as u64).is_err() {
-        return false;
-    }
+pub fn WriteEbmlElementArgSlice(writer: &mut dyn Writer, t: MkvId, value: &[u8]) -> Result<()> {
+    WriteID(writer, t).map_err(IoError(t))?;
+    WriteUInt(writer, value.len() as u64).map_err(IoError(t))?;
+    writer.write(value).map_err(IoError(t))
+}
 
-    if writer.write(value).is_err() {
-        return false;
+pub fn WriteVoidElement(writer: &mut dyn Writer, size: u64) -> Result<u64> {
+    if size < 2 {
+        return Err(MuxerError::kInvalidValue(MkvId::MkvVoid, "size too small"));
     }
 
-    true
-}
-
-pub fn WriteVoidElement(writer: &mut dyn Writer, size: u64) -> u64 {
     // Subtract one for the void ID and the coded size.
     let void_entry_size: u64 = size - 1 - GetCodedUIntSize(size - 1) as u64;
     let void_size: u64 = EbmlMasterElementSize(MkvId::MkvVoid, void_entry_size) + void_entry_size;
 
     if void_size != size {
-        return 0;
+        return Err(MuxerError::kInvalidValue(
+            MkvId::MkvVoid,
+            "size cannot be encoded",
+        ));
     }
 
     let payload_position = writer.get_position();
 
-    if WriteID(writer, MkvId::MkvVoid).is_err() {
-        return 0;
-    }
-
-    if WriteUInt(writer, void_entry_size).is_err() {
-        return 0;
-    }
+    WriteID(writer, MkvId::MkvVoid).map_err(IoError(MkvId::MkvVoid))?;
+    WriteUInt(writer, void_entry_size).map_err(IoError(MkvId::MkvVoid))?;
 
     let value = vec![0; void_entry_size as usize];
-    if writer.write(&value).is_err() {
-        return 0;
-    }
+    writer.write(&value).map_err(IoError(MkvId::MkvVoid))?;
 
     let stop_position = writer.get_position();
-    if stop_position - payload_position != void_size {
-        return 0;
-    }
+    CheckSize(MkvId::MkvVoid, void_size, stop_position - payload_position)?;
 
-    return void_size;
+    Ok(void_size)
 }
 
-pub fn WriteEbmlHeader(writer: &mut dyn Writer, doc_type_version: u64, doc_type: &str) -> bool {
+pub fn WriteEbmlHeader(
+    writer: &mut dyn Writer,
+    doc_type_version: u64,
+    doc_type: &str,
+) -> Result<()> {
     // Level 0
     let mut size: u64 = EbmlElementSizeArgU64(MkvId::MkvEBMLVersion, 1);
     size += EbmlElementSizeArgU64(MkvId::MkvEBMLReadVersion, 1);
@@ -414,35 +459,19 @@ pub fn WriteEbmlHeader(writer: &mut dyn Writer, doc_type_version: u64, doc_type:
     size += EbmlElementSizeArgU64(MkvId::MkvDocTypeVersion, doc_type_version);
     size += EbmlElementSizeArgU64(MkvId::MkvDocTypeReadVersion, 2);
 
-    if !WriteEbmlMasterElement(writer, MkvId::MkvEBML, size) {
-        return false;
-    }
-    if !WriteEbmlElementArgU64(writer, MkvId::MkvEBMLVersion, 1) {
-        return false;
-    }
-    if !WriteEbmlElementArgU64(writer, MkvId::MkvEBMLReadVersion, 1) {
-        return false;
-    }
-    if !WriteEbmlElementArgU64(writer, MkvId::MkvEBMLMaxIDLength, 4) {
-        return false;
-    }
-    if !WriteEbmlElementArgU64(writer, MkvId::MkvEBMLMaxSizeLength, 8) {
-        return false;
-    }
-    if !WriteEbmlElementArgStr(writer, MkvId::MkvDocType, doc_type) {
-        return false;
-    }
-    if !WriteEbmlElementArgU64(writer, MkvId::MkvDocTypeVersion, doc_type_version) {
-        return false;
-    }
-    if !WriteEbmlElementArgU64(writer, MkvId::MkvDocTypeReadVersion, 2) {
-        return false;
-    }
+    WriteEbmlMasterElement(writer, MkvId::MkvEBML, size)?;
+    WriteEbmlElementArgU64(writer, MkvId::MkvEBMLVersion, 1)?;
+    WriteEbmlElementArgU64(writer, MkvId::MkvEBMLReadVersion, 1)?;
+    WriteEbmlElementArgU64(writer, MkvId::MkvEBMLMaxIDLength, 4)?;
+    WriteEbmlElementArgU64(writer, MkvId::MkvEBMLMaxSizeLength, 8)?;
+    WriteEbmlElementArgStr(writer, MkvId::MkvDocType, doc_type)?;
+    WriteEbmlElementArgU64(writer, MkvId::MkvDocTypeVersion, doc_type_version)?;
+    WriteEbmlElementArgU64(writer, MkvId::MkvDocTypeReadVersion, 2)?;
 
-    true
+    Ok(())
 }
 
-pub fn WriteEbmlHeader2(writer: &mut dyn Writer, doc_type_version: u64) -> bool {
+pub fn WriteEbmlHeader2(writer: &mut dyn Writer, doc_type_version: u64) -> Result<()> {
     WriteEbmlHeader(writer, doc_type_version, DOC_TYPE_WEBM)
 }
 
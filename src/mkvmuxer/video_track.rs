@@ -1,13 +1,15 @@
 use super::colour::Colour;
+use super::error::{CheckSize, Result};
 use super::projection::Projection;
 use super::track::Track;
 use super::util;
 use super::writer::Writer;
 use crate::MkvId;
 
-use std::ops::Deref;
+use std::ops::{Deref, DerefMut};
 
 // Supported modes for stereo 3D.
+#[allow(clippy::enum_variant_names)]
 enum StereoMode {
     kMono = 0,
     kSideBySideLeftIsFirst = 1,
@@ -52,6 +54,18 @@ impl Deref for VideoTrack {
     }
 }
 
+impl DerefMut for VideoTrack {
+    fn deref_mut(&mut self) -> &mut Track {
+        &mut self.track_
+    }
+}
+
+impl Default for VideoTrack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl VideoTrack {
     pub fn new() -> VideoTrack {
         VideoTrack {
@@ -104,85 +118,85 @@ impl VideoTrack {
         self.display_height_ = height;
     }
     pub fn display_height(&self) -> u64 {
-        return self.display_height_;
+        self.display_height_
     }
     pub fn set_display_width(&mut self, width: u64) {
         self.display_width_ = width;
     }
     pub fn display_width(&self) -> u64 {
-        return self.display_width_;
+        self.display_width_
     }
     pub fn set_pixel_height(&mut self, height: u64) {
         self.pixel_height_ = height;
     }
     pub fn pixel_height(&self) -> u64 {
-        return self.pixel_height_;
+        self.pixel_height_
     }
     pub fn set_pixel_width(&mut self, width: u64) {
         self.pixel_width_ = width;
     }
     pub fn pixel_width(&self) -> u64 {
-        return self.pixel_width_;
+        self.pixel_width_
     }
 
     pub fn set_crop_left(&mut self, crop_left: u64) {
         self.crop_left_ = crop_left;
     }
     pub fn crop_left(&self) -> u64 {
-        return self.crop_left_;
+        self.crop_left_
     }
     pub fn set_crop_right(&mut self, crop_right: u64) {
         self.crop_right_ = crop_right;
     }
     pub fn crop_right(&self) -> u64 {
-        return self.crop_right_;
+        self.crop_right_
     }
     pub fn set_crop_top(&mut self, crop_top: u64) {
         self.crop_top_ = crop_top;
     }
     pub fn crop_top(&self) -> u64 {
-        return self.crop_top_;
+        self.crop_top_
     }
     pub fn set_crop_bottom(&mut self, crop_bottom: u64) {
         self.crop_bottom_ = crop_bottom;
     }
     pub fn crop_bottom(&self) -> u64 {
-        return self.crop_bottom_;
+        self.crop_bottom_
     }
 
     pub fn set_frame_rate(&mut self, frame_rate: f64) {
         self.frame_rate_ = frame_rate;
     }
     pub fn frame_rate(&self) -> f64 {
-        return self.frame_rate_;
+        self.frame_rate_
     }
     pub fn set_height(&mut self, height: u64) {
         self.height_ = height;
     }
     pub fn height(&self) -> u64 {
-        return self.height_;
+        self.height_
     }
     pub fn stereo_mode(&self) -> u64 {
-        return self.stereo_mode_;
+        self.stereo_mode_
     }
     pub fn alpha_mode(&self) -> u64 {
-        return self.alpha_mode_;
+        self.alpha_mode_
     }
     pub fn set_width(&mut self, width: u64) {
         self.width_ = width;
     }
     pub fn width(&self) -> u64 {
-        return self.width_;
+        self.width_
     }
     pub fn set_colour_space(&mut self, colour_space: &str) {
         self.colour_space_ = colour_space.to_string();
     }
     pub fn colour_space(&self) -> &str {
-        return &self.colour_space_;
+        &self.colour_space_
     }
 
     pub fn colour(&self) -> Option<&Colour> {
-        return self.colour_.as_ref();
+        self.colour_.as_ref()
     }
 
     // Deep copies |colour|.
@@ -209,7 +223,7 @@ impl VideoTrack {
     }
 
     pub fn projection(&self) -> Option<&Projection> {
-        return self.projection_.as_ref();
+        self.projection_.as_ref()
     }
 
     // Deep copies |projection|.
@@ -280,7 +294,7 @@ impl VideoTrack {
             size += p.Size();
         }
 
-        return size;
+        size
     }
 
     pub fn PayloadSize(&self) -> u64 {
@@ -292,20 +306,22 @@ impl VideoTrack {
         parent_size + size
     }
 
-    pub fn Write(&self, writer: &mut dyn Writer) -> bool {
-        if !self.track_.Write(writer) {
-            return false;
-        }
+    pub fn Size(&self) -> u64 {
+        let mut size = self.PayloadSize();
+        size += util::EbmlMasterElementSize(MkvId::MkvTrackEntry, size);
+        size
+    }
+
+    pub fn Write(&self, writer: &mut dyn Writer) -> Result<()> {
+        self.track_.WriteEntry(writer, self.PayloadSize())?;
 
         let size = self.VideoPayloadSize();
 
-        if !util::WriteEbmlMasterElement(writer, MkvId::MkvVideo, size) {
-            return false;
-        }
+        util::WriteEbmlMasterElement(writer, MkvId::MkvVideo, size)?;
 
         let payload_position = writer.get_position();
 
-        if !util::WriteEbmlElementArgU64(
+        util::WriteEbmlElementArgU64(
             writer,
             MkvId::MkvPixelWidth,
             if self.pixel_width_ > 0 {
@@ -313,10 +329,8 @@ impl VideoTrack {
             } else {
                 self.width_
             },
-        ) {
-            return false;
-        }
-        if !util::WriteEbmlElementArgU64(
+        )?;
+        util::WriteEbmlElementArgU64(
             writer,
             MkvId::MkvPixelHeight,
             if self.pixel_height_ > 0 {
@@ -324,76 +338,47 @@ impl VideoTrack {
             } else {
                 self.height_
             },
-        ) {
-            return false;
-        }
+        )?;
         if self.display_width_ > 0 {
-            if !util::WriteEbmlElementArgU64(writer, MkvId::MkvDisplayWidth, self.display_width_) {
-                return false;
-            }
+            util::WriteEbmlElementArgU64(writer, MkvId::MkvDisplayWidth, self.display_width_)?;
         }
         if self.display_height_ > 0 {
-            if !util::WriteEbmlElementArgU64(writer, MkvId::MkvDisplayHeight, self.display_height_)
-            {
-                return false;
-            }
+            util::WriteEbmlElementArgU64(writer, MkvId::MkvDisplayHeight, self.display_height_)?;
         }
         if self.crop_left_ > 0 {
-            if !util::WriteEbmlElementArgU64(writer, MkvId::MkvPixelCropLeft, self.crop_left_) {
-                return false;
-            }
+            util::WriteEbmlElementArgU64(writer, MkvId::MkvPixelCropLeft, self.crop_left_)?;
         }
         if self.crop_right_ > 0 {
-            if !util::WriteEbmlElementArgU64(writer, MkvId::MkvPixelCropRight, self.crop_right_) {
-                return false;
-            }
+            util::WriteEbmlElementArgU64(writer, MkvId::MkvPixelCropRight, self.crop_right_)?;
         }
         if self.crop_top_ > 0 {
-            if !util::WriteEbmlElementArgU64(writer, MkvId::MkvPixelCropTop, self.crop_top_) {
-                return false;
-            }
+            util::WriteEbmlElementArgU64(writer, MkvId::MkvPixelCropTop, self.crop_top_)?;
         }
         if self.crop_bottom_ > 0 {
-            if !util::WriteEbmlElementArgU64(writer, MkvId::MkvPixelCropBottom, self.crop_bottom_) {
-                return false;
-            }
+            util::WriteEbmlElementArgU64(writer, MkvId::MkvPixelCropBottom, self.crop_bottom_)?;
         }
         if self.stereo_mode_ > StereoMode::kMono as u64 {
-            if !util::WriteEbmlElementArgU64(writer, MkvId::MkvStereoMode, self.stereo_mode_) {
-                return false;
-            }
+            util::WriteEbmlElementArgU64(writer, MkvId::MkvStereoMode, self.stereo_mode_)?;
         }
         if self.alpha_mode_ > AlphaMode::kNoAlpha as u64 {
-            if !util::WriteEbmlElementArgU64(writer, MkvId::MkvAlphaMode, self.alpha_mode_) {
-                return false;
-            }
+            util::WriteEbmlElementArgU64(writer, MkvId::MkvAlphaMode, self.alpha_mode_)?;
         }
         if !self.colour_space_.is_empty() {
-            if !util::WriteEbmlElementArgStr(writer, MkvId::MkvColourSpace, &self.colour_space_) {
-                return false;
-            }
+            util::WriteEbmlElementArgStr(writer, MkvId::MkvColourSpace, &self.colour_space_)?;
         }
         if self.frame_rate_ > 0.0 {
-            if !util::WriteEbmlElementArgF32(writer, MkvId::MkvFrameRate, self.frame_rate_ as f32) {
-                return false;
-            }
+            util::WriteEbmlElementArgF32(writer, MkvId::MkvFrameRate, self.frame_rate_ as f32)?;
         }
         if let Some(c) = self.colour_.as_ref() {
-            if !c.Write(writer) {
-                return false;
-            }
+            c.Write(writer)?;
         }
         if let Some(p) = self.projection_.as_ref() {
-            if !p.Write(writer) {
-                return false;
-            }
+            p.Write(writer)?;
         }
 
         let stop_position = writer.get_position();
-        if stop_position - payload_position != size {
-            return false;
-        }
+        CheckSize(MkvId::MkvVideo, size, stop_position - payload_position)?;
 
-        true
+        Ok(())
     }
 }
@@ -1,9 +1,10 @@
+use super::util;
 use crate::MkvId;
 use std::fs::File;
 use std::io;
 use std::io::prelude::*;
+use std::io::Error;
 use std::io::SeekFrom;
-use std::io::{Error, ErrorKind};
 
 pub trait Writer {
     fn write(&mut self, buf: &[u8]) -> io::Result<()>;
@@ -34,10 +35,7 @@ impl Writer for MkvWriter {
         if size == buffer.len() {
             Ok(())
         } else {
-            Err(Error::new(
-                ErrorKind::Other,
-                "Write size is not equal to buffer size",
-            ))
+            Err(Error::other("Write size is not equal to buffer size"))
         }
     }
 
@@ -51,10 +49,7 @@ impl Writer for MkvWriter {
         if size == position {
             Ok(())
         } else {
-            Err(Error::new(
-                ErrorKind::Other,
-                "Seek position is not equal to input position",
-            ))
+            Err(Error::other("Seek position is not equal to input position"))
         }
     }
 
@@ -62,3 +57,202 @@ impl Writer for MkvWriter {
         true
     }
 }
+
+// Writer over any seekable output, e.g. a |Cursor<Vec<u8>>| or a |File|.
+pub struct IoWriter<W: Write + Seek> {
+    inner: W,
+    position: u64,
+}
+
+impl<W: Write + Seek> IoWriter<W> {
+    pub fn new(mut inner: W) -> io::Result<IoWriter<W>> {
+        let position = inner.stream_position()?;
+        Ok(IoWriter { inner, position })
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write + Seek> Writer for IoWriter<W> {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<()> {
+        self.inner.write_all(buffer)?;
+        self.position += buffer.len() as u64;
+        Ok(())
+    }
+
+    fn get_position(&self) -> u64 {
+        self.position
+    }
+
+    fn set_position(&mut self, position: u64) -> io::Result<()> {
+        self.position = self.inner.seek(SeekFrom::Start(position))?;
+        if self.position == position {
+            Ok(())
+        } else {
+            Err(Error::other("Seek position is not equal to input position"))
+        }
+    }
+
+    fn seekable(&self) -> bool {
+        true
+    }
+}
+
+// Writer over a forward-only output such as a pipe or a socket. The
+// position is the number of bytes written so far, and the muxer skips
+// everything that needs to go back and patch earlier output.
+pub struct StreamWriter<W: Write> {
+    inner: W,
+    position: u64,
+}
+
+impl<W: Write> StreamWriter<W> {
+    pub fn new(inner: W) -> StreamWriter<W> {
+        StreamWriter { inner, position: 0 }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Writer for StreamWriter<W> {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<()> {
+        self.inner.write_all(buffer)?;
+        self.position += buffer.len() as u64;
+        Ok(())
+    }
+
+    fn get_position(&self) -> u64 {
+        self.position
+    }
+
+    fn set_position(&mut self, _position: u64) -> io::Result<()> {
+        Err(Error::new(
+            io::ErrorKind::Unsupported,
+            "StreamWriter is not seekable",
+        ))
+    }
+
+    fn seekable(&self) -> bool {
+        false
+    }
+}
+
+// Writer over a growable in-memory buffer. Seeking back allows the muxer to
+// patch sizes and the duration once they are known.
+#[derive(Default)]
+pub struct MemoryWriter {
+    buffer: Vec<u8>,
+    position: u64,
+}
+
+impl MemoryWriter {
+    pub fn new() -> MemoryWriter {
+        MemoryWriter::default()
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.buffer
+    }
+}
+
+impl Writer for MemoryWriter {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<()> {
+        let start = self.position as usize;
+        let end = start + buffer.len();
+        if end > self.buffer.len() {
+            self.buffer.resize(end, 0);
+        }
+        self.buffer[start..end].copy_from_slice(buffer);
+        self.position = end as u64;
+        Ok(())
+    }
+
+    fn get_position(&self) -> u64 {
+        self.position
+    }
+
+    fn set_position(&mut self, position: u64) -> io::Result<()> {
+        if position > self.buffer.len() as u64 {
+            return Err(Error::other(
+                "Seek position is beyond the end of the buffer",
+            ));
+        }
+        self.position = position;
+        Ok(())
+    }
+
+    fn seekable(&self) -> bool {
+        true
+    }
+}
+
+// Writer that forwards to another writer and keeps the CRC-32 of the bytes
+// written through it, e.g. to checksum a Cluster while it is written.
+pub struct Crc32Writer<'a> {
+    inner: &'a mut dyn Writer,
+    crc: u32,
+}
+
+impl<'a> Crc32Writer<'a> {
+    // Continues the CRC-32 |crc| of earlier output; use 0 to start a new one.
+    pub fn new(inner: &'a mut dyn Writer, crc: u32) -> Crc32Writer<'a> {
+        Crc32Writer { inner, crc }
+    }
+
+    pub fn crc(&self) -> u32 {
+        self.crc
+    }
+}
+
+impl<'a> Writer for Crc32Writer<'a> {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<()> {
+        self.inner.write(buffer)?;
+        self.crc = util::Crc32Update(self.crc, buffer);
+        Ok(())
+    }
+
+    fn get_position(&self) -> u64 {
+        self.inner.get_position()
+    }
+
+    // A hashed element must be written sequentially: seeking would leave the
+    // CRC-32 out of sync with the bytes of the element.
+    fn set_position(&mut self, _position: u64) -> io::Result<()> {
+        Err(Error::new(
+            io::ErrorKind::Unsupported,
+            "Crc32Writer is not seekable",
+        ))
+    }
+
+    fn seekable(&self) -> bool {
+        false
+    }
+
+    fn element_start_notify(&self, element_id: MkvId, position: u64) {
+        self.inner.element_start_notify(element_id, position)
+    }
+}
@@ -0,0 +1,116 @@
+use super::element::{
+    ElementHeader, Elements, ReadBytesElement, ReadStringElement, ReadUIntElement,
+};
+use super::error::{ParseError, Result};
+use super::reader::Reader;
+use crate::MkvId;
+
+pub struct AttachedFile {
+    file_name_: String,
+    mime_type_: String,
+    data_: Vec<u8>,
+    uid_: u64,
+    description_: String,
+}
+
+impl AttachedFile {
+    fn Parse(reader: &mut dyn Reader, header: &ElementHeader) -> Result<AttachedFile> {
+        let mut file = AttachedFile {
+            file_name_: String::new(),
+            mime_type_: String::new(),
+            data_: Vec::new(),
+            uid_: 0,
+            description_: String::new(),
+        };
+
+        let mut children = Elements::new(header);
+        while let Some(child) = children.next(reader)? {
+            match child.mkv_id() {
+                Some(MkvId::MkvFileName) => file.file_name_ = ReadStringElement(reader, &child)?,
+                Some(MkvId::MkvFileMimeType) => {
+                    file.mime_type_ = ReadStringElement(reader, &child)?;
+                }
+                Some(MkvId::MkvFileData) => file.data_ = ReadBytesElement(reader, &child)?,
+                Some(MkvId::MkvFileUID) => file.uid_ = ReadUIntElement(reader, &child)?,
+                Some(MkvId::MkvFileDescription) => {
+                    file.description_ = ReadStringElement(reader, &child)?;
+                }
+                _ => {}
+            }
+        }
+
+        // FileName, FileMimeType, FileData and FileUID are mandatory.
+        if file.file_name_.is_empty() || file.mime_type_.is_empty() || file.uid_ == 0 {
+            return Err(ParseError::kFileFormatInvalid);
+        }
+        Ok(file)
+    }
+
+    pub fn file_name(&self) -> &str {
+        &self.file_name_
+    }
+    pub fn mime_type(&self) -> &str {
+        &self.mime_type_
+    }
+    pub fn data(&self) -> &[u8] {
+        &self.data_
+    }
+    pub fn uid(&self) -> u64 {
+        self.uid_
+    }
+    pub fn description(&self) -> &str {
+        &self.description_
+    }
+}
+
+pub struct Attachments {
+    files_: Vec<AttachedFile>,
+
+    // Position of the Attachments element id.
+    element_start_: u64,
+    element_size_: u64,
+}
+
+impl Attachments {
+    pub fn Parse(reader: &mut dyn Reader, header: &ElementHeader) -> Result<Attachments> {
+        let mut attachments = Attachments {
+            files_: Vec::new(),
+            element_start_: header.start,
+            element_size_: header.header_size() + header.payload_size()?,
+        };
+
+        let mut children = Elements::new(header);
+        while let Some(child) = children.next(reader)? {
+            if child.is(MkvId::MkvAttachedFile) {
+                attachments
+                    .files_
+                    .push(AttachedFile::Parse(reader, &child)?);
+            }
+        }
+
+        if attachments.files_.is_empty() {
+            return Err(ParseError::kFileFormatInvalid);
+        }
+        Ok(attachments)
+    }
+
+    pub fn count(&self) -> usize {
+        self.files_.len()
+    }
+
+    pub fn GetFile(&self, index: usize) -> Option<&AttachedFile> {
+        self.files_.get(index)
+    }
+
+    // Returns the file with the FileUID |uid|, e.g. from a TagAttachmentUID.
+    pub fn GetFileByUID(&self, uid: u64) -> Option<&AttachedFile> {
+        self.files_.iter().find(|file| file.uid_ == uid)
+    }
+
+    pub fn element_start(&self) -> u64 {
+        self.element_start_
+    }
+    pub fn element_size(&self) -> u64 {
+        self.element_size_
+    }
+}
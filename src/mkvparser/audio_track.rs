@@ -0,0 +1,105 @@
+use super::element::{ElementHeader, Elements, ReadFloatElement, ReadUIntElement};
+use super::error::{ParseError, Result};
+use super::reader::Reader;
+use super::track::Track;
+use crate::mkvmuxer;
+use crate::MkvId;
+
+use std::ops::{Deref, DerefMut};
+
+pub struct AudioTrack {
+    track_: Track,
+
+    // Audio element values.
+    sample_rate_: f64,
+    // 0 when the element is not present.
+    output_sample_rate_: f64,
+    channels_: u64,
+    bit_depth_: u64,
+}
+
+impl Deref for AudioTrack {
+    type Target = Track;
+
+    fn deref(&self) -> &Track {
+        &self.track_
+    }
+}
+
+impl DerefMut for AudioTrack {
+    fn deref_mut(&mut self) -> &mut Track {
+        &mut self.track_
+    }
+}
+
+impl AudioTrack {
+    // Parses the Audio element |header| of |track|.
+    pub fn Parse(
+        reader: &mut dyn Reader,
+        track: Track,
+        header: &ElementHeader,
+    ) -> Result<AudioTrack> {
+        let mut audio = AudioTrack {
+            track_: track,
+            sample_rate_: 8000.0,
+            output_sample_rate_: 0.0,
+            channels_: 1,
+            bit_depth_: 0,
+        };
+
+        let mut children = Elements::new(header);
+        while let Some(child) = children.next(reader)? {
+            match child.mkv_id() {
+                Some(MkvId::MkvSamplingFrequency) => {
+                    audio.sample_rate_ = ReadFloatElement(reader, &child)?;
+                    if audio.sample_rate_.is_nan() || audio.sample_rate_ <= 0.0 {
+                        return Err(ParseError::kFileFormatInvalid);
+                    }
+                }
+                Some(MkvId::MkvOutputSamplingFrequency) => {
+                    audio.output_sample_rate_ = ReadFloatElement(reader, &child)?;
+                    if audio.output_sample_rate_.is_nan() || audio.output_sample_rate_ <= 0.0 {
+                        return Err(ParseError::kFileFormatInvalid);
+                    }
+                }
+                Some(MkvId::MkvChannels) => {
+                    audio.channels_ = ReadUIntElement(reader, &child)?;
+                    if audio.channels_ == 0 {
+                        return Err(ParseError::kFileFormatInvalid);
+                    }
+                }
+                Some(MkvId::MkvBitDepth) => {
+                    audio.bit_depth_ = ReadUIntElement(reader, &child)?;
+                    if audio.bit_depth_ == 0 {
+                        return Err(ParseError::kFileFormatInvalid);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(audio)
+    }
+
+    pub fn sample_rate(&self) -> f64 {
+        self.sample_rate_
+    }
+    pub fn output_sample_rate(&self) -> f64 {
+        self.output_sample_rate_
+    }
+    pub fn channels(&self) -> u64 {
+        self.channels_
+    }
+    pub fn bit_depth(&self) -> u64 {
+        self.bit_depth_
+    }
+
+    // Copies the track and audio configuration to a muxer audio track.
+    pub fn CopyTo(&self, track: &mut mkvmuxer::audio_track::AudioTrack) -> Result<()> {
+        self.track_.CopyTo(track)?;
+        track.set_sample_rate(self.sample_rate_);
+        track.set_channels(self.channels_);
+        track.set_bit_depth(self.bit_depth_);
+        Ok(())
+    }
+}
@@ -0,0 +1,374 @@
+use super::element::{
+    ElementHeader, Elements, ReadBytesElement, ReadStringElement, ReadUIntElement,
+};
+use super::error::{ParseError, Result};
+use super::reader::Reader;
+use crate::MkvId;
+
+pub struct Display {
+    string_: String,
+    language_: String,
+    country_: String,
+}
+
+impl Display {
+    fn Parse(reader: &mut dyn Reader, header: &ElementHeader) -> Result<Display> {
+        let mut display = Display {
+            string_: String::new(),
+            language_: String::new(),
+            country_: String::new(),
+        };
+
+        let mut children = Elements::new(header);
+        while let Some(child) = children.next(reader)? {
+            match child.mkv_id() {
+                Some(MkvId::MkvChapString) => display.string_ = ReadStringElement(reader, &child)?,
+                Some(MkvId::MkvChapLanguage) => {
+                    display.language_ = ReadStringElement(reader, &child)?;
+                }
+                Some(MkvId::MkvChapCountry) => {
+                    display.country_ = ReadStringElement(reader, &child)?;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(display)
+    }
+
+    pub fn string(&self) -> &str {
+        &self.string_
+    }
+    pub fn language(&self) -> &str {
+        &self.language_
+    }
+    pub fn country(&self) -> &str {
+        &self.country_
+    }
+}
+
+pub struct ProcessCommand {
+    time_: u64,
+    data_: Vec<u8>,
+}
+
+impl ProcessCommand {
+    fn Parse(reader: &mut dyn Reader, header: &ElementHeader) -> Result<ProcessCommand> {
+        let mut command = ProcessCommand {
+            time_: 0,
+            data_: Vec::new(),
+        };
+
+        let mut children = Elements::new(header);
+        while let Some(child) = children.next(reader)? {
+            match child.mkv_id() {
+                Some(MkvId::MkvChapProcessTime) => command.time_ = ReadUIntElement(reader, &child)?,
+                Some(MkvId::MkvChapProcessData) => {
+                    command.data_ = ReadBytesElement(reader, &child)?;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(command)
+    }
+
+    pub fn time(&self) -> u64 {
+        self.time_
+    }
+    pub fn data(&self) -> &[u8] {
+        &self.data_
+    }
+}
+
+pub struct Process {
+    codec_id_: u64,
+    private_: Vec<u8>,
+    commands_: Vec<ProcessCommand>,
+}
+
+impl Process {
+    fn Parse(reader: &mut dyn Reader, header: &ElementHeader) -> Result<Process> {
+        let mut process = Process {
+            codec_id_: 0,
+            private_: Vec::new(),
+            commands_: Vec::new(),
+        };
+
+        let mut children = Elements::new(header);
+        while let Some(child) = children.next(reader)? {
+            match child.mkv_id() {
+                Some(MkvId::MkvChapProcessCodecID) => {
+                    process.codec_id_ = ReadUIntElement(reader, &child)?;
+                }
+                Some(MkvId::MkvChapProcessPrivate) => {
+                    process.private_ = ReadBytesElement(reader, &child)?;
+                }
+                Some(MkvId::MkvChapProcessCommand) => {
+                    process
+                        .commands_
+                        .push(ProcessCommand::Parse(reader, &child)?);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(process)
+    }
+
+    pub fn codec_id(&self) -> u64 {
+        self.codec_id_
+    }
+    pub fn private(&self) -> &[u8] {
+        &self.private_
+    }
+
+    pub fn command_count(&self) -> usize {
+        self.commands_.len()
+    }
+
+    pub fn GetCommand(&self, index: usize) -> Option<&ProcessCommand> {
+        self.commands_.get(index)
+    }
+}
+
+pub struct Atom {
+    uid_: u64,
+    string_uid_: String,
+    // Start and end timecodes, in timecode ticks as written by the muxer.
+    start_timecode_: u64,
+    end_timecode_: u64,
+    hidden_: bool,
+    enabled_: bool,
+    segment_uid_: Vec<u8>,
+    displays_: Vec<Display>,
+    processes_: Vec<Process>,
+    // Nested ChapterAtoms.
+    atoms_: Vec<Atom>,
+}
+
+impl Atom {
+    fn Parse(reader: &mut dyn Reader, header: &ElementHeader) -> Result<Atom> {
+        let mut atom = Atom {
+            uid_: 0,
+            string_uid_: String::new(),
+            start_timecode_: 0,
+            end_timecode_: 0,
+            hidden_: false,
+            enabled_: true,
+            segment_uid_: Vec::new(),
+            displays_: Vec::new(),
+            processes_: Vec::new(),
+            atoms_: Vec::new(),
+        };
+
+        let mut children = Elements::new(header);
+        while let Some(child) = children.next(reader)? {
+            match child.mkv_id() {
+                Some(MkvId::MkvChapterUID) => atom.uid_ = ReadUIntElement(reader, &child)?,
+                Some(MkvId::MkvChapterStringUID) => {
+                    atom.string_uid_ = ReadStringElement(reader, &child)?;
+                }
+                Some(MkvId::MkvChapterTimeStart) => {
+                    atom.start_timecode_ = ReadUIntElement(reader, &child)?;
+                }
+                Some(MkvId::MkvChapterTimeEnd) => {
+                    atom.end_timecode_ = ReadUIntElement(reader, &child)?;
+                }
+                Some(MkvId::MkvChapterFlagHidden) => {
+                    atom.hidden_ = ReadUIntElement(reader, &child)? != 0;
+                }
+                Some(MkvId::MkvChapterFlagEnabled) => {
+                    atom.enabled_ = ReadUIntElement(reader, &child)? != 0;
+                }
+                Some(MkvId::MkvChapterSegmentUID) => {
+                    atom.segment_uid_ = ReadBytesElement(reader, &child)?;
+                }
+                Some(MkvId::MkvChapterDisplay) => {
+                    atom.displays_.push(Display::Parse(reader, &child)?);
+                }
+                Some(MkvId::MkvChapProcess) => {
+                    atom.processes_.push(Process::Parse(reader, &child)?);
+                }
+                Some(MkvId::MkvChapterAtom) => {
+                    atom.atoms_.push(Atom::Parse(reader, &child)?);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(atom)
+    }
+
+    pub fn uid(&self) -> u64 {
+        self.uid_
+    }
+    pub fn string_uid(&self) -> &str {
+        &self.string_uid_
+    }
+    pub fn start_timecode(&self) -> u64 {
+        self.start_timecode_
+    }
+    pub fn end_timecode(&self) -> u64 {
+        self.end_timecode_
+    }
+    pub fn hidden(&self) -> bool {
+        self.hidden_
+    }
+    pub fn enabled(&self) -> bool {
+        self.enabled_
+    }
+    // Empty unless the chapter links to another Segment.
+    pub fn segment_uid(&self) -> &[u8] {
+        &self.segment_uid_
+    }
+
+    // Returns the start time in nanoseconds, or kFileFormatInvalid if it
+    // overflows.
+    pub fn GetStartTime(&self, timecode_scale: u64) -> Result<u64> {
+        self.start_timecode_
+            .checked_mul(timecode_scale)
+            .ok_or(ParseError::kFileFormatInvalid)
+    }
+
+    // Returns the end time in nanoseconds, or kFileFormatInvalid if it
+    // overflows.
+    pub fn GetEndTime(&self, timecode_scale: u64) -> Result<u64> {
+        self.end_timecode_
+            .checked_mul(timecode_scale)
+            .ok_or(ParseError::kFileFormatInvalid)
+    }
+
+    pub fn display_count(&self) -> usize {
+        self.displays_.len()
+    }
+
+    pub fn GetDisplay(&self, index: usize) -> Option<&Display> {
+        self.displays_.get(index)
+    }
+
+    pub fn process_count(&self) -> usize {
+        self.processes_.len()
+    }
+
+    pub fn GetProcess(&self, index: usize) -> Option<&Process> {
+        self.processes_.get(index)
+    }
+
+    // Returns the number of nested atoms.
+    pub fn atom_count(&self) -> usize {
+        self.atoms_.len()
+    }
+
+    pub fn GetAtom(&self, index: usize) -> Option<&Atom> {
+        self.atoms_.get(index)
+    }
+}
+
+pub struct Edition {
+    uid_: u64,
+    hidden_: bool,
+    default_: bool,
+    ordered_: bool,
+    atoms_: Vec<Atom>,
+}
+
+impl Edition {
+    fn Parse(reader: &mut dyn Reader, header: &ElementHeader) -> Result<Edition> {
+        let mut edition = Edition {
+            uid_: 0,
+            hidden_: false,
+            default_: false,
+            ordered_: false,
+            atoms_: Vec::new(),
+        };
+
+        let mut children = Elements::new(header);
+        while let Some(child) = children.next(reader)? {
+            match child.mkv_id() {
+                Some(MkvId::MkvEditionUID) => edition.uid_ = ReadUIntElement(reader, &child)?,
+                Some(MkvId::MkvEditionFlagHidden) => {
+                    edition.hidden_ = ReadUIntElement(reader, &child)? != 0;
+                }
+                Some(MkvId::MkvEditionFlagDefault) => {
+                    edition.default_ = ReadUIntElement(reader, &child)? != 0;
+                }
+                Some(MkvId::MkvEditionFlagOrdered) => {
+                    edition.ordered_ = ReadUIntElement(reader, &child)? != 0;
+                }
+                Some(MkvId::MkvChapterAtom) => {
+                    edition.atoms_.push(Atom::Parse(reader, &child)?);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(edition)
+    }
+
+    pub fn uid(&self) -> u64 {
+        self.uid_
+    }
+    pub fn hidden(&self) -> bool {
+        self.hidden_
+    }
+    pub fn default(&self) -> bool {
+        self.default_
+    }
+    pub fn ordered(&self) -> bool {
+        self.ordered_
+    }
+
+    pub fn atom_count(&self) -> usize {
+        self.atoms_.len()
+    }
+
+    pub fn GetAtom(&self, index: usize) -> Option<&Atom> {
+        self.atoms_.get(index)
+    }
+}
+
+pub struct Chapters {
+    editions_: Vec<Edition>,
+
+    // Position of the Chapters element id.
+    element_start_: u64,
+    element_size_: u64,
+}
+
+impl Chapters {
+    pub fn Parse(reader: &mut dyn Reader, header: &ElementHeader) -> Result<Chapters> {
+        let mut chapters = Chapters {
+            editions_: Vec::new(),
+            element_start_: header.start,
+            element_size_: header.header_size() + header.payload_size()?,
+        };
+
+        let mut children = Elements::new(header);
+        while let Some(child) = children.next(reader)? {
+            if child.is(MkvId::MkvEditionEntry) {
+                chapters.editions_.push(Edition::Parse(reader, &child)?);
+            }
+        }
+
+        if chapters.editions_.is_empty() {
+            return Err(ParseError::kFileFormatInvalid);
+        }
+        Ok(chapters)
+    }
+
+    pub fn edition_count(&self) -> usize {
+        self.editions_.len()
+    }
+
+    pub fn GetEdition(&self, index: usize) -> Option<&Edition> {
+        self.editions_.get(index)
+    }
+
+    pub fn element_start(&self) -> u64 {
+        self.element_start_
+    }
+    pub fn element_size(&self) -> u64 {
+        self.element_size_
+    }
+}
@@ -0,0 +1,480 @@
+use super::element::{
+    ElementEnd, ElementHeader, Elements, ReadBytesElement, ReadIntElement, ReadUIntElement,
+    VerifyCrc32,
+};
+use super::error::{ParseError, Result};
+use super::reader::Reader;
+use super::util::{IsUnknownSize, ReadUInt, UnserializeBytes, UnserializeUInt};
+use crate::mkvmuxer::frame::{Frame, Lacing};
+use crate::MkvId;
+use std::convert::TryFrom;
+
+// Header of a SimpleBlock or Block.
+struct BlockHeader {
+    track_number: u64,
+    // Timecode relative to the Cluster, in timecode ticks.
+    timecode: i16,
+    flags: u8,
+    // Position and size of the frame data.
+    frame_pos: u64,
+    frame_size: u64,
+}
+
+fn ParseBlockHeader(reader: &mut dyn Reader, header: &ElementHeader) -> Result<BlockHeader> {
+    let size = header.payload_size()?;
+    let mut pos = header.payload_start;
+    let stop = pos + size;
+
+    let (track_number, len) = ReadUInt(reader, pos)?;
+    if track_number == 0 || IsUnknownSize(track_number, len) {
+        return Err(ParseError::kFileFormatInvalid);
+    }
+    pos += len as u64;
+
+    // 2 byte timecode and 1 byte flags.
+    if stop < pos + 3 {
+        return Err(ParseError::kFileFormatInvalid);
+    }
+    let timecode = UnserializeUInt(reader, pos, 2)? as u16 as i16;
+    let flags = UnserializeUInt(reader, pos + 2, 1)? as u8;
+    pos += 3;
+
+    Ok(BlockHeader {
+        track_number,
+        timecode,
+        flags,
+        frame_pos: pos,
+        frame_size: stop - pos,
+    })
+}
+
+// Reads the lace header at the start of the frame data of |block| and
+// returns the size of each laced frame. |block| is updated to the frame data
+// following the header.
+fn ParseLaceSizes(
+    reader: &mut dyn Reader,
+    block: &mut BlockHeader,
+    lacing: Lacing,
+) -> Result<Vec<u64>> {
+    let stop = block.frame_pos + block.frame_size;
+    let mut pos = block.frame_pos;
+    if pos >= stop {
+        return Err(ParseError::kFileFormatInvalid);
+    }
+    let count = UnserializeUInt(reader, pos, 1)? + 1;
+    pos += 1;
+
+    let mut sizes = Vec::new();
+    match lacing {
+        Lacing::kNoLacing => return Err(ParseError::kParseFailed),
+        Lacing::kXiphLacing => {
+            for _ in 1..count {
+                let mut size = 0;
+                loop {
+                    if pos >= stop {
+                        return Err(ParseError::kFileFormatInvalid);
+                    }
+                    let value = UnserializeUInt(reader, pos, 1)?;
+                    pos += 1;
+                    size += value;
+                    if value != 255 {
+                        break;
+                    }
+                }
+                sizes.push(size);
+            }
+        }
+        Lacing::kEbmlLacing => {
+            if count > 1 {
+                let (size, len) = ReadUInt(reader, pos)?;
+                pos += len as u64;
+                sizes.push(size);
+            }
+            for _ in 2..count {
+                // Sizes after the first are signed differences to the
+                // previous size.
+                let (value, len) = ReadUInt(reader, pos)?;
+                pos += len as u64;
+                let bias = (1i64 << (len * 7 - 1)) - 1;
+                let size = sizes[sizes.len() - 1] as i64 + value as i64 - bias;
+                if size < 0 {
+                    return Err(ParseError::kFileFormatInvalid);
+                }
+                sizes.push(size as u64);
+            }
+        }
+        Lacing::kFixedLacing => {
+            if stop < pos || !(stop - pos).is_multiple_of(count) {
+                return Err(ParseError::kFileFormatInvalid);
+            }
+            sizes = vec![(stop - pos) / count; count as usize - 1];
+        }
+    }
+
+    // The last frame takes the rest of the block.
+    let laced_size: u64 = sizes.iter().sum();
+    if stop < pos + laced_size {
+        return Err(ParseError::kFileFormatInvalid);
+    }
+    sizes.push(stop - pos - laced_size);
+
+    block.frame_pos = pos;
+    block.frame_size = stop - pos;
+    Ok(sizes)
+}
+
+// Block picked by Cluster::FindSeekBlock().
+#[derive(Debug, Copy, Clone)]
+pub struct SeekBlock {
+    // Position of the SimpleBlock or BlockGroup element id.
+    pub pos: u64,
+    // True if the block is a key frame at or before the seek time.
+    pub is_key_before: bool,
+}
+
+#[derive(Clone)]
+pub struct Cluster {
+    header_: ElementHeader,
+    // Cluster Timecode, in timecode ticks.
+    timecode_: u64,
+    timecode_scale_: u64,
+    // Position and PrevSize elements, if present.
+    position_: Option<u64>,
+    prev_size_: Option<u64>,
+
+    // Walks the Cluster's children to find blocks.
+    blocks_: Elements,
+}
+
+impl Cluster {
+    // Reads the Timecode, Position and PrevSize of the Cluster |header|. These
+    // must come before the first block. The CRC-32 of the Cluster, if any, is
+    // checked.
+    pub fn Parse(
+        reader: &mut dyn Reader,
+        header: &ElementHeader,
+        timecode_scale: u64,
+    ) -> Result<Cluster> {
+        VerifyCrc32(reader, header)?;
+
+        let mut timecode = None;
+        let mut position = None;
+        let mut prev_size = None;
+        let mut children = Elements::new(header);
+        while let Some(child) = children.next(reader)? {
+            match child.mkv_id() {
+                Some(MkvId::MkvTimecode) => timecode = Some(ReadUIntElement(reader, &child)?),
+                Some(MkvId::MkvPosition) => position = Some(ReadUIntElement(reader, &child)?),
+                Some(MkvId::MkvPrevSize) => prev_size = Some(ReadUIntElement(reader, &child)?),
+                Some(MkvId::MkvSimpleBlock) | Some(MkvId::MkvBlockGroup) => break,
+                _ => {}
+            }
+        }
+
+        Ok(Cluster {
+            header_: *header,
+            timecode_: timecode.ok_or(ParseError::kFileFormatInvalid)?,
+            timecode_scale_: timecode_scale,
+            position_: position,
+            prev_size_: prev_size,
+            blocks_: Elements::new(header),
+        })
+    }
+
+    pub fn header(&self) -> &ElementHeader {
+        &self.header_
+    }
+
+    // Position of the Cluster element id.
+    pub fn element_start(&self) -> u64 {
+        self.header_.start
+    }
+
+    // Returns the position just past the Cluster.
+    pub fn GetEnd(&self, reader: &mut dyn Reader) -> Result<u64> {
+        ElementEnd(reader, &self.header_)
+    }
+
+    pub fn timecode(&self) -> u64 {
+        self.timecode_
+    }
+
+    // Position of the Cluster relative to the Segment payload, as written by
+    // the muxer.
+    pub fn position(&self) -> Option<u64> {
+        self.position_
+    }
+
+    // Size of the previous Cluster, as written by the muxer.
+    pub fn prev_size(&self) -> Option<u64> {
+        self.prev_size_
+    }
+
+    // Returns the Cluster time in nanoseconds, or kFileFormatInvalid if it
+    // overflows.
+    pub fn GetTime(&self) -> Result<i64> {
+        self.timecode_
+            .checked_mul(self.timecode_scale_)
+            .and_then(|time| i64::try_from(time).ok())
+            .ok_or(ParseError::kFileFormatInvalid)
+    }
+
+    // Moves back to the first block of the Cluster.
+    pub fn Rewind(&mut self) {
+        self.blocks_ = Elements::new(&self.header_);
+    }
+
+    // Moves to the SimpleBlock or BlockGroup at |pos|, which must be a child
+    // of the Cluster.
+    pub fn SetBlockPosition(&mut self, pos: u64) {
+        self.blocks_ = Elements::resume(&self.header_, pos);
+    }
+
+    // Returns the block of |track| to start playback from for |time_ns|: the
+    // last key frame at or before |time_ns|. Without one, the 1-based
+    // |block_number|th block of the Cluster is used if it belongs to
+    // |track|, and otherwise the first block of |track|. Returns None if the
+    // Cluster has no block for |track|.
+    pub fn FindSeekBlock(
+        &self,
+        reader: &mut dyn Reader,
+        track: u64,
+        time_ns: u64,
+        block_number: Option<u64>,
+    ) -> Result<Option<SeekBlock>> {
+        let mut key_before = None;
+        let mut numbered = None;
+        let mut first = None;
+
+        let mut index = 0;
+        let mut children = Elements::new(&self.header_);
+        while let Some(child) = children.next(reader)? {
+            let (block_track, timestamp, is_key) = match child.mkv_id() {
+                Some(MkvId::MkvSimpleBlock) => self.PeekSimpleBlock(reader, &child)?,
+                Some(MkvId::MkvBlockGroup) => self.PeekBlockGroup(reader, &child)?,
+                _ => continue,
+            };
+            index += 1;
+            if block_track != track {
+                continue;
+            }
+            if first.is_none() {
+                first = Some(child.start);
+            }
+            if block_number == Some(index) {
+                numbered = Some(child.start);
+            }
+            if is_key && timestamp <= time_ns {
+                key_before = Some(child.start);
+            }
+        }
+
+        if let Some(pos) = key_before {
+            return Ok(Some(SeekBlock {
+                pos,
+                is_key_before: true,
+            }));
+        }
+        Ok(numbered.or(first).map(|pos| SeekBlock {
+            pos,
+            is_key_before: false,
+        }))
+    }
+
+    // Returns the absolute timestamp of |block|, in nanoseconds.
+    fn BlockTimestamp(&self, block: &BlockHeader) -> Result<u64> {
+        let timecode = i64::try_from(self.timecode_)
+            .ok()
+            .and_then(|timecode| timecode.checked_add(block.timecode as i64))
+            .ok_or(ParseError::kFileFormatInvalid)?;
+        if timecode < 0 {
+            return Err(ParseError::kFileFormatInvalid);
+        }
+        (timecode as u64)
+            .checked_mul(self.timecode_scale_)
+            .ok_or(ParseError::kFileFormatInvalid)
+    }
+
+    // Returns the track number, timestamp and key flag of a SimpleBlock
+    // without reading its frame.
+    fn PeekSimpleBlock(
+        &self,
+        reader: &mut dyn Reader,
+        header: &ElementHeader,
+    ) -> Result<(u64, u64, bool)> {
+        let block = ParseBlockHeader(reader, header)?;
+        Ok((
+            block.track_number,
+            self.BlockTimestamp(&block)?,
+            block.flags & 0x80 != 0,
+        ))
+    }
+
+    // Same as PeekSimpleBlock() for a BlockGroup.
+    fn PeekBlockGroup(
+        &self,
+        reader: &mut dyn Reader,
+        header: &ElementHeader,
+    ) -> Result<(u64, u64, bool)> {
+        let mut block = None;
+        let mut is_key = true;
+
+        let mut children = Elements::new(header);
+        while let Some(child) = children.next(reader)? {
+            match child.mkv_id() {
+                Some(MkvId::MkvBlock) => block = Some(ParseBlockHeader(reader, &child)?),
+                Some(MkvId::MkvReferenceBlock) => is_key = false,
+                _ => {}
+            }
+        }
+
+        let block = block.ok_or(ParseError::kFileFormatInvalid)?;
+        Ok((block.track_number, self.BlockTimestamp(&block)?, is_key))
+    }
+
+    // Returns the frame held by the next SimpleBlock or BlockGroup, or None
+    // at the end of the Cluster. Frame timestamps are absolute, in
+    // nanoseconds.
+    pub fn GetNextFrame(&mut self, reader: &mut dyn Reader) -> Result<Option<Frame>> {
+        while let Some(child) = self.blocks_.next(reader)? {
+            match child.mkv_id() {
+                Some(MkvId::MkvSimpleBlock) => {
+                    return self.ParseSimpleBlock(reader, &child).map(Some);
+                }
+                Some(MkvId::MkvBlockGroup) => {
+                    return self.ParseBlockGroup(reader, &child).map(Some);
+                }
+                _ => {}
+            }
+        }
+        Ok(None)
+    }
+
+    // Creates a frame from the block at |header| and sets its track number
+    // and absolute timestamp. The frames of a laced block are split using
+    // its lace header; see Frame::GetFrame().
+    fn ReadBlock(&self, reader: &mut dyn Reader, header: &ElementHeader) -> Result<(Frame, u8)> {
+        let mut block = ParseBlockHeader(reader, header)?;
+        let lacing = Lacing::from_u8((block.flags >> 1) & 0x03).unwrap_or(Lacing::kNoLacing);
+        let lace_sizes = if lacing != Lacing::kNoLacing {
+            ParseLaceSizes(reader, &mut block, lacing)?
+        } else {
+            Vec::new()
+        };
+        if block.frame_size == 0 {
+            return Err(ParseError::kFileFormatInvalid);
+        }
+
+        let timestamp = self.BlockTimestamp(&block)?;
+
+        let data = UnserializeBytes(reader, block.frame_pos, block.frame_size)?;
+        let mut frame = Frame::new();
+        if lace_sizes.is_empty() {
+            frame.Init(&data);
+        } else {
+            let mut frames = Vec::with_capacity(lace_sizes.len());
+            let mut start = 0;
+            for size in lace_sizes {
+                frames.push(&data[start..start + size as usize]);
+                start += size as usize;
+            }
+            if !frame.InitLaced(&frames, lacing) {
+                return Err(ParseError::kFileFormatInvalid);
+            }
+        }
+        frame.set_track_number(block.track_number);
+        frame.set_timestamp(timestamp);
+        Ok((frame, block.flags))
+    }
+
+    fn ParseSimpleBlock(&self, reader: &mut dyn Reader, header: &ElementHeader) -> Result<Frame> {
+        let (mut frame, flags) = self.ReadBlock(reader, header)?;
+        frame.set_is_key(flags & 0x80 != 0);
+        Ok(frame)
+    }
+
+    fn ParseBlockGroup(&self, reader: &mut dyn Reader, header: &ElementHeader) -> Result<Frame> {
+        let mut frame = None;
+        let mut duration = None;
+        let mut reference_block = None;
+        let mut discard_padding = 0;
+        let mut additional = None;
+
+        let mut children = Elements::new(header);
+        while let Some(child) = children.next(reader)? {
+            match child.mkv_id() {
+                Some(MkvId::MkvBlock) => {
+                    if frame.is_some() {
+                        return Err(ParseError::kFileFormatInvalid);
+                    }
+                    frame = Some(self.ReadBlock(reader, &child)?.0);
+                }
+                Some(MkvId::MkvBlockDuration) => {
+                    duration = Some(ReadUIntElement(reader, &child)?);
+                }
+                // Only the first reference is kept.
+                Some(MkvId::MkvReferenceBlock) if reference_block.is_none() => {
+                    reference_block = Some(ReadIntElement(reader, &child)?);
+                }
+                Some(MkvId::MkvDiscardPadding) => {
+                    discard_padding = ReadIntElement(reader, &child)?;
+                }
+                Some(MkvId::MkvBlockAdditions) if additional.is_none() => {
+                    additional = ParseBlockAdditions(reader, &child)?;
+                }
+                _ => {}
+            }
+        }
+
+        let mut frame = frame.ok_or(ParseError::kFileFormatInvalid)?;
+        // A Block is a key frame unless it references another block.
+        frame.set_is_key(reference_block.is_none());
+        if let Some(duration) = duration {
+            let duration = duration
+                .checked_mul(self.timecode_scale_)
+                .ok_or(ParseError::kFileFormatInvalid)?;
+            frame.set_duration(duration);
+        }
+        if let Some(reference_block) = reference_block {
+            let reference_block = i64::try_from(self.timecode_scale_)
+                .ok()
+                .and_then(|scale| reference_block.checked_mul(scale))
+                .ok_or(ParseError::kFileFormatInvalid)?;
+            frame.set_reference_block_timestamp(reference_block);
+        }
+        frame.set_discard_padding(discard_padding);
+        if let Some((data, add_id)) = additional {
+            frame.AddAdditionalData(&data, add_id);
+        }
+        Ok(frame)
+    }
+}
+
+// Returns the data and BlockAddID of the first BlockMore.
+fn ParseBlockAdditions(
+    reader: &mut dyn Reader,
+    header: &ElementHeader,
+) -> Result<Option<(Vec<u8>, u64)>> {
+    let mut block_mores = Elements::new(header);
+    while let Some(block_more) = block_mores.next(reader)? {
+        if !block_more.is(MkvId::MkvBlockMore) {
+            continue;
+        }
+
+        let mut add_id = 1;
+        let mut data = None;
+        let mut children = Elements::new(&block_more);
+        while let Some(child) = children.next(reader)? {
+            match child.mkv_id() {
+                Some(MkvId::MkvBlockAddID) => add_id = ReadUIntElement(reader, &child)?,
+                Some(MkvId::MkvBlockAdditional) => data = Some(ReadBytesElement(reader, &child)?),
+                _ => {}
+            }
+        }
+
+        if let Some(data) = data {
+            return Ok(Some((data, add_id)));
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,238 @@
+use super::element::{ElementHeader, Elements, ReadBytesElement, ReadUIntElement};
+use super::error::{ParseError, Result};
+use super::reader::Reader;
+use crate::mkvmuxer::content_encryptor::{
+    kEncryptedFrame, kIVSize, kKeySize, kPartitionedFrame, CryptPartitions, ValidPartitionOffsets,
+};
+use crate::MkvId;
+use flate2::read::ZlibDecoder;
+use std::io::Read;
+
+// ContentEncodingType values.
+pub const kCompression: u64 = 0;
+pub const kEncryption: u64 = 1;
+
+// ContentCompAlgo values.
+pub const kZlib: u64 = 0;
+pub const kHeaderStripping: u64 = 3;
+
+// Largest frame or CodecPrivate zlib decompression may produce, so that a
+// few KB of crafted data in a Block can't inflate to gigabytes.
+pub const kMaxDecompressedSize: u64 = 64 * 1024 * 1024;
+
+pub struct ContentEncoding {
+    order_: u64,
+    scope_: u64,
+    type_: u64,
+
+    // ContentCompression values.
+    comp_algo_: u64,
+    comp_settings_: Vec<u8>,
+
+    // ContentEncryption values.
+    enc_algo_: u64,
+    enc_key_id_: Vec<u8>,
+    aes_cipher_mode_: u64,
+}
+
+impl ContentEncoding {
+    fn Parse(reader: &mut dyn Reader, header: &ElementHeader) -> Result<ContentEncoding> {
+        let mut encoding = ContentEncoding {
+            order_: 0,
+            scope_: 1,
+            type_: kCompression,
+            comp_algo_: kZlib,
+            comp_settings_: Vec::new(),
+            enc_algo_: 0,
+            enc_key_id_: Vec::new(),
+            aes_cipher_mode_: 0,
+        };
+
+        let mut children = Elements::new(header);
+        while let Some(child) = children.next(reader)? {
+            match child.mkv_id() {
+                Some(MkvId::MkvContentEncodingOrder) => {
+                    encoding.order_ = ReadUIntElement(reader, &child)?;
+                }
+                Some(MkvId::MkvContentEncodingScope) => {
+                    encoding.scope_ = ReadUIntElement(reader, &child)?;
+                }
+                Some(MkvId::MkvContentEncodingType) => {
+                    encoding.type_ = ReadUIntElement(reader, &child)?;
+                }
+                Some(MkvId::MkvContentCompression) => encoding.ParseCompression(reader, &child)?,
+                Some(MkvId::MkvContentEncryption) => encoding.ParseEncryption(reader, &child)?,
+                _ => {}
+            }
+        }
+
+        if encoding.scope_ == 0 {
+            return Err(ParseError::kFileFormatInvalid);
+        }
+        Ok(encoding)
+    }
+
+    fn ParseCompression(&mut self, reader: &mut dyn Reader, header: &ElementHeader) -> Result<()> {
+        let mut children = Elements::new(header);
+        while let Some(child) = children.next(reader)? {
+            match child.mkv_id() {
+                Some(MkvId::MkvContentCompAlgo) => {
+                    self.comp_algo_ = ReadUIntElement(reader, &child)?;
+                }
+                Some(MkvId::MkvContentCompSettings) => {
+                    self.comp_settings_ = ReadBytesElement(reader, &child)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn ParseEncryption(&mut self, reader: &mut dyn Reader, header: &ElementHeader) -> Result<()> {
+        let mut children = Elements::new(header);
+        while let Some(child) = children.next(reader)? {
+            match child.mkv_id() {
+                Some(MkvId::MkvContentEncAlgo) => self.enc_algo_ = ReadUIntElement(reader, &child)?,
+                Some(MkvId::MkvContentEncKeyID) => {
+                    self.enc_key_id_ = ReadBytesElement(reader, &child)?;
+                }
+                Some(MkvId::MkvContentEncAESSettings) => {
+                    let mut settings = Elements::new(&child);
+                    while let Some(setting) = settings.next(reader)? {
+                        if setting.is(MkvId::MkvAESSettingsCipherMode) {
+                            self.aes_cipher_mode_ = ReadUIntElement(reader, &setting)?;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    pub fn order(&self) -> u64 {
+        self.order_
+    }
+    pub fn scope(&self) -> u64 {
+        self.scope_
+    }
+    pub fn encoding_type(&self) -> u64 {
+        self.type_
+    }
+    pub fn comp_algo(&self) -> u64 {
+        self.comp_algo_
+    }
+    pub fn comp_settings(&self) -> &[u8] {
+        &self.comp_settings_
+    }
+    pub fn enc_algo(&self) -> u64 {
+        self.enc_algo_
+    }
+    pub fn enc_key_id(&self) -> &[u8] {
+        &self.enc_key_id_
+    }
+    pub fn aes_cipher_mode(&self) -> u64 {
+        self.aes_cipher_mode_
+    }
+
+    // Returns true if the encoding applies to the frames of the track.
+    pub fn AppliesToFrames(&self) -> bool {
+        self.scope_ & 1 != 0
+    }
+
+    // Returns true if the encoding applies to the CodecPrivate of the track.
+    pub fn AppliesToCodecPrivate(&self) -> bool {
+        self.scope_ & 2 != 0
+    }
+
+    // Reverses this encoding of the frame |data|. Encrypted data is decrypted
+    // with |key|, or returned unchanged without one.
+    pub fn DecodeFrame(&self, data: &[u8], key: Option<&[u8; kKeySize]>) -> Result<Vec<u8>> {
+        if self.type_ != kCompression {
+            return match key {
+                Some(key) if self.type_ == kEncryption => DecryptFrame(key, data),
+                _ => Ok(data.to_vec()),
+            };
+        }
+
+        match self.comp_algo_ {
+            kHeaderStripping => {
+                let mut frame = Vec::with_capacity(self.comp_settings_.len() + data.len());
+                frame.extend_from_slice(&self.comp_settings_);
+                frame.extend_from_slice(data);
+                Ok(frame)
+            }
+            kZlib => {
+                let mut frame = Vec::new();
+                let mut decoder = ZlibDecoder::new(data).take(kMaxDecompressedSize + 1);
+                if decoder.read_to_end(&mut frame).is_err()
+                    || frame.len() as u64 > kMaxDecompressedSize
+                {
+                    return Err(ParseError::kFileFormatInvalid);
+                }
+                Ok(frame)
+            }
+            // bzlib and lzo1x are not supported.
+            _ => Err(ParseError::kFileFormatInvalid),
+        }
+    }
+}
+
+// Returns the frame |data| in the WebM encryption format decrypted with the
+// AES-128 |key|; see mkvmuxer::content_encryptor for the format. Frames
+// without the encrypted flag are clear and only lose the signal byte.
+pub fn DecryptFrame(key: &[u8; kKeySize], data: &[u8]) -> Result<Vec<u8>> {
+    let (&signal, rest) = data.split_first().ok_or(ParseError::kFileFormatInvalid)?;
+    if signal & kEncryptedFrame == 0 {
+        return Ok(rest.to_vec());
+    }
+    if rest.len() < kIVSize {
+        return Err(ParseError::kFileFormatInvalid);
+    }
+    let (iv, mut rest) = rest.split_at(kIVSize);
+    let iv = iv.iter().fold(0u64, |iv, &byte| (iv << 8) | byte as u64);
+
+    let mut partition_offsets = Vec::new();
+    if signal & kPartitionedFrame != 0 {
+        let (&count, offsets) = rest.split_first().ok_or(ParseError::kFileFormatInvalid)?;
+        let offsets_size = count as usize * 4;
+        if offsets.len() < offsets_size {
+            return Err(ParseError::kFileFormatInvalid);
+        }
+        partition_offsets = offsets[..offsets_size]
+            .chunks(4)
+            .map(|offset| u32::from_be_bytes([offset[0], offset[1], offset[2], offset[3]]))
+            .collect();
+        rest = &offsets[offsets_size..];
+    }
+    if !ValidPartitionOffsets(&partition_offsets, rest.len()) {
+        return Err(ParseError::kFileFormatInvalid);
+    }
+
+    let mut frame = rest.to_vec();
+    CryptPartitions(key, iv, &mut frame, &partition_offsets);
+    Ok(frame)
+}
+
+// Parses the ContentEncodings element |header| and returns its encodings in
+// the order they were applied by the muxer, i.e. by ascending
+// ContentEncodingOrder.
+pub fn ParseContentEncodings(
+    reader: &mut dyn Reader,
+    header: &ElementHeader,
+) -> Result<Vec<ContentEncoding>> {
+    let mut encodings = Vec::new();
+
+    let mut children = Elements::new(header);
+    while let Some(child) = children.next(reader)? {
+        if child.is(MkvId::MkvContentEncoding) {
+            encodings.push(ContentEncoding::Parse(reader, &child)?);
+        }
+    }
+
+    if encodings.is_empty() {
+        return Err(ParseError::kFileFormatInvalid);
+    }
+    encodings.sort_by_key(|encoding| encoding.order_);
+    Ok(encodings)
+}
@@ -0,0 +1,171 @@
+use super::element::{ElementHeader, Elements, ReadUIntElement};
+use super::error::{ParseError, Result};
+use super::reader::Reader;
+use crate::MkvId;
+
+pub struct CueTrackPosition {
+    track_: u64,
+    // Position of the Cluster, relative to the start of the Segment payload.
+    cluster_pos_: u64,
+    // 1-based index of the block within the Cluster.
+    block_number_: u64,
+}
+
+impl CueTrackPosition {
+    fn Parse(reader: &mut dyn Reader, header: &ElementHeader) -> Result<CueTrackPosition> {
+        let mut track = None;
+        let mut cluster_pos = None;
+        let mut block_number = 1;
+
+        let mut children = Elements::new(header);
+        while let Some(child) = children.next(reader)? {
+            match child.mkv_id() {
+                Some(MkvId::MkvCueTrack) => track = Some(ReadUIntElement(reader, &child)?),
+                Some(MkvId::MkvCueClusterPosition) => {
+                    cluster_pos = Some(ReadUIntElement(reader, &child)?);
+                }
+                Some(MkvId::MkvCueBlockNumber) => {
+                    block_number = ReadUIntElement(reader, &child)?;
+                }
+                _ => {}
+            }
+        }
+
+        let track = track.ok_or(ParseError::kFileFormatInvalid)?;
+        if track == 0 || block_number == 0 {
+            return Err(ParseError::kFileFormatInvalid);
+        }
+        Ok(CueTrackPosition {
+            track_: track,
+            cluster_pos_: cluster_pos.ok_or(ParseError::kFileFormatInvalid)?,
+            block_number_: block_number,
+        })
+    }
+
+    pub fn track(&self) -> u64 {
+        self.track_
+    }
+    pub fn cluster_pos(&self) -> u64 {
+        self.cluster_pos_
+    }
+    pub fn block_number(&self) -> u64 {
+        self.block_number_
+    }
+}
+
+pub struct CuePoint {
+    // Time in timecode ticks.
+    time_: u64,
+    track_positions_: Vec<CueTrackPosition>,
+}
+
+impl CuePoint {
+    fn Parse(reader: &mut dyn Reader, header: &ElementHeader) -> Result<CuePoint> {
+        let mut time = None;
+        let mut track_positions = Vec::new();
+
+        let mut children = Elements::new(header);
+        while let Some(child) = children.next(reader)? {
+            match child.mkv_id() {
+                Some(MkvId::MkvCueTime) => time = Some(ReadUIntElement(reader, &child)?),
+                Some(MkvId::MkvCueTrackPositions) => {
+                    track_positions.push(CueTrackPosition::Parse(reader, &child)?);
+                }
+                _ => {}
+            }
+        }
+
+        if track_positions.is_empty() {
+            return Err(ParseError::kFileFormatInvalid);
+        }
+        Ok(CuePoint {
+            time_: time.ok_or(ParseError::kFileFormatInvalid)?,
+            track_positions_: track_positions,
+        })
+    }
+
+    pub fn time(&self) -> u64 {
+        self.time_
+    }
+
+    // Returns the time in nanoseconds, or kFileFormatInvalid if it
+    // overflows.
+    pub fn GetTime(&self, timecode_scale: u64) -> Result<u64> {
+        self.time_
+            .checked_mul(timecode_scale)
+            .ok_or(ParseError::kFileFormatInvalid)
+    }
+
+    pub fn track_positions(&self) -> &[CueTrackPosition] {
+        &self.track_positions_
+    }
+
+    pub fn Find(&self, track: u64) -> Option<&CueTrackPosition> {
+        self.track_positions_.iter().find(|tp| tp.track() == track)
+    }
+}
+
+pub struct Cues {
+    cue_points_: Vec<CuePoint>,
+
+    // Position of the Cues element id.
+    element_start_: u64,
+    element_size_: u64,
+}
+
+impl Cues {
+    pub fn Parse(reader: &mut dyn Reader, header: &ElementHeader) -> Result<Cues> {
+        let mut cues = Cues {
+            cue_points_: Vec::new(),
+            element_start_: header.start,
+            element_size_: header.header_size() + header.payload_size()?,
+        };
+
+        let mut children = Elements::new(header);
+        while let Some(child) = children.next(reader)? {
+            if child.is(MkvId::MkvCuePoint) {
+                cues.cue_points_.push(CuePoint::Parse(reader, &child)?);
+            }
+        }
+        // Cue points are expected in time order, but don't rely on it.
+        cues.cue_points_.sort_by_key(|cp| cp.time());
+
+        Ok(cues)
+    }
+
+    pub fn cue_entries_size(&self) -> usize {
+        self.cue_points_.len()
+    }
+
+    pub fn GetCueByIndex(&self, index: usize) -> Option<&CuePoint> {
+        self.cue_points_.get(index)
+    }
+
+    pub fn element_start(&self) -> u64 {
+        self.element_start_
+    }
+    pub fn element_size(&self) -> u64 {
+        self.element_size_
+    }
+
+    // Returns the last cue point for |track| at or before |time_ns|. If
+    // |time_ns| is before the first cue point for |track|, that cue point is
+    // returned.
+    pub fn Find(
+        &self,
+        time_ns: u64,
+        track: u64,
+        timecode_scale: u64,
+    ) -> Result<Option<(&CuePoint, &CueTrackPosition)>> {
+        let mut found = None;
+        for cp in &self.cue_points_ {
+            if let Some(tp) = cp.Find(track) {
+                if found.is_some() && cp.GetTime(timecode_scale)? > time_ns {
+                    break;
+                }
+                found = Some((cp, tp));
+            }
+        }
+        Ok(found)
+    }
+}
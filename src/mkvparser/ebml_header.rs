@@ -0,0 +1,118 @@
+use super::element::{Elements, ParseElementHeader};
+use super::error::{ParseError, Result};
+use super::reader::Reader;
+use super::util::{UnserializeString, UnserializeUInt};
+use crate::MkvId;
+
+pub struct EBMLHeader {
+    version_: u64,
+    read_version_: u64,
+    max_id_length_: u64,
+    max_size_length_: u64,
+    doc_type_: String,
+    doc_type_version_: u64,
+    doc_type_read_version_: u64,
+}
+
+impl Default for EBMLHeader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EBMLHeader {
+    pub fn new() -> EBMLHeader {
+        EBMLHeader {
+            version_: 1,
+            read_version_: 1,
+            max_id_length_: 4,
+            max_size_length_: 8,
+            doc_type_: "matroska".to_string(),
+            doc_type_version_: 1,
+            doc_type_read_version_: 1,
+        }
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version_
+    }
+    pub fn read_version(&self) -> u64 {
+        self.read_version_
+    }
+    pub fn max_id_length(&self) -> u64 {
+        self.max_id_length_
+    }
+    pub fn max_size_length(&self) -> u64 {
+        self.max_size_length_
+    }
+    pub fn doc_type(&self) -> &str {
+        &self.doc_type_
+    }
+    pub fn doc_type_version(&self) -> u64 {
+        self.doc_type_version_
+    }
+    pub fn doc_type_read_version(&self) -> u64 {
+        self.doc_type_read_version_
+    }
+
+    // Parses the EBML header at |pos|. Returns the position just past it.
+    pub fn Parse(&mut self, reader: &mut dyn Reader, pos: u64) -> Result<u64> {
+        let header = ParseElementHeader(reader, pos, None)?;
+        if !header.is(MkvId::MkvEBML) {
+            return Err(ParseError::kFileFormatInvalid);
+        }
+        if header.size.is_none() {
+            return Err(ParseError::kFileFormatInvalid);
+        }
+
+        *self = EBMLHeader::new();
+
+        let mut children = Elements::new(&header);
+        while let Some(child) = children.next(reader)? {
+            let size = child.size.ok_or(ParseError::kFileFormatInvalid)?;
+            let pos = child.payload_start;
+            match child.mkv_id() {
+                Some(MkvId::MkvEBMLVersion) => {
+                    self.version_ = UnserializeUInt(reader, pos, size)?;
+                }
+                Some(MkvId::MkvEBMLReadVersion) => {
+                    self.read_version_ = UnserializeUInt(reader, pos, size)?;
+                }
+                Some(MkvId::MkvEBMLMaxIDLength) => {
+                    self.max_id_length_ = UnserializeUInt(reader, pos, size)?;
+                }
+                Some(MkvId::MkvEBMLMaxSizeLength) => {
+                    self.max_size_length_ = UnserializeUInt(reader, pos, size)?;
+                }
+                Some(MkvId::MkvDocType) => {
+                    self.doc_type_ = UnserializeString(reader, pos, size)?;
+                }
+                Some(MkvId::MkvDocTypeVersion) => {
+                    self.doc_type_version_ = UnserializeUInt(reader, pos, size)?;
+                }
+                Some(MkvId::MkvDocTypeReadVersion) => {
+                    self.doc_type_read_version_ = UnserializeUInt(reader, pos, size)?;
+                }
+                _ => {}
+            }
+        }
+
+        if self.read_version_ > 1 {
+            return Err(ParseError::kFileFormatInvalid);
+        }
+        if self.max_id_length_ == 0 || self.max_id_length_ > 4 {
+            return Err(ParseError::kFileFormatInvalid);
+        }
+        if self.max_size_length_ == 0 || self.max_size_length_ > 8 {
+            return Err(ParseError::kFileFormatInvalid);
+        }
+        if self.doc_type_.is_empty()
+            || self.doc_type_read_version_ == 0
+            || self.doc_type_read_version_ > self.doc_type_version_
+        {
+            return Err(ParseError::kFileFormatInvalid);
+        }
+
+        Ok(children.position())
+    }
+}
@@ -0,0 +1,271 @@
+use super::error::{ParseError, Result};
+use super::reader::Reader;
+use super::util::{
+    IsUnknownSize, ReadID, ReadUInt, UnserializeBytes, UnserializeFloat, UnserializeInt,
+    UnserializeString, UnserializeUInt,
+};
+use crate::mkvmuxer::util::Crc32Update;
+use crate::MkvId;
+
+// Position and size of a single EBML element.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ElementHeader {
+    // Raw element id, including the length marker.
+    pub id: u64,
+    // Position of the first byte of the id.
+    pub start: u64,
+    // Position of the first byte of the payload.
+    pub payload_start: u64,
+    // Payload size, or None when the element was written with an unknown
+    // size.
+    pub size: Option<u64>,
+}
+
+impl ElementHeader {
+    pub fn mkv_id(&self) -> Option<MkvId> {
+        MkvId::from_u64(self.id)
+    }
+
+    pub fn is(&self, id: MkvId) -> bool {
+        self.id == id as u64
+    }
+
+    // Size of the id and size fields.
+    pub fn header_size(&self) -> u64 {
+        self.payload_start - self.start
+    }
+
+    // Position just past the payload, when the size is known.
+    pub fn end(&self) -> Option<u64> {
+        self.size.map(|size| self.payload_start + size)
+    }
+
+    // Payload size of an element that must have a known size.
+    pub fn payload_size(&self) -> Result<u64> {
+        self.size.ok_or(ParseError::kFileFormatInvalid)
+    }
+}
+
+pub fn ReadUIntElement(reader: &mut dyn Reader, header: &ElementHeader) -> Result<u64> {
+    UnserializeUInt(reader, header.payload_start, header.payload_size()?)
+}
+
+pub fn ReadIntElement(reader: &mut dyn Reader, header: &ElementHeader) -> Result<i64> {
+    UnserializeInt(reader, header.payload_start, header.payload_size()?)
+}
+
+pub fn ReadFloatElement(reader: &mut dyn Reader, header: &ElementHeader) -> Result<f64> {
+    UnserializeFloat(reader, header.payload_start, header.payload_size()?)
+}
+
+pub fn ReadStringElement(reader: &mut dyn Reader, header: &ElementHeader) -> Result<String> {
+    UnserializeString(reader, header.payload_start, header.payload_size()?)
+}
+
+pub fn ReadBytesElement(reader: &mut dyn Reader, header: &ElementHeader) -> Result<Vec<u8>> {
+    UnserializeBytes(reader, header.payload_start, header.payload_size()?)
+}
+
+// Reads the id and size of the element at |pos|. When |stop| is set the
+// element must end at or before it.
+pub fn ParseElementHeader(
+    reader: &mut dyn Reader,
+    pos: u64,
+    stop: Option<u64>,
+) -> Result<ElementHeader> {
+    let (id, id_len) = ReadID(reader, pos)?;
+    let size_pos = pos + id_len as u64;
+    let (value, size_len) = ReadUInt(reader, size_pos)?;
+    let payload_start = size_pos + size_len as u64;
+    let size = if IsUnknownSize(value, size_len) {
+        None
+    } else {
+        Some(value)
+    };
+
+    if let Some(stop) = stop {
+        if payload_start > stop {
+            return Err(ParseError::kFileFormatInvalid);
+        }
+        if let Some(size) = size {
+            if size > stop - payload_start {
+                return Err(ParseError::kFileFormatInvalid);
+            }
+        }
+    }
+
+    Ok(ElementHeader {
+        id,
+        start: pos,
+        payload_start,
+        size,
+    })
+}
+
+fn IsTopLevel(id: u64) -> bool {
+    id == MkvId::MkvEBML as u64 || id == MkvId::MkvSegment as u64
+}
+
+// Level 1 elements are the direct children of the Segment.
+pub fn IsLevel1(id: u64) -> bool {
+    matches!(
+        MkvId::from_u64(id),
+        Some(MkvId::MkvSeekHead)
+            | Some(MkvId::MkvInfo)
+            | Some(MkvId::MkvTracks)
+            | Some(MkvId::MkvCluster)
+            | Some(MkvId::MkvCues)
+            | Some(MkvId::MkvChapters)
+            | Some(MkvId::MkvTags)
+            | Some(MkvId::MkvAttachments)
+            | Some(MkvId::MkvSignatureSlot)
+    )
+}
+
+// Returns true if an element with |id| can't be a descendant of an element
+// with |parent_id|, so it ends a parent of unknown size.
+fn EndsParent(parent_id: u64, id: u64) -> bool {
+    if IsTopLevel(id) {
+        return true;
+    }
+    parent_id != MkvId::MkvSegment as u64 && IsLevel1(id)
+}
+
+// Walks the children of a master element. The reader is passed to each call
+// so the caller can read an element's payload between calls to next().
+#[derive(Clone)]
+pub struct Elements {
+    parent_id: u64,
+    pos: u64,
+    stop: Option<u64>,
+    // Last element returned by next(). Its end is only computed when the
+    // walker moves past it, since elements of unknown size have to be
+    // scanned.
+    current: Option<ElementHeader>,
+}
+
+impl Elements {
+    // Walks the payload of |parent|.
+    pub fn new(parent: &ElementHeader) -> Elements {
+        Elements {
+            parent_id: parent.id,
+            pos: parent.payload_start,
+            stop: parent.end(),
+            current: None,
+        }
+    }
+
+    // Walks the children of |parent| starting at |pos|, which must be the
+    // position of one of its children.
+    pub fn resume(parent: &ElementHeader, pos: u64) -> Elements {
+        Elements {
+            parent_id: parent.id,
+            pos,
+            stop: parent.end(),
+            current: None,
+        }
+    }
+
+    // Walks the elements in [start, stop). With no |stop| the walk ends at
+    // the end of the reader's data.
+    pub fn with_range(start: u64, stop: Option<u64>) -> Elements {
+        Elements {
+            parent_id: 0,
+            pos: start,
+            stop,
+            current: None,
+        }
+    }
+
+    // Position of the next element, or of the end of the walked range once
+    // next() has returned None.
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    pub fn next(&mut self, reader: &mut dyn Reader) -> Result<Option<ElementHeader>> {
+        if let Some(current) = self.current.take() {
+            self.pos = ElementEnd(reader, &current)?;
+        }
+
+        match self.stop {
+            Some(stop) => {
+                if self.pos >= stop {
+                    return Ok(None);
+                }
+            }
+            None => {
+                if let Some(total) = reader.length() {
+                    if self.pos >= total {
+                        return Ok(None);
+                    }
+                }
+            }
+        }
+
+        let header = ParseElementHeader(reader, self.pos, self.stop)?;
+        if self.stop.is_none() && self.parent_id != 0 && EndsParent(self.parent_id, header.id) {
+            return Ok(None);
+        }
+        self.current = Some(header);
+        Ok(Some(header))
+    }
+}
+
+// Returns the position just past |header|. Elements of unknown size are
+// scanned until the first element that can't be one of their children.
+pub fn ElementEnd(reader: &mut dyn Reader, header: &ElementHeader) -> Result<u64> {
+    if let Some(end) = header.end() {
+        return Ok(end);
+    }
+    let mut children = Elements::new(header);
+    while children.next(reader)?.is_some() {}
+    Ok(children.position())
+}
+
+// Returns the first child of |parent| with |id|.
+pub fn FindChild(
+    reader: &mut dyn Reader,
+    parent: &ElementHeader,
+    id: MkvId,
+) -> Result<Option<ElementHeader>> {
+    let mut children = Elements::new(parent);
+    while let Some(child) = children.next(reader)? {
+        if child.is(id) {
+            return Ok(Some(child));
+        }
+    }
+    Ok(None)
+}
+
+// Checks the payload of |parent| against its CRC-32 element, if the first
+// child is one. Elements of unknown size are not checked.
+pub fn VerifyCrc32(reader: &mut dyn Reader, parent: &ElementHeader) -> Result<()> {
+    let stop = match parent.end() {
+        Some(stop) if stop > parent.payload_start => stop,
+        _ => return Ok(()),
+    };
+    let child = ParseElementHeader(reader, parent.payload_start, Some(stop))?;
+    if !child.is(MkvId::MkvCrc32) {
+        return Ok(());
+    }
+    if child.size != Some(4) {
+        return Err(ParseError::kFileFormatInvalid);
+    }
+    let mut value = [0u8; 4];
+    reader.read(child.payload_start, &mut value)?;
+
+    let mut buf = [0u8; 4096];
+    let mut pos = child.payload_start + 4;
+    let mut crc = 0;
+    while pos < stop {
+        let len = (stop - pos).min(buf.len() as u64) as usize;
+        reader.read(pos, &mut buf[..len])?;
+        crc = Crc32Update(crc, &buf[..len]);
+        pos += len as u64;
+    }
+    if crc != u32::from_le_bytes(value) {
+        return Err(ParseError::kCrc32Mismatch(parent.id));
+    }
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use std::fmt;
+use std::io;
+
+// Status of a failed parse. Mirrors the negative status codes returned by
+// the C++ mkvparser.
+#[derive(Debug)]
+pub enum ParseError {
+    // The element could not be parsed, e.g. an id or size is out of range.
+    kParseFailed,
+    // The data does not describe a valid WebM/Matroska file.
+    kFileFormatInvalid,
+    // Not enough data is available yet. The caller may retry once the
+    // reader has more data.
+    kBufferNotFull,
+    // The reader failed.
+    kIoError(io::Error),
+    // The CRC-32 element of the master element with this id does not match
+    // its payload.
+    kCrc32Mismatch(u64),
+}
+
+pub type Result<T> = std::result::Result<T, ParseError>;
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::kParseFailed => write!(f, "parse failed"),
+            ParseError::kFileFormatInvalid => write!(f, "file format invalid"),
+            ParseError::kBufferNotFull => write!(f, "buffer not full"),
+            ParseError::kIoError(e) => write!(f, "io error: {}", e),
+            ParseError::kCrc32Mismatch(id) => write!(f, "CRC-32 mismatch in element {:#X}", id),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<io::Error> for ParseError {
+    fn from(e: io::Error) -> ParseError {
+        if e.kind() == io::ErrorKind::UnexpectedEof {
+            ParseError::kBufferNotFull
+        } else {
+            ParseError::kIoError(e)
+        }
+    }
+}
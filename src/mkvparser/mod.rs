@@ -1 +1,19 @@
-
+pub mod attachments;
+pub mod audio_track;
+pub mod chapters;
+pub mod cluster;
+pub mod content_encoding;
+pub mod cues;
+pub mod ebml_header;
+pub mod element;
+pub mod error;
+pub mod reader;
+pub mod seek_head;
+pub mod segment;
+pub mod segment_info;
+pub mod signature_slot;
+pub mod tags;
+pub mod track;
+pub mod tracks;
+pub mod util;
+pub mod video_track;
@@ -0,0 +1,44 @@
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+
+pub trait Reader {
+    // Reads exactly |buf.len()| bytes starting at |position|.
+    fn read(&mut self, position: u64, buf: &mut [u8]) -> io::Result<()>;
+    // Total length of the source, or None when it is not known yet (for
+    // example a live stream that is still being written).
+    fn length(&self) -> Option<u64>;
+    // Number of bytes that can currently be read from the start of the source.
+    fn available(&self) -> u64;
+}
+
+pub struct MkvReader {
+    file: Box<File>,
+    length: u64,
+}
+
+impl MkvReader {
+    pub fn new(file: File) -> io::Result<MkvReader> {
+        let length = file.metadata()?.len();
+        Ok(MkvReader {
+            file: Box::new(file),
+            length,
+        })
+    }
+}
+
+impl Reader for MkvReader {
+    fn read(&mut self, position: u64, buf: &mut [u8]) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(position))?;
+        self.file.read_exact(buf)
+    }
+
+    fn length(&self) -> Option<u64> {
+        Some(self.length)
+    }
+
+    fn available(&self) -> u64 {
+        self.length
+    }
+}
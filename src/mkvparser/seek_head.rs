@@ -0,0 +1,80 @@
+use super::element::{ElementHeader, Elements, ReadBytesElement, ReadUIntElement};
+use super::error::{ParseError, Result};
+use super::reader::Reader;
+use super::util::MAX_ID_LENGTH;
+use crate::MkvId;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SeekEntry {
+    // Id of the indexed element, including the length marker.
+    pub id: u64,
+    // Position of the indexed element, relative to the start of the Segment
+    // payload.
+    pub pos: u64,
+}
+
+pub struct SeekHead {
+    entries_: Vec<SeekEntry>,
+
+    // Position of the SeekHead element id.
+    element_start_: u64,
+    element_size_: u64,
+}
+
+impl SeekHead {
+    pub fn Parse(reader: &mut dyn Reader, header: &ElementHeader) -> Result<SeekHead> {
+        let mut seek_head = SeekHead {
+            entries_: Vec::new(),
+            element_start_: header.start,
+            element_size_: header.header_size() + header.payload_size()?,
+        };
+
+        let mut children = Elements::new(header);
+        while let Some(child) = children.next(reader)? {
+            if !child.is(MkvId::MkvSeek) {
+                continue;
+            }
+
+            let mut id = None;
+            let mut pos = None;
+            let mut seek_children = Elements::new(&child);
+            while let Some(seek_child) = seek_children.next(reader)? {
+                match seek_child.mkv_id() {
+                    Some(MkvId::MkvSeekID) => {
+                        let bytes = ReadBytesElement(reader, &seek_child)?;
+                        if bytes.is_empty() || bytes.len() > MAX_ID_LENGTH {
+                            return Err(ParseError::kFileFormatInvalid);
+                        }
+                        id = Some(bytes.iter().fold(0u64, |id, b| (id << 8) | *b as u64));
+                    }
+                    Some(MkvId::MkvSeekPosition) => {
+                        pos = Some(ReadUIntElement(reader, &seek_child)?);
+                    }
+                    _ => {}
+                }
+            }
+
+            // Entries missing either element are ignored.
+            if let (Some(id), Some(pos)) = (id, pos) {
+                seek_head.entries_.push(SeekEntry { id, pos });
+            }
+        }
+
+        Ok(seek_head)
+    }
+
+    pub fn entry_count(&self) -> usize {
+        self.entries_.len()
+    }
+
+    pub fn GetEntry(&self, index: usize) -> Option<&SeekEntry> {
+        self.entries_.get(index)
+    }
+
+    pub fn element_start(&self) -> u64 {
+        self.element_start_
+    }
+    pub fn element_size(&self) -> u64 {
+        self.element_size_
+    }
+}
@@ -0,0 +1,443 @@
+use super::attachments::Attachments;
+use super::chapters::Chapters;
+use super::cluster::Cluster;
+use super::cues::Cues;
+use super::element::{ElementEnd, ElementHeader, Elements, ParseElementHeader, VerifyCrc32};
+use super::error::{ParseError, Result};
+use super::reader::Reader;
+use super::seek_head::SeekHead;
+use super::segment_info::SegmentInfo;
+use super::signature_slot::{kSignatureHashSha1, SignatureSlot};
+use super::tags::Tags;
+use super::tracks::Tracks;
+use crate::MkvId;
+use sha1::{Digest, Sha1};
+
+pub struct Segment {
+    // Segment element header. The payload start is the origin of the
+    // positions stored in SeekHead and Cues.
+    header_: ElementHeader,
+
+    seek_heads_: Vec<SeekHead>,
+    // Ids and absolute positions of the level 1 elements found while parsing
+    // the headers, either directly or through a SeekHead.
+    element_positions_: Vec<(u64, u64)>,
+
+    info_: Option<SegmentInfo>,
+    tracks_: Option<Tracks>,
+    cues_: Option<Cues>,
+    chapters_: Option<Chapters>,
+    tags_: Option<Tags>,
+    attachments_: Option<Attachments>,
+    signature_slot_: Option<SignatureSlot>,
+
+    // Position of the first Cluster element id, if one was found while
+    // parsing the headers.
+    first_cluster_pos_: Option<u64>,
+}
+
+impl Segment {
+    // Reads the Segment element header at |pos|, which usually follows the
+    // EBML header.
+    pub fn CreateInstance(reader: &mut dyn Reader, pos: u64) -> Result<Segment> {
+        let header = ParseElementHeader(reader, pos, None)?;
+        if !header.is(MkvId::MkvSegment) {
+            return Err(ParseError::kFileFormatInvalid);
+        }
+
+        Ok(Segment {
+            header_: header,
+            seek_heads_: Vec::new(),
+            element_positions_: Vec::new(),
+            info_: None,
+            tracks_: None,
+            cues_: None,
+            chapters_: None,
+            tags_: None,
+            attachments_: None,
+            signature_slot_: None,
+            first_cluster_pos_: None,
+        })
+    }
+
+    // Walks the level 1 elements up to the first Cluster and reads every
+    // SeekHead, including SeekHeads that are only referenced from another
+    // SeekHead. SegmentInfo, which is mandatory, and Tracks are loaded. Cues,
+    // Chapters, Tags and Attachments are loaded on demand.
+    pub fn ParseHeaders(&mut self, reader: &mut dyn Reader) -> Result<()> {
+        let mut seek_head_positions = Vec::new();
+
+        let mut children = Elements::new(&self.header_);
+        while let Some(child) = children.next(reader)? {
+            if child.is(MkvId::MkvCluster) {
+                self.first_cluster_pos_ = Some(child.start);
+                break;
+            }
+            if child.is(MkvId::MkvSeekHead) {
+                seek_head_positions.push(child.start);
+            } else {
+                self.AddElementPosition(child.id, child.start);
+            }
+        }
+
+        let mut parsed = Vec::new();
+        while let Some(pos) = seek_head_positions.pop() {
+            if parsed.contains(&pos) {
+                continue;
+            }
+            parsed.push(pos);
+
+            let header = match self.ParseLevel1Header(reader, pos, MkvId::MkvSeekHead)? {
+                Some(header) => header,
+                None => continue,
+            };
+            let seek_head = SeekHead::Parse(reader, &header)?;
+            for i in 0..seek_head.entry_count() {
+                let entry = seek_head.GetEntry(i).unwrap();
+                let pos = self.header_.payload_start + entry.pos;
+                if entry.id == MkvId::MkvSeekHead as u64 {
+                    seek_head_positions.push(pos);
+                } else {
+                    self.AddElementPosition(entry.id, pos);
+                }
+            }
+            self.seek_heads_.push(seek_head);
+        }
+
+        if let Some(header) = self.LoadElement(reader, MkvId::MkvInfo)? {
+            self.info_ = Some(SegmentInfo::Parse(reader, &header)?);
+        }
+        if self.info_.is_none() {
+            return Err(ParseError::kFileFormatInvalid);
+        }
+        self.LoadTracks(reader)?;
+        Ok(())
+    }
+
+    fn AddElementPosition(&mut self, id: u64, pos: u64) {
+        if !self.element_positions_.contains(&(id, pos)) {
+            self.element_positions_.push((id, pos));
+        }
+    }
+
+    // Returns the absolute position of the first known element with |id|.
+    pub fn GetElementPosition(&self, id: MkvId) -> Option<u64> {
+        self.element_positions_
+            .iter()
+            .find(|(element_id, _)| *element_id == id as u64)
+            .map(|(_, pos)| *pos)
+    }
+
+    // Reads the header of the element with |id| at |pos|. Returns None if
+    // |pos| is outside the Segment or holds a different element, as happens
+    // with stale SeekHead entries.
+    fn ParseLevel1Header(
+        &self,
+        reader: &mut dyn Reader,
+        pos: u64,
+        id: MkvId,
+    ) -> Result<Option<ElementHeader>> {
+        if let Some(end) = self.header_.end() {
+            if pos >= end {
+                return Ok(None);
+            }
+        }
+        let header = ParseElementHeader(reader, pos, self.header_.end())?;
+        if !header.is(id) {
+            return Ok(None);
+        }
+        Ok(Some(header))
+    }
+
+    // Reads the header of the element with |id| and checks its CRC-32, if it
+    // has one.
+    fn LoadElement(&self, reader: &mut dyn Reader, id: MkvId) -> Result<Option<ElementHeader>> {
+        let header = match self.GetElementPosition(id) {
+            Some(pos) => self.ParseLevel1Header(reader, pos, id)?,
+            None => None,
+        };
+        if let Some(header) = header.as_ref() {
+            VerifyCrc32(reader, header)?;
+        }
+        Ok(header)
+    }
+
+    pub fn LoadTracks(&mut self, reader: &mut dyn Reader) -> Result<Option<&Tracks>> {
+        if self.tracks_.is_none() {
+            if let Some(header) = self.LoadElement(reader, MkvId::MkvTracks)? {
+                self.tracks_ = Some(Tracks::Parse(reader, &header)?);
+            }
+        }
+        Ok(self.tracks_.as_ref())
+    }
+
+    // Loads the Cues, wherever they are in the Segment, as long as they come
+    // before the first Cluster or are indexed by a SeekHead.
+    pub fn LoadCues(&mut self, reader: &mut dyn Reader) -> Result<Option<&Cues>> {
+        if self.cues_.is_none() {
+            if let Some(header) = self.LoadElement(reader, MkvId::MkvCues)? {
+                self.cues_ = Some(Cues::Parse(reader, &header)?);
+            }
+        }
+        Ok(self.cues_.as_ref())
+    }
+
+    pub fn LoadChapters(&mut self, reader: &mut dyn Reader) -> Result<Option<&Chapters>> {
+        if self.chapters_.is_none() {
+            if let Some(header) = self.LoadElement(reader, MkvId::MkvChapters)? {
+                self.chapters_ = Some(Chapters::Parse(reader, &header)?);
+            }
+        }
+        Ok(self.chapters_.as_ref())
+    }
+
+    pub fn LoadTags(&mut self, reader: &mut dyn Reader) -> Result<Option<&Tags>> {
+        if self.tags_.is_none() {
+            if let Some(header) = self.LoadElement(reader, MkvId::MkvTags)? {
+                self.tags_ = Some(Tags::Parse(reader, &header)?);
+            }
+        }
+        Ok(self.tags_.as_ref())
+    }
+
+    pub fn LoadAttachments(&mut self, reader: &mut dyn Reader) -> Result<Option<&Attachments>> {
+        if self.attachments_.is_none() {
+            if let Some(header) = self.LoadElement(reader, MkvId::MkvAttachments)? {
+                self.attachments_ = Some(Attachments::Parse(reader, &header)?);
+            }
+        }
+        Ok(self.attachments_.as_ref())
+    }
+
+    // Loads the SignatureSlot. The whole Segment is walked, as the slot
+    // usually follows the Clusters.
+    pub fn LoadSignatureSlot(&mut self, reader: &mut dyn Reader) -> Result<Option<&SignatureSlot>> {
+        if self.signature_slot_.is_none() {
+            let mut children = Elements::new(&self.header_);
+            while let Some(child) = children.next(reader)? {
+                if child.is(MkvId::MkvSignatureSlot) {
+                    self.signature_slot_ = Some(SignatureSlot::Parse(reader, &child)?);
+                    break;
+                }
+            }
+        }
+        Ok(self.signature_slot_.as_ref())
+    }
+
+    // Checks the signature of the Segment. The level 1 elements listed in the
+    // SignatureSlot are hashed in its order, and |verifier| is given the slot
+    // and the hash, and returns whether the signature matches. Returns false
+    // if the Segment is not signed or uses a hash other than SHA-1.
+    pub fn VerifySignature(
+        &mut self,
+        reader: &mut dyn Reader,
+        verifier: impl FnOnce(&SignatureSlot, &[u8]) -> bool,
+    ) -> Result<bool> {
+        self.LoadSignatureSlot(reader)?;
+        let slot = match self.signature_slot_.as_ref() {
+            Some(slot) if slot.hash() == kSignatureHashSha1 => slot,
+            _ => return Ok(false),
+        };
+
+        let mut hasher = Sha1::new();
+        let mut buf = [0u8; 4096];
+        for &id in slot.signed_elements() {
+            let mut children = Elements::new(&self.header_);
+            while let Some(child) = children.next(reader)? {
+                if child.id != id {
+                    continue;
+                }
+                let mut pos = child.start;
+                let stop = ElementEnd(reader, &child)?;
+                while pos < stop {
+                    let len = (stop - pos).min(buf.len() as u64) as usize;
+                    reader.read(pos, &mut buf[..len])?;
+                    hasher.update(&buf[..len]);
+                    pos += len as u64;
+                }
+            }
+        }
+        Ok(verifier(slot, &hasher.finalize()))
+    }
+
+    pub fn GetSignatureSlot(&self) -> Option<&SignatureSlot> {
+        self.signature_slot_.as_ref()
+    }
+
+    pub fn GetInfo(&self) -> Option<&SegmentInfo> {
+        self.info_.as_ref()
+    }
+
+    pub fn GetTracks(&self) -> Option<&Tracks> {
+        self.tracks_.as_ref()
+    }
+
+    pub fn GetCues(&self) -> Option<&Cues> {
+        self.cues_.as_ref()
+    }
+
+    pub fn GetChapters(&self) -> Option<&Chapters> {
+        self.chapters_.as_ref()
+    }
+
+    pub fn GetTags(&self) -> Option<&Tags> {
+        self.tags_.as_ref()
+    }
+
+    pub fn GetAttachments(&self) -> Option<&Attachments> {
+        self.attachments_.as_ref()
+    }
+
+    pub fn seek_head_count(&self) -> usize {
+        self.seek_heads_.len()
+    }
+
+    pub fn GetSeekHead(&self, index: usize) -> Option<&SeekHead> {
+        self.seek_heads_.get(index)
+    }
+
+    // Position of the Segment element id.
+    pub fn element_start(&self) -> u64 {
+        self.header_.start
+    }
+
+    // Position of the Segment payload.
+    pub fn payload_start(&self) -> u64 {
+        self.header_.payload_start
+    }
+
+    // Payload size, or None for a Segment of unknown size.
+    pub fn size(&self) -> Option<u64> {
+        self.header_.size
+    }
+
+    pub fn first_cluster_pos(&self) -> Option<u64> {
+        self.first_cluster_pos_
+    }
+
+    fn timecode_scale(&self) -> u64 {
+        match self.info_.as_ref() {
+            Some(info) => info.timecode_scale(),
+            None => 1000000,
+        }
+    }
+
+    // Returns the first Cluster of the Segment.
+    pub fn GetFirstCluster(&self, reader: &mut dyn Reader) -> Result<Option<Cluster>> {
+        let pos = self
+            .first_cluster_pos_
+            .unwrap_or(self.header_.payload_start);
+        self.FindCluster(reader, pos)
+    }
+
+    // Returns the Cluster following |cluster|.
+    pub fn GetNextCluster(
+        &self,
+        reader: &mut dyn Reader,
+        cluster: &Cluster,
+    ) -> Result<Option<Cluster>> {
+        let pos = cluster.GetEnd(reader)?;
+        self.FindCluster(reader, pos)
+    }
+
+    // Returns the Cluster preceding |cluster|, located with the PrevSize of
+    // |cluster|. Returns None if |cluster| has no PrevSize.
+    pub fn GetPreviousCluster(
+        &self,
+        reader: &mut dyn Reader,
+        cluster: &Cluster,
+    ) -> Result<Option<Cluster>> {
+        let prev_size = match cluster.prev_size() {
+            Some(size) => size,
+            None => return Ok(None),
+        };
+        let pos = cluster
+            .element_start()
+            .checked_sub(prev_size)
+            .filter(|&pos| pos >= self.header_.payload_start)
+            .ok_or(ParseError::kFileFormatInvalid)?;
+        let header = ParseElementHeader(reader, pos, self.header_.end())?;
+        if !header.is(MkvId::MkvCluster) {
+            return Err(ParseError::kFileFormatInvalid);
+        }
+        Cluster::Parse(reader, &header, self.timecode_scale()).map(Some)
+    }
+
+    // Returns the first Cluster at or after |pos|, which must be the position
+    // of a level 1 element.
+    fn FindCluster(&self, reader: &mut dyn Reader, pos: u64) -> Result<Option<Cluster>> {
+        let mut children = Elements::resume(&self.header_, pos);
+        while let Some(child) = children.next(reader)? {
+            if child.is(MkvId::MkvCluster) {
+                return Cluster::Parse(reader, &child, self.timecode_scale()).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    // Returns the Cluster holding the block of |track| to start playback from
+    // for |time_ns|, positioned so its next frame is that block. The block is
+    // the last key frame of |track| at or before |time_ns|, or the first
+    // block of |track| when |time_ns| is before its first key frame. Cues are
+    // loaded and used when present; otherwise the Clusters are scanned from
+    // the start.
+    pub fn seek(
+        &mut self,
+        reader: &mut dyn Reader,
+        track: u64,
+        time_ns: u64,
+    ) -> Result<Option<Cluster>> {
+        self.LoadCues(reader)?;
+        if let Some(cues) = self.cues_.as_ref() {
+            if let Some((_, tp)) = cues.Find(time_ns, track, self.timecode_scale())? {
+                let pos = self.header_.payload_start + tp.cluster_pos();
+                let header = ParseElementHeader(reader, pos, self.header_.end())?;
+                if !header.is(MkvId::MkvCluster) {
+                    return Err(ParseError::kFileFormatInvalid);
+                }
+                let mut cluster = Cluster::Parse(reader, &header, self.timecode_scale())?;
+                let block =
+                    cluster.FindSeekBlock(reader, track, time_ns, Some(tp.block_number()))?;
+                if let Some(block) = block {
+                    cluster.SetBlockPosition(block.pos);
+                    return Ok(Some(cluster));
+                }
+            }
+        }
+
+        self.SeekLinear(reader, track, time_ns)
+    }
+
+    fn SeekLinear(
+        &self,
+        reader: &mut dyn Reader,
+        track: u64,
+        time_ns: u64,
+    ) -> Result<Option<Cluster>> {
+        // Last key frame at or before |time_ns|, and the first block of the
+        // track in case there is none.
+        let mut key_before = None;
+        let mut first = None;
+
+        let mut cluster = self.GetFirstCluster(reader)?;
+        while let Some(c) = cluster {
+            if c.GetTime()? as u64 > time_ns && (key_before.is_some() || first.is_some()) {
+                break;
+            }
+            let next = self.GetNextCluster(reader, &c)?;
+            if let Some(block) = c.FindSeekBlock(reader, track, time_ns, None)? {
+                if block.is_key_before {
+                    key_before = Some((c, block.pos));
+                } else if first.is_none() {
+                    first = Some((c, block.pos));
+                }
+            }
+            cluster = next;
+        }
+
+        Ok(key_before.or(first).map(|(mut cluster, pos)| {
+            cluster.SetBlockPosition(pos);
+            cluster
+        }))
+    }
+}
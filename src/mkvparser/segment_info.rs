@@ -0,0 +1,106 @@
+use super::element::{
+    ElementHeader, Elements, ReadFloatElement, ReadIntElement, ReadStringElement, ReadUIntElement,
+};
+use super::error::{ParseError, Result};
+use super::reader::Reader;
+use crate::MkvId;
+
+pub struct SegmentInfo {
+    // Segment Information element values.
+    timecode_scale_: u64,
+    // Duration in timecode ticks. -1 when the element is not present.
+    duration_: f64,
+    muxing_app_: String,
+    writing_app_: String,
+    title_: String,
+    // Nanoseconds since 2001-01-01. i64::MIN when DateUTC is not present.
+    date_utc_: i64,
+
+    // Position of the Info element id.
+    element_start_: u64,
+    element_size_: u64,
+}
+
+impl SegmentInfo {
+    pub fn Parse(reader: &mut dyn Reader, header: &ElementHeader) -> Result<SegmentInfo> {
+        let mut info = SegmentInfo {
+            timecode_scale_: 1000000,
+            duration_: -1.0,
+            muxing_app_: String::new(),
+            writing_app_: String::new(),
+            title_: String::new(),
+            date_utc_: i64::MIN,
+            element_start_: header.start,
+            element_size_: header.header_size() + header.payload_size()?,
+        };
+
+        let mut children = Elements::new(header);
+        while let Some(child) = children.next(reader)? {
+            match child.mkv_id() {
+                Some(MkvId::MkvTimecodeScale) => {
+                    info.timecode_scale_ = ReadUIntElement(reader, &child)?;
+                    if info.timecode_scale_ == 0 {
+                        return Err(ParseError::kFileFormatInvalid);
+                    }
+                }
+                Some(MkvId::MkvDuration) => {
+                    info.duration_ = ReadFloatElement(reader, &child)?;
+                    if info.duration_.is_nan() || info.duration_ < 0.0 {
+                        return Err(ParseError::kFileFormatInvalid);
+                    }
+                }
+                Some(MkvId::MkvMuxingApp) => {
+                    info.muxing_app_ = ReadStringElement(reader, &child)?;
+                }
+                Some(MkvId::MkvWritingApp) => {
+                    info.writing_app_ = ReadStringElement(reader, &child)?;
+                }
+                Some(MkvId::MkvTitle) => {
+                    info.title_ = ReadStringElement(reader, &child)?;
+                }
+                Some(MkvId::MkvDateUTC) => {
+                    if child.payload_size()? != 8 {
+                        return Err(ParseError::kFileFormatInvalid);
+                    }
+                    info.date_utc_ = ReadIntElement(reader, &child)?;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(info)
+    }
+
+    pub fn timecode_scale(&self) -> u64 {
+        self.timecode_scale_
+    }
+    pub fn duration(&self) -> f64 {
+        self.duration_
+    }
+    pub fn muxing_app(&self) -> &str {
+        &self.muxing_app_
+    }
+    pub fn writing_app(&self) -> &str {
+        &self.writing_app_
+    }
+    pub fn title(&self) -> &str {
+        &self.title_
+    }
+    pub fn date_utc(&self) -> i64 {
+        self.date_utc_
+    }
+    pub fn element_start(&self) -> u64 {
+        self.element_start_
+    }
+    pub fn element_size(&self) -> u64 {
+        self.element_size_
+    }
+
+    // Returns the duration in nanoseconds, or -1 if the duration is unknown.
+    pub fn GetDuration(&self) -> i64 {
+        if self.duration_ < 0.0 {
+            return -1;
+        }
+        (self.duration_ * self.timecode_scale_ as f64) as i64
+    }
+}
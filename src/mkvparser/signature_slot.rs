@@ -0,0 +1,90 @@
+use super::element::{ElementHeader, Elements, ReadBytesElement, ReadUIntElement};
+use super::error::Result;
+use super::reader::Reader;
+use crate::MkvId;
+
+// SignatureHash value for SHA1-160.
+pub const kSignatureHashSha1: u64 = 1;
+
+pub struct SignatureSlot {
+    algo_: u64,
+    hash_: u64,
+    public_key_: Vec<u8>,
+    signature_: Vec<u8>,
+    // Raw ids of the signed level 1 elements, in the order they are hashed.
+    signed_elements_: Vec<u64>,
+
+    // Position of the SignatureSlot element id.
+    element_start_: u64,
+}
+
+impl SignatureSlot {
+    pub fn Parse(reader: &mut dyn Reader, header: &ElementHeader) -> Result<SignatureSlot> {
+        let mut slot = SignatureSlot {
+            algo_: 0,
+            hash_: 0,
+            public_key_: Vec::new(),
+            signature_: Vec::new(),
+            signed_elements_: Vec::new(),
+            element_start_: header.start,
+        };
+
+        let mut children = Elements::new(header);
+        while let Some(child) = children.next(reader)? {
+            match child.mkv_id() {
+                Some(MkvId::MkvSignatureAlgo) => slot.algo_ = ReadUIntElement(reader, &child)?,
+                Some(MkvId::MkvSignatureHash) => slot.hash_ = ReadUIntElement(reader, &child)?,
+                Some(MkvId::MkvSignaturePublicKey) => {
+                    slot.public_key_ = ReadBytesElement(reader, &child)?
+                }
+                Some(MkvId::MkvSignature) => slot.signature_ = ReadBytesElement(reader, &child)?,
+                Some(MkvId::MkvSignatureElements) => slot.ParseSignatureElements(reader, &child)?,
+                _ => {}
+            }
+        }
+
+        Ok(slot)
+    }
+
+    fn ParseSignatureElements(
+        &mut self,
+        reader: &mut dyn Reader,
+        header: &ElementHeader,
+    ) -> Result<()> {
+        let mut lists = Elements::new(header);
+        while let Some(list) = lists.next(reader)? {
+            if !list.is(MkvId::MkvSignatureElementList) {
+                continue;
+            }
+            let mut children = Elements::new(&list);
+            while let Some(child) = children.next(reader)? {
+                if child.is(MkvId::MkvSignedElement) {
+                    let id = ReadBytesElement(reader, &child)?
+                        .iter()
+                        .fold(0u64, |id, &byte| id << 8 | byte as u64);
+                    self.signed_elements_.push(id);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn algo(&self) -> u64 {
+        self.algo_
+    }
+    pub fn hash(&self) -> u64 {
+        self.hash_
+    }
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key_
+    }
+    pub fn signature(&self) -> &[u8] {
+        &self.signature_
+    }
+    pub fn signed_elements(&self) -> &[u64] {
+        &self.signed_elements_
+    }
+    pub fn element_start(&self) -> u64 {
+        self.element_start_
+    }
+}
@@ -0,0 +1,237 @@
+use super::element::{
+    ElementHeader, Elements, ReadBytesElement, ReadStringElement, ReadUIntElement,
+};
+use super::error::Result;
+use super::reader::Reader;
+use crate::MkvId;
+
+pub struct Targets {
+    type_value_: u64,
+    type_: String,
+    track_uids_: Vec<u64>,
+    edition_uids_: Vec<u64>,
+    chapter_uids_: Vec<u64>,
+    attachment_uids_: Vec<u64>,
+}
+
+impl Targets {
+    // Targets of a Tag without a Targets element. TargetTypeValue defaults to
+    // 50, the album, movie or episode level.
+    fn new() -> Targets {
+        Targets {
+            type_value_: 50,
+            type_: String::new(),
+            track_uids_: Vec::new(),
+            edition_uids_: Vec::new(),
+            chapter_uids_: Vec::new(),
+            attachment_uids_: Vec::new(),
+        }
+    }
+
+    fn Parse(reader: &mut dyn Reader, header: &ElementHeader) -> Result<Targets> {
+        let mut targets = Targets::new();
+
+        let mut children = Elements::new(header);
+        while let Some(child) = children.next(reader)? {
+            match child.mkv_id() {
+                Some(MkvId::MkvTargetTypeValue) => {
+                    targets.type_value_ = ReadUIntElement(reader, &child)?;
+                }
+                Some(MkvId::MkvTargetType) => targets.type_ = ReadStringElement(reader, &child)?,
+                Some(MkvId::MkvTagTrackUID) => {
+                    targets.track_uids_.push(ReadUIntElement(reader, &child)?);
+                }
+                Some(MkvId::MkvTagEditionUID) => {
+                    targets.edition_uids_.push(ReadUIntElement(reader, &child)?);
+                }
+                Some(MkvId::MkvTagChapterUID) => {
+                    targets.chapter_uids_.push(ReadUIntElement(reader, &child)?);
+                }
+                Some(MkvId::MkvTagAttachmentUID) => {
+                    targets
+                        .attachment_uids_
+                        .push(ReadUIntElement(reader, &child)?);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(targets)
+    }
+
+    pub fn type_value(&self) -> u64 {
+        self.type_value_
+    }
+    pub fn target_type(&self) -> &str {
+        &self.type_
+    }
+    pub fn track_uids(&self) -> &[u64] {
+        &self.track_uids_
+    }
+    pub fn edition_uids(&self) -> &[u64] {
+        &self.edition_uids_
+    }
+    pub fn chapter_uids(&self) -> &[u64] {
+        &self.chapter_uids_
+    }
+    pub fn attachment_uids(&self) -> &[u64] {
+        &self.attachment_uids_
+    }
+}
+
+pub struct SimpleTag {
+    tag_name_: String,
+    tag_string_: String,
+    tag_language_: String,
+    default_: bool,
+    tag_binary_: Vec<u8>,
+    simple_tags_: Vec<SimpleTag>,
+}
+
+impl SimpleTag {
+    fn Parse(reader: &mut dyn Reader, header: &ElementHeader) -> Result<SimpleTag> {
+        let mut simple_tag = SimpleTag {
+            tag_name_: String::new(),
+            tag_string_: String::new(),
+            tag_language_: String::from("und"),
+            default_: true,
+            tag_binary_: Vec::new(),
+            simple_tags_: Vec::new(),
+        };
+
+        let mut children = Elements::new(header);
+        while let Some(child) = children.next(reader)? {
+            match child.mkv_id() {
+                Some(MkvId::MkvTagName) => {
+                    simple_tag.tag_name_ = ReadStringElement(reader, &child)?
+                }
+                Some(MkvId::MkvTagString) => {
+                    simple_tag.tag_string_ = ReadStringElement(reader, &child)?;
+                }
+                Some(MkvId::MkvTagLanguage) => {
+                    simple_tag.tag_language_ = ReadStringElement(reader, &child)?;
+                }
+                Some(MkvId::MkvTagDefault) => {
+                    simple_tag.default_ = ReadUIntElement(reader, &child)? != 0;
+                }
+                Some(MkvId::MkvTagBinary) => {
+                    simple_tag.tag_binary_ = ReadBytesElement(reader, &child)?;
+                }
+                Some(MkvId::MkvSimpleTag) => {
+                    simple_tag
+                        .simple_tags_
+                        .push(SimpleTag::Parse(reader, &child)?);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(simple_tag)
+    }
+
+    pub fn tag_name(&self) -> &str {
+        &self.tag_name_
+    }
+    pub fn tag_string(&self) -> &str {
+        &self.tag_string_
+    }
+    pub fn tag_language(&self) -> &str {
+        &self.tag_language_
+    }
+    pub fn default(&self) -> bool {
+        self.default_
+    }
+    pub fn tag_binary(&self) -> &[u8] {
+        &self.tag_binary_
+    }
+
+    // Returns the number of nested SimpleTags.
+    pub fn simple_tag_count(&self) -> usize {
+        self.simple_tags_.len()
+    }
+
+    pub fn GetSimpleTag(&self, index: usize) -> Option<&SimpleTag> {
+        self.simple_tags_.get(index)
+    }
+}
+
+pub struct Tag {
+    targets_: Targets,
+    simple_tags_: Vec<SimpleTag>,
+}
+
+impl Tag {
+    fn Parse(reader: &mut dyn Reader, header: &ElementHeader) -> Result<Tag> {
+        let mut tag = Tag {
+            targets_: Targets::new(),
+            simple_tags_: Vec::new(),
+        };
+
+        let mut children = Elements::new(header);
+        while let Some(child) = children.next(reader)? {
+            match child.mkv_id() {
+                Some(MkvId::MkvTargets) => tag.targets_ = Targets::Parse(reader, &child)?,
+                Some(MkvId::MkvSimpleTag) => {
+                    tag.simple_tags_.push(SimpleTag::Parse(reader, &child)?);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(tag)
+    }
+
+    pub fn targets(&self) -> &Targets {
+        &self.targets_
+    }
+
+    pub fn simple_tag_count(&self) -> usize {
+        self.simple_tags_.len()
+    }
+
+    pub fn GetSimpleTag(&self, index: usize) -> Option<&SimpleTag> {
+        self.simple_tags_.get(index)
+    }
+}
+
+pub struct Tags {
+    tags_: Vec<Tag>,
+
+    // Position of the Tags element id.
+    element_start_: u64,
+    element_size_: u64,
+}
+
+impl Tags {
+    pub fn Parse(reader: &mut dyn Reader, header: &ElementHeader) -> Result<Tags> {
+        let mut tags = Tags {
+            tags_: Vec::new(),
+            element_start_: header.start,
+            element_size_: header.header_size() + header.payload_size()?,
+        };
+
+        let mut children = Elements::new(header);
+        while let Some(child) = children.next(reader)? {
+            if child.is(MkvId::MkvTag) {
+                tags.tags_.push(Tag::Parse(reader, &child)?);
+            }
+        }
+
+        Ok(tags)
+    }
+
+    pub fn Count(&self) -> usize {
+        self.tags_.len()
+    }
+
+    pub fn GetTag(&self, index: usize) -> Option<&Tag> {
+        self.tags_.get(index)
+    }
+
+    pub fn element_start(&self) -> u64 {
+        self.element_start_
+    }
+    pub fn element_size(&self) -> u64 {
+        self.element_size_
+    }
+}
@@ -0,0 +1,227 @@
+use super::content_encoding::{kEncryption, ContentEncoding, ParseContentEncodings};
+use super::element::{ElementHeader, ReadBytesElement, ReadStringElement, ReadUIntElement};
+use super::error::{ParseError, Result};
+use super::reader::Reader;
+use crate::mkvmuxer;
+use crate::mkvmuxer::content_encryptor::kKeySize;
+use crate::mkvmuxer::frame::Frame;
+use crate::MkvId;
+
+pub struct Track {
+    // Track element values.
+    number_: u64,
+    uid_: u64,
+    track_type_: u64,
+    name_: String,
+    // Empty when the element is not present, which means "eng".
+    language_: String,
+    codec_id_: String,
+    codec_private_: Vec<u8>,
+    codec_name_: String,
+    default_duration_: u64,
+    codec_delay_: u64,
+    seek_pre_roll_: u64,
+    max_block_additional_id_: u64,
+    flag_enabled_: bool,
+    flag_default_: bool,
+    flag_forced_: bool,
+    flag_lacing_: bool,
+
+    // ContentEncoding elements, by ascending ContentEncodingOrder.
+    content_encodings_: Vec<ContentEncoding>,
+
+    // Position of the TrackEntry element id.
+    element_start_: u64,
+    element_size_: u64,
+}
+
+impl Track {
+    pub fn new(header: &ElementHeader) -> Result<Track> {
+        Ok(Track {
+            number_: 0,
+            uid_: 0,
+            track_type_: 0,
+            name_: String::new(),
+            language_: String::new(),
+            codec_id_: String::new(),
+            codec_private_: Vec::new(),
+            codec_name_: String::new(),
+            default_duration_: 0,
+            codec_delay_: 0,
+            seek_pre_roll_: 0,
+            max_block_additional_id_: 0,
+            flag_enabled_: true,
+            flag_default_: true,
+            flag_forced_: false,
+            flag_lacing_: true,
+            content_encodings_: Vec::new(),
+            element_start_: header.start,
+            element_size_: header.header_size() + header.payload_size()?,
+        })
+    }
+
+    // Parses |child| if it is one of the elements common to all tracks.
+    // Unknown elements are skipped.
+    pub fn ParseElement(&mut self, reader: &mut dyn Reader, child: &ElementHeader) -> Result<()> {
+        match child.mkv_id() {
+            Some(MkvId::MkvTrackNumber) => {
+                self.number_ = ReadUIntElement(reader, child)?;
+                if self.number_ == 0 {
+                    return Err(ParseError::kFileFormatInvalid);
+                }
+            }
+            Some(MkvId::MkvTrackUID) => self.uid_ = ReadUIntElement(reader, child)?,
+            Some(MkvId::MkvTrackType) => self.track_type_ = ReadUIntElement(reader, child)?,
+            Some(MkvId::MkvName) => self.name_ = ReadStringElement(reader, child)?,
+            Some(MkvId::MkvLanguage) => self.language_ = ReadStringElement(reader, child)?,
+            Some(MkvId::MkvCodecID) => self.codec_id_ = ReadStringElement(reader, child)?,
+            Some(MkvId::MkvCodecPrivate) => {
+                self.codec_private_ = ReadBytesElement(reader, child)?;
+            }
+            Some(MkvId::MkvCodecName) => self.codec_name_ = ReadStringElement(reader, child)?,
+            Some(MkvId::MkvDefaultDuration) => {
+                self.default_duration_ = ReadUIntElement(reader, child)?;
+            }
+            Some(MkvId::MkvCodecDelay) => self.codec_delay_ = ReadUIntElement(reader, child)?,
+            Some(MkvId::MkvSeekPreRoll) => self.seek_pre_roll_ = ReadUIntElement(reader, child)?,
+            Some(MkvId::MkvMaxBlockAdditionID) => {
+                self.max_block_additional_id_ = ReadUIntElement(reader, child)?;
+            }
+            Some(MkvId::MkvFlagEnabled) => {
+                self.flag_enabled_ = ReadUIntElement(reader, child)? != 0
+            }
+            Some(MkvId::MkvFlagDefault) => {
+                self.flag_default_ = ReadUIntElement(reader, child)? != 0
+            }
+            Some(MkvId::MkvFlagForced) => self.flag_forced_ = ReadUIntElement(reader, child)? != 0,
+            Some(MkvId::MkvFlagLacing) => self.flag_lacing_ = ReadUIntElement(reader, child)? != 0,
+            Some(MkvId::MkvContentEncodings) => {
+                self.content_encodings_ = ParseContentEncodings(reader, child)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub fn number(&self) -> u64 {
+        self.number_
+    }
+    pub fn uid(&self) -> u64 {
+        self.uid_
+    }
+    pub fn track_type(&self) -> u64 {
+        self.track_type_
+    }
+    pub fn name(&self) -> &str {
+        &self.name_
+    }
+    pub fn language(&self) -> &str {
+        &self.language_
+    }
+    pub fn codec_id(&self) -> &str {
+        &self.codec_id_
+    }
+    pub fn codec_private(&self) -> &[u8] {
+        &self.codec_private_
+    }
+    // Returns the CodecPrivate with the encodings that apply to it reversed.
+    pub fn DecodeCodecPrivate(&self) -> Result<Vec<u8>> {
+        let mut codec_private = self.codec_private_.clone();
+        if codec_private.is_empty() {
+            return Ok(codec_private);
+        }
+        for encoding in self.content_encodings_.iter().rev() {
+            if encoding.AppliesToCodecPrivate() {
+                codec_private = encoding.DecodeFrame(&codec_private, None)?;
+            }
+        }
+        Ok(codec_private)
+    }
+    pub fn codec_name(&self) -> &str {
+        &self.codec_name_
+    }
+    pub fn default_duration(&self) -> u64 {
+        self.default_duration_
+    }
+    pub fn codec_delay(&self) -> u64 {
+        self.codec_delay_
+    }
+    pub fn seek_pre_roll(&self) -> u64 {
+        self.seek_pre_roll_
+    }
+    pub fn max_block_additional_id(&self) -> u64 {
+        self.max_block_additional_id_
+    }
+    pub fn flag_enabled(&self) -> bool {
+        self.flag_enabled_
+    }
+    pub fn flag_default(&self) -> bool {
+        self.flag_default_
+    }
+    pub fn flag_forced(&self) -> bool {
+        self.flag_forced_
+    }
+    pub fn flag_lacing(&self) -> bool {
+        self.flag_lacing_
+    }
+    pub fn content_encoding_count(&self) -> usize {
+        self.content_encodings_.len()
+    }
+    pub fn GetContentEncoding(&self, index: usize) -> Option<&ContentEncoding> {
+        self.content_encodings_.get(index)
+    }
+    pub fn element_start(&self) -> u64 {
+        self.element_start_
+    }
+    pub fn element_size(&self) -> u64 {
+        self.element_size_
+    }
+
+    // Reverses the content encodings of the track on the frames of |frame|,
+    // starting with the highest ContentEncodingOrder. Encrypted frames are
+    // left encrypted, along with the encodings below the encryption; see
+    // DecryptFrame().
+    pub fn DecodeFrame(&self, frame: &mut Frame) -> Result<()> {
+        self.DoDecodeFrame(frame, None)
+    }
+
+    // Same as DecodeFrame(), also decrypting the frames with the AES-128
+    // |key|.
+    pub fn DecryptFrame(&self, frame: &mut Frame, key: &[u8; kKeySize]) -> Result<()> {
+        self.DoDecodeFrame(frame, Some(key))
+    }
+
+    fn DoDecodeFrame(&self, frame: &mut Frame, key: Option<&[u8; kKeySize]>) -> Result<()> {
+        for encoding in self.content_encodings_.iter().rev() {
+            if !encoding.AppliesToFrames() {
+                continue;
+            }
+            // The lower encodings apply to the decrypted data.
+            if key.is_none() && encoding.encoding_type() == kEncryption {
+                return Ok(());
+            }
+            if !frame.TransformFrames(|data| encoding.DecodeFrame(data, key))? {
+                return Err(ParseError::kFileFormatInvalid);
+            }
+        }
+        Ok(())
+    }
+
+    // Copies the track configuration to a muxer track. The track number and
+    // type are left alone since the muxer assigns them when the track is
+    // added. The content encodings are not copied, so the CodecPrivate is
+    // copied decoded, and frames must be decoded with DecodeFrame() before
+    // they are added to |track|.
+    pub fn CopyTo(&self, track: &mut mkvmuxer::track::Track) -> Result<()> {
+        track.set_uid(self.uid_);
+        track.set_name(&self.name_);
+        track.set_language(&self.language_);
+        track.set_codec_id(&self.codec_id_);
+        track.set_codec_private(&self.DecodeCodecPrivate()?);
+        track.set_default_duration(self.default_duration_);
+        track.set_codec_delay(self.codec_delay_);
+        track.set_seek_pre_roll(self.seek_pre_roll_);
+        track.set_max_block_additional_id(self.max_block_additional_id_);
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+use super::audio_track::AudioTrack;
+use super::element::{ElementHeader, Elements};
+use super::error::{ParseError, Result};
+use super::reader::Reader;
+use super::track::Track;
+use super::video_track::VideoTrack;
+use crate::mkvmuxer::tracks::TrackType;
+use crate::MkvId;
+
+use std::ops::Deref;
+
+// A parsed TrackEntry. Video and audio tracks carry the contents of their
+// settings element.
+#[allow(clippy::large_enum_variant)]
+pub enum TrackEntry {
+    Generic(Track),
+    Video(VideoTrack),
+    Audio(AudioTrack),
+}
+
+impl Deref for TrackEntry {
+    type Target = Track;
+
+    fn deref(&self) -> &Track {
+        match self {
+            TrackEntry::Generic(t) => t,
+            TrackEntry::Video(t) => t,
+            TrackEntry::Audio(t) => t,
+        }
+    }
+}
+
+impl TrackEntry {
+    pub fn Parse(reader: &mut dyn Reader, header: &ElementHeader) -> Result<TrackEntry> {
+        let mut track = Track::new(header)?;
+        let mut video = None;
+        let mut audio = None;
+
+        let mut children = Elements::new(header);
+        while let Some(child) = children.next(reader)? {
+            match child.mkv_id() {
+                Some(MkvId::MkvVideo) => video = Some(child),
+                Some(MkvId::MkvAudio) => audio = Some(child),
+                _ => track.ParseElement(reader, &child)?,
+            }
+        }
+
+        if track.number() == 0 {
+            return Err(ParseError::kFileFormatInvalid);
+        }
+
+        if track.track_type() == TrackType::kVideo as u64 {
+            let video = video.ok_or(ParseError::kFileFormatInvalid)?;
+            Ok(TrackEntry::Video(VideoTrack::Parse(reader, track, &video)?))
+        } else if track.track_type() == TrackType::kAudio as u64 {
+            let audio = audio.ok_or(ParseError::kFileFormatInvalid)?;
+            Ok(TrackEntry::Audio(AudioTrack::Parse(reader, track, &audio)?))
+        } else {
+            Ok(TrackEntry::Generic(track))
+        }
+    }
+
+    pub fn as_video(&self) -> Option<&VideoTrack> {
+        match self {
+            TrackEntry::Video(t) => Some(t),
+            _ => None,
+        }
+    }
+
+    pub fn as_audio(&self) -> Option<&AudioTrack> {
+        match self {
+            TrackEntry::Audio(t) => Some(t),
+            _ => None,
+        }
+    }
+}
+
+pub struct Tracks {
+    track_entries_: Vec<TrackEntry>,
+
+    // Position of the Tracks element id.
+    element_start_: u64,
+    element_size_: u64,
+}
+
+impl Tracks {
+    pub fn Parse(reader: &mut dyn Reader, header: &ElementHeader) -> Result<Tracks> {
+        let mut tracks = Tracks {
+            track_entries_: Vec::new(),
+            element_start_: header.start,
+            element_size_: header.header_size() + header.payload_size()?,
+        };
+
+        let mut children = Elements::new(header);
+        while let Some(child) = children.next(reader)? {
+            if !child.is(MkvId::MkvTrackEntry) {
+                continue;
+            }
+            let entry = TrackEntry::Parse(reader, &child)?;
+            if tracks.GetTrackByNumber(entry.number()).is_some() {
+                return Err(ParseError::kFileFormatInvalid);
+            }
+            tracks.track_entries_.push(entry);
+        }
+
+        Ok(tracks)
+    }
+
+    pub fn track_entries_size(&self) -> usize {
+        self.track_entries_.len()
+    }
+
+    pub fn GetTrackByIndex(&self, index: usize) -> Option<&TrackEntry> {
+        self.track_entries_.get(index)
+    }
+
+    pub fn GetTrackByNumber(&self, track_number: u64) -> Option<&TrackEntry> {
+        self.track_entries_
+            .iter()
+            .find(|entry| entry.number() == track_number)
+    }
+
+    pub fn element_start(&self) -> u64 {
+        self.element_start_
+    }
+    pub fn element_size(&self) -> u64 {
+        self.element_size_
+    }
+}
@@ -0,0 +1,139 @@
+use super::error::{ParseError, Result};
+use super::reader::Reader;
+
+// Largest id length, in bytes, supported by the parser.
+pub const MAX_ID_LENGTH: usize = 4;
+// Largest coded integer length, in bytes, supported by the parser.
+pub const MAX_UINT_LENGTH: usize = 8;
+
+// Checks that |len| bytes starting at |pos| can be read from |reader|.
+pub fn CheckAvailable(reader: &dyn Reader, pos: u64, len: u64) -> Result<()> {
+    let end = pos.checked_add(len).ok_or(ParseError::kFileFormatInvalid)?;
+    if let Some(total) = reader.length() {
+        if end > total {
+            return Err(ParseError::kFileFormatInvalid);
+        }
+    }
+    if end > reader.available() {
+        return Err(ParseError::kBufferNotFull);
+    }
+    Ok(())
+}
+
+fn ReadByte(reader: &mut dyn Reader, pos: u64) -> Result<u8> {
+    CheckAvailable(reader, pos, 1)?;
+    let mut b = [0u8; 1];
+    reader.read(pos, &mut b)?;
+    Ok(b[0])
+}
+
+// Returns the length in bytes of the coded integer starting at |pos|.
+pub fn GetUIntLength(reader: &mut dyn Reader, pos: u64) -> Result<usize> {
+    let b = ReadByte(reader, pos)?;
+    if b == 0 {
+        // We can't handle coded integers larger than 8 bytes.
+        return Err(ParseError::kFileFormatInvalid);
+    }
+    Ok(b.leading_zeros() as usize + 1)
+}
+
+// Reads the coded integer at |pos| with its length marker removed. Returns
+// the value and its length in bytes.
+pub fn ReadUInt(reader: &mut dyn Reader, pos: u64) -> Result<(u64, usize)> {
+    let len = GetUIntLength(reader, pos)?;
+    CheckAvailable(reader, pos, len as u64)?;
+    let mut buf = [0u8; MAX_UINT_LENGTH];
+    reader.read(pos, &mut buf[..len])?;
+
+    let mut value = (buf[0] as u64) & ((0x80u64 >> (len - 1)) - 1);
+    for b in &buf[1..len] {
+        value = (value << 8) | *b as u64;
+    }
+    Ok((value, len))
+}
+
+// Reads the element id at |pos|. Unlike ReadUInt() the length marker is kept
+// so the result can be compared against MkvId values.
+pub fn ReadID(reader: &mut dyn Reader, pos: u64) -> Result<(u64, usize)> {
+    let len = GetUIntLength(reader, pos)?;
+    if len > MAX_ID_LENGTH {
+        return Err(ParseError::kFileFormatInvalid);
+    }
+    CheckAvailable(reader, pos, len as u64)?;
+    let mut buf = [0u8; MAX_ID_LENGTH];
+    reader.read(pos, &mut buf[..len])?;
+
+    let mut id: u64 = 0;
+    for b in &buf[..len] {
+        id = (id << 8) | *b as u64;
+    }
+
+    // An id with all value bits set is reserved.
+    let all_ones = (1u64 << (7 * len)) - 1;
+    if id & all_ones == all_ones {
+        return Err(ParseError::kFileFormatInvalid);
+    }
+    Ok((id, len))
+}
+
+// Returns true if |value| read by ReadUInt() with |len| bytes is the reserved
+// "unknown size" value.
+pub fn IsUnknownSize(value: u64, len: usize) -> bool {
+    value == (1u64 << (7 * len)) - 1
+}
+
+pub fn UnserializeUInt(reader: &mut dyn Reader, pos: u64, size: u64) -> Result<u64> {
+    if size > 8 {
+        return Err(ParseError::kFileFormatInvalid);
+    }
+    let buf = UnserializeBytes(reader, pos, size)?;
+    let mut value: u64 = 0;
+    for b in &buf {
+        value = (value << 8) | *b as u64;
+    }
+    Ok(value)
+}
+
+pub fn UnserializeInt(reader: &mut dyn Reader, pos: u64, size: u64) -> Result<i64> {
+    if size > 8 {
+        return Err(ParseError::kFileFormatInvalid);
+    }
+    if size == 0 {
+        return Ok(0);
+    }
+    let value = UnserializeUInt(reader, pos, size)?;
+    // Sign extend from the top bit of the stored value.
+    let shift = 64 - 8 * size as u32;
+    Ok(((value << shift) as i64) >> shift)
+}
+
+pub fn UnserializeFloat(reader: &mut dyn Reader, pos: u64, size: u64) -> Result<f64> {
+    match size {
+        0 => Ok(0.0),
+        4 => {
+            let bits = UnserializeUInt(reader, pos, size)? as u32;
+            Ok(f32::from_bits(bits) as f64)
+        }
+        8 => {
+            let bits = UnserializeUInt(reader, pos, size)?;
+            Ok(f64::from_bits(bits))
+        }
+        _ => Err(ParseError::kFileFormatInvalid),
+    }
+}
+
+// Strings may be zero padded; the value ends at the first null byte.
+pub fn UnserializeString(reader: &mut dyn Reader, pos: u64, size: u64) -> Result<String> {
+    let mut buf = UnserializeBytes(reader, pos, size)?;
+    if let Some(end) = buf.iter().position(|b| *b == 0) {
+        buf.truncate(end);
+    }
+    String::from_utf8(buf).map_err(|_| ParseError::kFileFormatInvalid)
+}
+
+pub fn UnserializeBytes(reader: &mut dyn Reader, pos: u64, size: u64) -> Result<Vec<u8>> {
+    CheckAvailable(reader, pos, size)?;
+    let mut buf = vec![0u8; size as usize];
+    reader.read(pos, &mut buf)?;
+    Ok(buf)
+}
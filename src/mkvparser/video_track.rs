@@ -0,0 +1,343 @@
+use super::element::{
+    ElementHeader, Elements, ReadBytesElement, ReadFloatElement, ReadStringElement, ReadUIntElement,
+};
+use super::error::{ParseError, Result};
+use super::reader::Reader;
+use super::track::Track;
+use crate::mkvmuxer;
+use crate::mkvmuxer::colour::{Colour, MasteringMetadata, PrimaryChromaticity};
+use crate::mkvmuxer::projection::{Projection, ProjectionType};
+use crate::MkvId;
+
+use std::ops::{Deref, DerefMut};
+
+pub struct VideoTrack {
+    track_: Track,
+
+    // Video element values.
+    width_: u64,
+    height_: u64,
+    display_width_: u64,
+    display_height_: u64,
+    display_unit_: u64,
+    crop_left_: u64,
+    crop_right_: u64,
+    crop_top_: u64,
+    crop_bottom_: u64,
+    frame_rate_: f64,
+    stereo_mode_: u64,
+    alpha_mode_: u64,
+    colour_space_: String,
+
+    colour_: Option<Colour>,
+    projection_: Option<Projection>,
+}
+
+impl Deref for VideoTrack {
+    type Target = Track;
+
+    fn deref(&self) -> &Track {
+        &self.track_
+    }
+}
+
+impl DerefMut for VideoTrack {
+    fn deref_mut(&mut self) -> &mut Track {
+        &mut self.track_
+    }
+}
+
+impl VideoTrack {
+    // Parses the Video element |header| of |track|.
+    pub fn Parse(
+        reader: &mut dyn Reader,
+        track: Track,
+        header: &ElementHeader,
+    ) -> Result<VideoTrack> {
+        let mut video = VideoTrack {
+            track_: track,
+            width_: 0,
+            height_: 0,
+            display_width_: 0,
+            display_height_: 0,
+            display_unit_: 0,
+            crop_left_: 0,
+            crop_right_: 0,
+            crop_top_: 0,
+            crop_bottom_: 0,
+            frame_rate_: 0.0,
+            stereo_mode_: 0,
+            alpha_mode_: 0,
+            colour_space_: String::new(),
+            colour_: None,
+            projection_: None,
+        };
+
+        let mut children = Elements::new(header);
+        while let Some(child) = children.next(reader)? {
+            match child.mkv_id() {
+                Some(MkvId::MkvPixelWidth) => video.width_ = ReadUIntElement(reader, &child)?,
+                Some(MkvId::MkvPixelHeight) => video.height_ = ReadUIntElement(reader, &child)?,
+                Some(MkvId::MkvDisplayWidth) => {
+                    video.display_width_ = ReadUIntElement(reader, &child)?;
+                }
+                Some(MkvId::MkvDisplayHeight) => {
+                    video.display_height_ = ReadUIntElement(reader, &child)?;
+                }
+                Some(MkvId::MkvDisplayUnit) => {
+                    video.display_unit_ = ReadUIntElement(reader, &child)?;
+                }
+                Some(MkvId::MkvPixelCropLeft) => {
+                    video.crop_left_ = ReadUIntElement(reader, &child)?;
+                }
+                Some(MkvId::MkvPixelCropRight) => {
+                    video.crop_right_ = ReadUIntElement(reader, &child)?;
+                }
+                Some(MkvId::MkvPixelCropTop) => video.crop_top_ = ReadUIntElement(reader, &child)?,
+                Some(MkvId::MkvPixelCropBottom) => {
+                    video.crop_bottom_ = ReadUIntElement(reader, &child)?;
+                }
+                Some(MkvId::MkvFrameRate) => {
+                    video.frame_rate_ = ReadFloatElement(reader, &child)?;
+                    if video.frame_rate_.is_nan() || video.frame_rate_ < 0.0 {
+                        return Err(ParseError::kFileFormatInvalid);
+                    }
+                }
+                Some(MkvId::MkvStereoMode) => {
+                    video.stereo_mode_ = ReadUIntElement(reader, &child)?;
+                }
+                Some(MkvId::MkvAlphaMode) => video.alpha_mode_ = ReadUIntElement(reader, &child)?,
+                Some(MkvId::MkvColourSpace) => {
+                    video.colour_space_ = ReadStringElement(reader, &child)?;
+                }
+                Some(MkvId::MkvColour) => video.colour_ = Some(ParseColour(reader, &child)?),
+                Some(MkvId::MkvProjection) => {
+                    video.projection_ = Some(ParseProjection(reader, &child)?);
+                }
+                _ => {}
+            }
+        }
+
+        if video.width_ == 0 || video.height_ == 0 {
+            return Err(ParseError::kFileFormatInvalid);
+        }
+
+        Ok(video)
+    }
+
+    pub fn width(&self) -> u64 {
+        self.width_
+    }
+    pub fn height(&self) -> u64 {
+        self.height_
+    }
+    pub fn display_width(&self) -> u64 {
+        self.display_width_
+    }
+    pub fn display_height(&self) -> u64 {
+        self.display_height_
+    }
+    pub fn display_unit(&self) -> u64 {
+        self.display_unit_
+    }
+    pub fn crop_left(&self) -> u64 {
+        self.crop_left_
+    }
+    pub fn crop_right(&self) -> u64 {
+        self.crop_right_
+    }
+    pub fn crop_top(&self) -> u64 {
+        self.crop_top_
+    }
+    pub fn crop_bottom(&self) -> u64 {
+        self.crop_bottom_
+    }
+    pub fn frame_rate(&self) -> f64 {
+        self.frame_rate_
+    }
+    pub fn stereo_mode(&self) -> u64 {
+        self.stereo_mode_
+    }
+    pub fn alpha_mode(&self) -> u64 {
+        self.alpha_mode_
+    }
+    pub fn colour_space(&self) -> &str {
+        &self.colour_space_
+    }
+    pub fn colour(&self) -> Option<&Colour> {
+        self.colour_.as_ref()
+    }
+    pub fn projection(&self) -> Option<&Projection> {
+        self.projection_.as_ref()
+    }
+
+    // Copies the track and video configuration to a muxer video track.
+    // Returns false if the muxer rejects the stereo or alpha mode.
+    pub fn CopyTo(&self, track: &mut mkvmuxer::video_track::VideoTrack) -> Result<bool> {
+        self.track_.CopyTo(track)?;
+        track.set_width(self.width_);
+        track.set_height(self.height_);
+        track.set_display_width(self.display_width_);
+        track.set_display_height(self.display_height_);
+        track.set_crop_left(self.crop_left_);
+        track.set_crop_right(self.crop_right_);
+        track.set_crop_top(self.crop_top_);
+        track.set_crop_bottom(self.crop_bottom_);
+        track.set_frame_rate(self.frame_rate_);
+        track.set_colour_space(&self.colour_space_);
+        if let Some(colour) = self.colour_.as_ref() {
+            track.SetColour(colour);
+        }
+        if let Some(projection) = self.projection_.as_ref() {
+            track.SetProjection(projection);
+        }
+        Ok(track.SetStereoMode(self.stereo_mode_) && track.SetAlphaMode(self.alpha_mode_))
+    }
+}
+
+fn ParseChromaticityValue(reader: &mut dyn Reader, header: &ElementHeader) -> Result<f32> {
+    let value = ReadFloatElement(reader, header)?;
+    if !(0.0..=f32::MAX as f64).contains(&value) {
+        return Err(ParseError::kFileFormatInvalid);
+    }
+    Ok(value as f32)
+}
+
+// The muxer only stores complete sets of primaries, so chromaticities are
+// kept when all four of them are present.
+fn ParseMasteringMetadata(
+    reader: &mut dyn Reader,
+    header: &ElementHeader,
+) -> Result<MasteringMetadata> {
+    let mut mastering_metadata = MasteringMetadata::new();
+    // R, G, B and white point.
+    let mut primaries: [Option<PrimaryChromaticity>; 4] = [None; 4];
+
+    let mut children = Elements::new(header);
+    while let Some(child) = children.next(reader)? {
+        let (index, is_x) = match child.mkv_id() {
+            Some(MkvId::MkvLuminanceMax) => {
+                let value = ReadFloatElement(reader, &child)?;
+                if !(0.0..=f32::MAX as f64).contains(&value) {
+                    return Err(ParseError::kFileFormatInvalid);
+                }
+                mastering_metadata.set_luminance_max(value as f32);
+                continue;
+            }
+            Some(MkvId::MkvLuminanceMin) => {
+                let value = ReadFloatElement(reader, &child)?;
+                if !(0.0..=f32::MAX as f64).contains(&value) {
+                    return Err(ParseError::kFileFormatInvalid);
+                }
+                mastering_metadata.set_luminance_min(value as f32);
+                continue;
+            }
+            Some(MkvId::MkvPrimaryRChromaticityX) => (0, true),
+            Some(MkvId::MkvPrimaryRChromaticityY) => (0, false),
+            Some(MkvId::MkvPrimaryGChromaticityX) => (1, true),
+            Some(MkvId::MkvPrimaryGChromaticityY) => (1, false),
+            Some(MkvId::MkvPrimaryBChromaticityX) => (2, true),
+            Some(MkvId::MkvPrimaryBChromaticityY) => (2, false),
+            Some(MkvId::MkvWhitePointChromaticityX) => (3, true),
+            Some(MkvId::MkvWhitePointChromaticityY) => (3, false),
+            _ => continue,
+        };
+        let value = ParseChromaticityValue(reader, &child)?;
+        let chromaticity = primaries[index].get_or_insert_with(PrimaryChromaticity::new);
+        if is_x {
+            chromaticity.set_x(value);
+        } else {
+            chromaticity.set_y(value);
+        }
+    }
+
+    if let [Some(r), Some(g), Some(b), Some(white_point)] = primaries {
+        mastering_metadata.SetChromaticity(&r, &g, &b, &white_point);
+    }
+    Ok(mastering_metadata)
+}
+
+pub fn ParseColour(reader: &mut dyn Reader, header: &ElementHeader) -> Result<Colour> {
+    let mut colour = Colour::new();
+
+    let mut children = Elements::new(header);
+    while let Some(child) = children.next(reader)? {
+        match child.mkv_id() {
+            Some(MkvId::MkvMatrixCoefficients) => {
+                colour.set_matrix_coefficients(ReadUIntElement(reader, &child)?);
+            }
+            Some(MkvId::MkvBitsPerChannel) => {
+                colour.set_bits_per_channel(ReadUIntElement(reader, &child)?);
+            }
+            Some(MkvId::MkvChromaSubsamplingHorz) => {
+                colour.set_chroma_subsampling_horz(ReadUIntElement(reader, &child)?);
+            }
+            Some(MkvId::MkvChromaSubsamplingVert) => {
+                colour.set_chroma_subsampling_vert(ReadUIntElement(reader, &child)?);
+            }
+            Some(MkvId::MkvCbSubsamplingHorz) => {
+                colour.set_cb_subsampling_horz(ReadUIntElement(reader, &child)?);
+            }
+            Some(MkvId::MkvCbSubsamplingVert) => {
+                colour.set_cb_subsampling_vert(ReadUIntElement(reader, &child)?);
+            }
+            Some(MkvId::MkvChromaSitingHorz) => {
+                colour.set_chroma_siting_horz(ReadUIntElement(reader, &child)?);
+            }
+            Some(MkvId::MkvChromaSitingVert) => {
+                colour.set_chroma_siting_vert(ReadUIntElement(reader, &child)?);
+            }
+            Some(MkvId::MkvRange) => colour.set_range(ReadUIntElement(reader, &child)?),
+            Some(MkvId::MkvTransferCharacteristics) => {
+                colour.set_transfer_characteristics(ReadUIntElement(reader, &child)?);
+            }
+            Some(MkvId::MkvPrimaries) => colour.set_primaries(ReadUIntElement(reader, &child)?),
+            Some(MkvId::MkvMaxCLL) => colour.set_max_cll(ReadUIntElement(reader, &child)?),
+            Some(MkvId::MkvMaxFALL) => colour.set_max_fall(ReadUIntElement(reader, &child)?),
+            Some(MkvId::MkvMasteringMetadata) => {
+                colour.set_mastering_metadata(&ParseMasteringMetadata(reader, &child)?);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(colour)
+}
+
+fn ParsePose(reader: &mut dyn Reader, header: &ElementHeader, limit: f64) -> Result<f32> {
+    let value = ReadFloatElement(reader, header)?;
+    if !(-limit..=limit).contains(&value) {
+        return Err(ParseError::kFileFormatInvalid);
+    }
+    Ok(value as f32)
+}
+
+pub fn ParseProjection(reader: &mut dyn Reader, header: &ElementHeader) -> Result<Projection> {
+    let mut projection = Projection::new();
+
+    let mut children = Elements::new(header);
+    while let Some(child) = children.next(reader)? {
+        match child.mkv_id() {
+            Some(MkvId::MkvProjectionType) => {
+                let t = ProjectionType::from_u64(ReadUIntElement(reader, &child)?)
+                    .ok_or(ParseError::kFileFormatInvalid)?;
+                projection.set_type(t);
+            }
+            Some(MkvId::MkvProjectionPrivate) => {
+                projection.set_private_data(&ReadBytesElement(reader, &child)?);
+            }
+            Some(MkvId::MkvProjectionPoseYaw) => {
+                projection.set_pose_yaw(ParsePose(reader, &child, 180.0)?);
+            }
+            Some(MkvId::MkvProjectionPosePitch) => {
+                projection.set_pose_pitch(ParsePose(reader, &child, 90.0)?);
+            }
+            Some(MkvId::MkvProjectionPoseRoll) => {
+                projection.set_pose_roll(ParsePose(reader, &child, 180.0)?);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(projection)
+}
@@ -1,11 +1,789 @@
+#![allow(non_snake_case)]
+
 extern crate libwebm;
 
+use libwebm::mkvmuxer::cluster::Cluster;
+use libwebm::mkvmuxer::content_encoding::{
+    kCompression, kEncryption, kScopeCodecPrivate, kScopeFrames, kScopeNextEncoding,
+    ContentCompAlgo, ContentEncoding,
+};
+use libwebm::mkvmuxer::content_encryptor::{kEncryptedFrame, kPartitionedFrame, ContentEncryptor};
+use libwebm::mkvmuxer::error::MuxerError;
+use libwebm::mkvmuxer::frame::{Frame, Lacing};
+use libwebm::mkvmuxer::opus::OpusConfig;
+use libwebm::mkvmuxer::segment::CuesPosition;
+use libwebm::mkvmuxer::segment::Segment;
+use libwebm::mkvmuxer::util::Crc32Update;
+use libwebm::mkvmuxer::writer::{Crc32Writer, IoWriter, MemoryWriter, MkvWriter, Writer};
+use libwebm::mkvparser::reader::MkvReader;
 use std::fs::File;
-use libwebm::mkvmuxer::writer::MkvWriter;
+use std::io::Cursor;
 
 mod util;
+use util::*;
+
+#[test]
+fn segment_info() {
+    let test = MuxerTest::new();
+    let mut writer = test.writer();
+    {
+        let mut segment = Segment::new();
+        assert!(SegmentInit(&mut segment, &mut writer, false, false, false));
+        let info = segment.GetSegmentInfo();
+        info.set_timecode_scale(kTimeCodeScale as u64);
+        info.set_duration(kDuration);
+        AddVideoTrack(&mut segment);
+
+        segment
+            .AddFrame(&test.dummy_data_, kVideoTrackNumber as u64, 0, false)
+            .unwrap();
+        segment.Finalize().unwrap();
+    }
+
+    test.CompareWith(&writer, "segment_info.webm").unwrap();
+}
+
+#[test]
+fn simple_block() {
+    let test = MuxerTest::new();
+    let mut writer = test.writer();
+    {
+        let mut segment = Segment::new();
+        assert!(SegmentInit(&mut segment, &mut writer, false, false, false));
+        AddVideoTrack(&mut segment);
+
+        segment
+            .AddFrame(&test.dummy_data_, kVideoTrackNumber as u64, 0, false)
+            .unwrap();
+        segment
+            .AddFrame(&test.dummy_data_, kVideoTrackNumber as u64, 2000000, false)
+            .unwrap();
+        segment.Finalize().unwrap();
+    }
+
+    test.CompareWith(&writer, "simple_block.webm").unwrap();
+}
+
+#[test]
+fn force_new_cluster() {
+    let test = MuxerTest::new();
+    let mut writer = test.writer();
+    {
+        let mut segment = Segment::new();
+        assert!(SegmentInit(&mut segment, &mut writer, false, false, false));
+        AddVideoTrack(&mut segment);
+
+        let track = kVideoTrackNumber as u64;
+        segment
+            .AddFrame(&test.dummy_data_, track, 0, false)
+            .unwrap();
+        segment.ForceNewClusterOnNextFrame();
+        segment
+            .AddFrame(&test.dummy_data_, track, 2000000, false)
+            .unwrap();
+        segment
+            .AddFrame(&test.dummy_data_, track, 4000000, false)
+            .unwrap();
+        segment.ForceNewClusterOnNextFrame();
+        segment
+            .AddFrame(&test.dummy_data_, track, 6000000, false)
+            .unwrap();
+        segment.Finalize().unwrap();
+    }
+
+    test.CompareWith(&writer, "force_new_cluster.webm").unwrap();
+}
+
+#[test]
+fn accurate_cluster_duration() {
+    // Every Cluster holds a lone non-key frame of each track, with no earlier
+    // block of the track to reference.
+    let test = MuxerTest::new();
+    let mut writer = test.writer();
+    {
+        let mut segment = Segment::new();
+        assert!(SegmentInit(&mut segment, &mut writer, false, true, false));
+        AddVideoTrack(&mut segment);
+        AddAudioTrack(&mut segment);
+
+        for i in 0..3 {
+            if i > 0 {
+                segment.ForceNewClusterOnNextFrame();
+            }
+            for &track in &[kVideoTrackNumber as u64, kAudioTrackNumber as u64] {
+                segment
+                    .AddFrame(&test.dummy_data_, track, i * 2000000, false)
+                    .unwrap();
+            }
+        }
+        segment.Finalize().unwrap();
+    }
+    assert!(!writer.data().is_empty());
+}
+
+#[test]
+fn output_cues() {
+    let test = MuxerTest::new();
+    let mut writer = test.writer();
+    {
+        let mut segment = Segment::new();
+        assert!(SegmentInit(&mut segment, &mut writer, true, false, false));
+        AddVideoTrack(&mut segment);
+
+        let track = kVideoTrackNumber as u64;
+        segment.AddFrame(&test.dummy_data_, track, 0, true).unwrap();
+        segment
+            .AddFrame(&test.dummy_data_, track, 2000000, false)
+            .unwrap();
+        segment
+            .AddFrame(&test.dummy_data_, track, 4000000, false)
+            .unwrap();
+        segment.ForceNewClusterOnNextFrame();
+        segment
+            .AddFrame(&test.dummy_data_, track, 6000000, true)
+            .unwrap();
+        segment.AddCuePoint(4000000, track).unwrap();
+        segment.Finalize().unwrap();
+    }
+
+    test.CompareWith(&writer, "output_cues.webm").unwrap();
+}
+
+#[test]
+fn block_with_additional() {
+    let test = MuxerTest::new();
+    let mut writer = test.writer();
+    {
+        let mut segment = Segment::new();
+        assert!(SegmentInit(&mut segment, &mut writer, false, false, false));
+        AddVideoTrack(&mut segment);
+
+        let track = kVideoTrackNumber as u64;
+        let data = &test.dummy_data_;
+        segment
+            .AddFrameWithAdditional(data, data, 1, track, 0, true)
+            .unwrap();
+        segment
+            .AddFrameWithAdditional(data, data, 1, track, 2000000, false)
+            .unwrap();
+        segment.Finalize().unwrap();
+    }
+
+    test.CompareWith(&writer, "block_with_additional.webm")
+        .unwrap();
+}
+
+#[test]
+fn discard_padding() {
+    let test = MuxerTest::new();
+    let mut writer = test.writer();
+    {
+        let mut segment = Segment::new();
+        assert!(SegmentInit(&mut segment, &mut writer, false, false, false));
+        AddAudioTrack(&mut segment);
+        let audio = segment.GetTrackByNumber(kAudioTrackNumber as u64).unwrap();
+        audio.set_codec_id(kOpusCodecId);
+
+        let track = kAudioTrackNumber as u64;
+        let data = &test.dummy_data_;
+        segment
+            .AddFrameWithDiscardPadding(data, 12810000, track, 0, true)
+            .unwrap();
+        segment
+            .AddFrameWithDiscardPadding(data, 127, track, 1500, true)
+            .unwrap();
+        segment
+            .AddFrameWithDiscardPadding(data, -128, track, 3000, true)
+            .unwrap();
+        segment.Finalize().unwrap();
+    }
+
+    test.CompareWith(&writer, "discard_padding.webm").unwrap();
+}
+
+#[test]
+fn metadata_block() {
+    let test = MuxerTest::new();
+    let mut writer = test.writer();
+    {
+        let mut segment = Segment::new();
+        assert!(SegmentInit(&mut segment, &mut writer, false, false, false));
+        let metadata = segment.AddTrack(kMetadataTrackNumber).unwrap();
+        metadata.set_track_type(kMetadataTrackType as u64);
+        metadata.set_codec_id(kMetadataCodecId);
+        metadata.set_uid(kMetadataTrackNumber as u64);
+
+        let track = kMetadataTrackNumber as u64;
+        let data = &test.dummy_data_;
+        segment.AddMetadata(data, track, 0, 2000000).unwrap();
+        segment.AddMetadata(data, track, 2000000, 6000000).unwrap();
+        segment.Finalize().unwrap();
+    }
+
+    test.CompareWith(&writer, "metadata_block.webm").unwrap();
+}
+
+#[test]
+fn io_writer() {
+    let mut writer = IoWriter::new(Cursor::new(Vec::new())).unwrap();
+    {
+        let mut segment = Segment::new();
+        assert!(SegmentInit(&mut segment, &mut writer, true, false, false));
+        AddVideoTrack(&mut segment);
+
+        let track = kVideoTrackNumber as u64;
+        let dummy_data = vec![0; kFrameLength as usize];
+        segment.AddFrame(&dummy_data, track, 0, true).unwrap();
+        segment
+            .AddFrame(&dummy_data, track, 2000000, false)
+            .unwrap();
+        segment
+            .AddFrame(&dummy_data, track, 4000000, false)
+            .unwrap();
+        segment.ForceNewClusterOnNextFrame();
+        segment.AddFrame(&dummy_data, track, 6000000, true).unwrap();
+        segment.AddCuePoint(4000000, track).unwrap();
+        segment.Finalize().unwrap();
+    }
+    let size = writer.get_position();
+    let data = writer.into_inner().into_inner();
+    assert_eq!(size, data.len() as u64);
+
+    let expected = std::fs::read(GetTestFilePath("output_cues.webm")).unwrap();
+    assert!(expected == data);
+}
+
+#[test]
+fn memory_writer() {
+    let mut writer = MemoryWriter::new();
+    assert!(writer.seekable());
+    assert!(writer.write(&[1, 2, 3, 4]).is_ok());
+    assert!(writer.set_position(1).is_ok());
+    assert!(writer.write(&[5]).is_ok());
+    assert_eq!(2, writer.get_position());
+    assert_eq!(&[1, 5, 3, 4], writer.data());
+
+    // Writing over the end grows the buffer.
+    assert!(writer.set_position(3).is_ok());
+    assert!(writer.write(&[6, 7]).is_ok());
+    assert_eq!(&[1, 5, 3, 6, 7], writer.data());
+    assert!(writer.set_position(6).is_err());
+    assert_eq!(5, writer.get_position());
+}
+
+#[test]
+fn crc32_writer() {
+    let mut writer = MemoryWriter::new();
+    {
+        let mut crc_writer = Crc32Writer::new(&mut writer, 0);
+        assert!(crc_writer.write(b"1234").is_ok());
+        assert!(crc_writer.write(b"56789").is_ok());
+        assert_eq!(0xCBF43926, crc_writer.crc());
+        assert_eq!(9, crc_writer.get_position());
+        assert_eq!(0xCBF43926, Crc32Update(0, b"123456789"));
+        assert_eq!(0xCBF43926, Crc32Update(Crc32Update(0, b"1234"), b"56789"));
+
+        // Seeking would leave the CRC-32 out of sync with the output.
+        assert!(!crc_writer.seekable());
+        assert!(crc_writer.set_position(0).is_err());
+        assert_eq!(0xCBF43926, crc_writer.crc());
+    }
+    assert_eq!(b"123456789", writer.data());
+}
+
+#[test]
+fn cues_before_clusters() {
+    // The Segment keeps its writer borrowed, so the output is written to a
+    // file that can be read back while the Segment is alive.
+    let test = MuxerTest::new();
+    let filename = GetTempFileName();
+    let mut writer = MkvWriter::new(File::create(&filename).unwrap());
+    let mut cues_writer = test.writer();
+    {
+        let mut segment = Segment::new();
+        assert!(SegmentInit(&mut segment, &mut writer, true, false, false));
+        AddVideoTrack(&mut segment);
+
+        let track = kVideoTrackNumber as u64;
+        segment.AddFrame(&test.dummy_data_, track, 0, true).unwrap();
+        segment
+            .AddFrame(&test.dummy_data_, track, 2000000, false)
+            .unwrap();
+        segment
+            .AddFrame(&test.dummy_data_, track, 4000000, false)
+            .unwrap();
+        segment.ForceNewClusterOnNextFrame();
+        segment
+            .AddFrame(&test.dummy_data_, track, 6000000, true)
+            .unwrap();
+        segment.Finalize().unwrap();
+        assert_eq!(CuesPosition::kAfterClusters, segment.cues_position());
+
+        let mut reader = MkvReader::new(File::open(&filename).unwrap()).unwrap();
+        segment
+            .CopyAndMoveCuesBeforeClusters(&mut reader, &mut cues_writer)
+            .unwrap();
+        assert_eq!(CuesPosition::kBeforeClusters, segment.cues_position());
+
+        // The cue points have already been moved.
+        let mut other = MemoryWriter::new();
+        assert!(segment
+            .CopyAndMoveCuesBeforeClusters(&mut reader, &mut other)
+            .is_err());
+    }
+    let _ = std::fs::remove_file(&filename);
+
+    test.CompareWith(&cues_writer, "cues_before_clusters.webm")
+        .unwrap();
+}
 
 #[test]
-fn test_new_writer() {
-    //let w = MkvWriter::new(File::create());
-}
\ No newline at end of file
+fn chunking() {
+    let test = MuxerTest::new();
+    let base_name = GetTempFileName();
+    let mut writer = test.writer();
+    let mut chunks = Vec::new();
+    {
+        let mut segment = Segment::new();
+        assert!(SegmentInit(&mut segment, &mut writer, true, false, false));
+        segment.SetChunking(true, &base_name).unwrap();
+        assert!(segment.chunking());
+        segment.SetChunkCallback(|name: &str| chunks.push(name.to_string()));
+        AddVideoTrack(&mut segment);
+
+        let track = kVideoTrackNumber as u64;
+        segment.AddFrame(&test.dummy_data_, track, 0, true).unwrap();
+        segment
+            .AddFrame(&test.dummy_data_, track, 2000000, false)
+            .unwrap();
+        segment
+            .AddFrame(&test.dummy_data_, track, 4000000, false)
+            .unwrap();
+        assert_eq!(0, segment.chunk_count());
+        segment.ForceNewClusterOnNextFrame();
+        segment
+            .AddFrame(&test.dummy_data_, track, 6000000, true)
+            .unwrap();
+        assert_eq!(1, segment.chunk_count());
+        segment.AddCuePoint(4000000, track).unwrap();
+        assert!(segment.SetChunking(false, "").is_err());
+        segment.Finalize().unwrap();
+        assert_eq!(2, segment.chunk_count());
+    }
+    // Nothing goes to the writer passed to Init().
+    assert!(writer.data().is_empty());
+
+    let expected = vec![
+        format!("{}_000000.chk", base_name),
+        format!("{}_000001.chk", base_name),
+        format!("{}.cues", base_name),
+        format!("{}.hdr", base_name),
+    ];
+    assert_eq!(expected, chunks);
+
+    // The header, Clusters and Cues put back together make up the regular
+    // output.
+    let mut output = MemoryWriter::new();
+    for name in [&expected[3], &expected[0], &expected[1], &expected[2]] {
+        output.write(&std::fs::read(name).unwrap()).unwrap();
+        std::fs::remove_file(name).unwrap();
+    }
+    test.CompareWith(&output, "output_cues.webm").unwrap();
+}
+
+#[test]
+fn muxer_errors() {
+    let test = MuxerTest::new();
+    let track = kVideoTrackNumber as u64;
+
+    let mut writer = test.writer();
+
+    // No writer yet.
+    let mut segment = Segment::new();
+    let result = segment.AddFrame(&test.dummy_data_, track, 0, true);
+    assert!(matches!(result, Err(MuxerError::kInvalidState(_))));
+
+    assert!(SegmentInit(&mut segment, &mut writer, false, false, false));
+    AddVideoTrack(&mut segment);
+    let result = segment.AddVideoTrack(kWidth, kHeight, kVideoTrackNumber);
+    assert!(matches!(result, Err(MuxerError::kInvalidTrackNumber(1))));
+
+    let result = segment.AddFrame(&test.dummy_data_, 5, 0, true);
+    assert!(matches!(result, Err(MuxerError::kInvalidTrackNumber(5))));
+    assert!(matches!(
+        segment.CuesTrack(5),
+        Err(MuxerError::kInvalidTrackNumber(5))
+    ));
+
+    segment
+        .AddFrame(&test.dummy_data_, track, 2000000, true)
+        .unwrap();
+    let result = segment.AddFrame(&test.dummy_data_, track, 1000000, false);
+    assert!(matches!(
+        result,
+        Err(MuxerError::kTimecodeOutOfRange(-1000000))
+    ));
+    segment.Finalize().unwrap();
+
+    // The Segment size can't be patched before the header is written.
+    let mut writer = test.writer();
+    let mut segment = Segment::new();
+    assert!(SegmentInit(&mut segment, &mut writer, false, false, false));
+    AddVideoTrack(&mut segment);
+    let result = segment.Finalize();
+    assert!(
+        matches!(result, Err(MuxerError::kInvalidState(_))),
+        "{:?}",
+        result
+    );
+    drop(segment);
+    assert!(writer.data().is_empty());
+
+    // Finalize() rewrites the EBML header at the start of a seekable writer,
+    // and the writer can't change once the headers are written.
+    let mut writer = test.writer();
+    writer.write(&[0]).unwrap();
+    let mut segment = Segment::new();
+    let result = segment.Init(&mut writer);
+    assert!(matches!(result, Err(MuxerError::kInvalidState(_))));
+    let mut writer = test.writer();
+    let mut other_writer = test.writer();
+    let mut segment = Segment::new();
+    segment.Init(&mut writer).unwrap();
+    AddVideoTrack(&mut segment);
+    segment.AddFrame(&test.dummy_data_, track, 0, true).unwrap();
+    let result = segment.Init(&mut other_writer);
+    assert!(matches!(result, Err(MuxerError::kInvalidState(_))));
+    segment.Finalize().unwrap();
+}
+
+#[test]
+fn cluster_errors() {
+    let test = MuxerTest::new();
+    let mut writer = test.writer();
+    let mut cluster = Cluster::new(0, 0, 1000000, false, false);
+    let track = kVideoTrackNumber as u64;
+
+    let mut frame = Frame::new();
+    assert!(frame.Init(&test.dummy_data_));
+    frame.set_track_number(track);
+    frame.set_is_key(true);
+
+    // Relative timecodes must fit in a signed 16-bit integer.
+    frame.set_timestamp(40000 * 1000000);
+    assert!(matches!(
+        cluster.AddFrame(&mut writer, &frame),
+        Err(MuxerError::kTimecodeOutOfRange(40000))
+    ));
+
+    frame.set_timestamp(0);
+    cluster.AddFrame(&mut writer, &frame).unwrap();
+    cluster.Finalize(&mut writer).unwrap();
+    assert!(matches!(
+        cluster.AddFrame(&mut writer, &frame),
+        Err(MuxerError::kClusterFinalized)
+    ));
+
+    frame.set_track_number(0);
+    assert!(matches!(
+        frame.Validate(),
+        Err(MuxerError::kInvalidTrackNumber(0))
+    ));
+}
+
+#[test]
+fn io_error() {
+    let test = MuxerTest::new();
+    let mut buffer = [0u8; 16];
+    let mut writer = IoWriter::new(Cursor::new(&mut buffer[..])).unwrap();
+    let mut segment = Segment::new();
+    assert!(SegmentInit(&mut segment, &mut writer, false, false, false));
+    AddVideoTrack(&mut segment);
+
+    // The EBML header does not fit in the buffer.
+    let result = segment.AddFrame(&test.dummy_data_, kVideoTrackNumber as u64, 0, true);
+    match result {
+        Err(MuxerError::kIoError(_, e)) => assert_eq!(std::io::ErrorKind::WriteZero, e.kind()),
+        _ => panic!("expected an io error, got {:?}", result),
+    }
+}
+
+#[test]
+fn content_encryptor() {
+    let plain_text: Vec<u8> = (0..32).collect();
+    let iv = kEncryptionIV.to_be_bytes();
+    let mut encryptor = ContentEncryptor::new(&kEncryptionKey, b"key id").unwrap();
+    assert_eq!(b"key id", encryptor.key_id());
+
+    encryptor.set_next_iv(kEncryptionIV);
+    let frame = encryptor.EncryptFrame(&plain_text, &[]).unwrap();
+    assert_eq!(kEncryptedFrame, frame[0]);
+    assert_eq!(&iv[..], &frame[1..9]);
+    assert_eq!(&kCipherText[..], &frame[9..]);
+    assert_eq!(kEncryptionIV + 1, encryptor.next_iv());
+
+    // Only the partition between the offsets is encrypted.
+    let frame = encryptor
+        .EncryptFrameWithIV(kEncryptionIV, &plain_text, &[4, 20])
+        .unwrap();
+    assert_eq!(kEncryptedFrame | kPartitionedFrame, frame[0]);
+    assert_eq!(&iv[..], &frame[1..9]);
+    assert_eq!(&[2, 0, 0, 0, 4, 0, 0, 0, 20][..], &frame[9..18]);
+    let data = &frame[18..];
+    assert_eq!(&plain_text[..4], &data[..4]);
+    assert_eq!(&kPartitionCipherText[..], &data[4..20]);
+    assert_eq!(&plain_text[20..], &data[20..]);
+
+    // An odd number of offsets encrypts up to the end of the frame.
+    let frame = encryptor
+        .EncryptFrameWithIV(kEncryptionIV, &plain_text, &[0])
+        .unwrap();
+    assert_eq!(&kCipherText[..], &frame[14..]);
+
+    assert!(encryptor
+        .EncryptFrameWithIV(kEncryptionIV, &plain_text, &[20, 4])
+        .is_err());
+    assert!(encryptor
+        .EncryptFrameWithIV(kEncryptionIV, &plain_text, &[33])
+        .is_err());
+    assert!(ContentEncryptor::new(&kEncryptionKey[..8], b"key id").is_err());
+    assert!(ContentEncryptor::new(&kEncryptionKey, b"").is_err());
+}
+
+#[test]
+fn content_encodings() {
+    // Encodings are kept by order, which must be unique, and only
+    // compression can apply to the CodecPrivate.
+    let mut segment = Segment::new();
+    AddAudioTrack(&mut segment);
+    let track = segment.GetTrackByNumber(kAudioTrackNumber as u64).unwrap();
+    let mut encryption = ContentEncoding::new();
+    encryption.set_encoding_order(5);
+    track.AddContentEncoding(encryption).unwrap();
+    let mut compression = ContentEncoding::new();
+    compression.set_encoding_type(kCompression).unwrap();
+    compression.set_encoding_order(2);
+    compression.set_encoding_scope(kScopeFrames | kScopeCodecPrivate);
+    track.AddContentEncoding(compression).unwrap();
+    let compression = track.GetContentEncoding(0).unwrap();
+    assert_eq!(2, compression.encoding_order());
+    assert_eq!(kCompression, compression.encoding_type());
+    assert_eq!(ContentCompAlgo::kZlib, compression.comp_algo());
+    let encryption = track.GetContentEncoding(1).unwrap();
+    assert_eq!(5, encryption.encoding_order());
+    assert_eq!(kEncryption, encryption.encoding_type());
+
+    let mut duplicate = ContentEncoding::new();
+    duplicate.set_encoding_order(2);
+    assert!(track.AddContentEncoding(duplicate).is_err());
+    // Encodings of the next encoding are not supported.
+    for &scope in &[0, kScopeNextEncoding, kScopeFrames | kScopeNextEncoding, 8] {
+        let mut encoding = ContentEncoding::new();
+        encoding.set_encoding_order(6);
+        encoding.set_encoding_scope(scope);
+        assert!(track.AddContentEncoding(encoding).is_err());
+    }
+    let mut encoding = ContentEncoding::new();
+    encoding.set_encoding_order(6);
+    encoding.set_encoding_scope(kScopeCodecPrivate);
+    assert!(track.AddContentEncoding(encoding).is_err());
+    let mut encoding = ContentEncoding::new();
+    encoding.set_encoding_order(6);
+    encoding.set_encoding_type(kCompression).unwrap();
+    assert!(encoding.set_encoding_type(2).is_err());
+    encoding.set_encoding_scope(kScopeCodecPrivate);
+    track.AddContentEncoding(encoding).unwrap();
+    assert_eq!(3, track.content_encoding_count());
+
+    // A header stripping compression needs the stripped bytes, and an
+    // encryptor can't compress.
+    let mut encoding = ContentEncoding::new_compression(ContentCompAlgo::kHeaderStripping, &[]);
+    encoding.set_encoding_order(7);
+    assert!(track.AddContentEncoding(encoding).is_err());
+    let encryptor = ContentEncryptor::new(&kEncryptionKey, b"key id").unwrap();
+    let mut encoding = ContentEncoding::new_encryption(encryptor);
+    assert!(encoding.set_encoding_type(kCompression).is_err());
+    assert_eq!(kEncryption, encoding.encoding_type());
+}
+
+// Returns whether |bytes| appear in |data|.
+fn Contains(data: &[u8], bytes: &[u8]) -> bool {
+    data.windows(bytes.len()).any(|window| window == bytes)
+}
+
+#[test]
+fn encrypted_frames() {
+    // A key frame encrypted whole, then one with its first 4 and last 12
+    // bytes in the clear. Each starts with the signal byte and the IV, and a
+    // partitioned frame with its offsets.
+    let plain_text: Vec<u8> = (0..32).collect();
+    let video = kVideoTrackNumber as u64;
+    let mut expected = vec![kEncryptedFrame];
+    expected.extend_from_slice(&kEncryptionIV.to_be_bytes());
+    expected.extend_from_slice(&kCipherText);
+    let mut partitioned = vec![kEncryptedFrame | kPartitionedFrame];
+    partitioned.extend_from_slice(&kEncryptionIV.to_be_bytes());
+    partitioned.extend_from_slice(&[2, 0, 0, 0, 4, 0, 0, 0, 20]);
+    partitioned.extend_from_slice(&plain_text[..4]);
+    partitioned.extend_from_slice(&kPartitionCipherText);
+    partitioned.extend_from_slice(&plain_text[20..]);
+
+    for (offsets, expected) in [(&[][..], expected), (&[4, 20][..], partitioned)] {
+        let mut frame = NewFrame(&plain_text, video, 0, true);
+        frame.set_partition_offsets(offsets);
+        let mut writer = MemoryWriter::new();
+        let setup = |segment: &mut Segment| {
+            AddVideoTrack(segment);
+            AddEncryption(segment, video);
+        };
+        MuxFrames(&mut writer, true, setup, &[frame], &[]);
+        assert!(Contains(writer.data(), &expected));
+        assert!(!Contains(writer.data(), &plain_text));
+    }
+
+    // Partition offsets need an encrypted, unlaced frame within the data.
+    let mut writer = MemoryWriter::new();
+    let mut segment = Segment::new();
+    segment.Init(&mut writer).unwrap();
+    AddVideoTrack(&mut segment);
+    let mut frame = NewFrame(&[0; 10], video, 0, true);
+    frame.set_partition_offsets(&[4]);
+    assert!(segment.AddGenericFrame(&frame).is_err());
+    segment
+        .GetTrackByNumber(video)
+        .unwrap()
+        .AddContentEncryption(&kEncryptionKey, b"key id")
+        .unwrap();
+    frame.set_partition_offsets(&[11]);
+    assert!(segment.AddGenericFrame(&frame).is_err());
+    let frames: Vec<&[u8]> = vec![&[0; 10], &[0; 10]];
+    frame.InitLaced(&frames, Lacing::kXiphLacing);
+    frame.set_partition_offsets(&[4]);
+    assert!(segment.AddGenericFrame(&frame).is_err());
+    frame.Init(&[0; 10]);
+    segment.AddGenericFrame(&frame).unwrap();
+
+    // The offsets can't point into a frame compressed before the encryption,
+    // but the encrypted frame may be compressed.
+    for compress_first in [true, false] {
+        let mut writer = MemoryWriter::new();
+        let mut segment = Segment::new();
+        segment.Init(&mut writer).unwrap();
+        AddVideoTrack(&mut segment);
+        let track = segment.GetTrackByNumber(video).unwrap();
+        if compress_first {
+            track
+                .AddContentCompression(ContentCompAlgo::kHeaderStripping, &[0])
+                .unwrap();
+        }
+        track
+            .AddContentEncryption(&kEncryptionKey, b"key id")
+            .unwrap();
+        if !compress_first {
+            track
+                .AddContentCompression(ContentCompAlgo::kZlib, &[])
+                .unwrap();
+        }
+        let mut frame = NewFrame(&[0; 10], video, 0, true);
+        frame.set_partition_offsets(&[4]);
+        assert_eq!(compress_first, segment.AddGenericFrame(&frame).is_err());
+    }
+}
+
+#[test]
+fn header_stripping() {
+    // The stripped bytes are removed from the start of every frame.
+    let header = [0xAAu8, 0xBB, 0xCC];
+    let data: Vec<u8> = header.iter().cloned().chain(1..=50).collect();
+    let audio = kAudioTrackNumber as u64;
+    let mut writer = MemoryWriter::new();
+    let setup = |segment: &mut Segment| {
+        AddAudioTrack(segment);
+        segment
+            .GetTrackByNumber(audio)
+            .unwrap()
+            .AddContentCompression(ContentCompAlgo::kHeaderStripping, &header)
+            .unwrap();
+    };
+    let frames = [NewFrame(&data, audio, 0, true)];
+    MuxFrames(&mut writer, false, setup, &frames, &[]);
+    assert!(Contains(writer.data(), &data[header.len()..]));
+    assert!(!Contains(writer.data(), &data));
+
+    // Every frame must start with the stripped bytes.
+    let mut writer = MemoryWriter::new();
+    let mut segment = Segment::new();
+    segment.Init(&mut writer).unwrap();
+    AddAudioTrack(&mut segment);
+    segment
+        .GetTrackByNumber(audio)
+        .unwrap()
+        .AddContentCompression(ContentCompAlgo::kHeaderStripping, &header)
+        .unwrap();
+    assert!(segment.AddFrame(&[1, 2, 3, 4], audio, 0, true).is_err());
+}
+
+// Adds an Opus frame of |duration_ms| (0 if unknown) at |timestamp_ms| with
+// |discard_padding| in nanoseconds.
+fn AddOpusFrame(
+    segment: &mut Segment,
+    timestamp_ms: u64,
+    duration_ms: u64,
+    discard_padding: i64,
+) -> Result<(), MuxerError> {
+    let mut frame = Frame::new();
+    frame.Init(&[0; 10]);
+    frame.set_track_number(kAudioTrackNumber as u64);
+    frame.set_timestamp(timestamp_ms * 1000000);
+    if duration_ms > 0 {
+        frame.set_duration(duration_ms * 1000000);
+    }
+    frame.set_discard_padding(discard_padding);
+    frame.set_is_key(true);
+    segment.AddGenericFrame(&frame)
+}
+
+#[test]
+fn opus_discard_padding() {
+    // 312 samples at 48kHz: the pre-skip trims the first 6.5ms.
+    let mut config = OpusConfig::new(2);
+    config.set_pre_skip(312);
+    let mut writer = MemoryWriter::new();
+    let mut segment = Segment::new();
+    segment.Init(&mut writer).unwrap();
+    segment.AddOpusTrack(&config, kAudioTrackNumber).unwrap();
+    assert_eq!(
+        kOpusCodecDelay,
+        segment
+            .GetTrackByNumber(kAudioTrackNumber as u64)
+            .unwrap()
+            .codec_delay()
+    );
+
+    // The pre-skip and the padding can't trim more than the frame holds, and
+    // the start of the first frame is already trimmed by the pre-skip.
+    assert!(AddOpusFrame(&mut segment, 0, 20, 13500001).is_err());
+    assert!(AddOpusFrame(&mut segment, 0, 20, -1).is_err());
+    AddOpusFrame(&mut segment, 0, 20, 13500000).unwrap();
+
+    // A negative padding trims the start of a frame after the pre-skip.
+    assert!(AddOpusFrame(&mut segment, 20, 20, -20000001).is_err());
+    AddOpusFrame(&mut segment, 20, 20, -20000000).unwrap();
+    AddOpusFrame(&mut segment, 40, 20, 5000000).unwrap();
+
+    // Without a duration, a frame is at most the longest Opus packet.
+    assert!(AddOpusFrame(&mut segment, 60, 0, 120000001).is_err());
+    AddOpusFrame(&mut segment, 60, 0, -120000000).unwrap();
+    segment.Finalize().unwrap();
+
+    // CodecDelay must stay in sync with the pre-skip.
+    let mut writer = MemoryWriter::new();
+    let mut segment = Segment::new();
+    segment.Init(&mut writer).unwrap();
+    segment.AddOpusTrack(&config, kAudioTrackNumber).unwrap();
+    segment
+        .GetTrackByNumber(kAudioTrackNumber as u64)
+        .unwrap()
+        .set_codec_delay(0);
+    assert!(AddOpusFrame(&mut segment, 0, 20, 2500000).is_err());
+}
@@ -0,0 +1,2332 @@
+#![allow(non_snake_case, non_upper_case_globals)]
+
+extern crate libwebm;
+
+use libwebm::mkvmuxer::audio_track::AudioTrack;
+use libwebm::mkvmuxer::content_encoding::{
+    kScopeCodecPrivate, kScopeFrames, ContentCompAlgo, ContentEncoding,
+};
+use libwebm::mkvmuxer::frame::{Frame, Lacing};
+use libwebm::mkvmuxer::opus::OpusConfig;
+use libwebm::mkvmuxer::projection::ProjectionType;
+use libwebm::mkvmuxer::segment::{DocType, Mode, Segment};
+use libwebm::mkvmuxer::signature_slot::SignatureAlgo;
+use libwebm::mkvmuxer::writer::{MemoryWriter, MkvWriter, StreamWriter, Writer};
+use libwebm::mkvparser::cluster::Cluster;
+use libwebm::mkvparser::content_encoding::{kCompression, kEncryption, kMaxDecompressedSize};
+use libwebm::mkvparser::ebml_header::EBMLHeader;
+use libwebm::mkvparser::element::{Elements, FindChild, ParseElementHeader};
+use libwebm::mkvparser::error::ParseError;
+use libwebm::mkvparser::reader::{MkvReader, Reader};
+use libwebm::mkvparser::segment::Segment as ParserSegment;
+use libwebm::mkvparser::tracks::TrackEntry;
+use libwebm::mkvparser::util::*;
+use libwebm::MkvId;
+use std::fs::File;
+use std::io;
+
+mod util;
+use util::*;
+
+#[test]
+fn read_uint() {
+    let mut reader = BufferReader::new(vec![0x81, 0x40, 0x02, 0x10, 0x00, 0x00, 0x05]);
+    assert_eq!((1, 1), ReadUInt(&mut reader, 0).unwrap());
+    assert_eq!((2, 2), ReadUInt(&mut reader, 1).unwrap());
+    assert_eq!((5, 4), ReadUInt(&mut reader, 3).unwrap());
+
+    let mut reader = BufferReader::new(vec![0xFF, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+    let (value, len) = ReadUInt(&mut reader, 0).unwrap();
+    assert!(IsUnknownSize(value, len));
+    let (value, len) = ReadUInt(&mut reader, 1).unwrap();
+    assert_eq!(8, len);
+    assert!(IsUnknownSize(value, len));
+
+    // Coded integers longer than 8 bytes are not supported.
+    let mut reader = BufferReader::new(vec![0x00, 0x81]);
+    assert!(matches!(
+        ReadUInt(&mut reader, 0),
+        Err(ParseError::kFileFormatInvalid)
+    ));
+}
+
+#[test]
+fn read_id() {
+    let mut reader = BufferReader::new(vec![0x1A, 0x45, 0xDF, 0xA3, 0xEC, 0xFF]);
+    assert_eq!((MkvId::MkvEBML as u64, 4), ReadID(&mut reader, 0).unwrap());
+    assert_eq!((MkvId::MkvVoid as u64, 1), ReadID(&mut reader, 4).unwrap());
+    // 0xFF is a reserved id.
+    assert!(ReadID(&mut reader, 5).is_err());
+}
+
+#[test]
+fn unserialize() {
+    let mut reader = BufferReader::new(vec![
+        0x01, 0x00, 0xFF, 0x80, 0x3F, 0x80, 0x00, 0x00, b'w', b'e', b'b', b'm', 0x00, 0x00,
+    ]);
+    assert_eq!(0x0100, UnserializeUInt(&mut reader, 0, 2).unwrap());
+    assert_eq!(-128, UnserializeInt(&mut reader, 2, 2).unwrap());
+    assert_eq!(-1, UnserializeInt(&mut reader, 2, 1).unwrap());
+    assert_eq!(1.0, UnserializeFloat(&mut reader, 4, 4).unwrap());
+    assert!(UnserializeFloat(&mut reader, 4, 3).is_err());
+    assert_eq!("webm", UnserializeString(&mut reader, 8, 6).unwrap());
+    assert!(UnserializeUInt(&mut reader, 0, 9).is_err());
+}
+
+#[test]
+fn buffer_not_full() {
+    let mut reader = BufferReader::new(vec![0x1A, 0x45, 0xDF, 0xA3, 0x80]);
+    reader.available_ = 2;
+    assert!(matches!(
+        ReadID(&mut reader, 0),
+        Err(ParseError::kBufferNotFull)
+    ));
+    reader.available_ = 5;
+    let header = ParseElementHeader(&mut reader, 0, None).unwrap();
+    assert_eq!(Some(0), header.size);
+    assert_eq!(5, header.payload_start);
+
+    // Once the total length is known, reading past it is an error.
+    assert!(matches!(
+        ReadID(&mut reader, 5),
+        Err(ParseError::kFileFormatInvalid)
+    ));
+}
+
+#[test]
+fn element_exceeds_parent() {
+    // A Void element of size 4 in a range of 3 bytes.
+    let mut reader = BufferReader::new(vec![0xEC, 0x84, 0x00, 0x00, 0x00, 0x00]);
+    assert!(matches!(
+        ParseElementHeader(&mut reader, 0, Some(3)),
+        Err(ParseError::kFileFormatInvalid)
+    ));
+}
+
+#[test]
+fn unknown_size_elements() {
+    let data = vec![
+        // Segment, unknown size.
+        0x18, 0x53, 0x80, 0x67, 0xFF, // Cluster, unknown size, with a Timecode.
+        0x1F, 0x43, 0xB6, 0x75, 0xFF, 0xE7, 0x81, 0x00,
+        // Cluster, unknown size, with a Timecode and a Void.
+        0x1F, 0x43, 0xB6, 0x75, 0xFF, 0xE7, 0x81, 0x05, 0xEC, 0x80,
+    ];
+    let mut reader = BufferReader::new(data);
+
+    let mut top = Elements::with_range(0, None);
+    let segment = top.next(&mut reader).unwrap().unwrap();
+    assert!(segment.is(MkvId::MkvSegment));
+    assert_eq!(None, segment.size);
+
+    let mut clusters = Vec::new();
+    let mut children = Elements::new(&segment);
+    while let Some(cluster) = children.next(&mut reader).unwrap() {
+        assert!(cluster.is(MkvId::MkvCluster));
+        let mut ids = Vec::new();
+        let mut elements = Elements::new(&cluster);
+        while let Some(child) = elements.next(&mut reader).unwrap() {
+            ids.push(child.mkv_id().unwrap());
+        }
+        clusters.push((cluster.start, elements.position(), ids));
+    }
+    assert_eq!(
+        vec![
+            (5, 13, vec![MkvId::MkvTimecode]),
+            (13, 23, vec![MkvId::MkvTimecode, MkvId::MkvVoid]),
+        ],
+        clusters
+    );
+    assert_eq!(23, children.position());
+    assert!(top.next(&mut reader).unwrap().is_none());
+}
+
+#[test]
+fn ebml_header() {
+    let mut reader = OpenTestFile("bbb_480p_vp9_opus_1second.webm");
+    let mut header = EBMLHeader::new();
+    let pos = header.Parse(&mut reader, 0).unwrap();
+    assert!(pos > 0);
+    assert_eq!(1, header.version());
+    assert_eq!(1, header.read_version());
+    assert_eq!(4, header.max_id_length());
+    assert_eq!(8, header.max_size_length());
+    assert_eq!("webm", header.doc_type());
+
+    let segment = ParseElementHeader(&mut reader, pos, None).unwrap();
+    assert!(segment.is(MkvId::MkvSegment));
+
+    let mut reader = OpenTestFile("matroska_doctype.mkv");
+    assert!(header.Parse(&mut reader, 0).is_ok());
+    assert_eq!("matroska", header.doc_type());
+}
+
+#[test]
+fn walk_test_files() {
+    for name in [
+        "bbb_480p_vp9_opus_1second.webm",
+        "test_stereo_left_right.webm",
+        "output_cues.webm",
+        "chapters.webm",
+    ] {
+        let mut reader = OpenTestFile(name);
+        let mut top = Elements::with_range(0, None);
+        let ebml = top.next(&mut reader).unwrap().unwrap();
+        assert!(ebml.is(MkvId::MkvEBML));
+        let segment = top.next(&mut reader).unwrap().unwrap();
+        assert!(segment.is(MkvId::MkvSegment));
+
+        let mut has_info = false;
+        let mut has_cluster = false;
+        let mut children = Elements::new(&segment);
+        while let Some(child) = children.next(&mut reader).unwrap() {
+            has_info |= child.is(MkvId::MkvInfo);
+            has_cluster |= child.is(MkvId::MkvCluster);
+        }
+        assert!(has_info, "{}", name);
+        assert!(has_cluster, "{}", name);
+        assert!(top.next(&mut reader).unwrap().is_none());
+    }
+}
+
+#[test]
+fn read_muxer_output() {
+    let test = MuxerTest::new();
+    let mut writer = test.writer();
+    {
+        let mut segment = Segment::new();
+        assert!(SegmentInit(&mut segment, &mut writer, false, false, false));
+        AddVideoTrack(&mut segment);
+        segment
+            .AddFrame(&test.dummy_data_, kVideoTrackNumber as u64, 0, false)
+            .unwrap();
+        segment.Finalize().unwrap();
+    }
+    let mut reader = BufferReader::new(writer.into_inner());
+    let mut header = EBMLHeader::new();
+    let pos = header.Parse(&mut reader, 0).unwrap();
+    assert_eq!("webm", header.doc_type());
+
+    let segment = ParseElementHeader(&mut reader, pos, None).unwrap();
+    assert!(segment.is(MkvId::MkvSegment));
+    let info = FindChild(&mut reader, &segment, MkvId::MkvInfo)
+        .unwrap()
+        .unwrap();
+    let app = FindChild(&mut reader, &info, MkvId::MkvMuxingApp)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        kAppString,
+        UnserializeString(&mut reader, app.payload_start, app.size.unwrap()).unwrap()
+    );
+}
+
+fn ParseSegment(reader: &mut dyn Reader) -> ParserSegment {
+    let mut header = EBMLHeader::new();
+    let pos = header.Parse(reader, 0).unwrap();
+    let mut segment = ParserSegment::CreateInstance(reader, pos).unwrap();
+    segment.ParseHeaders(reader).unwrap();
+    segment
+}
+
+#[test]
+fn segment_info_and_tracks() {
+    let mut reader = OpenTestFile("bbb_480p_vp9_opus_1second.webm");
+    let segment = ParseSegment(&mut reader);
+    assert!(segment.first_cluster_pos().is_some());
+
+    let info = segment.GetInfo().unwrap();
+    assert_eq!(1000000, info.timecode_scale());
+    assert_eq!(1008.0, info.duration());
+    assert_eq!(1008000000, info.GetDuration());
+    assert_eq!("Lavf56.40.101", info.muxing_app());
+    assert_eq!("Lavf56.40.101", info.writing_app());
+    assert_eq!(i64::MIN, info.date_utc());
+
+    let tracks = segment.GetTracks().unwrap();
+    assert_eq!(2, tracks.track_entries_size());
+
+    let video = tracks.GetTrackByNumber(1).unwrap().as_video().unwrap();
+    assert_eq!("V_VP9", video.codec_id());
+    assert_eq!("und", video.language());
+    assert_eq!(41666666, video.default_duration());
+    assert_eq!(854, video.width());
+    assert_eq!(480, video.height());
+
+    let audio = tracks.GetTrackByNumber(2).unwrap().as_audio().unwrap();
+    assert_eq!("A_OPUS", audio.codec_id());
+    assert_eq!(kOpusCodecDelay, audio.codec_delay());
+    assert_eq!(kOpusSeekPreroll, audio.seek_pre_roll());
+    assert!(audio.codec_private().len() >= kOpusPrivateDataSizeMinimum);
+    assert_eq!(48000.0, audio.sample_rate());
+    assert_eq!(6, audio.channels());
+    assert_eq!(32, audio.bit_depth());
+}
+
+#[test]
+fn muxer_tracks() {
+    let mut reader = OpenTestFile("tracks.webm");
+    let segment = ParseSegment(&mut reader);
+    assert_eq!(kAppString, segment.GetInfo().unwrap().muxing_app());
+
+    let tracks = segment.GetTracks().unwrap();
+    let video = tracks
+        .GetTrackByNumber(kVideoTrackNumber as u64)
+        .unwrap()
+        .as_video()
+        .unwrap();
+    assert_eq!(kTrackName, video.name());
+    assert_eq!(kVP8CodecId, video.codec_id());
+    assert_eq!(kWidth as u64, video.width());
+    assert_eq!(kHeight as u64, video.height());
+    assert_eq!(kWidth as u64 - 10, video.display_width());
+    assert_eq!(kHeight as u64 - 10, video.display_height());
+    assert_eq!(kVideoFrameRate, video.frame_rate());
+
+    let audio = tracks
+        .GetTrackByNumber(kAudioTrackNumber as u64)
+        .unwrap()
+        .as_audio()
+        .unwrap();
+    assert_eq!(kTrackName, audio.name());
+    assert_eq!(kVorbisCodecId, audio.codec_id());
+    assert_eq!(kSampleRate as f64, audio.sample_rate());
+    assert_eq!(kChannels as u64, audio.channels());
+    assert_eq!(kBitDepth as u64, audio.bit_depth());
+}
+
+#[test]
+fn colour_and_projection() {
+    let mut reader = OpenTestFile("colour.webm");
+    let segment = ParseSegment(&mut reader);
+    let video = segment.GetTracks().unwrap().GetTrackByIndex(0).unwrap();
+    let colour = video.as_video().unwrap().colour().unwrap();
+    assert_eq!(0, colour.matrix_coefficients());
+    assert_eq!(1, colour.bits_per_channel());
+    assert_eq!(2, colour.chroma_subsampling_horz());
+    assert_eq!(3, colour.chroma_subsampling_vert());
+    assert_eq!(4, colour.cb_subsampling_horz());
+    assert_eq!(5, colour.cb_subsampling_vert());
+    assert_eq!(1, colour.chroma_siting_horz());
+    assert_eq!(1, colour.chroma_siting_vert());
+    assert_eq!(2, colour.range());
+    assert_eq!(9, colour.transfer_characteristics());
+    assert_eq!(10, colour.primaries());
+    assert_eq!(11, colour.max_cll());
+    assert_eq!(12, colour.max_fall());
+    let mastering_metadata = colour.mastering_metadata().unwrap();
+    assert_eq!(40.0, mastering_metadata.luminance_max());
+    assert_eq!(30.0, mastering_metadata.luminance_min());
+    for chromaticity in [
+        mastering_metadata.r(),
+        mastering_metadata.g(),
+        mastering_metadata.b(),
+        mastering_metadata.white_point(),
+    ] {
+        let chromaticity = chromaticity.unwrap();
+        assert_eq!(0.1, chromaticity.x());
+        assert_eq!(0.2, chromaticity.y());
+    }
+
+    let mut reader = OpenTestFile("projection.webm");
+    let segment = ParseSegment(&mut reader);
+    let video = segment.GetTracks().unwrap().GetTrackByIndex(0).unwrap();
+    let projection = video.as_video().unwrap().projection().unwrap();
+    assert_eq!(
+        ProjectionType::kRectangular as i32,
+        projection.project_type() as i32
+    );
+    assert_eq!(1.0, projection.pose_yaw());
+    assert_eq!(2.0, projection.pose_pitch());
+    assert_eq!(3.0, projection.pose_roll());
+    assert_eq!(&[4u8][..], projection.private_data());
+}
+
+#[test]
+fn stereo_mode() {
+    let mut reader = OpenTestFile("test_stereo_left_right.webm");
+    let segment = ParseSegment(&mut reader);
+    let video = segment.GetTracks().unwrap().GetTrackByIndex(0).unwrap();
+    let video = video.as_video().unwrap();
+    assert_eq!(1, video.stereo_mode());
+    assert_eq!(256, video.width());
+    assert_eq!(128, video.display_width());
+}
+
+#[test]
+fn invalid_headers() {
+    for name in [
+        "invalid/primarychromaticity_fieldtoolarge.webm",
+        "invalid/projection_float_overflow.webm",
+    ] {
+        let mut reader = OpenTestFile(name);
+        let mut header = EBMLHeader::new();
+        let pos = header.Parse(&mut reader, 0).unwrap();
+        let mut segment = ParserSegment::CreateInstance(&mut reader, pos).unwrap();
+        assert!(
+            matches!(
+                segment.ParseHeaders(&mut reader),
+                Err(ParseError::kFileFormatInvalid)
+            ),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn copy_tracks_to_muxer() {
+    let mut input = OpenTestFile("colour.webm");
+    let parsed = ParseSegment(&mut input);
+    let mut input_audio = OpenTestFile("bbb_480p_vp9_opus_1second.webm");
+    let parsed_audio = ParseSegment(&mut input_audio);
+
+    let test = MuxerTest::new();
+    let mut writer = test.writer();
+    {
+        let mut segment = Segment::new();
+        assert!(SegmentInit(&mut segment, &mut writer, false, false, false));
+
+        let source = parsed.GetTracks().unwrap().GetTrackByIndex(0).unwrap();
+        let source = source.as_video().unwrap();
+        let number = segment
+            .AddVideoTrack(
+                source.width() as i32,
+                source.height() as i32,
+                kVideoTrackNumber,
+            )
+            .unwrap();
+        let track = segment.GetTrackByNumber(number).unwrap();
+        assert!(source.CopyTo(track.as_video_mut().unwrap()).unwrap());
+
+        let source = parsed_audio
+            .GetTracks()
+            .unwrap()
+            .GetTrackByNumber(2)
+            .unwrap();
+        let source = source.as_audio().unwrap();
+        let number = segment
+            .AddAudioTrack(
+                source.sample_rate() as i32,
+                source.channels() as i32,
+                kAudioTrackNumber,
+            )
+            .unwrap();
+        let track = segment.GetTrackByNumber(number).unwrap();
+        source.CopyTo(track.as_audio_mut().unwrap()).unwrap();
+
+        segment
+            .AddFrame(&test.dummy_data_, kVideoTrackNumber as u64, 0, true)
+            .unwrap();
+        segment.Finalize().unwrap();
+    }
+    let mut reader = BufferReader::new(writer.into_inner());
+    let output = ParseSegment(&mut reader);
+    let tracks = output.GetTracks().unwrap();
+
+    let video = tracks
+        .GetTrackByNumber(kVideoTrackNumber as u64)
+        .unwrap()
+        .as_video()
+        .unwrap();
+    let source = parsed.GetTracks().unwrap().GetTrackByIndex(0).unwrap();
+    let source = source.as_video().unwrap();
+    assert_eq!(source.codec_id(), video.codec_id());
+    assert_eq!(source.uid(), video.uid());
+    assert_eq!(source.width(), video.width());
+    let colour = video.colour().unwrap();
+    assert_eq!(12, colour.max_fall());
+    assert_eq!(40.0, colour.mastering_metadata().unwrap().luminance_max());
+
+    let audio = tracks
+        .GetTrackByNumber(kAudioTrackNumber as u64)
+        .unwrap()
+        .as_audio()
+        .unwrap();
+    let source = parsed_audio
+        .GetTracks()
+        .unwrap()
+        .GetTrackByNumber(2)
+        .unwrap();
+    let source = source.as_audio().unwrap();
+    assert_eq!(source.codec_id(), audio.codec_id());
+    assert_eq!(source.codec_private(), audio.codec_private());
+    assert_eq!(source.codec_delay(), audio.codec_delay());
+    assert_eq!(source.seek_pre_roll(), audio.seek_pre_roll());
+    assert_eq!(source.language(), audio.language());
+    assert_eq!(source.sample_rate(), audio.sample_rate());
+    assert_eq!(source.channels(), audio.channels());
+    assert_eq!(source.bit_depth(), audio.bit_depth());
+}
+
+// Demuxes |name| and muxes its tracks and frames into |test|'s file. A new
+// cluster is started wherever the input starts one.
+fn Remux(test: &MuxerTest, name: &str) -> MemoryWriter {
+    let mut input = OpenTestFile(name);
+    let parsed = ParseSegment(&mut input);
+
+    let mut frames = Vec::new();
+    let mut cluster_starts = Vec::new();
+    let mut cluster = parsed.GetFirstCluster(&mut input).unwrap();
+    while let Some(mut c) = cluster {
+        if !frames.is_empty() {
+            cluster_starts.push(frames.len());
+        }
+        while let Some(frame) = c.GetNextFrame(&mut input).unwrap() {
+            frames.push(frame);
+        }
+        cluster = parsed.GetNextCluster(&mut input, &c).unwrap();
+    }
+
+    let mut writer = test.writer();
+    let setup = |segment: &mut Segment| {
+        let info = parsed.GetInfo().unwrap();
+        segment
+            .GetSegmentInfo()
+            .set_timecode_scale(info.timecode_scale());
+
+        let tracks = parsed.GetTracks().unwrap();
+        for i in 0..tracks.track_entries_size() {
+            let entry = tracks.GetTrackByIndex(i).unwrap();
+            let number = entry.number() as i32;
+            match entry {
+                TrackEntry::Video(source) => {
+                    let n = segment
+                        .AddVideoTrack(source.width() as i32, source.height() as i32, number)
+                        .unwrap();
+                    let track = segment.GetTrackByNumber(n).unwrap();
+                    assert!(source.CopyTo(track.as_video_mut().unwrap()).unwrap());
+                }
+                TrackEntry::Audio(source) => {
+                    let n = segment
+                        .AddAudioTrack(
+                            source.sample_rate() as i32,
+                            source.channels() as i32,
+                            number,
+                        )
+                        .unwrap();
+                    let track = segment.GetTrackByNumber(n).unwrap();
+                    source.CopyTo(track.as_audio_mut().unwrap()).unwrap();
+                }
+                TrackEntry::Generic(source) => {
+                    let track = segment.AddTrack(number).unwrap();
+                    track.set_track_type(source.track_type());
+                    source.CopyTo(track).unwrap();
+                }
+            }
+        }
+    };
+    MuxFrames(&mut writer, false, setup, &frames, &cluster_starts);
+    writer
+}
+
+// discard_padding.webm is left out: its frames are less than a timecode tick
+// apart, so the remuxed Segment duration differs.
+#[test]
+fn remux_blocks() {
+    for name in [
+        "simple_block.webm",
+        "force_new_cluster.webm",
+        "block_with_additional.webm",
+        "metadata_block.webm",
+    ] {
+        let test = MuxerTest::new();
+        let writer = Remux(&test, name);
+        test.CompareWith(&writer, name).expect(name);
+    }
+}
+
+#[test]
+fn block_fields() {
+    let mut reader = OpenTestFile("block_with_additional.webm");
+    let segment = ParseSegment(&mut reader);
+    let mut cluster = segment.GetFirstCluster(&mut reader).unwrap().unwrap();
+    assert_eq!(0, cluster.GetTime().unwrap());
+
+    let frame = cluster.GetNextFrame(&mut reader).unwrap().unwrap();
+    assert_eq!(kVideoTrackNumber as u64, frame.track_number());
+    assert_eq!(0, frame.timestamp());
+    assert!(frame.is_key());
+    assert_eq!(kFrameLength as u64, frame.length());
+    assert_eq!(1, frame.add_id());
+    assert_eq!(kFrameLength as u64, frame.additional_length());
+
+    let frame = cluster.GetNextFrame(&mut reader).unwrap().unwrap();
+    assert_eq!(2000000, frame.timestamp());
+    assert!(!frame.is_key());
+    assert!(frame.reference_block_timestamp_set());
+    assert!(cluster.GetNextFrame(&mut reader).unwrap().is_none());
+    assert!(segment
+        .GetNextCluster(&mut reader, &cluster)
+        .unwrap()
+        .is_none());
+
+    let mut reader = OpenTestFile("discard_padding.webm");
+    let segment = ParseSegment(&mut reader);
+    let mut cluster = segment.GetFirstCluster(&mut reader).unwrap().unwrap();
+    let mut paddings = Vec::new();
+    while let Some(frame) = cluster.GetNextFrame(&mut reader).unwrap() {
+        assert_eq!(kAudioTrackNumber as u64, frame.track_number());
+        paddings.push(frame.discard_padding());
+    }
+    assert_eq!(vec![12810000, 127, -128], paddings);
+
+    let mut reader = OpenTestFile("metadata_block.webm");
+    let segment = ParseSegment(&mut reader);
+    let mut cluster = segment.GetFirstCluster(&mut reader).unwrap().unwrap();
+    let frame = cluster.GetNextFrame(&mut reader).unwrap().unwrap();
+    assert_eq!(kMetadataTrackNumber as u64, frame.track_number());
+    assert!(frame.duration_set());
+    assert_eq!(2000000, frame.duration());
+}
+
+#[test]
+fn laced_blocks() {
+    let small = vec![1u8; 10];
+    let large = vec![2u8; 300];
+    let medium = vec![3u8; 100];
+    for &lacing in &[
+        Lacing::kXiphLacing,
+        Lacing::kEbmlLacing,
+        Lacing::kFixedLacing,
+    ] {
+        let frames: Vec<&[u8]> = if lacing == Lacing::kFixedLacing {
+            vec![&medium, &medium, &medium]
+        } else {
+            vec![&small, &large, &medium]
+        };
+        let mut writer = MemoryWriter::new();
+        {
+            let mut segment = Segment::new();
+            segment.Init(&mut writer).unwrap();
+            AddAudioTrack(&mut segment);
+            segment
+                .GetTrackByNumber(kAudioTrackNumber as u64)
+                .unwrap()
+                .set_lacing(lacing);
+            segment
+                .AddLacedFrames(&frames, kAudioTrackNumber as u64, 0, true)
+                .unwrap();
+            segment.Finalize().unwrap();
+        }
+        let mut reader = BufferReader::new(writer.into_inner());
+        let segment = ParseSegment(&mut reader);
+        let tracks = segment.GetTracks().unwrap();
+        assert!(tracks
+            .GetTrackByNumber(kAudioTrackNumber as u64)
+            .unwrap()
+            .flag_lacing());
+
+        let mut cluster = segment.GetFirstCluster(&mut reader).unwrap().unwrap();
+        let frame = cluster.GetNextFrame(&mut reader).unwrap().unwrap();
+        assert_eq!(lacing, frame.lacing());
+        assert_eq!(frames.len(), frame.GetFrameCount());
+        for (i, expected) in frames.iter().enumerate() {
+            assert_eq!(Some(*expected), frame.GetFrame(i));
+        }
+        assert!(frame.GetFrame(frames.len()).is_none());
+        assert!(cluster.GetNextFrame(&mut reader).unwrap().is_none());
+    }
+
+    // Lacing must be enabled on the track, and fixed-size lacing needs
+    // frames of equal size.
+    let mut writer = MemoryWriter::new();
+    let mut segment = Segment::new();
+    segment.Init(&mut writer).unwrap();
+    AddAudioTrack(&mut segment);
+    let frames: Vec<&[u8]> = vec![&small, &large];
+    assert!(segment
+        .AddLacedFrames(&frames, kAudioTrackNumber as u64, 0, true)
+        .is_err());
+    segment
+        .GetTrackByNumber(kAudioTrackNumber as u64)
+        .unwrap()
+        .set_lacing(Lacing::kFixedLacing);
+    assert!(segment
+        .AddLacedFrames(&frames, kAudioTrackNumber as u64, 0, true)
+        .is_err());
+}
+
+#[test]
+fn content_compression() {
+    let header = [0xAAu8, 0xBB, 0xCC];
+    let frame1: Vec<u8> = header.iter().cloned().chain(vec![1u8; 50]).collect();
+    let frame2: Vec<u8> = header.iter().cloned().chain(vec![2u8; 200]).collect();
+    let frame3: Vec<u8> = header.iter().cloned().chain(vec![3u8; 20]).collect();
+    let laced: Vec<&[u8]> = vec![&frame2, &frame3];
+
+    for &(algo, settings) in &[
+        (ContentCompAlgo::kHeaderStripping, &header[..]),
+        (ContentCompAlgo::kZlib, &[][..]),
+    ] {
+        let mut writer = MemoryWriter::new();
+        {
+            let mut segment = Segment::new();
+            segment.Init(&mut writer).unwrap();
+            AddAudioTrack(&mut segment);
+            let track = segment.GetTrackByNumber(kAudioTrackNumber as u64).unwrap();
+            track.set_lacing(Lacing::kXiphLacing);
+            track.AddContentCompression(algo, settings).unwrap();
+            segment
+                .AddFrame(&frame1, kAudioTrackNumber as u64, 0, true)
+                .unwrap();
+            segment
+                .AddLacedFrames(&laced, kAudioTrackNumber as u64, 1000000, true)
+                .unwrap();
+            segment.Finalize().unwrap();
+        }
+        let mut reader = BufferReader::new(writer.into_inner());
+        let segment = ParseSegment(&mut reader);
+        let track = segment
+            .GetTracks()
+            .unwrap()
+            .GetTrackByNumber(kAudioTrackNumber as u64)
+            .unwrap();
+        assert_eq!(1, track.content_encoding_count());
+        let encoding = track.GetContentEncoding(0).unwrap();
+        assert_eq!(kCompression, encoding.encoding_type());
+        assert_eq!(1, encoding.scope());
+        assert_eq!(algo as u64, encoding.comp_algo());
+        assert_eq!(settings, encoding.comp_settings());
+
+        let mut cluster = segment.GetFirstCluster(&mut reader).unwrap().unwrap();
+        let mut frame = cluster.GetNextFrame(&mut reader).unwrap().unwrap();
+        assert_ne!(&frame1[..], frame.frame());
+        track.DecodeFrame(&mut frame).unwrap();
+        assert_eq!(&frame1[..], frame.frame());
+
+        let mut frame = cluster.GetNextFrame(&mut reader).unwrap().unwrap();
+        track.DecodeFrame(&mut frame).unwrap();
+        assert_eq!(Lacing::kXiphLacing, frame.lacing());
+        assert_eq!(Some(&frame2[..]), frame.GetFrame(0));
+        assert_eq!(Some(&frame3[..]), frame.GetFrame(1));
+    }
+
+    // A zlib frame inflating past the limit is rejected.
+    let audio = kAudioTrackNumber as u64;
+    let frames = [NewFrame(
+        &vec![0; kMaxDecompressedSize as usize + 1],
+        audio,
+        0,
+        true,
+    )];
+    let mut writer = MemoryWriter::new();
+    let setup = |segment: &mut Segment| {
+        AddAudioTrack(segment);
+        segment
+            .GetTrackByNumber(audio)
+            .unwrap()
+            .AddContentCompression(ContentCompAlgo::kZlib, &[])
+            .unwrap();
+    };
+    MuxFrames(&mut writer, false, setup, &frames, &[]);
+    let mut reader = BufferReader::new(writer.into_inner());
+    let segment = ParseSegment(&mut reader);
+    let track = segment
+        .GetTracks()
+        .unwrap()
+        .GetTrackByNumber(audio)
+        .unwrap();
+    let mut cluster = segment.GetFirstCluster(&mut reader).unwrap().unwrap();
+    let mut frame = cluster.GetNextFrame(&mut reader).unwrap().unwrap();
+    assert!(matches!(
+        track.DecodeFrame(&mut frame),
+        Err(ParseError::kFileFormatInvalid)
+    ));
+}
+
+#[test]
+fn content_encryption() {
+    let data: Vec<u8> = (0..32).collect();
+    let video = kVideoTrackNumber as u64;
+    let frames: Vec<Frame> = (0..2)
+        .map(|i| NewFrame(&data, video, i * 1000000, i == 0))
+        .collect();
+    let mut writer = MemoryWriter::new();
+    let setup = |segment: &mut Segment| {
+        AddVideoTrack(segment);
+        AddEncryption(segment, video);
+    };
+    // Encrypted tracks are allowed in WebM.
+    let doc_type = MuxFrames(&mut writer, true, setup, &frames, &[]).doc_type();
+    assert_eq!(DocType::kWebm, doc_type);
+
+    let mut reader = BufferReader::new(writer.into_inner());
+    let segment = ParseSegment(&mut reader);
+    let track = segment
+        .GetTracks()
+        .unwrap()
+        .GetTrackByNumber(kVideoTrackNumber as u64)
+        .unwrap();
+    assert_eq!(1, track.content_encoding_count());
+    let encoding = track.GetContentEncoding(0).unwrap();
+    assert_eq!(kEncryption, encoding.encoding_type());
+    assert_eq!(5, encoding.enc_algo());
+    assert_eq!(b"key id", encoding.enc_key_id());
+    assert_eq!(1, encoding.aes_cipher_mode());
+
+    // Every frame carries its own IV and decrypts to the original data.
+    let mut cluster = segment.GetFirstCluster(&mut reader).unwrap().unwrap();
+    for iv in kEncryptionIV..kEncryptionIV + 2 {
+        let mut frame = cluster.GetNextFrame(&mut reader).unwrap().unwrap();
+        assert_eq!(&iv.to_be_bytes()[..], &frame.frame()[1..9]);
+        track.DecryptFrame(&mut frame, &kEncryptionKey).unwrap();
+        assert_eq!(&data[..], frame.frame());
+    }
+}
+
+// Muxes six video frames with a key frame every 3ms, encrypting the track
+// if |encrypt| is set. Key frames keep their first 4 and last 12 bytes in the
+// clear.
+fn MuxPartitionedFrames(encrypt: bool) -> Vec<u8> {
+    let video = kVideoTrackNumber as u64;
+    let frames: Vec<Frame> = (0..6u8)
+        .map(|i| {
+            let data: Vec<u8> = (i..i + 32).collect();
+            let mut frame = NewFrame(&data, video, i as u64 * 1000000, i % 3 == 0);
+            if encrypt && frame.is_key() {
+                frame.set_partition_offsets(&[4, 20]);
+            }
+            frame
+        })
+        .collect();
+
+    let mut writer = MemoryWriter::new();
+    let setup = |segment: &mut Segment| {
+        AddVideoTrack(segment);
+        if encrypt {
+            AddEncryption(segment, video);
+        }
+    };
+    MuxFrames(&mut writer, true, setup, &frames, &[]);
+    writer.into_inner()
+}
+
+#[test]
+fn partitioned_encryption() {
+    let mut reader = BufferReader::new(MuxPartitionedFrames(true));
+    let mut segment = ParseSegment(&mut reader);
+
+    // Every frame decrypts to the original data, whether partitioned or not.
+    let track = segment
+        .GetTracks()
+        .unwrap()
+        .GetTrackByNumber(kVideoTrackNumber as u64)
+        .unwrap();
+    let mut frames = 0;
+    let mut cluster = segment.GetFirstCluster(&mut reader).unwrap();
+    while let Some(mut c) = cluster {
+        while let Some(mut frame) = c.GetNextFrame(&mut reader).unwrap() {
+            track.DecryptFrame(&mut frame, &kEncryptionKey).unwrap();
+            let expected: Vec<u8> = (frames..frames + 32).collect();
+            assert_eq!(&expected[..], frame.frame());
+            frames += 1;
+        }
+        cluster = segment.GetNextCluster(&mut reader, &c).unwrap();
+    }
+    assert_eq!(6, frames);
+
+    // Cues and seeking are the same as for the clear file.
+    let mut clear_reader = BufferReader::new(MuxPartitionedFrames(false));
+    let mut clear_segment = ParseSegment(&mut clear_reader);
+    let clear_cues = clear_segment.LoadCues(&mut clear_reader).unwrap().unwrap();
+    assert_eq!(2, clear_cues.cue_entries_size());
+    let cues = segment.LoadCues(&mut reader).unwrap().unwrap();
+    assert_eq!(2, cues.cue_entries_size());
+    let video = kVideoTrackNumber as u64;
+    for &time in &[0, 2000000, 3000000, 5000000] {
+        assert_eq!(
+            SeekTimestamp(&mut clear_reader, &mut clear_segment, video, time),
+            SeekTimestamp(&mut reader, &mut segment, video, time)
+        );
+    }
+}
+
+#[test]
+fn multiple_content_encodings() {
+    let codec_private = vec![7u8; 100];
+    let frames: Vec<Vec<u8>> = (0..3u8).map(|i| vec![i; 64]).collect();
+    let mut writer = MemoryWriter::new();
+    {
+        let mut segment = Segment::new();
+        segment.Init(&mut writer).unwrap();
+        AddAudioTrack(&mut segment);
+        let track = segment.GetTrackByNumber(kAudioTrackNumber as u64).unwrap();
+        track.set_codec_private(&codec_private);
+
+        // The frames and the CodecPrivate are compressed, then the frames are
+        // encrypted.
+        let mut compression = ContentEncoding::new_compression(ContentCompAlgo::kZlib, &[]);
+        compression.set_encoding_scope(kScopeFrames | kScopeCodecPrivate);
+        track.AddContentEncoding(compression).unwrap();
+        track
+            .AddContentEncryption(&kEncryptionKey, b"key id")
+            .unwrap();
+        assert_eq!(1, track.GetContentEncoding(1).unwrap().encoding_order());
+
+        for (i, frame) in frames.iter().enumerate() {
+            segment
+                .AddFrame(frame, kAudioTrackNumber as u64, i as u64 * 1000000, true)
+                .unwrap();
+        }
+        segment.Finalize().unwrap();
+    }
+
+    let mut reader = BufferReader::new(writer.into_inner());
+    let segment = ParseSegment(&mut reader);
+    let track = segment
+        .GetTracks()
+        .unwrap()
+        .GetTrackByNumber(kAudioTrackNumber as u64)
+        .unwrap();
+    assert_eq!(2, track.content_encoding_count());
+    let compression = track.GetContentEncoding(0).unwrap();
+    assert_eq!(
+        (0, 3, kCompression),
+        (
+            compression.order(),
+            compression.scope(),
+            compression.encoding_type()
+        )
+    );
+    let encryption = track.GetContentEncoding(1).unwrap();
+    assert_eq!(
+        (1, 1, kEncryption),
+        (
+            encryption.order(),
+            encryption.scope(),
+            encryption.encoding_type()
+        )
+    );
+    assert!(track.codec_private().len() < codec_private.len());
+    assert_eq!(codec_private, track.DecodeCodecPrivate().unwrap());
+
+    // The encodings are not copied to a muxer track, so its CodecPrivate is
+    // the decoded one.
+    let mut copy = AudioTrack::new();
+    track.as_audio().unwrap().CopyTo(&mut copy).unwrap();
+    assert_eq!(&codec_private[..], copy.codec_private());
+    assert_eq!(0, copy.content_encoding_count());
+
+    let mut decoded = Vec::new();
+    let mut cluster = segment.GetFirstCluster(&mut reader).unwrap();
+    while let Some(mut c) = cluster {
+        while let Some(mut frame) = c.GetNextFrame(&mut reader).unwrap() {
+            assert_eq!(0x01, frame.frame()[0]);
+            // Without the key, the compression under the encryption is left
+            // alone too.
+            let encrypted = frame.frame().to_vec();
+            track.DecodeFrame(&mut frame).unwrap();
+            assert_eq!(&encrypted[..], frame.frame());
+            track.DecryptFrame(&mut frame, &kEncryptionKey).unwrap();
+            decoded.push(frame.frame().to_vec());
+        }
+        cluster = segment.GetNextCluster(&mut reader, &c).unwrap();
+    }
+    assert_eq!(frames, decoded);
+}
+
+#[test]
+fn opus_track() {
+    let mut config = OpusConfig::new(2);
+    config.set_pre_skip(312);
+    config.set_input_sample_rate(44100);
+    config.set_output_gain(-256);
+    let audio = kAudioTrackNumber as u64;
+    let mut writer = MemoryWriter::new();
+    {
+        let mut segment = Segment::new();
+        segment.Init(&mut writer).unwrap();
+        assert_eq!(
+            audio,
+            segment.AddOpusTrack(&config, kAudioTrackNumber).unwrap()
+        );
+        segment
+            .AddFrameWithDiscardPadding(&[0; 10], 2500000, audio, 0, true)
+            .unwrap();
+        segment.Finalize().unwrap();
+    }
+
+    let mut reader = BufferReader::new(writer.into_inner());
+    let segment = ParseSegment(&mut reader);
+    let track = segment
+        .GetTracks()
+        .unwrap()
+        .GetTrackByNumber(audio)
+        .unwrap()
+        .as_audio()
+        .unwrap();
+    assert_eq!(kOpusCodecId, track.codec_id());
+    assert_eq!(kOpusCodecDelay, track.codec_delay());
+    assert_eq!(kOpusSeekPreroll, track.seek_pre_roll());
+    assert_eq!(48000.0, track.sample_rate());
+    assert_eq!(2, track.channels());
+    let head = track.codec_private();
+    assert_eq!(kOpusPrivateDataSizeMinimum, head.len());
+    assert_eq!(b"OpusHead", &head[..8]);
+    assert_eq!(
+        &[1, 2, 0x38, 0x01, 0x44, 0xAC, 0x00, 0x00, 0x00, 0xFF, 0][..],
+        &head[8..]
+    );
+    let mut cluster = segment.GetFirstCluster(&mut reader).unwrap().unwrap();
+    let frame = cluster.GetNextFrame(&mut reader).unwrap().unwrap();
+    assert_eq!(2500000, frame.discard_padding());
+
+    // Families other than 0 carry a channel mapping table.
+    let mut config = OpusConfig::new(6);
+    assert!(config.OpusHead().is_err());
+    config.set_channel_mapping(1, 4, 2, &[0, 4, 1, 2, 3, 5]);
+    let head = config.OpusHead().unwrap();
+    assert_eq!(kOpusPrivateDataSizeMinimum + 2 + 6, head.len());
+    assert_eq!(&[1, 4, 2, 0, 4, 1, 2, 3, 5][..], &head[18..]);
+    config.set_channel_mapping(1, 4, 2, &[0, 4, 1, 2, 3]);
+    assert!(config.OpusHead().is_err());
+    config.set_channel_mapping(1, 4, 2, &[0, 4, 1, 2, 3, 6]);
+    assert!(config.OpusHead().is_err());
+    config.set_channel_mapping(1, 2, 3, &[0, 4, 1, 2, 3, 255]);
+    assert!(config.OpusHead().is_err());
+    assert!(OpusConfig::new(0).OpusHead().is_err());
+}
+
+#[test]
+fn iterate_clusters() {
+    let mut reader = OpenTestFile("bbb_480p_vp9_opus_1second.webm");
+    let segment = ParseSegment(&mut reader);
+
+    let mut clusters = 0;
+    let mut video_frames = 0;
+    let mut audio_frames = 0;
+    let mut last_video_timestamp = None;
+    let mut cluster = segment.GetFirstCluster(&mut reader).unwrap();
+    while let Some(mut c) = cluster {
+        clusters += 1;
+        while let Some(frame) = c.GetNextFrame(&mut reader).unwrap() {
+            assert!(frame.timestamp() >= c.GetTime().unwrap() as u64);
+            match frame.track_number() {
+                1 => {
+                    if last_video_timestamp.is_none() {
+                        assert!(frame.is_key());
+                    }
+                    assert!(Some(frame.timestamp()) > last_video_timestamp);
+                    last_video_timestamp = Some(frame.timestamp());
+                    video_frames += 1;
+                }
+                2 => audio_frames += 1,
+                n => panic!("unexpected track {}", n),
+            }
+        }
+        cluster = segment.GetNextCluster(&mut reader, &c).unwrap();
+    }
+    assert!(clusters >= 1);
+    assert_eq!(24, video_frames);
+    assert!(audio_frames > 0);
+}
+
+// Seeks |track| to |time_ns| and returns the timestamp of the next frame.
+fn SeekTimestamp(
+    reader: &mut dyn Reader,
+    segment: &mut ParserSegment,
+    track: u64,
+    time_ns: u64,
+) -> u64 {
+    let mut cluster = segment.seek(reader, track, time_ns).unwrap().unwrap();
+    let frame = cluster.GetNextFrame(reader).unwrap().unwrap();
+    assert_eq!(track, frame.track_number());
+    frame.timestamp()
+}
+
+#[test]
+fn seek_with_cues() {
+    let mut reader = OpenTestFile("cues_before_clusters.webm");
+    let mut segment = ParseSegment(&mut reader);
+
+    assert!(segment.GetCues().is_none());
+    let cues = segment.LoadCues(&mut reader).unwrap().unwrap();
+    assert_eq!(2, cues.cue_entries_size());
+    let (cp, tp) = cues
+        .Find(7000000, kVideoTrackNumber as u64, 1000000)
+        .unwrap()
+        .unwrap();
+    assert_eq!(6, cp.time());
+    assert_eq!(1, tp.block_number());
+    assert!(cp.GetTime(u64::MAX).is_err());
+
+    let track = kVideoTrackNumber as u64;
+    assert_eq!(0, SeekTimestamp(&mut reader, &mut segment, track, 0));
+    assert_eq!(0, SeekTimestamp(&mut reader, &mut segment, track, 5000000));
+    assert_eq!(
+        6000000,
+        SeekTimestamp(&mut reader, &mut segment, track, 6000000)
+    );
+    assert_eq!(
+        6000000,
+        SeekTimestamp(&mut reader, &mut segment, track, 100000000)
+    );
+    assert!(segment
+        .seek(&mut reader, kAudioTrackNumber as u64, 0)
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn seek_with_cues_after_clusters() {
+    let mut reader = OpenTestFile("output_cues.webm");
+    let mut segment = ParseSegment(&mut reader);
+    let track = kVideoTrackNumber as u64;
+    assert_eq!(0, SeekTimestamp(&mut reader, &mut segment, track, 3000000));
+    // The cue point added at 4ms refers to the cluster that was open when it
+    // was added, which starts at 6ms.
+    assert_eq!(
+        6000000,
+        SeekTimestamp(&mut reader, &mut segment, track, 5000000)
+    );
+    assert_eq!(3, segment.GetCues().unwrap().cue_entries_size());
+
+    let mut reader = OpenTestFile("bbb_480p_vp9_opus_1second.webm");
+    let mut segment = ParseSegment(&mut reader);
+    // The only video key frame is at 7ms.
+    assert_eq!(7000000, SeekTimestamp(&mut reader, &mut segment, 1, 0));
+    assert_eq!(
+        7000000,
+        SeekTimestamp(&mut reader, &mut segment, 1, 500000000)
+    );
+    // The Cues only index the video track.
+    assert_eq!(
+        481000000,
+        SeekTimestamp(&mut reader, &mut segment, 2, 500000000)
+    );
+    assert!(segment.GetCues().is_some());
+
+    // Frames after the seek point keep coming in order.
+    let mut cluster = segment.seek(&mut reader, 2, 500000000).unwrap().unwrap();
+    let mut last = 0;
+    while let Some(frame) = cluster.GetNextFrame(&mut reader).unwrap() {
+        assert!(frame.timestamp() >= 481000000);
+        if frame.track_number() == 2 {
+            assert!(frame.timestamp() > last);
+            last = frame.timestamp();
+        }
+    }
+    assert_eq!(1001000000, last);
+}
+
+#[test]
+fn seek_without_cues() {
+    let test = MuxerTest::new();
+    let mut writer = test.writer();
+    {
+        let mut segment = Segment::new();
+        assert!(SegmentInit(&mut segment, &mut writer, false, false, false));
+        AddVideoTrack(&mut segment);
+
+        let track = kVideoTrackNumber as u64;
+        segment.AddFrame(&test.dummy_data_, track, 0, true).unwrap();
+        segment
+            .AddFrame(&test.dummy_data_, track, 2000000, false)
+            .unwrap();
+        segment.ForceNewClusterOnNextFrame();
+        segment
+            .AddFrame(&test.dummy_data_, track, 4000000, false)
+            .unwrap();
+        segment.ForceNewClusterOnNextFrame();
+        segment
+            .AddFrame(&test.dummy_data_, track, 6000000, true)
+            .unwrap();
+        segment
+            .AddFrame(&test.dummy_data_, track, 8000000, false)
+            .unwrap();
+        segment.Finalize().unwrap();
+    }
+    let mut reader = BufferReader::new(writer.into_inner());
+    let mut segment = ParseSegment(&mut reader);
+    let track = kVideoTrackNumber as u64;
+    assert_eq!(0, SeekTimestamp(&mut reader, &mut segment, track, 0));
+    assert_eq!(0, SeekTimestamp(&mut reader, &mut segment, track, 4000000));
+    assert_eq!(0, SeekTimestamp(&mut reader, &mut segment, track, 5000000));
+    assert_eq!(
+        6000000,
+        SeekTimestamp(&mut reader, &mut segment, track, 7000000)
+    );
+    assert_eq!(
+        6000000,
+        SeekTimestamp(&mut reader, &mut segment, track, 9000000)
+    );
+    assert!(segment.GetCues().is_none());
+}
+
+#[test]
+fn load_chapters_and_tags() {
+    let mut reader = OpenTestFile("chapters.webm");
+    let mut segment = ParseSegment(&mut reader);
+    assert!(segment.GetChapters().is_none());
+    assert!(segment.LoadTags(&mut reader).unwrap().is_none());
+    let scale = segment.GetInfo().unwrap().timecode_scale();
+    let chapters = segment.LoadChapters(&mut reader).unwrap().unwrap();
+    assert_eq!(1, chapters.edition_count());
+    let edition = chapters.GetEdition(0).unwrap();
+    assert_eq!(1, edition.atom_count());
+    let atom = edition.GetAtom(0).unwrap();
+    assert_eq!(1, atom.uid());
+    assert_eq!(kTrackName, atom.string_uid());
+    assert_eq!(0, atom.GetStartTime(scale).unwrap());
+    assert_eq!(1000 * scale, atom.GetEndTime(scale).unwrap());
+    assert!(atom.GetEndTime(u64::MAX).is_err());
+    assert_eq!(1, atom.display_count());
+    let display = atom.GetDisplay(0).unwrap();
+    assert_eq!(kTrackName, display.string());
+    assert_eq!("english", display.language());
+    assert_eq!("us", display.country());
+
+    let mut reader = OpenTestFile("long_tag_string.webm");
+    let mut segment = ParseSegment(&mut reader);
+    assert!(segment.LoadChapters(&mut reader).unwrap().is_none());
+    let tags = segment.LoadTags(&mut reader).unwrap().unwrap();
+    assert_eq!(1, tags.Count());
+    let tag = tags.GetTag(0).unwrap();
+    assert_eq!(1, tag.simple_tag_count());
+    let simple_tag = tag.GetSimpleTag(0).unwrap();
+    assert_eq!("long_tag", simple_tag.tag_name());
+    assert_eq!(160, simple_tag.tag_string().len());
+}
+
+#[test]
+fn nested_chapters_and_editions() {
+    let mut writer = MemoryWriter::new();
+    {
+        let mut segment = Segment::new();
+        assert!(SegmentInit(&mut segment, &mut writer, false, false, false));
+        AddVideoTrack(&mut segment);
+
+        let edition = segment.AddEdition();
+        edition.set_uid(10);
+        edition.set_default(true);
+        let lecture = edition.AddEmptyChapter();
+        lecture.set_uid(1);
+        lecture.set_time(1000000, 0, 60000000000);
+        lecture.add_string("lecture", "eng", "");
+        let section = lecture.AddEmptyChapter();
+        section.set_uid(2);
+        section.set_time(1000000, 0, 30000000000);
+        section.set_hidden(true);
+        let subsection = section.AddEmptyChapter();
+        subsection.set_uid(3);
+        subsection.set_enabled(false);
+        subsection.set_segment_uid(&[9; 16]);
+        let process = subsection.AddProcess(0);
+        process.set_private(&[1, 2]);
+        process.AddCommand(1, b"GotoAndPlay( 0 );");
+
+        let edition = segment.AddEdition();
+        edition.set_uid(20);
+        edition.set_hidden(true);
+        edition.set_ordered(true);
+        let chapter = segment.AddChapter();
+        chapter.set_uid(4);
+        chapter.add_string("cut", "", "");
+
+        let track = kVideoTrackNumber as u64;
+        segment.AddFrame(&[0; 10], track, 0, true).unwrap();
+        segment.Finalize().unwrap();
+    }
+
+    let mut reader = BufferReader::new(writer.into_inner());
+    let mut segment = ParseSegment(&mut reader);
+    let chapters = segment.LoadChapters(&mut reader).unwrap().unwrap();
+    assert_eq!(2, chapters.edition_count());
+
+    let edition = chapters.GetEdition(0).unwrap();
+    assert_eq!(10, edition.uid());
+    assert!(edition.default() && !edition.hidden() && !edition.ordered());
+    assert_eq!(1, edition.atom_count());
+    let lecture = edition.GetAtom(0).unwrap();
+    assert_eq!(1, lecture.uid());
+    assert_eq!(60000, lecture.end_timecode());
+    assert!(!lecture.hidden() && lecture.enabled());
+    assert_eq!("lecture", lecture.GetDisplay(0).unwrap().string());
+    assert_eq!(1, lecture.atom_count());
+    let section = lecture.GetAtom(0).unwrap();
+    assert_eq!(2, section.uid());
+    assert_eq!(30000, section.end_timecode());
+    assert!(section.hidden() && section.enabled());
+    assert_eq!(1, section.atom_count());
+    let subsection = section.GetAtom(0).unwrap();
+    assert_eq!(3, subsection.uid());
+    assert!(!subsection.enabled());
+    assert_eq!(&[9; 16], subsection.segment_uid());
+    assert_eq!(0, subsection.atom_count());
+    assert_eq!(1, subsection.process_count());
+    let process = subsection.GetProcess(0).unwrap();
+    assert_eq!(0, process.codec_id());
+    assert_eq!(&[1, 2], process.private());
+    assert_eq!(1, process.command_count());
+    let command = process.GetCommand(0).unwrap();
+    assert_eq!(1, command.time());
+    assert_eq!(b"GotoAndPlay( 0 );", command.data());
+
+    let edition = chapters.GetEdition(1).unwrap();
+    assert_eq!(20, edition.uid());
+    assert!(!edition.default() && edition.hidden() && edition.ordered());
+    assert_eq!(1, edition.atom_count());
+    assert_eq!(4, edition.GetAtom(0).unwrap().uid());
+    assert!(edition.GetAtom(0).unwrap().segment_uid().is_empty());
+}
+
+#[test]
+fn tag_targets_and_nested_simple_tags() {
+    let mut writer = MemoryWriter::new();
+    {
+        let mut segment = Segment::new();
+        assert!(SegmentInit(&mut segment, &mut writer, false, false, false));
+        AddVideoTrack(&mut segment);
+
+        // Per-track statistics, as written by mkvmerge.
+        let tag = segment.AddTag();
+        tag.targets().AddTrackUID(kVideoTrackNumber as u64);
+        tag.add_simple_tag("BPS", "8000");
+        tag.add_simple_tag("NUMBER_OF_FRAMES", "1");
+        tag.AddSimpleTag("ENCODER_SETTINGS", "--good")
+            .set_tag_language("eng");
+
+        let tag = segment.AddTag();
+        tag.targets().set_type_value(30);
+        tag.targets().set_type("CHAPTER");
+        tag.targets().AddChapterUID(7);
+        tag.targets().AddEditionUID(8);
+        tag.targets().AddAttachmentUID(9);
+        let publisher = tag.AddSimpleTag("PUBLISHER", "webm");
+        publisher.set_default(false);
+        publisher.AddSimpleTag("URL", "https://www.webmproject.org");
+        tag.AddSimpleTag("THUMBNAIL", "").set_tag_binary(&[1, 2, 3]);
+
+        let track = kVideoTrackNumber as u64;
+        segment.AddFrame(&[0; 10], track, 0, true).unwrap();
+        segment.Finalize().unwrap();
+    }
+
+    let mut reader = BufferReader::new(writer.into_inner());
+    let mut segment = ParseSegment(&mut reader);
+    let tags = segment.LoadTags(&mut reader).unwrap().unwrap();
+    assert_eq!(2, tags.Count());
+
+    let tag = tags.GetTag(0).unwrap();
+    let targets = tag.targets();
+    assert_eq!(50, targets.type_value());
+    assert_eq!(&[kVideoTrackNumber as u64], targets.track_uids());
+    assert!(targets.chapter_uids().is_empty());
+    assert_eq!(3, tag.simple_tag_count());
+    let bps = tag.GetSimpleTag(0).unwrap();
+    assert_eq!("BPS", bps.tag_name());
+    assert_eq!("8000", bps.tag_string());
+    assert_eq!("und", bps.tag_language());
+    assert!(bps.default());
+    let settings = tag.GetSimpleTag(2).unwrap();
+    assert_eq!("--good", settings.tag_string());
+    assert_eq!("eng", settings.tag_language());
+
+    let tag = tags.GetTag(1).unwrap();
+    let targets = tag.targets();
+    assert_eq!(30, targets.type_value());
+    assert_eq!("CHAPTER", targets.target_type());
+    assert!(targets.track_uids().is_empty());
+    assert_eq!(&[7], targets.chapter_uids());
+    assert_eq!(&[8], targets.edition_uids());
+    assert_eq!(&[9], targets.attachment_uids());
+    assert_eq!(2, tag.simple_tag_count());
+    let publisher = tag.GetSimpleTag(0).unwrap();
+    assert!(!publisher.default());
+    assert_eq!(1, publisher.simple_tag_count());
+    let url = publisher.GetSimpleTag(0).unwrap();
+    assert_eq!("URL", url.tag_name());
+    assert_eq!("https://www.webmproject.org", url.tag_string());
+    let thumbnail = tag.GetSimpleTag(1).unwrap();
+    assert_eq!(&[1, 2, 3], thumbnail.tag_binary());
+    assert!(thumbnail.tag_string().is_empty());
+}
+
+#[test]
+fn attachments() {
+    let font = vec![0x5A; 3000];
+    let cover = vec![0xFF, 0xD8, 0xFF, 0xE0];
+    let mut writer = MemoryWriter::new();
+    {
+        let mut segment = Segment::new();
+        assert!(SegmentInit(&mut segment, &mut writer, true, false, false));
+        AddVideoTrack(&mut segment);
+        segment.AddChapter().add_string("chapter", "", "");
+        segment.AddTag().add_simple_tag("TITLE", "attachments");
+
+        let file = segment.AddAttachment().unwrap();
+        file.set_file_name("font.ttf");
+        file.set_mime_type("font/ttf");
+        file.set_data(&font);
+        file.set_uid(1);
+        let file = segment.AddAttachment().unwrap();
+        file.set_file_name("cover.jpg");
+        file.set_mime_type("image/jpeg");
+        file.set_description("Cover art");
+        file.set_data(&cover);
+        file.set_uid(2);
+
+        let track = kVideoTrackNumber as u64;
+        segment.AddFrame(&[0; 10], track, 0, true).unwrap();
+        segment.Finalize().unwrap();
+    }
+
+    let mut reader = BufferReader::new(writer.into_inner());
+    let mut segment = ParseSegment(&mut reader);
+    // Chapters, Tags and Attachments all get a SeekHead entry.
+    for &id in &[
+        MkvId::MkvCues,
+        MkvId::MkvChapters,
+        MkvId::MkvTags,
+        MkvId::MkvAttachments,
+    ] {
+        assert!(segment.GetElementPosition(id).is_some(), "{:?}", id);
+    }
+    assert!(segment.LoadChapters(&mut reader).unwrap().is_some());
+    assert!(segment.LoadTags(&mut reader).unwrap().is_some());
+    let attachments = segment.LoadAttachments(&mut reader).unwrap().unwrap();
+    assert_eq!(2, attachments.count());
+    let file = attachments.GetFile(0).unwrap();
+    assert_eq!("font.ttf", file.file_name());
+    assert_eq!("font/ttf", file.mime_type());
+    assert_eq!(&font[..], file.data());
+    assert_eq!(1, file.uid());
+    assert!(file.description().is_empty());
+    let file = attachments.GetFileByUID(2).unwrap();
+    assert_eq!("cover.jpg", file.file_name());
+    assert_eq!("image/jpeg", file.mime_type());
+    assert_eq!("Cover art", file.description());
+    assert_eq!(&cover[..], file.data());
+
+    // An attached file needs a name and a MIME type.
+    let mut writer = MemoryWriter::new();
+    let mut segment = Segment::new();
+    assert!(SegmentInit(&mut segment, &mut writer, false, false, false));
+    AddVideoTrack(&mut segment);
+    segment.AddAttachment().unwrap().set_file_name("font.ttf");
+    let track = kVideoTrackNumber as u64;
+    assert!(segment.AddFrame(&[0; 10], track, 0, true).is_err());
+}
+
+// Muxes a video and an audio track with |video_codec| and |audio_codec| and
+// returns the DocType of the file and the codec ids read back.
+fn MuxWithCodecs(
+    doc_type: Option<DocType>,
+    video_codec: &str,
+    audio_codec: &str,
+) -> (String, String, String) {
+    let video = kVideoTrackNumber as u64;
+    let audio = kAudioTrackNumber as u64;
+    let frames = [
+        NewFrame(&[0; 10], video, 0, true),
+        NewFrame(&[0; 10], audio, 0, true),
+    ];
+    let mut writer = MemoryWriter::new();
+    let setup = |segment: &mut Segment| {
+        if let Some(doc_type) = doc_type {
+            segment.set_doc_type(doc_type).unwrap();
+        }
+        AddVideoTrack(segment);
+        AddAudioTrack(segment);
+        segment
+            .GetTrackByNumber(video)
+            .unwrap()
+            .set_codec_id(video_codec);
+        segment
+            .GetTrackByNumber(audio)
+            .unwrap()
+            .set_codec_id(audio_codec);
+    };
+    MuxFrames(&mut writer, false, setup, &frames, &[]);
+
+    let mut reader = BufferReader::new(writer.into_inner());
+    let mut header = EBMLHeader::new();
+    header.Parse(&mut reader, 0).unwrap();
+    let segment = ParseSegment(&mut reader);
+    let tracks = segment.GetTracks().unwrap();
+    (
+        header.doc_type().to_string(),
+        tracks.GetTrackByNumber(1).unwrap().codec_id().to_string(),
+        tracks.GetTrackByNumber(2).unwrap().codec_id().to_string(),
+    )
+}
+
+#[test]
+fn doc_type() {
+    let (doc_type, video, audio) = MuxWithCodecs(None, "V_VP9", "A_OPUS");
+    assert_eq!("webm", doc_type);
+    assert_eq!(("V_VP9", "A_OPUS"), (video.as_str(), audio.as_str()));
+    let (doc_type, _, _) = MuxWithCodecs(None, "V_MPEG4/ISO/AVC", "A_OPUS");
+    assert_eq!("matroska", doc_type);
+    let (doc_type, _, _) = MuxWithCodecs(Some(DocType::kWebm), "V_VP8", "A_VORBIS");
+    assert_eq!("webm", doc_type);
+    let (doc_type, video, audio) =
+        MuxWithCodecs(Some(DocType::kMatroska), "V_MPEG4/ISO/AVC", "A_AAC");
+    assert_eq!("matroska", doc_type);
+    assert_eq!(
+        ("V_MPEG4/ISO/AVC", "A_AAC"),
+        (video.as_str(), audio.as_str())
+    );
+    // WebM codecs can be written to a Matroska file.
+    let (doc_type, _, _) = MuxWithCodecs(Some(DocType::kMatroska), "V_VP8", "A_VORBIS");
+    assert_eq!("matroska", doc_type);
+
+    // A WebM Segment rejects other codecs once the headers are written.
+    let mut writer = MemoryWriter::new();
+    let mut segment = Segment::new();
+    assert!(SegmentInit(&mut segment, &mut writer, false, false, false));
+    segment.set_doc_type(DocType::kWebm).unwrap();
+    AddAudioTrack(&mut segment);
+    let audio = kAudioTrackNumber as u64;
+    segment
+        .GetTrackByNumber(audio)
+        .unwrap()
+        .set_codec_id("A_AAC");
+    assert!(segment.AddFrame(&[0; 10], audio, 0, true).is_err());
+
+    // And other codecs, attachments and signatures as soon as they are used.
+    let mut writer = MemoryWriter::new();
+    let mut segment = Segment::new();
+    assert!(SegmentInit(&mut segment, &mut writer, false, false, false));
+    AddAudioTrack(&mut segment);
+    segment.set_doc_type(DocType::kWebm).unwrap();
+    assert!(segment.AddAttachment().is_err());
+    let signer = |hash: &[u8]| hash.to_vec();
+    assert!(segment
+        .SetSigner(&[MkvId::MkvInfo], SignatureAlgo::kRsa, &[1], signer)
+        .is_err());
+    segment.AddChapter().AddProcess(0);
+    assert!(segment.AddFrame(&[0; 10], audio, 0, true).is_err());
+
+    // Compressed tracks are Matroska only.
+    let mut writer = MemoryWriter::new();
+    let mut segment = Segment::new();
+    assert!(SegmentInit(&mut segment, &mut writer, false, false, false));
+    AddAudioTrack(&mut segment);
+    segment
+        .GetTrackByNumber(audio)
+        .unwrap()
+        .AddContentCompression(ContentCompAlgo::kZlib, &[])
+        .unwrap();
+    assert_eq!(DocType::kMatroska, segment.doc_type());
+    assert!(segment.set_doc_type(DocType::kWebm).is_err());
+
+    let mut writer = MemoryWriter::new();
+    let mut segment = Segment::new();
+    assert!(SegmentInit(&mut segment, &mut writer, false, false, false));
+    AddAudioTrack(&mut segment);
+    segment
+        .GetTrackByNumber(audio)
+        .unwrap()
+        .set_codec_id("A_AAC");
+    assert!(segment.set_doc_type(DocType::kWebm).is_err());
+    segment.set_doc_type(DocType::kMatroska).unwrap();
+    segment.AddFrame(&[0; 10], audio, 0, true).unwrap();
+    // The DocType can't change once the headers are written.
+    assert!(segment.set_doc_type(DocType::kMatroska).is_err());
+}
+
+#[test]
+fn invalid_chapters() {
+    // An edition without chapters cannot be written. Chapters are written
+    // with the headers, before the first frame.
+    let mut writer = MemoryWriter::new();
+    let mut segment = Segment::new();
+    assert!(SegmentInit(&mut segment, &mut writer, false, false, false));
+    AddVideoTrack(&mut segment);
+    segment.AddChapter().set_uid(1);
+    segment.AddEdition();
+    let track = kVideoTrackNumber as u64;
+    assert!(segment.AddFrame(&[0; 10], track, 0, true).is_err());
+
+    // ChapterSegmentUID must be 16 bytes.
+    let mut writer = MemoryWriter::new();
+    let mut segment = Segment::new();
+    assert!(SegmentInit(&mut segment, &mut writer, false, false, false));
+    AddVideoTrack(&mut segment);
+    segment.AddChapter().set_segment_uid(&[1; 8]);
+    assert!(segment.AddFrame(&[0; 10], track, 0, true).is_err());
+}
+
+// Encodes an element with a known size.
+fn Element(id: MkvId, payload: &[u8]) -> Vec<u8> {
+    let id = id as u64;
+    let id_len = (8 - id.leading_zeros() / 8) as usize;
+    let mut out = id.to_be_bytes()[8 - id_len..].to_vec();
+    let size = payload.len() as u64;
+    if size < 0x7F {
+        out.push(0x80 | size as u8);
+    } else {
+        out.extend_from_slice(&(0x4000 | size as u16).to_be_bytes());
+    }
+    out.extend_from_slice(payload);
+    out
+}
+
+// Encodes an unsigned integer element with a fixed size.
+fn UIntElement(id: MkvId, value: u64, size: usize) -> Vec<u8> {
+    Element(id, &value.to_be_bytes()[8 - size..])
+}
+
+fn SeekElement(id: MkvId, pos: u64) -> Vec<u8> {
+    let id = id as u32;
+    Element(
+        MkvId::MkvSeek,
+        &[
+            Element(MkvId::MkvSeekID, &id.to_be_bytes()),
+            UIntElement(MkvId::MkvSeekPosition, pos, 4),
+        ]
+        .concat(),
+    )
+}
+
+// Returns the EBML header, Info and Tracks of a file with one VP8 track.
+fn RawHeaders() -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+    let ebml = Element(
+        MkvId::MkvEBML,
+        &[
+            Element(MkvId::MkvDocType, b"webm"),
+            UIntElement(MkvId::MkvDocTypeVersion, 2, 1),
+            UIntElement(MkvId::MkvDocTypeReadVersion, 2, 1),
+        ]
+        .concat(),
+    );
+    let info = Element(
+        MkvId::MkvInfo,
+        &UIntElement(MkvId::MkvTimecodeScale, 1000000, 3),
+    );
+    let tracks = Element(
+        MkvId::MkvTracks,
+        &Element(
+            MkvId::MkvTrackEntry,
+            &[
+                UIntElement(MkvId::MkvTrackNumber, 1, 1),
+                UIntElement(MkvId::MkvTrackUID, 1, 1),
+                UIntElement(MkvId::MkvTrackType, 1, 1),
+                Element(MkvId::MkvCodecID, kVP8CodecId.as_bytes()),
+                Element(
+                    MkvId::MkvVideo,
+                    &[
+                        UIntElement(MkvId::MkvPixelWidth, 2, 1),
+                        UIntElement(MkvId::MkvPixelHeight, 2, 1),
+                    ]
+                    .concat(),
+                ),
+            ]
+            .concat(),
+        ),
+    );
+    (ebml, info, tracks)
+}
+
+#[test]
+fn timestamp_overflow() {
+    let (ebml, info, tracks) = RawHeaders();
+    let simple_block = Element(MkvId::MkvSimpleBlock, &[0x81, 0x00, 0x01, 0x80, 0xAA]);
+    let block = Element(MkvId::MkvBlock, &[0x81, 0x00, 0x00, 0x00, 0xAA]);
+    let block_group =
+        |child: Vec<u8>| Element(MkvId::MkvBlockGroup, &[block.clone(), child].concat());
+    // The Cluster Timecode, the Block timecode, the BlockDuration and the
+    // ReferenceBlock can't overflow when scaled to nanoseconds.
+    for (timecode, block) in [
+        (u64::MAX / 2, simple_block.clone()),
+        (i64::MAX as u64, simple_block),
+        (
+            0,
+            block_group(UIntElement(MkvId::MkvBlockDuration, u64::MAX, 8)),
+        ),
+        (
+            0,
+            block_group(Element(
+                MkvId::MkvReferenceBlock,
+                &(i64::MIN / 1000).to_be_bytes(),
+            )),
+        ),
+    ] {
+        let cluster = Element(
+            MkvId::MkvCluster,
+            &[UIntElement(MkvId::MkvTimecode, timecode, 8), block].concat(),
+        );
+        let payload = [info.clone(), tracks.clone(), cluster].concat();
+        let data = [ebml.clone(), Element(MkvId::MkvSegment, &payload)].concat();
+        let mut reader = BufferReader::new(data);
+        let segment = ParseSegment(&mut reader);
+        let mut cluster = segment.GetFirstCluster(&mut reader).unwrap().unwrap();
+        assert_eq!(timecode != 0, cluster.GetTime().is_err());
+        assert!(matches!(
+            cluster.GetNextFrame(&mut reader),
+            Err(ParseError::kFileFormatInvalid)
+        ));
+    }
+}
+
+#[test]
+fn multiple_seek_heads() {
+    let (ebml, info, tracks) = RawHeaders();
+    let cluster = Element(
+        MkvId::MkvCluster,
+        &[
+            UIntElement(MkvId::MkvTimecode, 0, 1),
+            Element(MkvId::MkvSimpleBlock, &[0x81, 0x00, 0x00, 0x80, 0xAA]),
+        ]
+        .concat(),
+    );
+    let tags = Element(
+        MkvId::MkvTags,
+        &Element(
+            MkvId::MkvTag,
+            &Element(
+                MkvId::MkvSimpleTag,
+                &[
+                    Element(MkvId::MkvTagName, b"A"),
+                    Element(MkvId::MkvTagString, b"B"),
+                ]
+                .concat(),
+            ),
+        ),
+    );
+
+    // The first SeekHead only points to a second one after the Cluster,
+    // which indexes the Cues and Tags. Entries have a fixed size, so the
+    // layout can be computed up front.
+    let seek_head_size = Element(MkvId::MkvSeekHead, &SeekElement(MkvId::MkvSeekHead, 0)).len();
+    let cluster_pos = (seek_head_size + info.len() + tracks.len()) as u64;
+    let seek_head2_pos = cluster_pos + cluster.len() as u64;
+    let seek_head = Element(
+        MkvId::MkvSeekHead,
+        &SeekElement(MkvId::MkvSeekHead, seek_head2_pos),
+    );
+    let cues = Element(
+        MkvId::MkvCues,
+        &Element(
+            MkvId::MkvCuePoint,
+            &[
+                UIntElement(MkvId::MkvCueTime, 0, 1),
+                Element(
+                    MkvId::MkvCueTrackPositions,
+                    &[
+                        UIntElement(MkvId::MkvCueTrack, 1, 1),
+                        UIntElement(MkvId::MkvCueClusterPosition, cluster_pos, 4),
+                    ]
+                    .concat(),
+                ),
+            ]
+            .concat(),
+        ),
+    );
+    let seek_head2_size = Element(
+        MkvId::MkvSeekHead,
+        &[
+            SeekElement(MkvId::MkvCues, 0),
+            SeekElement(MkvId::MkvTags, 0),
+        ]
+        .concat(),
+    )
+    .len();
+    let cues_pos = seek_head2_pos + seek_head2_size as u64;
+    let tags_pos = cues_pos + cues.len() as u64;
+    let seek_head2 = Element(
+        MkvId::MkvSeekHead,
+        &[
+            SeekElement(MkvId::MkvCues, cues_pos),
+            SeekElement(MkvId::MkvTags, tags_pos),
+        ]
+        .concat(),
+    );
+
+    let payload = [seek_head, info, tracks, cluster, seek_head2, cues, tags].concat();
+    let data = [ebml, Element(MkvId::MkvSegment, &payload)].concat();
+    let mut reader = BufferReader::new(data);
+
+    let mut segment = ParseSegment(&mut reader);
+    assert_eq!(2, segment.seek_head_count());
+    assert_eq!(
+        Some(segment.payload_start() + cues_pos),
+        segment.GetElementPosition(MkvId::MkvCues)
+    );
+    assert_eq!(1, segment.GetTracks().unwrap().track_entries_size());
+
+    let tags = segment.LoadTags(&mut reader).unwrap().unwrap();
+    assert_eq!(
+        "B",
+        tags.GetTag(0)
+            .unwrap()
+            .GetSimpleTag(0)
+            .unwrap()
+            .tag_string()
+    );
+    assert_eq!(
+        1,
+        segment
+            .LoadCues(&mut reader)
+            .unwrap()
+            .unwrap()
+            .cue_entries_size()
+    );
+    assert_eq!(0, SeekTimestamp(&mut reader, &mut segment, 1, 1000000));
+}
+
+#[test]
+fn stream_writer() {
+    let mut writer = StreamWriter::new(Vec::new());
+    assert!(!writer.seekable());
+    assert!(writer.set_position(0).is_err());
+    let dummy_data = vec![0; kFrameLength as usize];
+    {
+        let mut segment = Segment::new();
+        assert!(SegmentInit(&mut segment, &mut writer, true, false, false));
+        AddVideoTrack(&mut segment);
+
+        let track = kVideoTrackNumber as u64;
+        segment.AddFrame(&dummy_data, track, 0, true).unwrap();
+        segment
+            .AddFrame(&dummy_data, track, 2000000, false)
+            .unwrap();
+        segment.ForceNewClusterOnNextFrame();
+        segment.AddFrame(&dummy_data, track, 4000000, true).unwrap();
+        segment.Finalize().unwrap();
+    }
+    let size = writer.get_position();
+    let data = writer.into_inner();
+    assert_eq!(size, data.len() as u64);
+
+    // Nothing is patched after the fact: no SeekHead, no duration, and the
+    // Segment keeps its unknown size.
+    let mut reader = BufferReader::new(data);
+    let mut segment = ParseSegment(&mut reader);
+    assert_eq!(None, segment.size());
+    assert_eq!(0, segment.seek_head_count());
+    assert_eq!(-1, segment.GetInfo().unwrap().GetDuration());
+    assert!(segment.LoadCues(&mut reader).unwrap().is_none());
+
+    let mut timestamps = Vec::new();
+    let mut cluster = segment.GetFirstCluster(&mut reader).unwrap();
+    while let Some(mut c) = cluster {
+        while let Some(frame) = c.GetNextFrame(&mut reader).unwrap() {
+            assert_eq!(dummy_data, frame.frame());
+            timestamps.push(frame.timestamp());
+        }
+        cluster = segment.GetNextCluster(&mut reader, &c).unwrap();
+    }
+    assert_eq!(vec![0, 2000000, 4000000], timestamps);
+}
+
+// Seekable writer that fails the test on any backward seek.
+struct NoSeekWriter {
+    writer_: MemoryWriter,
+}
+
+impl Writer for NoSeekWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.writer_.write(buf)
+    }
+
+    fn get_position(&self) -> u64 {
+        self.writer_.get_position()
+    }
+
+    fn set_position(&mut self, _position: u64) -> io::Result<()> {
+        panic!("set_position called in live mode");
+    }
+
+    fn seekable(&self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn live_mode() {
+    let mut writer = NoSeekWriter {
+        writer_: MemoryWriter::new(),
+    };
+    let dummy_data = vec![0; kFrameLength as usize];
+    {
+        let mut segment = Segment::new();
+        assert!(SegmentInit(&mut segment, &mut writer, true, true, false));
+        segment.set_mode(Mode::kLive);
+        AddVideoTrack(&mut segment);
+        AddAudioTrack(&mut segment);
+
+        let video = kVideoTrackNumber as u64;
+        let audio = kAudioTrackNumber as u64;
+        segment.AddFrame(&dummy_data, video, 0, true).unwrap();
+        segment.AddFrame(&dummy_data, audio, 1000000, true).unwrap();
+        segment
+            .AddFrame(&dummy_data, video, 2000000, false)
+            .unwrap();
+        segment.AddFrame(&dummy_data, video, 4000000, true).unwrap();
+        segment.AddFrame(&dummy_data, audio, 5000000, true).unwrap();
+        segment.Finalize().unwrap();
+    }
+
+    let mut reader = BufferReader::new(writer.writer_.into_inner());
+    let mut segment = ParseSegment(&mut reader);
+    assert_eq!(None, segment.size());
+    assert_eq!(0, segment.seek_head_count());
+    assert_eq!(-1, segment.GetInfo().unwrap().GetDuration());
+    assert!(segment.LoadCues(&mut reader).unwrap().is_none());
+
+    let mut clusters = 0;
+    let mut frames = Vec::new();
+    let mut cluster = segment.GetFirstCluster(&mut reader).unwrap();
+    while let Some(mut c) = cluster {
+        assert_eq!(None, c.header().size);
+        clusters += 1;
+        while let Some(frame) = c.GetNextFrame(&mut reader).unwrap() {
+            frames.push((frame.track_number(), frame.timestamp()));
+        }
+        cluster = segment.GetNextCluster(&mut reader, &c).unwrap();
+    }
+    assert_eq!(2, clusters);
+    assert_eq!(
+        vec![
+            (1, 0),
+            (2, 1000000),
+            (1, 2000000),
+            (1, 4000000),
+            (2, 5000000)
+        ],
+        frames
+    );
+}
+
+#[test]
+fn cues_before_clusters_positions() {
+    // Enough data for the cluster positions to need wider CueClusterPosition
+    // values once the Cues are moved in front of them.
+    let filename = GetTempFileName();
+    let mut writer = MkvWriter::new(File::create(&filename).unwrap());
+    let mut cues_writer = MemoryWriter::new();
+    let frame = vec![0; 3000];
+    {
+        let mut segment = Segment::new();
+        assert!(SegmentInit(&mut segment, &mut writer, true, false, false));
+        AddVideoTrack(&mut segment);
+        let track = kVideoTrackNumber as u64;
+        for i in 0..200 {
+            segment
+                .AddFrame(&frame, track, i * 33000000, i % 5 == 0)
+                .unwrap();
+        }
+        segment.Finalize().unwrap();
+
+        let mut reader = MkvReader::new(File::open(&filename).unwrap()).unwrap();
+        segment
+            .CopyAndMoveCuesBeforeClusters(&mut reader, &mut cues_writer)
+            .unwrap();
+    }
+    let _ = std::fs::remove_file(&filename);
+
+    let mut reader = BufferReader::new(cues_writer.into_inner());
+    let mut segment = ParseSegment(&mut reader);
+    assert_eq!(
+        reader.data_.len() as u64,
+        segment.payload_start() + segment.size().unwrap()
+    );
+    let cues_pos = segment.GetElementPosition(MkvId::MkvCues).unwrap();
+    let cluster_pos = segment.GetElementPosition(MkvId::MkvCluster).unwrap();
+    assert!(cues_pos < cluster_pos);
+    assert_eq!(cluster_pos, segment.first_cluster_pos().unwrap());
+
+    let payload_start = segment.payload_start();
+    let cues = segment.LoadCues(&mut reader).unwrap().unwrap();
+    assert_eq!(40, cues.cue_entries_size());
+    let positions: Vec<(u64, u64)> = (0..cues.cue_entries_size())
+        .map(|i| {
+            let cp = cues.GetCueByIndex(i).unwrap();
+            let tp = cp.Find(kVideoTrackNumber as u64).unwrap();
+            (cp.time(), tp.cluster_pos())
+        })
+        .collect();
+    assert!(positions.last().unwrap().1 > 0xFFFF);
+    for (time, pos) in positions {
+        let header = ParseElementHeader(&mut reader, payload_start + pos, None).unwrap();
+        assert!(header.is(MkvId::MkvCluster));
+        let cluster = Cluster::Parse(&mut reader, &header, 1000000).unwrap();
+        assert_eq!(time, cluster.timecode());
+    }
+}
+
+// Checks the Position and PrevSize of every Cluster of |data| and walks the
+// Clusters backwards with PrevSize. Returns the number of Clusters.
+fn CheckClusterLinks(data: Vec<u8>) -> usize {
+    let mut reader = BufferReader::new(data);
+    let segment = ParseSegment(&mut reader);
+    let payload_start = segment.payload_start();
+
+    let mut starts = Vec::new();
+    let mut cluster = segment.GetFirstCluster(&mut reader).unwrap();
+    while let Some(c) = cluster {
+        assert_eq!(Some(c.element_start() - payload_start), c.position());
+        match starts.last() {
+            Some(&prev) => assert_eq!(Some(c.element_start() - prev), c.prev_size()),
+            None => assert_eq!(None, c.prev_size()),
+        }
+        starts.push(c.element_start());
+        cluster = segment.GetNextCluster(&mut reader, &c).unwrap();
+    }
+
+    let mut last = segment.GetFirstCluster(&mut reader).unwrap().unwrap();
+    while let Some(next) = segment.GetNextCluster(&mut reader, &last).unwrap() {
+        last = next;
+    }
+    let mut backwards = vec![last.element_start()];
+    let mut cluster = segment.GetPreviousCluster(&mut reader, &last).unwrap();
+    while let Some(c) = cluster {
+        backwards.push(c.element_start());
+        cluster = segment.GetPreviousCluster(&mut reader, &c).unwrap();
+    }
+    backwards.reverse();
+    assert_eq!(starts, backwards);
+    starts.len()
+}
+
+#[test]
+fn cluster_prev_size_and_position() {
+    let frame = vec![0; 300];
+    let track = kVideoTrackNumber as u64;
+    let mut writer = MemoryWriter::new();
+    {
+        let mut segment = Segment::new();
+        assert!(SegmentInit(&mut segment, &mut writer, true, false, false));
+        AddVideoTrack(&mut segment);
+        segment.OutputClusterPrevSize(true);
+        segment.OutputClusterPosition(true);
+        for i in 0..9 {
+            if i % 3 == 0 {
+                segment.ForceNewClusterOnNextFrame();
+            }
+            segment
+                .AddFrame(&frame, track, i * 33000000, i % 3 == 0)
+                .unwrap();
+        }
+        segment.Finalize().unwrap();
+    }
+    assert_eq!(3, CheckClusterLinks(writer.into_inner()));
+
+    // Live output can be walked backwards as well.
+    let mut writer = MemoryWriter::new();
+    {
+        let mut segment = Segment::new();
+        assert!(SegmentInit(&mut segment, &mut writer, false, false, false));
+        segment.set_mode(Mode::kLive);
+        AddVideoTrack(&mut segment);
+        segment.OutputClusterPrevSize(true);
+        segment.OutputClusterPosition(true);
+        for i in 0..6 {
+            if i % 2 == 0 {
+                segment.ForceNewClusterOnNextFrame();
+            }
+            segment
+                .AddFrame(&frame, track, i * 33000000, i % 2 == 0)
+                .unwrap();
+        }
+        segment.Finalize().unwrap();
+    }
+    let mut reader = BufferReader::new(writer.into_inner());
+    let segment = ParseSegment(&mut reader);
+    let mut cluster = segment.GetFirstCluster(&mut reader).unwrap().unwrap();
+    let mut sizes = vec![cluster.prev_size()];
+    while let Some(next) = segment.GetNextCluster(&mut reader, &cluster).unwrap() {
+        let start = cluster.element_start();
+        assert_eq!(Some(next.element_start() - start), next.prev_size());
+        let prev = segment
+            .GetPreviousCluster(&mut reader, &next)
+            .unwrap()
+            .unwrap();
+        assert_eq!(start, prev.element_start());
+        sizes.push(next.prev_size());
+        cluster = next;
+    }
+    assert_eq!(3, sizes.len());
+    assert_eq!(None, sizes[0]);
+
+    // Positions are rewritten when the Cues are moved in front of the
+    // Clusters.
+    let filename = GetTempFileName();
+    let mut writer = MkvWriter::new(File::create(&filename).unwrap());
+    let mut cues_writer = MemoryWriter::new();
+    {
+        let mut segment = Segment::new();
+        assert!(SegmentInit(&mut segment, &mut writer, true, false, false));
+        AddVideoTrack(&mut segment);
+        segment.OutputClusterPrevSize(true);
+        segment.OutputClusterPosition(true);
+        for i in 0..9 {
+            if i % 3 == 0 {
+                segment.ForceNewClusterOnNextFrame();
+            }
+            segment
+                .AddFrame(&frame, track, i * 33000000, i % 3 == 0)
+                .unwrap();
+        }
+        segment.Finalize().unwrap();
+
+        let mut reader = MkvReader::new(File::open(&filename).unwrap()).unwrap();
+        segment
+            .CopyAndMoveCuesBeforeClusters(&mut reader, &mut cues_writer)
+            .unwrap();
+    }
+    let _ = std::fs::remove_file(&filename);
+    assert_eq!(3, CheckClusterLinks(cues_writer.into_inner()));
+}
+
+// Muxes two Clusters of video with CRC-32 elements in every supported master
+// element. |position| adds the Cluster Positions, PrevSizes and Tags, and
+// Chapters otherwise. Returns the finalized Segment.
+fn MuxWithCrc32(writer: &mut dyn Writer, position: bool) -> Segment<'_> {
+    let track = kVideoTrackNumber as u64;
+    let frames: Vec<Frame> = (0..6)
+        .map(|i| NewFrame(&[7; 500], track, i * 33000000, i % 3 == 0))
+        .collect();
+    let setup = |segment: &mut Segment| {
+        AddVideoTrack(segment);
+        for &id in &[
+            MkvId::MkvInfo,
+            MkvId::MkvTracks,
+            MkvId::MkvCues,
+            MkvId::MkvTags,
+            MkvId::MkvChapters,
+            MkvId::MkvCluster,
+        ] {
+            segment.OutputCrc32(id, true).unwrap();
+        }
+        segment.OutputClusterPosition(position);
+        segment.OutputClusterPrevSize(position);
+        if position {
+            segment.AddTag().add_simple_tag("TITLE", "crc");
+        } else {
+            let chapter = segment.AddChapter();
+            chapter.set_id("chapter");
+            chapter.set_time(1000000, 0, 1000000000);
+            chapter.add_string("title", "eng", "us");
+        }
+    };
+    MuxFrames(writer, true, setup, &frames, &[0, 3])
+}
+
+// Parses |data| and checks that every checksummed element starts with its
+// CRC-32 element. Returns the number of frames read.
+fn ReadWithCrc32(data: Vec<u8>) -> Result<usize, ParseError> {
+    let mut reader = BufferReader::new(data);
+    let mut header = EBMLHeader::new();
+    let pos = header.Parse(&mut reader, 0)?;
+    let mut segment = ParserSegment::CreateInstance(&mut reader, pos)?;
+    segment.ParseHeaders(&mut reader)?;
+    assert!(segment.LoadCues(&mut reader)?.is_some());
+    segment.LoadChapters(&mut reader)?;
+    segment.LoadTags(&mut reader)?;
+    assert!(segment.GetChapters().is_some() || segment.GetTags().is_some());
+    for &id in &[
+        MkvId::MkvInfo,
+        MkvId::MkvTracks,
+        MkvId::MkvCues,
+        MkvId::MkvTags,
+        MkvId::MkvChapters,
+    ] {
+        let pos = match segment.GetElementPosition(id) {
+            Some(pos) => pos,
+            None => continue,
+        };
+        let element = ParseElementHeader(&mut reader, pos, None)?;
+        let first = ParseElementHeader(&mut reader, element.payload_start, None)?;
+        assert!(first.is(MkvId::MkvCrc32), "{:?}", id);
+    }
+
+    let mut frames = 0;
+    let mut cluster = segment.GetFirstCluster(&mut reader)?;
+    while let Some(mut c) = cluster {
+        let first = ParseElementHeader(&mut reader, c.header().payload_start, None)?;
+        assert!(first.is(MkvId::MkvCrc32));
+        while c.GetNextFrame(&mut reader)?.is_some() {
+            frames += 1;
+        }
+        cluster = segment.GetNextCluster(&mut reader, &c)?;
+    }
+    Ok(frames)
+}
+
+#[test]
+fn crc32_elements() {
+    let mut writer = MemoryWriter::new();
+    {
+        let mut segment = MuxWithCrc32(&mut writer, false);
+        assert!(segment.OutputCrc32(MkvId::MkvInfo, false).is_err());
+        assert!(segment.OutputCrc32(MkvId::MkvSeekHead, true).is_err());
+    }
+    let data = writer.into_inner();
+    assert_eq!(6, ReadWithCrc32(data.clone()).unwrap());
+
+    // Damage a frame in the second Cluster, then the Tracks.
+    let mut reader = BufferReader::new(data.clone());
+    let segment = ParseSegment(&mut reader);
+    let first = segment.GetFirstCluster(&mut reader).unwrap().unwrap();
+    let second = segment
+        .GetNextCluster(&mut reader, &first)
+        .unwrap()
+        .unwrap();
+    let tracks_pos = segment.GetElementPosition(MkvId::MkvTracks).unwrap();
+
+    let mut damaged = data.clone();
+    damaged[second.GetEnd(&mut reader).unwrap() as usize - 10] ^= 0x01;
+    match ReadWithCrc32(damaged) {
+        Err(ParseError::kCrc32Mismatch(id)) => assert_eq!(MkvId::MkvCluster as u64, id),
+        _ => panic!("damaged Cluster was not detected"),
+    }
+    let mut damaged = data;
+    damaged[tracks_pos as usize + 20] ^= 0x01;
+    match ReadWithCrc32(damaged) {
+        Err(ParseError::kCrc32Mismatch(id)) => assert_eq!(MkvId::MkvTracks as u64, id),
+        _ => panic!("damaged Tracks were not detected"),
+    }
+
+    // Moving the Cues rewrites the Cluster Positions and their CRC-32.
+    let filename = GetTempFileName();
+    let mut writer = MkvWriter::new(File::create(&filename).unwrap());
+    let mut cues_writer = MemoryWriter::new();
+    {
+        let mut segment = MuxWithCrc32(&mut writer, true);
+        let mut reader = MkvReader::new(File::open(&filename).unwrap()).unwrap();
+        segment
+            .CopyAndMoveCuesBeforeClusters(&mut reader, &mut cues_writer)
+            .unwrap();
+    }
+    let _ = std::fs::remove_file(&filename);
+    let data = cues_writer.into_inner();
+    assert_eq!(6, ReadWithCrc32(data.clone()).unwrap());
+    assert_eq!(2, CheckClusterLinks(data));
+}
+
+const kPublicKey: &[u8] = b"public key";
+
+// Stand-in for a real signature: the hash XORed with the public key.
+fn FakeSignature(hash: &[u8]) -> Vec<u8> {
+    hash.iter()
+        .zip(kPublicKey.iter().cycle())
+        .map(|(a, b)| a ^ b)
+        .collect()
+}
+
+// Muxes three Clusters of video to |writer|, signing the Info, Clusters,
+// Tracks and Cues.
+fn MuxSigned(writer: &mut dyn Writer, live: bool) {
+    let track = kVideoTrackNumber as u64;
+    let frames: Vec<Frame> = (0..9)
+        .map(|i| NewFrame(&[5; 200], track, i * 33000000, i % 3 == 0))
+        .collect();
+    let setup = |segment: &mut Segment| {
+        if live {
+            segment.set_mode(Mode::kLive);
+        }
+        AddVideoTrack(segment);
+        segment.OutputCrc32(MkvId::MkvCluster, true).unwrap();
+        segment
+            .SetSigner(
+                &[
+                    MkvId::MkvInfo,
+                    MkvId::MkvCluster,
+                    MkvId::MkvTracks,
+                    MkvId::MkvCues,
+                ],
+                SignatureAlgo::kRsa,
+                kPublicKey,
+                FakeSignature,
+            )
+            .unwrap();
+    };
+    let segment = MuxFrames(writer, !live, setup, &frames, &[0, 3, 6]);
+
+    let slot = segment.GetSignatureSlot().unwrap();
+    assert_eq!(
+        &[
+            MkvId::MkvCluster,
+            MkvId::MkvInfo,
+            MkvId::MkvTracks,
+            MkvId::MkvCues
+        ],
+        slot.signed_elements()
+    );
+    assert_eq!(20, slot.signature().len());
+}
+
+fn VerifySigned(data: Vec<u8>) -> bool {
+    let mut reader = BufferReader::new(data);
+    let mut segment = ParseSegment(&mut reader);
+    segment
+        .VerifySignature(&mut reader, |slot, hash| {
+            assert_eq!(SignatureAlgo::kRsa as u64, slot.algo());
+            assert_eq!(kPublicKey, slot.public_key());
+            slot.signature() == FakeSignature(hash).as_slice()
+        })
+        .unwrap()
+}
+
+#[test]
+fn signed_segment() {
+    let mut writer = MemoryWriter::new();
+    MuxSigned(&mut writer, false);
+    let data = writer.into_inner();
+    assert!(VerifySigned(data.clone()));
+
+    let mut reader = BufferReader::new(data.clone());
+    let mut segment = ParseSegment(&mut reader);
+    let slot = segment.LoadSignatureSlot(&mut reader).unwrap().unwrap();
+    assert_eq!(
+        vec![
+            MkvId::MkvCluster as u64,
+            MkvId::MkvInfo as u64,
+            MkvId::MkvTracks as u64,
+            MkvId::MkvCues as u64
+        ],
+        slot.signed_elements()
+    );
+    let cues_pos = segment.GetElementPosition(MkvId::MkvCues).unwrap();
+    let cluster = segment.GetFirstCluster(&mut reader).unwrap().unwrap();
+    let frame_pos = cluster.GetEnd(&mut reader).unwrap() as usize - 10;
+
+    // Any change to a signed element breaks the signature.
+    let mut tampered = data.clone();
+    tampered[frame_pos] ^= 0x01;
+    assert!(!VerifySigned(tampered));
+    let mut tampered = data;
+    tampered[cues_pos as usize + 8] ^= 0x01;
+    assert!(!VerifySigned(tampered));
+
+    // Live output and output without seeking are signed as well.
+    let mut writer = MemoryWriter::new();
+    MuxSigned(&mut writer, true);
+    assert!(VerifySigned(writer.into_inner()));
+    let mut writer = StreamWriter::new(Vec::new());
+    MuxSigned(&mut writer, false);
+    assert!(VerifySigned(writer.into_inner()));
+
+    // Unsigned output has nothing to verify.
+    let mut writer = MemoryWriter::new();
+    {
+        let mut segment = Segment::new();
+        assert!(SegmentInit(&mut segment, &mut writer, true, false, false));
+        AddVideoTrack(&mut segment);
+        assert!(segment
+            .SetSigner(
+                &[MkvId::MkvSeekHead],
+                SignatureAlgo::kRsa,
+                kPublicKey,
+                FakeSignature
+            )
+            .is_err());
+        assert!(segment
+            .SetSigner(
+                &[MkvId::MkvInfo, MkvId::MkvInfo],
+                SignatureAlgo::kRsa,
+                kPublicKey,
+                FakeSignature
+            )
+            .is_err());
+        segment
+            .AddFrame(&[0; 10], kVideoTrackNumber as u64, 0, true)
+            .unwrap();
+        segment.Finalize().unwrap();
+    }
+    assert!(!VerifySigned(writer.into_inner()));
+}
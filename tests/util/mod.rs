@@ -1,102 +1,229 @@
-use libwebm::mkvmuxer::writer::MkvWriter;
+#![allow(dead_code, non_snake_case, non_upper_case_globals)]
+
+use libwebm::mkvmuxer::frame::Frame;
+use libwebm::mkvmuxer::segment::Segment;
+use libwebm::mkvmuxer::util;
+use libwebm::mkvmuxer::writer::{MemoryWriter, Writer};
+use libwebm::mkvparser::reader::{MkvReader, Reader};
 use std::fs::File;
 use std::io;
-use std::io::{Error, ErrorKind, Read};
-use libwebm::mkvmuxer::util;
+use std::io::Error;
 
 // constants for muxer and parser tests
-const kAppString: &'static str = "mkvmuxer_unit_tests";
-const kOpusCodecId: &'static str = "A_OPUS";
-const kVorbisCodecId: &'static str = "A_VORBIS";
-const kAudioTrackNumber: i32 = 2;
-const kBitDepth: i32 = 2;
-const kChannels: i32 = 2;
-const kDuration: f64 = 2.345;
-const kFrameLength: i32 = 10;
-const kHeight: i32 = 180;
-const kInvalidTrackNumber: i32 = 100;
-const kOpusCodecDelay: u64 = 6500000;
-const kOpusPrivateDataSizeMinimum: usize = 19;
-const kOpusSeekPreroll: u64 = 80000000;
-const kMetadataCodecId: &'static str = "D_WEBVTT/METADATA";
-const kMetadataTrackNumber: i32 = 3;
-const kMetadataTrackType: i32 = 0x21;
-const kSampleRate: i32 = 30;
-const kTimeCodeScale: i32 = 1000;
-const kTrackName: &'static str = "unit_test";
-const kVP8CodecId: &'static str = "V_VP8";
-const kVP9CodecId: &'static str = "V_VP9";
-const kVideoFrameRate: f64 = 0.5;
-const kVideoTrackNumber: i32 = 1;
-const kWidth: i32 = 320;
-
-fn GetTempFileName() -> String {
+pub const kAppString: &str = "mkvmuxer_unit_tests";
+pub const kOpusCodecId: &str = "A_OPUS";
+pub const kVorbisCodecId: &str = "A_VORBIS";
+pub const kAudioTrackNumber: i32 = 2;
+pub const kBitDepth: i32 = 2;
+pub const kChannels: i32 = 2;
+pub const kDuration: f64 = 2.345;
+pub const kFrameLength: i32 = 10;
+pub const kHeight: i32 = 180;
+pub const kInvalidTrackNumber: i32 = 100;
+pub const kOpusCodecDelay: u64 = 6500000;
+pub const kOpusPrivateDataSizeMinimum: usize = 19;
+pub const kOpusSeekPreroll: u64 = 80000000;
+pub const kMetadataCodecId: &str = "D_WEBVTT/METADATA";
+pub const kMetadataTrackNumber: i32 = 3;
+pub const kMetadataTrackType: i32 = 0x21;
+pub const kSampleRate: i32 = 30;
+pub const kTimeCodeScale: i32 = 1000;
+pub const kTrackName: &str = "unit_test";
+pub const kVP8CodecId: &str = "V_VP8";
+pub const kVP9CodecId: &str = "V_VP9";
+pub const kVideoFrameRate: f64 = 0.5;
+pub const kVideoTrackNumber: i32 = 1;
+pub const kWidth: i32 = 320;
+
+// AES-128-CTR of the bytes 0..32 with the key 00..0F and the counter block
+// 0102030405060708 0000000000000000.
+pub const kEncryptionKey: [u8; 16] = [
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F,
+];
+pub const kEncryptionIV: u64 = 0x0102030405060708;
+pub const kCipherText: [u8; 32] = [
+    0x18, 0xBB, 0x6B, 0xB8, 0x42, 0x64, 0xF8, 0xE2, 0xAF, 0xC5, 0x94, 0xCA, 0xAB, 0x3C, 0xEC, 0x77,
+    0xDF, 0x80, 0xBF, 0x8D, 0xC9, 0xEC, 0xD8, 0x73, 0x43, 0x48, 0x44, 0x38, 0x9E, 0xCC, 0xD6, 0xD3,
+];
+// The same for the bytes 4..20.
+pub const kPartitionCipherText: [u8; 16] = [
+    0x1C, 0xBF, 0x6F, 0xBC, 0x4E, 0x68, 0xF4, 0xEE, 0xAB, 0xC1, 0x90, 0xCE, 0xB7, 0x20, 0xF0, 0x6B,
+];
+
+pub fn GetTempFileName() -> String {
     let temp_dir = std::env::temp_dir().to_str().unwrap().to_string();
     temp_dir + "/libwebm_temp." + &util::MakeUID().to_string()
 }
 
-fn GetTestDataDir() -> String {
-    let test_data_path = std::env::var("LIBWEBM_TEST_DATA_PATH");
-    match test_data_path {
+pub fn GetTestDataDir() -> String {
+    match std::env::var("LIBWEBM_TEST_DATA_PATH") {
         Ok(path) => path,
-        Err(err) => ".".to_string(),
+        Err(_) => concat!(env!("CARGO_MANIFEST_DIR"), "/tests/testdata").to_string(),
     }
 }
 
-fn GetTestFilePath(name: &str) -> String {
+pub fn GetTestFilePath(name: &str) -> String {
     let libwebm_testdata_dir = GetTestDataDir();
     libwebm_testdata_dir + "/" + name
 }
 
-fn CompareFiles(file1: &str, file2: &str) -> io::Result<()> {
-    let mut f1 = File::open(file1)?;
-    let mut f2 = File::open(file2)?;
-
-    let block_size = 4096;
-    let mut buf1 = vec![0u8; block_size];
-    let mut buf2 = vec![0u8; block_size];
-
-    loop {
-        match (f1.read(&mut buf1), f2.read(&mut buf2)) {
-            (Ok(n1), Ok(n2)) => {
-                if n1 != n2 {
-                    return Err(Error::new(ErrorKind::Other, "f1 and f2 not same size"));
-                } else if buf1 != buf2 {
-                    return Err(Error::new(ErrorKind::Other, "f1 and f2 not same content"));
-                } else if n1 < block_size {
-                    return Ok(());
-                }
-            }
-            _ => {
-                return Err(Error::new(
-                    ErrorKind::Other,
-                    "f1 or f2 eof but another not eof",
-                ));
-            }
+pub struct MuxerTest {
+    pub dummy_data_: Vec<u8>,
+}
+
+impl MuxerTest {
+    pub fn new() -> MuxerTest {
+        MuxerTest {
+            dummy_data_: vec![0; kFrameLength as usize],
+        }
+    }
+
+    pub fn writer(&self) -> MemoryWriter {
+        MemoryWriter::new()
+    }
+
+    // Compares the muxed output in |writer| with the test file |name|.
+    pub fn CompareWith(&self, writer: &MemoryWriter, name: &str) -> io::Result<()> {
+        let expected = std::fs::read(GetTestFilePath(name))?;
+        let output = writer.data();
+        if expected.len() != output.len() {
+            Err(Error::other("output and test file not same size"))
+        } else if expected != output {
+            Err(Error::other("output and test file not same content"))
+        } else {
+            Ok(())
         }
     }
 }
 
-struct MuxerTest {
-    writer_: MkvWriter,
-    filename_: String,
-    //segment_: Segment,
-    dummy_data_: Vec<u8>,//[u8; ],
+pub fn SegmentInit<'a>(
+    segment: &mut Segment<'a>,
+    writer: &'a mut dyn Writer,
+    output_cues: bool,
+    accurate_cluster_duration: bool,
+    fixed_size_cluster_timecode: bool,
+) -> bool {
+    if segment.Init(writer).is_err() {
+        return false;
+    }
+    let info = segment.GetSegmentInfo();
+    info.set_writing_app(kAppString);
+    info.set_muxing_app(kAppString);
+    segment.OutputCues(output_cues);
+    segment.AccurateClusterDuration(accurate_cluster_duration);
+    segment.UseFixedSizeClusterTimecode(fixed_size_cluster_timecode);
+    true
 }
 
-impl MuxerTest{
-    fn new() -> MuxerTest {
-        let temp_file = GetTempFileName();
-        let file = File::open(temp_file.clone()).unwrap();
+pub fn AddVideoTrack(segment: &mut Segment) {
+    let vid_track = segment
+        .AddVideoTrack(kWidth, kHeight, kVideoTrackNumber)
+        .unwrap();
+    assert_eq!(kVideoTrackNumber as u64, vid_track);
+    let video = segment.GetTrackByNumber(vid_track).unwrap();
+    assert!(video.as_video().is_some());
+    video.set_uid(kVideoTrackNumber as u64);
+}
 
-        MuxerTest{
-            writer_: MkvWriter::new(file),
-            filename_: temp_file,
-            dummy_data_: vec![0; kFrameLength as usize],
+pub fn AddAudioTrack(segment: &mut Segment) {
+    let aud_track = segment
+        .AddAudioTrack(kSampleRate, kChannels, kAudioTrackNumber)
+        .unwrap();
+    assert_eq!(kAudioTrackNumber as u64, aud_track);
+    let audio = segment.GetTrackByNumber(aud_track).unwrap();
+    assert!(audio.as_audio().is_some());
+    audio.set_uid(kAudioTrackNumber as u64);
+}
+
+// Encrypts the frames of |track_number| with kEncryptionKey, starting from
+// kEncryptionIV.
+pub fn AddEncryption(segment: &mut Segment, track_number: u64) {
+    let track = segment.GetTrackByNumber(track_number).unwrap();
+    track
+        .AddContentEncryption(&kEncryptionKey, b"key id")
+        .unwrap();
+    track
+        .GetContentEncoding(0)
+        .unwrap()
+        .encryptor()
+        .unwrap()
+        .set_next_iv(kEncryptionIV);
+}
+
+// Returns a frame of |track_number| holding |data| at |timestamp| ns.
+pub fn NewFrame(data: &[u8], track_number: u64, timestamp: u64, is_key: bool) -> Frame {
+    let mut frame = Frame::new();
+    frame.Init(data);
+    frame.set_track_number(track_number);
+    frame.set_timestamp(timestamp);
+    frame.set_is_key(is_key);
+    frame
+}
+
+// Muxes |frames| to |writer| once |setup| has added and configured the
+// tracks of the Segment. A new Cluster is started before each frame in
+// |cluster_starts|. Returns the finalized Segment.
+pub fn MuxFrames<'a>(
+    writer: &'a mut dyn Writer,
+    output_cues: bool,
+    setup: impl FnOnce(&mut Segment<'a>),
+    frames: &[Frame],
+    cluster_starts: &[usize],
+) -> Segment<'a> {
+    let mut segment = Segment::new();
+    assert!(SegmentInit(&mut segment, writer, output_cues, false, false));
+    setup(&mut segment);
+    for (i, frame) in frames.iter().enumerate() {
+        if cluster_starts.contains(&i) {
+            segment.ForceNewClusterOnNextFrame();
         }
+        segment.AddGenericFrame(frame).unwrap();
     }
+    segment.Finalize().unwrap();
+    segment
+}
+
+pub fn OpenTestFile(name: &str) -> MkvReader {
+    MkvReader::new(File::open(GetTestFilePath(name)).unwrap()).unwrap()
+}
+
+// Reader over an in-memory buffer. |available_| can be lowered to simulate a
+// partially received stream, in which case the total length is unknown.
+pub struct BufferReader {
+    pub data_: Vec<u8>,
+    pub available_: u64,
+}
 
-    fn get_filename(&self) ->&str {
-        &self.filename_
+impl BufferReader {
+    pub fn new(data: Vec<u8>) -> BufferReader {
+        let available = data.len() as u64;
+        BufferReader {
+            data_: data,
+            available_: available,
+        }
     }
-}
\ No newline at end of file
+}
+
+impl Reader for BufferReader {
+    fn read(&mut self, position: u64, buf: &mut [u8]) -> io::Result<()> {
+        let start = position as usize;
+        let end = start + buf.len();
+        if end as u64 > self.available_ {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        buf.copy_from_slice(&self.data_[start..end]);
+        Ok(())
+    }
+
+    fn length(&self) -> Option<u64> {
+        if self.available_ == self.data_.len() as u64 {
+            Some(self.available_)
+        } else {
+            None
+        }
+    }
+
+    fn available(&self) -> u64 {
+        self.available_
+    }
+}